@@ -0,0 +1,196 @@
+use super::address::{address_from_script, p2pkh_script, p2sh_script, p2tr_script, p2wpkh_script};
+use super::hd::{
+    DerivationPath, ExtendedPrivateKey, ExtendedPublicKey, HARDENED, UPRV, UPUB, VPRV, VPUB, YPRV,
+    YPUB, ZPRV, ZPUB,
+};
+use super::network::Network;
+use super::s256ecc::S256Point;
+use super::sha256ser::Sha256Ripemd160;
+use crate::ser::chained_hash::ChainedCompute;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum ScriptType {
+    P2pkh,
+    P2shP2wpkh,
+    P2wpkh,
+    P2tr,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum AccountPreset {
+    Bip44,
+    Bip49,
+    Bip84,
+    Bip86,
+}
+
+impl AccountPreset {
+    pub const ALL: [AccountPreset; 4] = [Self::Bip44, Self::Bip49, Self::Bip84, Self::Bip86];
+
+    #[inline]
+    pub fn purpose(&self) -> u32 {
+        match self {
+            Self::Bip44 => 44,
+            Self::Bip49 => 49,
+            Self::Bip84 => 84,
+            Self::Bip86 => 86,
+        }
+    }
+
+    #[inline]
+    pub fn script_type(&self) -> ScriptType {
+        match self {
+            Self::Bip44 => ScriptType::P2pkh,
+            Self::Bip49 => ScriptType::P2shP2wpkh,
+            Self::Bip84 => ScriptType::P2wpkh,
+            Self::Bip86 => ScriptType::P2tr,
+        }
+    }
+
+    pub fn path(&self, account: u32, network: Network) -> Result<DerivationPath, String> {
+        if account >= HARDENED {
+            return Err(format!("Invalid account index: {}", account));
+        }
+        Ok(DerivationPath::new(vec![
+            self.purpose() + HARDENED,
+            network.coin_type() + HARDENED,
+            account + HARDENED,
+        ]))
+    }
+
+    #[inline]
+    pub fn public_version(&self, network: Network) -> [u8; 4] {
+        match (self, network.is_mainnet()) {
+            (Self::Bip44 | Self::Bip86, _) => network.xpub_version(),
+            (Self::Bip49, true) => YPUB,
+            (Self::Bip49, false) => UPUB,
+            (Self::Bip84, true) => ZPUB,
+            (Self::Bip84, false) => VPUB,
+        }
+    }
+
+    #[inline]
+    pub fn private_version(&self, network: Network) -> [u8; 4] {
+        match (self, network.is_mainnet()) {
+            (Self::Bip44 | Self::Bip86, _) => network.xprv_version(),
+            (Self::Bip49, true) => YPRV,
+            (Self::Bip49, false) => UPRV,
+            (Self::Bip84, true) => ZPRV,
+            (Self::Bip84, false) => VPRV,
+        }
+    }
+
+    #[inline]
+    pub fn from_public_version(version: [u8; 4], network: Network) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|preset| preset.public_version(network) == version)
+    }
+
+    pub fn account_xprv(
+        &self,
+        master: &ExtendedPrivateKey,
+        account: u32,
+        network: Network,
+    ) -> Result<ExtendedPrivateKey, String> {
+        Ok(master
+            .derive_path(&self.path(account, network)?)?
+            .with_version(self.private_version(network)))
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Account {
+    preset: AccountPreset,
+    network: Network,
+    xpub: ExtendedPublicKey,
+}
+
+impl Account {
+    #[inline]
+    pub fn new(xpub: ExtendedPublicKey, preset: AccountPreset, network: Network) -> Self {
+        Self {
+            preset,
+            network,
+            xpub: xpub.with_version(preset.public_version(network)),
+        }
+    }
+
+    pub fn from_master(
+        master: &ExtendedPrivateKey,
+        preset: AccountPreset,
+        account: u32,
+        network: Network,
+    ) -> Result<Self, String> {
+        let xprv = preset.account_xprv(master, account, network)?;
+        Ok(Self::new(xprv.extended_public_key()?, preset, network))
+    }
+
+    pub fn parse(s: &str, network: Network) -> Result<Self, String> {
+        let xpub = s.parse::<ExtendedPublicKey>()?;
+        let preset = AccountPreset::from_public_version(xpub.version(), network).ok_or(format!(
+            "Unknown {} extended public key version: {:02x?}",
+            network,
+            xpub.version()
+        ))?;
+        Ok(Self::new(xpub, preset, network))
+    }
+
+    #[inline]
+    pub fn preset(&self) -> AccountPreset {
+        self.preset
+    }
+
+    #[inline]
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    #[inline]
+    pub fn xpub(&self) -> ExtendedPublicKey {
+        self.xpub
+    }
+
+    #[inline]
+    pub fn script_type(&self) -> ScriptType {
+        self.preset.script_type()
+    }
+
+    pub fn public_key(&self, change: bool, index: u32) -> Result<S256Point, String> {
+        let path = DerivationPath::new(vec![change as u32, index]);
+        Ok(self.xpub.derive_path(&path)?.point())
+    }
+
+    pub fn script_pubkey(&self, change: bool, index: u32) -> Result<Vec<u8>, String> {
+        let point = self.public_key(change, index)?;
+        Ok(match self.script_type() {
+            ScriptType::P2pkh => p2pkh_script(&point.hash160(true)),
+            ScriptType::P2shP2wpkh => p2sh_script(&Sha256Ripemd160::compute(
+                &point.p2sh_p2wpkh_redeem_script(),
+            )),
+            ScriptType::P2wpkh => p2wpkh_script(&point.hash160(true)),
+            ScriptType::P2tr => p2tr_script(&point.tap_tweak(None)?.xonly()),
+        })
+    }
+
+    #[inline]
+    pub fn address(&self, change: bool, index: u32) -> Result<String, String> {
+        address_from_script(&self.script_pubkey(change, index)?, self.network)
+    }
+}
+
+impl FromStr for Account {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s, Network::Mainnet).or_else(|_| Self::parse(s, Network::Testnet))
+    }
+}
+
+impl fmt::Display for Account {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.xpub)
+    }
+}
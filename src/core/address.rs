@@ -0,0 +1,130 @@
+use super::network::Network;
+use super::s256ecc::S256Point;
+use super::sha256ser::{Sha256Base58, Sha256Ripemd160};
+use crate::ser::base58::Base58;
+use crate::ser::bech32::{decode_segwit_address, encode_segwit_address};
+use crate::ser::chained_hash::ChainedCompute;
+use sha2::{Digest, Sha256};
+
+#[inline]
+pub fn p2pkh_script(h160: &[u8]) -> Vec<u8> {
+    [&[0x76, 0xa9, 0x14][..], h160, &[0x88, 0xac]].concat()
+}
+
+#[inline]
+pub fn p2sh_script(h160: &[u8]) -> Vec<u8> {
+    [&[0xa9, 0x14][..], h160, &[0x87]].concat()
+}
+
+#[inline]
+pub fn p2wpkh_script(h160: &[u8]) -> Vec<u8> {
+    [&[0x00, 0x14][..], h160].concat()
+}
+
+#[inline]
+pub fn p2wsh_script(sha256: &[u8]) -> Vec<u8> {
+    [&[0x00, 0x20][..], sha256].concat()
+}
+
+#[inline]
+pub fn p2tr_script(xonly: &[u8]) -> Vec<u8> {
+    [&[0x51, 0x20][..], xonly].concat()
+}
+
+#[inline]
+pub fn p2pkh_address(h160: &[u8], network: Network) -> String {
+    Sha256Base58::encode_base58_with_checksum([&[network.p2pkh_prefix()][..], h160].concat())
+}
+
+#[inline]
+pub fn p2sh_address(h160: &[u8], network: Network) -> String {
+    Sha256Base58::encode_base58_with_checksum([&[network.p2sh_prefix()][..], h160].concat())
+}
+
+#[inline]
+pub fn segwit_script(version: u8, program: &[u8]) -> Vec<u8> {
+    let version_op = if version == 0 { 0x00 } else { 0x50 + version };
+    [&[version_op, program.len() as u8][..], program].concat()
+}
+
+#[inline]
+pub fn segwit_address(version: u8, program: &[u8], network: Network) -> Result<String, String> {
+    encode_segwit_address(network.bech32_hrp(), version, program).map_err(|e| e.to_string())
+}
+
+#[inline]
+pub fn p2wsh_address(witness_script: &[u8], network: Network) -> String {
+    segwit_address(0, &Sha256::digest(witness_script), network).unwrap()
+}
+
+pub fn address_from_script(script_pubkey: &[u8], network: Network) -> Result<String, String> {
+    match script_pubkey {
+        [0x76, 0xa9, 0x14, h160 @ .., 0x88, 0xac] if h160.len() == 20 => {
+            Ok(p2pkh_address(h160, network))
+        }
+        [0xa9, 0x14, h160 @ .., 0x87] if h160.len() == 20 => Ok(p2sh_address(h160, network)),
+        [version_op @ (0x00 | 0x51..=0x60), len, program @ ..]
+            if *len as usize == program.len() =>
+        {
+            let version = if *version_op == 0 {
+                0
+            } else {
+                version_op - 0x50
+            };
+            segwit_address(version, program, network)
+        }
+        _ => Err("Script has no address form.".to_string()),
+    }
+}
+
+pub fn script_from_address(address: &str, network: Network) -> Result<Vec<u8>, String> {
+    let hrp = network.bech32_hrp();
+    if address.to_lowercase().starts_with(&format!("{}1", hrp)) {
+        let (version, program) = decode_segwit_address(hrp, address).map_err(|e| e.to_string())?;
+        return Ok(segwit_script(version, &program));
+    }
+    let payload = Sha256Base58::decode_base58_with_checksum(address)?;
+    match payload.split_first() {
+        Some((&prefix, h160)) if prefix == network.p2pkh_prefix() && h160.len() == 20 => {
+            Ok(p2pkh_script(h160))
+        }
+        Some((&prefix, h160)) if prefix == network.p2sh_prefix() && h160.len() == 20 => {
+            Ok(p2sh_script(h160))
+        }
+        _ => Err(format!("Invalid address for this network: {}", address)),
+    }
+}
+
+impl S256Point {
+    #[inline]
+    pub fn p2sh_p2wpkh_redeem_script(&self) -> Vec<u8> {
+        p2wpkh_script(&self.hash160(true))
+    }
+
+    #[inline]
+    pub fn p2sh_p2wpkh_address(&self, network: Network) -> String {
+        let redeem_script = self.p2sh_p2wpkh_redeem_script();
+        p2sh_address(&Sha256Ripemd160::compute(&redeem_script), network)
+    }
+
+    #[inline]
+    pub fn p2wpkh_address(&self, network: Network) -> String {
+        segwit_address(0, &self.hash160(true), network).unwrap()
+    }
+
+    #[inline]
+    pub fn p2pk_script(&self) -> Vec<u8> {
+        let sec = self.sec(true);
+        [&[sec.len() as u8][..], &sec, &[0xac]].concat()
+    }
+
+    #[inline]
+    pub fn p2wsh_address(&self, network: Network) -> String {
+        p2wsh_address(&self.p2pk_script(), network)
+    }
+
+    #[inline]
+    pub fn p2tr_address(&self, network: Network) -> Result<String, String> {
+        segwit_address(1, &self.tap_tweak(None)?.xonly(), network)
+    }
+}
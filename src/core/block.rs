@@ -0,0 +1,382 @@
+use super::sha256ser::DoubleSha256;
+use super::tx::{hash256, Tx, TxIn};
+use crate::ser::chained_hash::ChainedCompute;
+use crate::ser::hex;
+use crate::ser::stream::{read_array, read_u32_le};
+use crate::ser::varint::{encode_varint, read_varint};
+use bnum::types::U256;
+use std::fmt;
+use std::io::{Cursor, Read};
+use std::str::FromStr;
+
+pub const BLOCK_HEADER_SIZE: usize = 80;
+pub const MAX_TARGET_BITS: u32 = 0x1d00_ffff;
+pub const WITNESS_COMMITMENT_HEADER: [u8; 6] = [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+
+pub fn bits_to_target(bits: u32) -> Result<U256, String> {
+    let exponent = bits >> 24;
+    let mantissa = bits & 0x007f_ffff;
+    if mantissa == 0 {
+        return Ok(U256::ZERO);
+    }
+    if bits & 0x0080_0000 != 0 {
+        return Err(format!("Negative target in bits {:#010x}.", bits));
+    }
+    if exponent > 34 || (mantissa > 0xff && exponent > 33) || (mantissa > 0xffff && exponent > 32) {
+        return Err(format!("Target overflow in bits {:#010x}.", bits));
+    }
+    Ok(if exponent <= 3 {
+        U256::from(mantissa >> (8 * (3 - exponent)))
+    } else {
+        U256::from(mantissa) << (8 * (exponent - 3))
+    })
+}
+
+pub fn target_to_bits(target: U256) -> u32 {
+    let mut size = target.bits().div_ceil(8);
+    let mut mantissa = if size <= 3 {
+        (target << (8 * (3 - size))).digits()[0] as u32
+    } else {
+        (target >> (8 * (size - 3))).digits()[0] as u32
+    };
+    if mantissa & 0x0080_0000 != 0 {
+        mantissa >>= 8;
+        size += 1;
+    }
+    mantissa | size << 24
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
+pub struct BlockHeader {
+    version: u32,
+    prev_block: [u8; 32],
+    merkle_root: [u8; 32],
+    timestamp: u32,
+    bits: u32,
+    nonce: u32,
+}
+
+impl BlockHeader {
+    #[inline]
+    pub fn new(
+        version: u32,
+        prev_block: [u8; 32],
+        merkle_root: [u8; 32],
+        timestamp: u32,
+        bits: u32,
+        nonce: u32,
+    ) -> Self {
+        Self {
+            version,
+            prev_block,
+            merkle_root,
+            timestamp,
+            bits,
+            nonce,
+        }
+    }
+
+    #[inline]
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    #[inline]
+    pub fn prev_block(&self) -> [u8; 32] {
+        self.prev_block
+    }
+
+    #[inline]
+    pub fn merkle_root(&self) -> [u8; 32] {
+        self.merkle_root
+    }
+
+    #[inline]
+    pub fn timestamp(&self) -> u32 {
+        self.timestamp
+    }
+
+    #[inline]
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    #[inline]
+    pub fn nonce(&self) -> u32 {
+        self.nonce
+    }
+
+    #[inline]
+    pub fn set_nonce(&mut self, nonce: u32) {
+        self.nonce = nonce;
+    }
+
+    pub fn parse(reader: &mut impl Read) -> Result<Self, String> {
+        let version = read_u32_le(reader)?;
+        let mut prev_block = read_array::<32>(reader)?;
+        prev_block.reverse();
+        let mut merkle_root = read_array::<32>(reader)?;
+        merkle_root.reverse();
+        Ok(Self {
+            version,
+            prev_block,
+            merkle_root,
+            timestamp: read_u32_le(reader)?,
+            bits: read_u32_le(reader)?,
+            nonce: read_u32_le(reader)?,
+        })
+    }
+
+    pub fn serialize(&self) -> [u8; BLOCK_HEADER_SIZE] {
+        let mut result = [0u8; BLOCK_HEADER_SIZE];
+        result[..4].copy_from_slice(&self.version.to_le_bytes());
+        result[4..36].copy_from_slice(&self.prev_block);
+        result[4..36].reverse();
+        result[36..68].copy_from_slice(&self.merkle_root);
+        result[36..68].reverse();
+        result[68..72].copy_from_slice(&self.timestamp.to_le_bytes());
+        result[72..76].copy_from_slice(&self.bits.to_le_bytes());
+        result[76..].copy_from_slice(&self.nonce.to_le_bytes());
+        result
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() != BLOCK_HEADER_SIZE {
+            return Err(format!(
+                "Block header must be {} bytes, got {}.",
+                BLOCK_HEADER_SIZE,
+                bytes.len()
+            ));
+        }
+        Self::parse(&mut Cursor::new(bytes))
+    }
+
+    #[inline]
+    pub fn hash(&self) -> [u8; 32] {
+        hash256(&self.serialize())
+    }
+
+    #[inline]
+    pub fn id(&self) -> String {
+        hex::encode(&self.hash())
+    }
+
+    #[inline]
+    pub fn target(&self) -> Result<U256, String> {
+        bits_to_target(self.bits)
+    }
+
+    pub fn difficulty(&self) -> f64 {
+        let mantissa = self.bits & 0x00ff_ffff;
+        if mantissa == 0 {
+            return 0.0;
+        }
+        let mut difficulty = 0xffff as f64 / mantissa as f64;
+        let mut shift = self.bits >> 24;
+        while shift < 29 {
+            difficulty *= 256.0;
+            shift += 1;
+        }
+        while shift > 29 {
+            difficulty /= 256.0;
+            shift -= 1;
+        }
+        difficulty
+    }
+
+    pub fn check_pow(&self) -> bool {
+        match self.target() {
+            Ok(target) if !target.is_zero() => U256::from_be_slice(&self.hash()).unwrap() <= target,
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for BlockHeader {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_bytes(&hex::decode(s)?)
+    }
+}
+
+impl fmt::Display for BlockHeader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", hex::encode(&self.serialize()))
+    }
+}
+
+#[inline]
+pub fn merkle_parent(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut data = [*left, *right];
+    data.iter_mut().for_each(|hash| hash.reverse());
+    hash256(data.as_flattened())
+}
+
+pub fn merkle_root(hashes: &[[u8; 32]]) -> ([u8; 32], bool) {
+    let mut level = hashes.to_vec();
+    let mut mutated = false;
+    if level.is_empty() {
+        return ([0u8; 32], mutated);
+    }
+    while level.len() > 1 {
+        mutated |= level.chunks_exact(2).any(|pair| pair[0] == pair[1]);
+        if level.len() % 2 == 1 {
+            level.push(level[level.len() - 1]);
+        }
+        level = level
+            .chunks_exact(2)
+            .map(|pair| merkle_parent(&pair[0], &pair[1]))
+            .collect();
+    }
+    (level[0], mutated)
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Block {
+    header: BlockHeader,
+    txs: Vec<Tx>,
+}
+
+impl Block {
+    #[inline]
+    pub fn new(header: BlockHeader, txs: Vec<Tx>) -> Self {
+        Self { header, txs }
+    }
+
+    #[inline]
+    pub fn header(&self) -> &BlockHeader {
+        &self.header
+    }
+
+    #[inline]
+    pub fn txs(&self) -> &[Tx] {
+        &self.txs
+    }
+
+    #[inline]
+    pub fn hash(&self) -> [u8; 32] {
+        self.header.hash()
+    }
+
+    #[inline]
+    pub fn id(&self) -> String {
+        self.header.id()
+    }
+
+    #[inline]
+    pub fn weight(&self) -> usize {
+        self.txs.iter().map(Tx::weight).sum::<usize>()
+            + (BLOCK_HEADER_SIZE + encode_varint(self.txs.len() as u64).len()) * 4
+    }
+
+    pub fn parse(reader: &mut impl Read) -> Result<Self, String> {
+        let header = BlockHeader::parse(reader)?;
+        let txs = (0..read_varint(reader)?)
+            .map(|_| Tx::parse(reader))
+            .collect::<Result<Vec<Tx>, String>>()?;
+        Ok(Self { header, txs })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = self.header.serialize().to_vec();
+        result.extend(encode_varint(self.txs.len() as u64));
+        for tx in &self.txs {
+            result.extend(tx.serialize());
+        }
+        result
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let mut cursor = Cursor::new(bytes);
+        let block = Self::parse(&mut cursor)?;
+        if cursor.position() as usize != bytes.len() {
+            return Err("Trailing data after block.".to_string());
+        }
+        Ok(block)
+    }
+
+    #[inline]
+    pub fn compute_merkle_root(&self) -> ([u8; 32], bool) {
+        let hashes: Vec<[u8; 32]> = self.txs.iter().map(Tx::hash).collect();
+        merkle_root(&hashes)
+    }
+
+    pub fn witness_root(&self) -> [u8; 32] {
+        let hashes: Vec<[u8; 32]> = self
+            .txs
+            .iter()
+            .enumerate()
+            .map(|(index, tx)| match index {
+                0 => [0u8; 32],
+                _ => tx.witness_hash(),
+            })
+            .collect();
+        merkle_root(&hashes).0
+    }
+
+    pub fn witness_commitment(&self) -> Option<[u8; 32]> {
+        self.txs
+            .first()?
+            .outputs()
+            .iter()
+            .rev()
+            .map(|output| output.script_pubkey().as_bytes())
+            .find(|script| script.len() >= 38 && script.starts_with(&WITNESS_COMMITMENT_HEADER))
+            .map(|script| script[6..38].try_into().unwrap())
+    }
+
+    pub fn check_merkle_root(&self) -> Result<(), String> {
+        if !self.txs.first().is_some_and(Tx::is_coinbase) {
+            return Err("First transaction is not a coinbase.".to_string());
+        }
+        if self.txs[1..].iter().any(Tx::is_coinbase) {
+            return Err("Block has more than one coinbase.".to_string());
+        }
+        let (root, mutated) = self.compute_merkle_root();
+        if mutated {
+            return Err("Duplicate transactions in merkle tree.".to_string());
+        }
+        if root != self.header.merkle_root() {
+            return Err(format!(
+                "Merkle root mismatch: header has {}, transactions give {}.",
+                hex::encode(&self.header.merkle_root()),
+                hex::encode(&root)
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn check_witness_commitment(&self) -> Result<(), String> {
+        let Some(commitment) = self.witness_commitment() else {
+            if self.txs.iter().any(Tx::is_segwit) {
+                return Err("Witness data without a witness commitment.".to_string());
+            }
+            return Ok(());
+        };
+        let reserved = match self.txs[0].inputs().first().map(TxIn::witness) {
+            Some([reserved]) if reserved.len() == 32 => reserved,
+            _ => return Err("Coinbase witness must be a single 32 byte value.".to_string()),
+        };
+        let mut witness_root = self.witness_root();
+        witness_root.reverse();
+        if DoubleSha256::compute(&[&witness_root[..], reserved].concat()) != commitment {
+            return Err("Witness commitment mismatch.".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Block {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_bytes(&hex::decode(s)?)
+    }
+}
+
+impl fmt::Display for Block {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", hex::encode(&self.serialize()))
+    }
+}
@@ -0,0 +1,324 @@
+use super::address::{p2pkh_script, script_from_address};
+use super::coin_selection::{
+    fee_for_weight, select_coins, Selection, SelectionParams, WeightedUtxo, P2WPKH_INPUT_WEIGHT,
+};
+use super::network::Network;
+use super::s256ecc::S256PrivateKey;
+use super::script::Script;
+use super::sha256ser::Sha256Ripemd160;
+use super::sighash::{SighashCache, SIGHASH_ALL, SIGHASH_DEFAULT};
+use super::tx::{OutPoint, Tx, TxIn, TxOut};
+use super::weight::{estimate_fee, InputType};
+use crate::ser::chained_hash::ChainedCompute;
+use bnum::types::U256;
+use rand::Rng;
+
+pub const DUST_LIMIT: u64 = 546;
+pub const SEQUENCE_RBF: u32 = 0xffff_fffd;
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Utxo {
+    outpoint: OutPoint,
+    amount: u64,
+    script_pubkey: Script,
+}
+
+impl Utxo {
+    #[inline]
+    pub fn new(outpoint: OutPoint, amount: u64, script_pubkey: Script) -> Self {
+        Self {
+            outpoint,
+            amount,
+            script_pubkey,
+        }
+    }
+
+    #[inline]
+    pub fn outpoint(&self) -> OutPoint {
+        self.outpoint
+    }
+
+    #[inline]
+    pub fn amount(&self) -> u64 {
+        self.amount
+    }
+
+    #[inline]
+    pub fn script_pubkey(&self) -> &Script {
+        &self.script_pubkey
+    }
+
+    #[inline]
+    pub fn to_tx_out(&self) -> TxOut {
+        TxOut::new(self.amount, self.script_pubkey.clone())
+    }
+}
+
+#[inline]
+fn ecdsa_signature(key: &S256PrivateKey, z: U256) -> Vec<u8> {
+    [key.sign(z).der_encoded(), vec![SIGHASH_ALL as u8]].concat()
+}
+
+fn p2wpkh_witness(
+    cache: &SighashCache,
+    input_index: usize,
+    key: &S256PrivateKey,
+) -> Result<Vec<Vec<u8>>, String> {
+    let script_code = Script::from(p2pkh_script(&key.point().hash160(true)));
+    let amount = cache.spent_outputs()[input_index].amount();
+    let z = cache.segwit_v0_sighash(input_index, &script_code, amount, SIGHASH_ALL)?;
+    Ok(vec![ecdsa_signature(key, z), key.point().sec(true)])
+}
+
+fn sign_input(
+    cache: &SighashCache,
+    input_index: usize,
+    keys: &[S256PrivateKey],
+) -> Result<(Script, Vec<Vec<u8>>), String> {
+    let script_pubkey = cache.spent_outputs()[input_index].script_pubkey();
+    let bytes = script_pubkey.as_bytes();
+    let missing_key = || format!("No key for input {}: {}", input_index, script_pubkey);
+
+    if script_pubkey.is_p2pkh() {
+        let (key, compressed) = [true, false]
+            .into_iter()
+            .find_map(|compressed| {
+                keys.iter()
+                    .find(|key| key.point().hash160(compressed) == bytes[3..23])
+                    .map(|key| (key, compressed))
+            })
+            .ok_or_else(missing_key)?;
+        let z = cache
+            .tx()
+            .legacy_sighash(input_index, script_pubkey, SIGHASH_ALL);
+        let mut script_sig = Script::new();
+        script_sig
+            .push_data(&ecdsa_signature(key, z))
+            .push_data(&key.point().sec(compressed));
+        Ok((script_sig, Vec::new()))
+    } else if script_pubkey.is_p2wpkh() {
+        let key = keys
+            .iter()
+            .find(|key| key.point().hash160(true) == bytes[2..22])
+            .ok_or_else(missing_key)?;
+        Ok((Script::new(), p2wpkh_witness(cache, input_index, key)?))
+    } else if script_pubkey.is_p2sh() {
+        let key = keys
+            .iter()
+            .find(|key| {
+                Sha256Ripemd160::compute(&key.point().p2sh_p2wpkh_redeem_script()) == bytes[2..22]
+            })
+            .ok_or_else(missing_key)?;
+        let mut script_sig = Script::new();
+        script_sig.push_data(&key.point().p2sh_p2wpkh_redeem_script());
+        Ok((script_sig, p2wpkh_witness(cache, input_index, key)?))
+    } else if script_pubkey.is_p2tr() {
+        let key = keys
+            .iter()
+            .find(|key| {
+                key.point()
+                    .tap_tweak(None)
+                    .is_ok_and(|output_key| output_key.xonly() == bytes[2..34])
+            })
+            .ok_or_else(missing_key)?;
+        let msg = cache.taproot_key_spend_sighash(input_index, SIGHASH_DEFAULT, None)?;
+        let signature = key.tap_tweak(None)?.sign_schnorr(&msg, &rand::random());
+        Ok((Script::new(), vec![signature.to_vec()]))
+    } else {
+        Err(format!(
+            "Unsupported scriptPubKey for input {}: {}",
+            input_index, script_pubkey
+        ))
+    }
+}
+
+fn input_type(script_pubkey: &Script, keys: &[S256PrivateKey]) -> Result<InputType, String> {
+    if script_pubkey.is_p2sh() {
+        return Ok(InputType::P2shP2wpkh);
+    }
+    match InputType::from_script_pubkey(script_pubkey) {
+        Some(InputType::P2pkh)
+            if !keys
+                .iter()
+                .any(|key| key.point().hash160(true) == script_pubkey[3..23]) =>
+        {
+            Ok(InputType::P2pkhUncompressed)
+        }
+        Some(input_type) => Ok(input_type),
+        None => Err(format!("Unsupported scriptPubKey: {}", script_pubkey)),
+    }
+}
+
+pub fn sign_tx(
+    tx: &mut Tx,
+    spent_outputs: &[TxOut],
+    keys: &[S256PrivateKey],
+) -> Result<(), String> {
+    if spent_outputs.len() != tx.inputs().len() {
+        return Err(format!(
+            "Expected {} spent outputs, got {}.",
+            tx.inputs().len(),
+            spent_outputs.len()
+        ));
+    }
+    let unsigned = tx.clone();
+    let cache = SighashCache::with_spent_outputs(&unsigned, spent_outputs);
+    for (index, input) in tx.inputs_mut().iter_mut().enumerate() {
+        let (script_sig, witness) = sign_input(&cache, index, keys)?;
+        input.set_script_sig(script_sig);
+        input.set_witness(witness);
+    }
+    Ok(())
+}
+
+pub struct TxBuilder {
+    network: Network,
+    utxos: Vec<Utxo>,
+    outputs: Vec<TxOut>,
+    change_script: Option<Script>,
+    fee_rate: u64,
+    version: u32,
+    locktime: u32,
+    sequence: u32,
+}
+
+impl TxBuilder {
+    #[inline]
+    pub fn new(network: Network) -> Self {
+        Self {
+            network,
+            utxos: Vec::new(),
+            outputs: Vec::new(),
+            change_script: None,
+            fee_rate: 1,
+            version: 2,
+            locktime: 0,
+            sequence: SEQUENCE_RBF,
+        }
+    }
+
+    #[inline]
+    pub fn add_utxo(&mut self, utxo: Utxo) -> &mut Self {
+        self.utxos.push(utxo);
+        self
+    }
+
+    #[inline]
+    pub fn add_output(&mut self, output: TxOut) -> &mut Self {
+        self.outputs.push(output);
+        self
+    }
+
+    pub fn add_recipient(&mut self, address: &str, amount: u64) -> Result<&mut Self, String> {
+        if amount < DUST_LIMIT {
+            return Err(format!("Output amount {} is below the dust limit.", amount));
+        }
+        let script_pubkey = Script::from(script_from_address(address, self.network)?);
+        Ok(self.add_output(TxOut::new(amount, script_pubkey)))
+    }
+
+    pub fn change_address(&mut self, address: &str) -> Result<&mut Self, String> {
+        self.change_script = Some(Script::from(script_from_address(address, self.network)?));
+        Ok(self)
+    }
+
+    #[inline]
+    pub fn fee_rate(&mut self, sat_per_vbyte: u64) -> &mut Self {
+        self.fee_rate = sat_per_vbyte;
+        self
+    }
+
+    #[inline]
+    pub fn version(&mut self, version: u32) -> &mut Self {
+        self.version = version;
+        self
+    }
+
+    #[inline]
+    pub fn locktime(&mut self, locktime: u32) -> &mut Self {
+        self.locktime = locktime;
+        self
+    }
+
+    #[inline]
+    pub fn sequence(&mut self, sequence: u32) -> &mut Self {
+        self.sequence = sequence;
+        self
+    }
+
+    pub fn select_utxos(
+        &mut self,
+        candidates: &[WeightedUtxo],
+        rng: &mut impl Rng,
+    ) -> Result<Selection, String> {
+        let base = Tx::new(
+            self.version,
+            Vec::new(),
+            self.outputs.clone(),
+            self.locktime,
+        );
+        let base_weight = base.serialize_legacy().len() as u64 * 4 + 2;
+        let target = base.output_value() + fee_for_weight(base_weight, self.fee_rate);
+        let mut params = SelectionParams::new(self.fee_rate);
+        match &self.change_script {
+            Some(change_script) => {
+                let output_weight =
+                    TxOut::new(0, change_script.clone()).serialize().len() as u64 * 4;
+                params.change_weights(output_weight, P2WPKH_INPUT_WEIGHT);
+            }
+            None => {
+                params.min_change(u64::MAX);
+            }
+        }
+        let selection = select_coins(candidates, target, &params, rng)?;
+        self.utxos
+            .extend(selection.inputs().iter().map(|utxo| utxo.utxo().clone()));
+        Ok(selection)
+    }
+
+    fn assemble(&self, change: u64) -> Tx {
+        let inputs = self
+            .utxos
+            .iter()
+            .map(|utxo| TxIn::new(utxo.outpoint, Script::new(), self.sequence))
+            .collect();
+        let mut outputs = self.outputs.clone();
+        if let Some(change_script) = &self.change_script {
+            if change >= DUST_LIMIT {
+                outputs.push(TxOut::new(change, change_script.clone()));
+            }
+        }
+        Tx::new(self.version, inputs, outputs, self.locktime)
+    }
+
+    pub fn build_and_sign(&self, keys: &[S256PrivateKey]) -> Result<Tx, String> {
+        if self.utxos.is_empty() || self.outputs.is_empty() {
+            return Err("Transaction needs at least one input and one output.".to_string());
+        }
+        let input_types = self
+            .utxos
+            .iter()
+            .map(|utxo| input_type(utxo.script_pubkey(), keys))
+            .collect::<Result<Vec<InputType>, String>>()?;
+        let input_value: u64 = self.utxos.iter().map(|utxo| utxo.amount).sum();
+        let output_value: u64 = self.outputs.iter().map(|output| output.amount()).sum();
+        let needed = output_value + estimate_fee(&input_types, &self.outputs, self.fee_rate);
+        if input_value < needed {
+            return Err(format!(
+                "Insufficient funds: have {} sats, need {}.",
+                input_value, needed
+            ));
+        }
+        let mut change = 0;
+        if let Some(change_script) = &self.change_script {
+            let mut outputs = self.outputs.clone();
+            outputs.push(TxOut::new(0, change_script.clone()));
+            let fee = estimate_fee(&input_types, &outputs, self.fee_rate);
+            change = input_value.saturating_sub(output_value + fee);
+        }
+        let mut tx = self.assemble(change);
+        let spent_outputs: Vec<TxOut> = self.utxos.iter().map(Utxo::to_tx_out).collect();
+        sign_tx(&mut tx, &spent_outputs, keys)?;
+        Ok(tx)
+    }
+}
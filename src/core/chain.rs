@@ -0,0 +1,296 @@
+use super::block::{bits_to_target, target_to_bits, BlockHeader};
+use super::network::Network;
+use crate::ser::hex;
+use bnum::types::U256;
+use std::collections::HashMap;
+
+pub const DIFFICULTY_ADJUSTMENT_INTERVAL: u32 = 2016;
+pub const TARGET_SPACING: u32 = 10 * 60;
+pub const TARGET_TIMESPAN: u32 = DIFFICULTY_ADJUSTMENT_INTERVAL * TARGET_SPACING;
+pub const MEDIAN_TIME_SPAN: usize = 11;
+pub const MAX_FUTURE_BLOCK_TIME: u32 = 2 * 60 * 60;
+pub const MAX_TIMEWARP: u32 = 600;
+
+pub fn block_work(bits: u32) -> U256 {
+    match bits_to_target(bits) {
+        Ok(target) if !target.is_zero() => (!target) / (target + U256::ONE) + U256::ONE,
+        _ => U256::ZERO,
+    }
+}
+
+pub fn calculate_next_bits(bits: u32, first_time: u32, last_time: u32, pow_limit_bits: u32) -> u32 {
+    let timespan = (last_time as i64 - first_time as i64)
+        .clamp(TARGET_TIMESPAN as i64 / 4, TARGET_TIMESPAN as i64 * 4);
+    let limit = bits_to_target(pow_limit_bits).unwrap_or(U256::MAX);
+    let target = bits_to_target(bits)
+        .ok()
+        .and_then(|target| target.checked_mul(U256::from(timespan as u64)))
+        .map_or(limit, |target| target / U256::from(TARGET_TIMESPAN));
+    target_to_bits(target.min(limit))
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct ChainEntry {
+    header: BlockHeader,
+    height: u32,
+    chainwork: U256,
+}
+
+impl ChainEntry {
+    #[inline]
+    pub fn header(&self) -> &BlockHeader {
+        &self.header
+    }
+
+    #[inline]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    #[inline]
+    pub fn chainwork(&self) -> U256 {
+        self.chainwork
+    }
+
+    #[inline]
+    pub fn hash(&self) -> [u8; 32] {
+        self.header.hash()
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum ChainUpdate {
+    AlreadyKnown,
+    Extended,
+    SideChain,
+    Reorg {
+        disconnected: Vec<[u8; 32]>,
+        connected: Vec<[u8; 32]>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct HeaderChain {
+    network: Network,
+    entries: HashMap<[u8; 32], ChainEntry>,
+    best_chain: Vec<[u8; 32]>,
+}
+
+impl HeaderChain {
+    pub fn new(network: Network) -> Self {
+        let genesis = network.genesis_header();
+        let hash = genesis.hash();
+        let entry = ChainEntry {
+            header: genesis,
+            height: 0,
+            chainwork: block_work(genesis.bits()),
+        };
+        Self {
+            network,
+            entries: HashMap::from([(hash, entry)]),
+            best_chain: vec![hash],
+        }
+    }
+
+    #[inline]
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    #[inline]
+    pub fn height(&self) -> u32 {
+        self.best_chain.len() as u32 - 1
+    }
+
+    #[inline]
+    pub fn tip(&self) -> &ChainEntry {
+        &self.entries[&self.best_chain[self.best_chain.len() - 1]]
+    }
+
+    #[inline]
+    pub fn get(&self, hash: &[u8; 32]) -> Option<&ChainEntry> {
+        self.entries.get(hash)
+    }
+
+    #[inline]
+    pub fn contains(&self, hash: &[u8; 32]) -> bool {
+        self.entries.contains_key(hash)
+    }
+
+    #[inline]
+    pub fn hash_at(&self, height: u32) -> Option<[u8; 32]> {
+        self.best_chain.get(height as usize).copied()
+    }
+
+    #[inline]
+    pub fn entry_at(&self, height: u32) -> Option<&ChainEntry> {
+        self.hash_at(height).map(|hash| &self.entries[&hash])
+    }
+
+    #[inline]
+    pub fn is_in_best_chain(&self, hash: &[u8; 32]) -> bool {
+        self.entries
+            .get(hash)
+            .is_some_and(|entry| self.hash_at(entry.height) == Some(*hash))
+    }
+
+    pub fn ancestor(&self, hash: &[u8; 32], height: u32) -> Option<&ChainEntry> {
+        let mut entry = self.entries.get(hash)?;
+        if height > entry.height {
+            return None;
+        }
+        while entry.height > height {
+            if self.is_in_best_chain(&entry.hash()) {
+                return self.entry_at(height);
+            }
+            entry = &self.entries[&entry.header.prev_block()];
+        }
+        Some(entry)
+    }
+
+    pub fn median_time_past(&self, hash: &[u8; 32]) -> Option<u32> {
+        let mut timestamps: Vec<u32> = std::iter::successors(self.entries.get(hash), |entry| {
+            self.entries.get(&entry.header.prev_block())
+        })
+        .take(MEDIAN_TIME_SPAN)
+        .map(|entry| entry.header.timestamp())
+        .collect();
+        timestamps.sort_unstable();
+        timestamps.get(timestamps.len() / 2).copied()
+    }
+
+    pub fn next_bits(&self, parent: &ChainEntry, timestamp: u32) -> u32 {
+        let pow_limit_bits = self.network.pow_limit_bits();
+        let height = parent.height + 1;
+        if !height.is_multiple_of(DIFFICULTY_ADJUSTMENT_INTERVAL) {
+            if !self.network.allow_min_difficulty_blocks() {
+                return parent.header.bits();
+            }
+            if timestamp > parent.header.timestamp().saturating_add(2 * TARGET_SPACING) {
+                return pow_limit_bits;
+            }
+            let mut entry = parent;
+            while !entry.height.is_multiple_of(DIFFICULTY_ADJUSTMENT_INTERVAL)
+                && entry.header.bits() == pow_limit_bits
+            {
+                entry = &self.entries[&entry.header.prev_block()];
+            }
+            return entry.header.bits();
+        }
+        if self.network.no_pow_retargeting() {
+            return parent.header.bits();
+        }
+        let first = self
+            .ancestor(&parent.hash(), height - DIFFICULTY_ADJUSTMENT_INTERVAL)
+            .unwrap();
+        let bits = match self.network.enforce_bip94() {
+            true => first.header.bits(),
+            false => parent.header.bits(),
+        };
+        calculate_next_bits(
+            bits,
+            first.header.timestamp(),
+            parent.header.timestamp(),
+            pow_limit_bits,
+        )
+    }
+
+    fn check_header(
+        &self,
+        header: &BlockHeader,
+        parent: &ChainEntry,
+        now: u32,
+    ) -> Result<(), String> {
+        if !header.check_pow() {
+            return Err(format!("Header {} fails proof of work.", header.id()));
+        }
+        let expected_bits = self.next_bits(parent, header.timestamp());
+        if header.bits() != expected_bits {
+            return Err(format!(
+                "Header {} has bits {:#010x}, expected {:#010x}.",
+                header.id(),
+                header.bits(),
+                expected_bits
+            ));
+        }
+        let median_time_past = self.median_time_past(&parent.hash()).unwrap();
+        if header.timestamp() <= median_time_past {
+            return Err(format!(
+                "Header {} timestamp is not after median time past {}.",
+                header.id(),
+                median_time_past
+            ));
+        }
+        if header.timestamp() > now.saturating_add(MAX_FUTURE_BLOCK_TIME) {
+            return Err(format!("Header {} is too far in the future.", header.id()));
+        }
+        if self.network.enforce_bip94()
+            && (parent.height + 1).is_multiple_of(DIFFICULTY_ADJUSTMENT_INTERVAL)
+            && header.timestamp() < parent.header.timestamp().saturating_sub(MAX_TIMEWARP)
+        {
+            return Err(format!("Header {} is a time warp.", header.id()));
+        }
+        Ok(())
+    }
+
+    pub fn accept_header(&mut self, header: BlockHeader, now: u32) -> Result<ChainUpdate, String> {
+        let hash = header.hash();
+        if self.entries.contains_key(&hash) {
+            return Ok(ChainUpdate::AlreadyKnown);
+        }
+        let parent = self.entries.get(&header.prev_block()).ok_or_else(|| {
+            format!(
+                "Header {} builds on unknown block {}.",
+                header.id(),
+                hex::encode(&header.prev_block())
+            )
+        })?;
+        self.check_header(&header, parent, now)?;
+        let entry = ChainEntry {
+            header,
+            height: parent.height + 1,
+            chainwork: parent.chainwork + block_work(header.bits()),
+        };
+        let extends_tip = header.prev_block() == self.tip().hash();
+        let more_work = entry.chainwork > self.tip().chainwork;
+        self.entries.insert(hash, entry);
+        if !more_work {
+            return Ok(ChainUpdate::SideChain);
+        }
+        if extends_tip {
+            self.best_chain.push(hash);
+            return Ok(ChainUpdate::Extended);
+        }
+
+        let mut connected = Vec::new();
+        let mut cursor = hash;
+        while !self.is_in_best_chain(&cursor) {
+            connected.push(cursor);
+            cursor = self.entries[&cursor].header.prev_block();
+        }
+        connected.reverse();
+        let fork_height = self.entries[&cursor].height;
+        let mut disconnected = self.best_chain.split_off(fork_height as usize + 1);
+        disconnected.reverse();
+        self.best_chain.extend(&connected);
+        Ok(ChainUpdate::Reorg {
+            disconnected,
+            connected,
+        })
+    }
+
+    pub fn locator(&self) -> Vec<[u8; 32]> {
+        let mut locator = Vec::new();
+        let mut height = self.height() as i64;
+        let mut step = 1;
+        while height > 0 {
+            locator.push(self.best_chain[height as usize]);
+            if locator.len() > 10 {
+                step *= 2;
+            }
+            height -= step;
+        }
+        locator.push(self.best_chain[0]);
+        locator
+    }
+}
@@ -0,0 +1,486 @@
+use super::builder::{Utxo, DUST_LIMIT};
+use super::weight::InputType;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::cmp::Reverse;
+
+pub const BNB_MAX_TRIES: usize = 100_000;
+pub const KNAPSACK_ITERATIONS: usize = 1_000;
+pub const P2WPKH_INPUT_WEIGHT: u64 = 272;
+pub const P2WPKH_OUTPUT_WEIGHT: u64 = 124;
+pub const DEFAULT_LONG_TERM_FEE_RATE: u64 = 10;
+
+#[inline]
+pub fn fee_for_weight(weight: u64, fee_rate: u64) -> u64 {
+    (weight * fee_rate).div_ceil(4)
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum CoinSelectionAlgorithm {
+    BranchAndBound,
+    Knapsack,
+    LargestFirst,
+    SingleRandomDraw,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct WeightedUtxo {
+    utxo: Utxo,
+    input_weight: u64,
+}
+
+impl WeightedUtxo {
+    #[inline]
+    pub fn new(utxo: Utxo, input_weight: u64) -> Self {
+        Self { utxo, input_weight }
+    }
+
+    #[inline]
+    pub fn from_input_type(utxo: Utxo, input_type: InputType) -> Self {
+        Self::new(utxo, input_type.weight())
+    }
+
+    #[inline]
+    pub fn utxo(&self) -> &Utxo {
+        &self.utxo
+    }
+
+    #[inline]
+    pub fn input_weight(&self) -> u64 {
+        self.input_weight
+    }
+
+    #[inline]
+    pub fn fee(&self, fee_rate: u64) -> u64 {
+        fee_for_weight(self.input_weight, fee_rate)
+    }
+
+    #[inline]
+    pub fn effective_value(&self, fee_rate: u64) -> Option<u64> {
+        self.utxo
+            .amount()
+            .checked_sub(self.fee(fee_rate))
+            .filter(|value| *value > 0)
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct SelectionParams {
+    fee_rate: u64,
+    long_term_fee_rate: u64,
+    change_output_weight: u64,
+    change_spend_weight: u64,
+    min_change: u64,
+}
+
+impl SelectionParams {
+    #[inline]
+    pub fn new(fee_rate: u64) -> Self {
+        Self {
+            fee_rate,
+            long_term_fee_rate: DEFAULT_LONG_TERM_FEE_RATE,
+            change_output_weight: P2WPKH_OUTPUT_WEIGHT,
+            change_spend_weight: P2WPKH_INPUT_WEIGHT,
+            min_change: DUST_LIMIT,
+        }
+    }
+
+    #[inline]
+    pub fn long_term_fee_rate(&mut self, sat_per_vbyte: u64) -> &mut Self {
+        self.long_term_fee_rate = sat_per_vbyte;
+        self
+    }
+
+    #[inline]
+    pub fn change_weights(&mut self, output_weight: u64, spend_weight: u64) -> &mut Self {
+        self.change_output_weight = output_weight;
+        self.change_spend_weight = spend_weight;
+        self
+    }
+
+    #[inline]
+    pub fn min_change(&mut self, amount: u64) -> &mut Self {
+        self.min_change = amount.max(DUST_LIMIT);
+        self
+    }
+
+    #[inline]
+    pub fn change_fee(&self) -> u64 {
+        fee_for_weight(self.change_output_weight, self.fee_rate)
+    }
+
+    #[inline]
+    pub fn cost_of_change(&self) -> u64 {
+        self.change_fee() + fee_for_weight(self.change_spend_weight, self.long_term_fee_rate)
+    }
+
+    #[inline]
+    fn input_waste(&self, utxo: &WeightedUtxo) -> i64 {
+        utxo.fee(self.fee_rate) as i64 - utxo.fee(self.long_term_fee_rate) as i64
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Selection {
+    inputs: Vec<WeightedUtxo>,
+    effective_value: u64,
+    change: u64,
+    waste: i64,
+    algorithm: CoinSelectionAlgorithm,
+}
+
+impl Selection {
+    fn new(
+        inputs: Vec<WeightedUtxo>,
+        target: u64,
+        params: &SelectionParams,
+        algorithm: CoinSelectionAlgorithm,
+    ) -> Self {
+        let effective_value = inputs
+            .iter()
+            .filter_map(|utxo| utxo.effective_value(params.fee_rate))
+            .sum::<u64>();
+        let input_waste: i64 = inputs.iter().map(|utxo| params.input_waste(utxo)).sum();
+        let excess = effective_value - target;
+        let change = match algorithm {
+            CoinSelectionAlgorithm::BranchAndBound => None,
+            _ => excess
+                .checked_sub(params.change_fee())
+                .filter(|change| *change >= params.min_change),
+        };
+        let (change, waste) = match change {
+            Some(change) => (change, input_waste + params.cost_of_change() as i64),
+            None => (0, input_waste + excess as i64),
+        };
+        Self {
+            inputs,
+            effective_value,
+            change,
+            waste,
+            algorithm,
+        }
+    }
+
+    #[inline]
+    pub fn inputs(&self) -> &[WeightedUtxo] {
+        &self.inputs
+    }
+
+    #[inline]
+    pub fn input_value(&self) -> u64 {
+        self.inputs.iter().map(|utxo| utxo.utxo.amount()).sum()
+    }
+
+    #[inline]
+    pub fn effective_value(&self) -> u64 {
+        self.effective_value
+    }
+
+    #[inline]
+    pub fn change(&self) -> Option<u64> {
+        (self.change > 0).then_some(self.change)
+    }
+
+    #[inline]
+    pub fn waste(&self) -> i64 {
+        self.waste
+    }
+
+    #[inline]
+    pub fn algorithm(&self) -> CoinSelectionAlgorithm {
+        self.algorithm
+    }
+}
+
+fn insufficient_funds(available: u64, target: u64) -> String {
+    format!(
+        "Insufficient funds: {} sats of effective value available, need {}.",
+        available, target
+    )
+}
+
+fn effective_pool(candidates: &[WeightedUtxo], fee_rate: u64) -> Vec<(u64, &WeightedUtxo)> {
+    candidates
+        .iter()
+        .filter_map(|utxo| utxo.effective_value(fee_rate).map(|value| (value, utxo)))
+        .collect()
+}
+
+fn collect(
+    pool: &[(u64, &WeightedUtxo)],
+    indices: impl IntoIterator<Item = usize>,
+) -> Vec<WeightedUtxo> {
+    indices
+        .into_iter()
+        .map(|index| pool[index].1.clone())
+        .collect()
+}
+
+pub fn branch_and_bound(
+    candidates: &[WeightedUtxo],
+    target: u64,
+    params: &SelectionParams,
+) -> Result<Selection, String> {
+    let mut pool = effective_pool(candidates, params.fee_rate);
+    pool.sort_by_key(|(value, _)| Reverse(*value));
+    let mut available: u64 = pool.iter().map(|(value, _)| value).sum();
+    if available < target {
+        return Err(insufficient_funds(available, target));
+    }
+    let upper_bound = target + params.cost_of_change();
+    let waste_of = |index: usize| params.input_waste(pool[index].1);
+    let fee_of = |index: usize| pool[index].1.fee(params.fee_rate);
+    let high_fee_rate = params.fee_rate > params.long_term_fee_rate;
+
+    let mut value = 0;
+    let mut waste = 0i64;
+    let mut selection: Vec<usize> = Vec::new();
+    let mut best: Option<(Vec<usize>, i64)> = None;
+    let mut index = 0;
+    for _ in 0..BNB_MAX_TRIES {
+        let best_waste = best.as_ref().map_or(i64::MAX, |(_, waste)| *waste);
+        let mut backtrack = false;
+        if value + available < target
+            || value > upper_bound
+            || (high_fee_rate && waste > best_waste)
+        {
+            backtrack = true;
+        } else if value >= target {
+            let total_waste = waste + (value - target) as i64;
+            if total_waste <= best_waste {
+                best = Some((selection.clone(), total_waste));
+            }
+            backtrack = true;
+        }
+
+        if backtrack {
+            let Some(&last) = selection.last() else {
+                break;
+            };
+            index -= 1;
+            while index > last {
+                available += pool[index].0;
+                index -= 1;
+            }
+            value -= pool[index].0;
+            waste -= waste_of(index);
+            selection.pop();
+        } else {
+            available -= pool[index].0;
+            if selection.is_empty()
+                || selection.last() == Some(&(index - 1))
+                || pool[index].0 != pool[index - 1].0
+                || fee_of(index) != fee_of(index - 1)
+            {
+                selection.push(index);
+                value += pool[index].0;
+                waste += waste_of(index);
+            }
+        }
+        index += 1;
+    }
+
+    let (indices, _) = best.ok_or_else(|| "No changeless solution found.".to_string())?;
+    Ok(Selection::new(
+        collect(&pool, indices),
+        target,
+        params,
+        CoinSelectionAlgorithm::BranchAndBound,
+    ))
+}
+
+fn approximate_best_subset(
+    values: &[u64],
+    total: u64,
+    target: u64,
+    rng: &mut impl Rng,
+) -> (Vec<bool>, u64) {
+    let mut best = vec![true; values.len()];
+    let mut best_value = total;
+    for _ in 0..KNAPSACK_ITERATIONS {
+        if best_value == target {
+            break;
+        }
+        let mut included = vec![false; values.len()];
+        let mut value = 0;
+        let mut reached_target = false;
+        for pass in 0..2 {
+            if reached_target {
+                break;
+            }
+            for i in 0..values.len() {
+                let pick = if pass == 0 {
+                    rng.random_bool(0.5)
+                } else {
+                    !included[i]
+                };
+                if !pick {
+                    continue;
+                }
+                value += values[i];
+                included[i] = true;
+                if value >= target {
+                    reached_target = true;
+                    if value < best_value {
+                        best_value = value;
+                        best = included.clone();
+                    }
+                    value -= values[i];
+                    included[i] = false;
+                }
+            }
+        }
+    }
+    (best, best_value)
+}
+
+pub fn knapsack(
+    candidates: &[WeightedUtxo],
+    target: u64,
+    params: &SelectionParams,
+    rng: &mut impl Rng,
+) -> Result<Selection, String> {
+    let mut pool = effective_pool(candidates, params.fee_rate);
+    pool.shuffle(rng);
+    let change_target = params.change_fee().saturating_add(params.min_change);
+    let finish = |indices: Vec<usize>, pool: &[(u64, &WeightedUtxo)]| {
+        Selection::new(
+            collect(pool, indices),
+            target,
+            params,
+            CoinSelectionAlgorithm::Knapsack,
+        )
+    };
+
+    let mut smaller = Vec::new();
+    let mut smaller_total = 0;
+    let mut lowest_larger: Option<usize> = None;
+    for (index, (value, _)) in pool.iter().enumerate() {
+        if *value == target {
+            return Ok(finish(vec![index], &pool));
+        } else if *value < target.saturating_add(change_target) {
+            smaller.push(index);
+            smaller_total += value;
+        } else if lowest_larger.is_none_or(|lowest| *value < pool[lowest].0) {
+            lowest_larger = Some(index);
+        }
+    }
+
+    if smaller_total == target {
+        return Ok(finish(smaller, &pool));
+    }
+    if smaller_total < target {
+        return match lowest_larger {
+            Some(index) => Ok(finish(vec![index], &pool)),
+            None => Err(insufficient_funds(smaller_total, target)),
+        };
+    }
+
+    smaller.sort_by(|a, b| pool[*b].0.cmp(&pool[*a].0));
+    let values: Vec<u64> = smaller.iter().map(|index| pool[*index].0).collect();
+    let (mut best, mut best_value) = approximate_best_subset(&values, smaller_total, target, rng);
+    if best_value != target && smaller_total >= target.saturating_add(change_target) {
+        (best, best_value) =
+            approximate_best_subset(&values, smaller_total, target + change_target, rng);
+    }
+
+    match lowest_larger {
+        Some(index)
+            if (best_value != target && best_value < target.saturating_add(change_target))
+                || pool[index].0 <= best_value =>
+        {
+            Ok(finish(vec![index], &pool))
+        }
+        _ => {
+            let indices = smaller
+                .into_iter()
+                .zip(best)
+                .filter_map(|(index, included)| included.then_some(index));
+            Ok(finish(indices.collect(), &pool))
+        }
+    }
+}
+
+fn accumulate(
+    pool: &[(u64, &WeightedUtxo)],
+    target: u64,
+    params: &SelectionParams,
+    algorithm: CoinSelectionAlgorithm,
+) -> Result<Selection, String> {
+    let change_target = (target + params.change_fee()).saturating_add(params.min_change);
+    let mut value = 0;
+    let mut count = 0;
+    for (effective_value, _) in pool {
+        if value >= change_target {
+            break;
+        }
+        value += effective_value;
+        count += 1;
+    }
+    if value < target {
+        return Err(insufficient_funds(value, target));
+    }
+    Ok(Selection::new(
+        collect(pool, 0..count),
+        target,
+        params,
+        algorithm,
+    ))
+}
+
+pub fn largest_first(
+    candidates: &[WeightedUtxo],
+    target: u64,
+    params: &SelectionParams,
+) -> Result<Selection, String> {
+    let mut pool = effective_pool(candidates, params.fee_rate);
+    pool.sort_by_key(|(value, _)| Reverse(*value));
+    accumulate(&pool, target, params, CoinSelectionAlgorithm::LargestFirst)
+}
+
+pub fn single_random_draw(
+    candidates: &[WeightedUtxo],
+    target: u64,
+    params: &SelectionParams,
+    rng: &mut impl Rng,
+) -> Result<Selection, String> {
+    let mut pool = effective_pool(candidates, params.fee_rate);
+    pool.shuffle(rng);
+    accumulate(
+        &pool,
+        target,
+        params,
+        CoinSelectionAlgorithm::SingleRandomDraw,
+    )
+}
+
+pub fn select_coins(
+    candidates: &[WeightedUtxo],
+    target: u64,
+    params: &SelectionParams,
+    rng: &mut impl Rng,
+) -> Result<Selection, String> {
+    let results = [
+        branch_and_bound(candidates, target, params),
+        knapsack(candidates, target, params, rng),
+        single_random_draw(candidates, target, params, rng),
+        largest_first(candidates, target, params),
+    ];
+    let mut error = None;
+    let mut best: Option<Selection> = None;
+    for result in results {
+        match result {
+            Ok(selection) => {
+                let better = best.as_ref().is_none_or(|best| {
+                    (selection.waste, Reverse(selection.inputs.len()))
+                        < (best.waste, Reverse(best.inputs.len()))
+                });
+                if better {
+                    best = Some(selection);
+                }
+            }
+            Err(e) => error = Some(e),
+        }
+    }
+    best.ok_or_else(|| error.unwrap_or_else(|| insufficient_funds(0, target)))
+}
@@ -0,0 +1,650 @@
+use super::address::{
+    address_from_script, p2pkh_script, p2sh_script, p2tr_script, p2wpkh_script, p2wsh_script,
+};
+use super::hd::{DerivationPath, ExtendedPrivateKey, ExtendedPublicKey, HARDENED};
+use super::network::Network;
+use super::s256ecc::S256Point;
+use super::sha256ser::Sha256Ripemd160;
+use super::taproot::{tap_branch_hash, tap_leaf_hash, TAPSCRIPT_LEAF_VERSION};
+use crate::ser::chained_hash::ChainedCompute;
+use crate::ser::hex;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+const INPUT_CHARSET: &str =
+    "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+const CHECKSUM_CHARSET: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const CHECKSUM_GENERATOR: [u64; 5] = [
+    0xf5dee51989,
+    0xa9fdca3312,
+    0x1bab10e32d,
+    0x3706b1677a,
+    0x644d626ffd,
+];
+
+const MAX_MULTISIG_KEYS: usize = 20;
+const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+const OP_CHECKSIG: u8 = 0xac;
+const OP_CHECKMULTISIG: u8 = 0xae;
+
+fn checksum_polymod(symbols: &[u64]) -> u64 {
+    let mut chk = 1u64;
+    for &value in symbols {
+        let top = chk >> 35;
+        chk = ((chk & 0x7ffffffff) << 5) ^ value;
+        for (i, generator) in CHECKSUM_GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                chk ^= generator;
+            }
+        }
+    }
+    chk
+}
+
+pub fn descriptor_checksum(s: &str) -> Result<String, String> {
+    let mut symbols = Vec::new();
+    let mut groups = Vec::new();
+    for c in s.chars() {
+        let value = INPUT_CHARSET
+            .find(c)
+            .ok_or(format!("Invalid character in descriptor: {}", c))? as u64;
+        symbols.push(value & 31);
+        groups.push(value >> 5);
+        if groups.len() == 3 {
+            symbols.push(groups[0] * 9 + groups[1] * 3 + groups[2]);
+            groups.clear();
+        }
+    }
+    match groups.len() {
+        1 => symbols.push(groups[0]),
+        2 => symbols.push(groups[0] * 3 + groups[1]),
+        _ => {}
+    }
+    symbols.extend([0u64; 8]);
+    let checksum = checksum_polymod(&symbols) ^ 1;
+    Ok((0..8)
+        .map(|i| {
+            let index = (checksum >> (5 * (7 - i))) & 31;
+            CHECKSUM_CHARSET.as_bytes()[index as usize] as char
+        })
+        .collect())
+}
+
+fn push_data(data: &[u8]) -> Vec<u8> {
+    debug_assert!(data.len() <= 75);
+    [&[data.len() as u8][..], data].concat()
+}
+
+fn push_int(n: usize) -> Vec<u8> {
+    match n {
+        0 => vec![0x00],
+        1..=16 => vec![0x50 + n as u8],
+        _ => vec![0x01, n as u8],
+    }
+}
+
+fn split_args(s: &str) -> Result<Vec<&str>, String> {
+    let mut args = Vec::new();
+    let mut depth = 0i32;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            ',' if depth == 0 => {
+                args.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+        if depth < 0 {
+            return Err(format!("Unbalanced brackets in descriptor: {}", s));
+        }
+    }
+    if depth != 0 {
+        return Err(format!("Unbalanced brackets in descriptor: {}", s));
+    }
+    args.push(&s[start..]);
+    Ok(args)
+}
+
+fn split_function(s: &str) -> Result<(&str, &str), String> {
+    let open = s
+        .find('(')
+        .ok_or(format!("Invalid descriptor expression: {}", s))?;
+    let inner = s[open + 1..]
+        .strip_suffix(')')
+        .ok_or(format!("Invalid descriptor expression: {}", s))?;
+    Ok((&s[..open], inner))
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+enum Context {
+    Top,
+    Sh,
+    SegwitV0,
+    Tap,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct KeyOrigin {
+    fingerprint: [u8; 4],
+    path: DerivationPath,
+}
+
+impl KeyOrigin {
+    #[inline]
+    pub fn new(fingerprint: [u8; 4], path: DerivationPath) -> Self {
+        Self { fingerprint, path }
+    }
+
+    #[inline]
+    pub fn fingerprint(&self) -> [u8; 4] {
+        self.fingerprint
+    }
+
+    #[inline]
+    pub fn path(&self) -> &DerivationPath {
+        &self.path
+    }
+}
+
+impl FromStr for KeyOrigin {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (fingerprint, path) = s.split_once('/').unwrap_or((s, ""));
+        let fingerprint = hex::decode(fingerprint)
+            .ok()
+            .and_then(|f| f.try_into().ok())
+            .ok_or(format!("Invalid key origin fingerprint: {}", fingerprint))?;
+        Ok(Self::new(fingerprint, path.parse()?))
+    }
+}
+
+impl fmt::Display for KeyOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let path = self.path.to_string();
+        write!(f, "{}{}", hex::encode(&self.fingerprint), &path[1..])
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Wildcard {
+    None,
+    Unhardened,
+    Hardened,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum KeyFormat {
+    Compressed,
+    Uncompressed,
+    XOnly,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum KeySource {
+    Single(S256Point, KeyFormat),
+    Xpub(ExtendedPublicKey, DerivationPath, Wildcard),
+    Xprv(ExtendedPrivateKey, DerivationPath, Wildcard),
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct DescriptorKey {
+    origin: Option<KeyOrigin>,
+    source: KeySource,
+}
+
+impl DescriptorKey {
+    #[inline]
+    pub fn origin(&self) -> Option<&KeyOrigin> {
+        self.origin.as_ref()
+    }
+
+    #[inline]
+    pub fn source(&self) -> &KeySource {
+        &self.source
+    }
+
+    #[inline]
+    pub fn is_ranged(&self) -> bool {
+        match &self.source {
+            KeySource::Single(..) => false,
+            KeySource::Xpub(_, _, wildcard) | KeySource::Xprv(_, _, wildcard) => {
+                *wildcard != Wildcard::None
+            }
+        }
+    }
+
+    fn child_path(
+        path: &DerivationPath,
+        wildcard: Wildcard,
+        index: u32,
+    ) -> Result<DerivationPath, String> {
+        if index >= HARDENED {
+            return Err(format!("Invalid derivation index: {}", index));
+        }
+        Ok(match wildcard {
+            Wildcard::None => path.clone(),
+            Wildcard::Unhardened => path.child(index),
+            Wildcard::Hardened => path.child(index + HARDENED),
+        })
+    }
+
+    pub fn point_at(&self, index: u32) -> Result<S256Point, String> {
+        match &self.source {
+            KeySource::Single(point, _) => Ok(*point),
+            KeySource::Xpub(xpub, path, wildcard) => Ok(xpub
+                .derive_path(&Self::child_path(path, *wildcard, index)?)?
+                .point()),
+            KeySource::Xprv(xprv, path, wildcard) => Ok(xprv
+                .derive_path(&Self::child_path(path, *wildcard, index)?)?
+                .point()),
+        }
+    }
+
+    fn key_bytes_at(&self, index: u32, context: Context) -> Result<Vec<u8>, String> {
+        let point = self.point_at(index)?;
+        Ok(match (context, &self.source) {
+            (Context::Tap, _) => point.xonly().to_vec(),
+            (_, KeySource::Single(_, KeyFormat::Uncompressed)) => point.sec(false),
+            _ => point.sec(true),
+        })
+    }
+
+    fn parse(s: &str, context: Context) -> Result<Self, String> {
+        let (origin, key) = match s.strip_prefix('[') {
+            Some(rest) => {
+                let (origin, key) = rest
+                    .split_once(']')
+                    .ok_or(format!("Invalid key origin: {}", s))?;
+                (Some(origin.parse::<KeyOrigin>()?), key)
+            }
+            None => (None, s),
+        };
+
+        let mut parts = key.split('/');
+        let base = parts.next().unwrap_or_default();
+        let mut children = parts.collect::<Vec<&str>>();
+        let wildcard = match children.last() {
+            Some(&"*") => Wildcard::Unhardened,
+            Some(&"*'") | Some(&"*h") | Some(&"*H") => Wildcard::Hardened,
+            _ => Wildcard::None,
+        };
+        if wildcard != Wildcard::None {
+            children.pop();
+        }
+        let path = children.join("/").parse::<DerivationPath>()?;
+
+        let source = if let Ok(xpub) = base.parse::<ExtendedPublicKey>() {
+            if wildcard == Wildcard::Hardened || path.children().iter().any(|&i| i >= HARDENED) {
+                return Err(format!(
+                    "Hardened derivation requires an extended private key: {}",
+                    key
+                ));
+            }
+            KeySource::Xpub(xpub, path, wildcard)
+        } else if let Ok(xprv) = base.parse::<ExtendedPrivateKey>() {
+            KeySource::Xprv(xprv, path, wildcard)
+        } else {
+            if key.contains('/') {
+                return Err(format!("Invalid extended key: {}", base));
+            }
+            let bytes = hex::decode(base)?;
+            match (bytes.len(), context) {
+                (32, Context::Tap) => {
+                    KeySource::Single(S256Point::lift_x(&bytes)?, KeyFormat::XOnly)
+                }
+                (33, _) if bytes[0] == 2 || bytes[0] == 3 => {
+                    KeySource::Single(S256Point::INFINITY.parse(bytes)?, KeyFormat::Compressed)
+                }
+                (65, Context::Top | Context::Sh) if bytes[0] == 4 => {
+                    KeySource::Single(S256Point::INFINITY.parse(bytes)?, KeyFormat::Uncompressed)
+                }
+                _ => return Err(format!("Invalid public key in this context: {}", base)),
+            }
+        };
+        Ok(Self { origin, source })
+    }
+}
+
+impl fmt::Display for DescriptorKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(origin) = &self.origin {
+            write!(f, "[{}]", origin)?;
+        }
+        let (path, wildcard) = match &self.source {
+            KeySource::Single(point, format) => {
+                let bytes = match format {
+                    KeyFormat::Compressed => point.sec(true),
+                    KeyFormat::Uncompressed => point.sec(false),
+                    KeyFormat::XOnly => point.xonly().to_vec(),
+                };
+                return write!(f, "{}", hex::encode(&bytes));
+            }
+            KeySource::Xpub(xpub, path, wildcard) => {
+                write!(f, "{}", xpub)?;
+                (path, wildcard)
+            }
+            KeySource::Xprv(xprv, path, wildcard) => {
+                write!(f, "{}", xprv)?;
+                (path, wildcard)
+            }
+        };
+        write!(f, "{}", &path.to_string()[1..])?;
+        match wildcard {
+            Wildcard::None => Ok(()),
+            Wildcard::Unhardened => write!(f, "/*"),
+            Wildcard::Hardened => write!(f, "/*'"),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum TapTree {
+    Leaf(DescriptorKey),
+    Branch(Box<TapTree>, Box<TapTree>),
+}
+
+impl TapTree {
+    fn parse(s: &str) -> Result<Self, String> {
+        if let Some(inner) = s.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+            let args = split_args(inner)?;
+            if args.len() != 2 {
+                return Err(format!(
+                    "Taproot tree branches must have two children: {}",
+                    s
+                ));
+            }
+            return Ok(Self::Branch(
+                Box::new(Self::parse(args[0])?),
+                Box::new(Self::parse(args[1])?),
+            ));
+        }
+        match split_function(s)? {
+            ("pk", key) => Ok(Self::Leaf(DescriptorKey::parse(key, Context::Tap)?)),
+            _ => Err(format!("Unsupported taproot leaf script: {}", s)),
+        }
+    }
+
+    #[inline]
+    fn is_ranged(&self) -> bool {
+        match self {
+            Self::Leaf(key) => key.is_ranged(),
+            Self::Branch(left, right) => left.is_ranged() || right.is_ranged(),
+        }
+    }
+
+    pub fn leaf_script(key: &DescriptorKey, index: u32) -> Result<Vec<u8>, String> {
+        let mut script = push_data(&key.key_bytes_at(index, Context::Tap)?);
+        script.push(OP_CHECKSIG);
+        Ok(script)
+    }
+
+    pub fn merkle_root(&self, index: u32) -> Result<[u8; 32], String> {
+        match self {
+            Self::Leaf(key) => Ok(tap_leaf_hash(
+                TAPSCRIPT_LEAF_VERSION,
+                &Self::leaf_script(key, index)?,
+            )),
+            Self::Branch(left, right) => Ok(tap_branch_hash(
+                &left.merkle_root(index)?,
+                &right.merkle_root(index)?,
+            )),
+        }
+    }
+}
+
+impl fmt::Display for TapTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Leaf(key) => write!(f, "pk({})", key),
+            Self::Branch(left, right) => write!(f, "{{{},{}}}", left, right),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Descriptor {
+    Pk(DescriptorKey),
+    Pkh(DescriptorKey),
+    Wpkh(DescriptorKey),
+    Sh(Box<Descriptor>),
+    Wsh(Box<Descriptor>),
+    Multi {
+        threshold: usize,
+        keys: Vec<DescriptorKey>,
+        sorted: bool,
+    },
+    Tr {
+        internal_key: DescriptorKey,
+        tree: Option<TapTree>,
+    },
+}
+
+impl Descriptor {
+    fn parse(s: &str, context: Context) -> Result<Self, String> {
+        let (name, inner) = split_function(s)?;
+        let descriptor = match (name, context) {
+            ("pk", Context::Top | Context::Sh | Context::SegwitV0) => {
+                Self::Pk(DescriptorKey::parse(inner, context)?)
+            }
+            ("pkh", Context::Top | Context::Sh | Context::SegwitV0) => {
+                Self::Pkh(DescriptorKey::parse(inner, context)?)
+            }
+            ("wpkh", Context::Top | Context::Sh) => {
+                let key = DescriptorKey::parse(inner, Context::SegwitV0)?;
+                Self::Wpkh(key)
+            }
+            ("sh", Context::Top) => Self::Sh(Box::new(Self::parse(inner, Context::Sh)?)),
+            ("wsh", Context::Top | Context::Sh) => {
+                Self::Wsh(Box::new(Self::parse(inner, Context::SegwitV0)?))
+            }
+            ("multi" | "sortedmulti", Context::Top | Context::Sh | Context::SegwitV0) => {
+                let args = split_args(inner)?;
+                let threshold = args[0]
+                    .parse::<usize>()
+                    .map_err(|_| format!("Invalid multisig threshold: {}", args[0]))?;
+                let keys = args[1..]
+                    .iter()
+                    .map(|key| DescriptorKey::parse(key, context))
+                    .collect::<Result<Vec<DescriptorKey>, String>>()?;
+                if keys.is_empty() || keys.len() > MAX_MULTISIG_KEYS {
+                    return Err(format!("Invalid number of multisig keys: {}", keys.len()));
+                }
+                if threshold == 0 || threshold > keys.len() {
+                    return Err(format!(
+                        "Invalid multisig threshold {} for {} keys.",
+                        threshold,
+                        keys.len()
+                    ));
+                }
+                Self::Multi {
+                    threshold,
+                    keys,
+                    sorted: name == "sortedmulti",
+                }
+            }
+            ("tr", Context::Top) => {
+                let args = split_args(inner)?;
+                let internal_key = DescriptorKey::parse(args[0], Context::Tap)?;
+                let tree = match args.len() {
+                    1 => None,
+                    2 => Some(TapTree::parse(args[1])?),
+                    _ => return Err(format!("Invalid tr descriptor: {}", s)),
+                };
+                Self::Tr { internal_key, tree }
+            }
+            _ => {
+                return Err(format!(
+                    "Unsupported descriptor in this context: {}(...)",
+                    name
+                ))
+            }
+        };
+        if context == Context::Sh {
+            let redeem_script = descriptor.script_pubkey(0)?;
+            if redeem_script.len() > MAX_SCRIPT_ELEMENT_SIZE {
+                return Err("Redeem script exceeds 520 bytes.".to_string());
+            }
+        }
+        Ok(descriptor)
+    }
+
+    pub fn is_ranged(&self) -> bool {
+        match self {
+            Self::Pk(key) | Self::Pkh(key) | Self::Wpkh(key) => key.is_ranged(),
+            Self::Sh(inner) | Self::Wsh(inner) => inner.is_ranged(),
+            Self::Multi { keys, .. } => keys.iter().any(|key| key.is_ranged()),
+            Self::Tr { internal_key, tree } => {
+                internal_key.is_ranged() || tree.as_ref().is_some_and(|tree| tree.is_ranged())
+            }
+        }
+    }
+
+    pub fn script_pubkey(&self, index: u32) -> Result<Vec<u8>, String> {
+        match self {
+            Self::Pk(key) => {
+                let mut script = push_data(&key.key_bytes_at(index, Context::Top)?);
+                script.push(OP_CHECKSIG);
+                Ok(script)
+            }
+            Self::Pkh(key) => Ok(p2pkh_script(&Sha256Ripemd160::compute(
+                &key.key_bytes_at(index, Context::Top)?,
+            ))),
+            Self::Wpkh(key) => Ok(p2wpkh_script(&Sha256Ripemd160::compute(
+                &key.key_bytes_at(index, Context::SegwitV0)?,
+            ))),
+            Self::Sh(inner) => Ok(p2sh_script(&Sha256Ripemd160::compute(
+                &inner.script_pubkey(index)?,
+            ))),
+            Self::Wsh(inner) => Ok(p2wsh_script(&Sha256::digest(inner.script_pubkey(index)?))),
+            Self::Multi {
+                threshold,
+                keys,
+                sorted,
+            } => {
+                let mut key_bytes = keys
+                    .iter()
+                    .map(|key| key.key_bytes_at(index, Context::Top))
+                    .collect::<Result<Vec<Vec<u8>>, String>>()?;
+                if *sorted {
+                    key_bytes.sort();
+                }
+                let mut script = push_int(*threshold);
+                for key in key_bytes {
+                    script.extend(push_data(&key));
+                }
+                script.extend(push_int(keys.len()));
+                script.push(OP_CHECKMULTISIG);
+                Ok(script)
+            }
+            Self::Tr { internal_key, tree } => {
+                let merkle_root = tree
+                    .as_ref()
+                    .map(|tree| tree.merkle_root(index))
+                    .transpose()?;
+                let output_key = internal_key.point_at(index)?.tap_tweak(merkle_root)?;
+                Ok(p2tr_script(&output_key.xonly()))
+            }
+        }
+    }
+
+    pub fn redeem_script(&self, index: u32) -> Result<Option<Vec<u8>>, String> {
+        match self {
+            Self::Sh(inner) => Ok(Some(inner.script_pubkey(index)?)),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn witness_script(&self, index: u32) -> Result<Option<Vec<u8>>, String> {
+        match self {
+            Self::Wsh(inner) => Ok(Some(inner.script_pubkey(index)?)),
+            Self::Sh(inner) => inner.witness_script(index),
+            _ => Ok(None),
+        }
+    }
+
+    #[inline]
+    pub fn address(&self, index: u32, network: Network) -> Result<String, String> {
+        address_from_script(&self.script_pubkey(index)?, network)
+    }
+
+    pub fn script_pubkeys(&self, range: Range<u32>) -> Result<Vec<Vec<u8>>, String> {
+        range.map(|index| self.script_pubkey(index)).collect()
+    }
+
+    pub fn addresses(&self, range: Range<u32>, network: Network) -> Result<Vec<String>, String> {
+        range.map(|index| self.address(index, network)).collect()
+    }
+
+    fn fmt_body(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Pk(key) => write!(f, "pk({})", key),
+            Self::Pkh(key) => write!(f, "pkh({})", key),
+            Self::Wpkh(key) => write!(f, "wpkh({})", key),
+            Self::Sh(inner) => {
+                write!(f, "sh(")?;
+                inner.fmt_body(f)?;
+                write!(f, ")")
+            }
+            Self::Wsh(inner) => {
+                write!(f, "wsh(")?;
+                inner.fmt_body(f)?;
+                write!(f, ")")
+            }
+            Self::Multi {
+                threshold,
+                keys,
+                sorted,
+            } => {
+                let name = if *sorted { "sortedmulti" } else { "multi" };
+                write!(f, "{}({}", name, threshold)?;
+                for key in keys {
+                    write!(f, ",{}", key)?;
+                }
+                write!(f, ")")
+            }
+            Self::Tr { internal_key, tree } => match tree {
+                Some(tree) => write!(f, "tr({},{})", internal_key, tree),
+                None => write!(f, "tr({})", internal_key),
+            },
+        }
+    }
+}
+
+impl FromStr for Descriptor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let body = match s.split_once('#') {
+            Some((body, checksum)) => {
+                if descriptor_checksum(body)? != checksum {
+                    return Err(format!("Invalid descriptor checksum: {}", checksum));
+                }
+                body
+            }
+            None => s,
+        };
+        Self::parse(body, Context::Top)
+    }
+}
+
+impl fmt::Display for Descriptor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let body = DescriptorBody(self).to_string();
+        let checksum = descriptor_checksum(&body).map_err(|_| fmt::Error)?;
+        write!(f, "{}#{}", body, checksum)
+    }
+}
+
+struct DescriptorBody<'a>(&'a Descriptor);
+
+impl fmt::Display for DescriptorBody<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt_body(f)
+    }
+}
@@ -0,0 +1,503 @@
+use super::s256ecc::{S256CurveCfg, S256FieldCfg, S256Point, S256PrivateKey};
+use super::sha256ser::Sha256Base58;
+use crate::ecc::elliptic_curve::EllipticCurve;
+use crate::ecc::finite_field::Modulus;
+use crate::ser::base58::Base58;
+use bnum::types::U256;
+use hmac::{Hmac, Mac};
+use sha2::Sha512;
+use std::fmt;
+use std::str::FromStr;
+
+pub const HARDENED: u32 = 0x8000_0000;
+
+pub const XPRV: [u8; 4] = [0x04, 0x88, 0xad, 0xe4];
+pub const XPUB: [u8; 4] = [0x04, 0x88, 0xb2, 0x1e];
+pub const TPRV: [u8; 4] = [0x04, 0x35, 0x83, 0x94];
+pub const TPUB: [u8; 4] = [0x04, 0x35, 0x87, 0xcf];
+pub const YPRV: [u8; 4] = [0x04, 0x9d, 0x78, 0x78];
+pub const YPUB: [u8; 4] = [0x04, 0x9d, 0x7c, 0xb2];
+pub const UPRV: [u8; 4] = [0x04, 0x4a, 0x4e, 0x28];
+pub const UPUB: [u8; 4] = [0x04, 0x4a, 0x52, 0x62];
+pub const ZPRV: [u8; 4] = [0x04, 0xb2, 0x43, 0x0c];
+pub const ZPUB: [u8; 4] = [0x04, 0xb2, 0x47, 0x46];
+pub const VPRV: [u8; 4] = [0x04, 0x5f, 0x18, 0xbc];
+pub const VPUB: [u8; 4] = [0x04, 0x5f, 0x1c, 0xf6];
+
+const VERSION_PAIRS: [([u8; 4], [u8; 4]); 6] = [
+    (XPRV, XPUB),
+    (TPRV, TPUB),
+    (YPRV, YPUB),
+    (UPRV, UPUB),
+    (ZPRV, ZPUB),
+    (VPRV, VPUB),
+];
+
+const EXTENDED_KEY_LEN: usize = 78;
+
+type ExtendedKeyFields = ([u8; 4], u8, [u8; 4], u32, [u8; 32], [u8; 33]);
+
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct DerivationPath(Vec<u32>);
+
+impl DerivationPath {
+    #[inline]
+    pub fn new(children: Vec<u32>) -> Self {
+        Self(children)
+    }
+
+    #[inline]
+    pub fn children(&self) -> &[u32] {
+        &self.0
+    }
+
+    #[inline]
+    pub fn child(&self, index: u32) -> Self {
+        let mut children = self.0.clone();
+        children.push(index);
+        Self(children)
+    }
+
+    #[inline]
+    pub fn extend(&self, other: &DerivationPath) -> Self {
+        Self([&self.0[..], &other.0[..]].concat())
+    }
+}
+
+impl FromStr for DerivationPath {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.strip_prefix('m').unwrap_or(s);
+        let s = s.strip_prefix('/').unwrap_or(s);
+        if s.is_empty() {
+            return Ok(Self::default());
+        }
+        s.split('/')
+            .map(|segment| {
+                let (index, hardened) = match segment.strip_suffix(['\'', 'h', 'H']) {
+                    Some(index) => (index, true),
+                    None => (segment, false),
+                };
+                let index = index
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|&i| i < HARDENED)
+                    .ok_or(format!("Invalid derivation path segment: {}", segment))?;
+                Ok(if hardened { index + HARDENED } else { index })
+            })
+            .collect::<Result<Vec<u32>, String>>()
+            .map(Self)
+    }
+}
+
+impl fmt::Display for DerivationPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "m")?;
+        for &child in &self.0 {
+            if child >= HARDENED {
+                write!(f, "/{}'", child - HARDENED)?;
+            } else {
+                write!(f, "/{}", child)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[inline]
+pub fn public_version(private_version: [u8; 4]) -> Option<[u8; 4]> {
+    VERSION_PAIRS
+        .iter()
+        .find(|(private, _)| *private == private_version)
+        .map(|(_, public)| *public)
+}
+
+fn hmac_sha512(key: &[u8], data: &[&[u8]]) -> ([u8; 32], [u8; 32]) {
+    let mut mac = Hmac::<Sha512>::new_from_slice(key).unwrap();
+    for chunk in data {
+        mac.update(chunk);
+    }
+    let result = mac.finalize().into_bytes();
+    (
+        result[..32].try_into().unwrap(),
+        result[32..].try_into().unwrap(),
+    )
+}
+
+fn serialize_extended_key(
+    version: [u8; 4],
+    depth: u8,
+    parent_fingerprint: [u8; 4],
+    child_number: u32,
+    chain_code: [u8; 32],
+    key_data: &[u8],
+) -> Vec<u8> {
+    let mut result = version.to_vec();
+    result.push(depth);
+    result.extend_from_slice(&parent_fingerprint);
+    result.extend_from_slice(&child_number.to_be_bytes());
+    result.extend_from_slice(&chain_code);
+    result.extend_from_slice(key_data);
+    result
+}
+
+fn encode_extended_key(
+    version: [u8; 4],
+    depth: u8,
+    parent_fingerprint: [u8; 4],
+    child_number: u32,
+    chain_code: [u8; 32],
+    key_data: &[u8],
+) -> String {
+    Sha256Base58::encode_base58_with_checksum(serialize_extended_key(
+        version,
+        depth,
+        parent_fingerprint,
+        child_number,
+        chain_code,
+        key_data,
+    ))
+}
+
+fn decode_extended_key(s: &str) -> Result<ExtendedKeyFields, String> {
+    parse_extended_key(&Sha256Base58::decode_base58_with_checksum(s)?)
+}
+
+fn parse_extended_key(data: &[u8]) -> Result<ExtendedKeyFields, String> {
+    if data.len() != EXTENDED_KEY_LEN {
+        return Err(format!(
+            "Invalid extended key length: expected {} bytes, got {}.",
+            EXTENDED_KEY_LEN,
+            data.len()
+        ));
+    }
+    let depth = data[4];
+    let parent_fingerprint: [u8; 4] = data[5..9].try_into().unwrap();
+    let child_number = u32::from_be_bytes(data[9..13].try_into().unwrap());
+    if depth == 0 && (parent_fingerprint != [0u8; 4] || child_number != 0) {
+        return Err("Invalid extended key: zero depth with non-zero parent.".to_string());
+    }
+    Ok((
+        data[..4].try_into().unwrap(),
+        depth,
+        parent_fingerprint,
+        child_number,
+        data[13..45].try_into().unwrap(),
+        data[45..].try_into().unwrap(),
+    ))
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct ExtendedPrivateKey {
+    version: [u8; 4],
+    depth: u8,
+    parent_fingerprint: [u8; 4],
+    child_number: u32,
+    chain_code: [u8; 32],
+    secret: U256,
+}
+
+impl ExtendedPrivateKey {
+    pub fn from_seed(seed: &[u8], version: [u8; 4]) -> Result<Self, String> {
+        if seed.len() < 16 || seed.len() > 64 {
+            return Err("Seed must be between 16 and 64 bytes long.".to_string());
+        }
+        let (il, chain_code) = hmac_sha512(b"Bitcoin seed", &[seed]);
+        let secret = U256::from_be_bytes(il);
+        if secret == U256::ZERO || secret >= S256CurveCfg::N {
+            return Err("Invalid seed: master secret is out of range.".to_string());
+        }
+        Ok(Self {
+            version,
+            depth: 0,
+            parent_fingerprint: [0u8; 4],
+            child_number: 0,
+            chain_code,
+            secret,
+        })
+    }
+
+    #[inline]
+    pub fn version(&self) -> [u8; 4] {
+        self.version
+    }
+
+    #[inline]
+    pub fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    #[inline]
+    pub fn parent_fingerprint(&self) -> [u8; 4] {
+        self.parent_fingerprint
+    }
+
+    #[inline]
+    pub fn child_number(&self) -> u32 {
+        self.child_number
+    }
+
+    #[inline]
+    pub fn chain_code(&self) -> [u8; 32] {
+        self.chain_code
+    }
+
+    #[inline]
+    pub fn secret(&self) -> U256 {
+        self.secret
+    }
+
+    #[inline]
+    pub fn private_key(&self) -> S256PrivateKey {
+        S256PrivateKey::from_value(self.secret)
+    }
+
+    #[inline]
+    pub fn point(&self) -> S256Point {
+        *S256Point::G * self.secret
+    }
+
+    #[inline]
+    pub fn fingerprint(&self) -> [u8; 4] {
+        self.point().hash160(true)[..4].try_into().unwrap()
+    }
+
+    #[inline]
+    pub fn with_version(&self, version: [u8; 4]) -> Self {
+        Self { version, ..*self }
+    }
+
+    pub fn extended_public_key(&self) -> Result<ExtendedPublicKey, String> {
+        let version = public_version(self.version).ok_or(format!(
+            "Unknown extended private key version: {:02x?}",
+            self.version
+        ))?;
+        Ok(ExtendedPublicKey {
+            version,
+            depth: self.depth,
+            parent_fingerprint: self.parent_fingerprint,
+            child_number: self.child_number,
+            chain_code: self.chain_code,
+            point: self.point(),
+        })
+    }
+
+    pub fn derive_child(&self, index: u32) -> Result<Self, String> {
+        let point = self.point();
+        let index_bytes = index.to_be_bytes();
+        let (il, chain_code) = if index >= HARDENED {
+            let secret_bytes = self.secret.to_be_bytes();
+            hmac_sha512(&self.chain_code, &[&[0u8], &secret_bytes, &index_bytes])
+        } else {
+            hmac_sha512(&self.chain_code, &[&point.sec(true), &index_bytes])
+        };
+        let tweak = U256::from_be_bytes(il);
+        if tweak >= S256CurveCfg::N {
+            return Err(format!("Invalid child key at index {}.", index));
+        }
+        let secret = S256FieldCfg::from_big(
+            (S256FieldCfg::to_big(tweak) + S256FieldCfg::to_big(self.secret))
+                % S256FieldCfg::to_big(S256CurveCfg::N),
+        );
+        if secret == U256::ZERO {
+            return Err(format!("Invalid child key at index {}.", index));
+        }
+        Ok(Self {
+            version: self.version,
+            depth: self
+                .depth
+                .checked_add(1)
+                .ok_or("Maximum derivation depth exceeded.".to_string())?,
+            parent_fingerprint: point.hash160(true)[..4].try_into().unwrap(),
+            child_number: index,
+            chain_code,
+            secret,
+        })
+    }
+
+    pub fn derive_path(&self, path: &DerivationPath) -> Result<Self, String> {
+        path.children()
+            .iter()
+            .try_fold(*self, |key, &index| key.derive_child(index))
+    }
+}
+
+impl FromStr for ExtendedPrivateKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (version, depth, parent_fingerprint, child_number, chain_code, key_data) =
+            decode_extended_key(s)?;
+        if key_data[0] != 0 {
+            return Err("Invalid extended private key data.".to_string());
+        }
+        let secret = U256::from_be_slice(&key_data[1..]).unwrap();
+        if secret == U256::ZERO || secret >= S256CurveCfg::N {
+            return Err("Invalid extended private key: secret is out of range.".to_string());
+        }
+        Ok(Self {
+            version,
+            depth,
+            parent_fingerprint,
+            child_number,
+            chain_code,
+            secret,
+        })
+    }
+}
+
+impl fmt::Display for ExtendedPrivateKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let key_data = [&[0u8][..], &self.secret.to_be_bytes()[..]].concat();
+        write!(
+            f,
+            "{}",
+            encode_extended_key(
+                self.version,
+                self.depth,
+                self.parent_fingerprint,
+                self.child_number,
+                self.chain_code,
+                &key_data,
+            )
+        )
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct ExtendedPublicKey {
+    version: [u8; 4],
+    depth: u8,
+    parent_fingerprint: [u8; 4],
+    child_number: u32,
+    chain_code: [u8; 32],
+    point: S256Point,
+}
+
+impl ExtendedPublicKey {
+    #[inline]
+    pub fn version(&self) -> [u8; 4] {
+        self.version
+    }
+
+    #[inline]
+    pub fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    #[inline]
+    pub fn parent_fingerprint(&self) -> [u8; 4] {
+        self.parent_fingerprint
+    }
+
+    #[inline]
+    pub fn child_number(&self) -> u32 {
+        self.child_number
+    }
+
+    #[inline]
+    pub fn chain_code(&self) -> [u8; 32] {
+        self.chain_code
+    }
+
+    #[inline]
+    pub fn point(&self) -> S256Point {
+        self.point
+    }
+
+    #[inline]
+    pub fn fingerprint(&self) -> [u8; 4] {
+        self.point.hash160(true)[..4].try_into().unwrap()
+    }
+
+    #[inline]
+    pub fn with_version(&self, version: [u8; 4]) -> Self {
+        Self { version, ..*self }
+    }
+
+    pub fn derive_child(&self, index: u32) -> Result<Self, String> {
+        if index >= HARDENED {
+            return Err(format!(
+                "Cannot derive hardened child {}' from an extended public key.",
+                index - HARDENED
+            ));
+        }
+        let (il, chain_code) = hmac_sha512(
+            &self.chain_code,
+            &[&self.point.sec(true), &index.to_be_bytes()],
+        );
+        let tweak = U256::from_be_bytes(il);
+        if tweak >= S256CurveCfg::N {
+            return Err(format!("Invalid child key at index {}.", index));
+        }
+        let point = *S256Point::G * tweak + self.point;
+        if point.is_infinity() {
+            return Err(format!("Invalid child key at index {}.", index));
+        }
+        Ok(Self {
+            version: self.version,
+            depth: self
+                .depth
+                .checked_add(1)
+                .ok_or("Maximum derivation depth exceeded.".to_string())?,
+            parent_fingerprint: self.fingerprint(),
+            child_number: index,
+            chain_code,
+            point,
+        })
+    }
+
+    pub fn derive_path(&self, path: &DerivationPath) -> Result<Self, String> {
+        path.children()
+            .iter()
+            .try_fold(*self, |key, &index| key.derive_child(index))
+    }
+
+    #[inline]
+    pub fn serialize(&self) -> Vec<u8> {
+        serialize_extended_key(
+            self.version,
+            self.depth,
+            self.parent_fingerprint,
+            self.child_number,
+            self.chain_code,
+            &self.point.sec(true),
+        )
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, String> {
+        let (version, depth, parent_fingerprint, child_number, chain_code, key_data) =
+            parse_extended_key(data)?;
+        if key_data[0] != 2 && key_data[0] != 3 {
+            return Err("Invalid extended public key data.".to_string());
+        }
+        let point = S256Point::INFINITY.parse(key_data.to_vec())?;
+        Ok(Self {
+            version,
+            depth,
+            parent_fingerprint,
+            child_number,
+            chain_code,
+            point,
+        })
+    }
+}
+
+impl FromStr for ExtendedPublicKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_bytes(&Sha256Base58::decode_base58_with_checksum(s)?)
+    }
+}
+
+impl fmt::Display for ExtendedPublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            Sha256Base58::encode_base58_with_checksum(self.serialize())
+        )
+    }
+}
@@ -0,0 +1,1025 @@
+use super::s256ecc::{S256CurveCfg, S256Point, S256Signature};
+use super::script::{encode_num, Instruction, Opcode, Script};
+use super::sha256ser::{DoubleSha256, Sha256Ripemd160};
+use super::sighash::{SighashCache, SIGHASH_DEFAULT};
+use super::taproot::{tap_branch_hash, tap_leaf_hash, TAPSCRIPT_LEAF_VERSION};
+use super::tx::{
+    Tx, TxOut, LOCKTIME_THRESHOLD, SEQUENCE_FINAL, SEQUENCE_LOCKTIME_DISABLE_FLAG,
+    SEQUENCE_LOCKTIME_MASK, SEQUENCE_LOCKTIME_TYPE_FLAG,
+};
+use crate::ecc::elliptic_curve::EllipticCurve;
+use crate::ser::chained_hash::ChainedCompute;
+use bnum::types::U256;
+use sha2::Digest;
+use std::fmt;
+
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+pub const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+pub const MAX_OPS_PER_SCRIPT: usize = 201;
+pub const MAX_STACK_SIZE: usize = 1000;
+pub const MAX_PUBKEYS_PER_MULTISIG: i64 = 20;
+pub const ANNEX_TAG: u8 = 0x50;
+pub const TAPROOT_CONTROL_BASE_SIZE: usize = 33;
+pub const TAPROOT_CONTROL_NODE_SIZE: usize = 32;
+pub const TAPROOT_CONTROL_MAX_NODE_COUNT: usize = 128;
+
+pub const VERIFY_NONE: u32 = 0;
+pub const VERIFY_P2SH: u32 = 1 << 0;
+pub const VERIFY_STRICTENC: u32 = 1 << 1;
+pub const VERIFY_DERSIG: u32 = 1 << 2;
+pub const VERIFY_LOW_S: u32 = 1 << 3;
+pub const VERIFY_NULLDUMMY: u32 = 1 << 4;
+pub const VERIFY_SIGPUSHONLY: u32 = 1 << 5;
+pub const VERIFY_MINIMALDATA: u32 = 1 << 6;
+pub const VERIFY_CLEANSTACK: u32 = 1 << 8;
+pub const VERIFY_CHECKLOCKTIMEVERIFY: u32 = 1 << 9;
+pub const VERIFY_CHECKSEQUENCEVERIFY: u32 = 1 << 10;
+pub const VERIFY_WITNESS: u32 = 1 << 11;
+pub const VERIFY_NULLFAIL: u32 = 1 << 14;
+pub const VERIFY_WITNESS_PUBKEYTYPE: u32 = 1 << 15;
+pub const VERIFY_TAPROOT: u32 = 1 << 17;
+
+pub const MANDATORY_VERIFY_FLAGS: u32 = VERIFY_P2SH
+    | VERIFY_DERSIG
+    | VERIFY_NULLDUMMY
+    | VERIFY_CHECKLOCKTIMEVERIFY
+    | VERIFY_CHECKSEQUENCEVERIFY
+    | VERIFY_WITNESS
+    | VERIFY_TAPROOT;
+pub const STANDARD_VERIFY_FLAGS: u32 = MANDATORY_VERIFY_FLAGS
+    | VERIFY_STRICTENC
+    | VERIFY_LOW_S
+    | VERIFY_MINIMALDATA
+    | VERIFY_CLEANSTACK
+    | VERIFY_NULLFAIL
+    | VERIFY_WITNESS_PUBKEYTYPE;
+
+const LOCKTIME_DISABLE_FLAG: i64 = 1 << 31;
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum ScriptError {
+    EvalFalse,
+    OpReturn,
+    ScriptSize,
+    PushSize,
+    OpCount,
+    StackSize,
+    SigCount,
+    PubkeyCount,
+    Verify,
+    EqualVerify,
+    CheckSigVerify,
+    CheckMultiSigVerify,
+    NumEqualVerify,
+    BadOpcode,
+    DisabledOpcode,
+    InvalidStackOperation,
+    InvalidAltstackOperation,
+    UnbalancedConditional,
+    NegativeLocktime,
+    UnsatisfiedLocktime,
+    SigHashType,
+    SigDer,
+    SigHighS,
+    SigNullDummy,
+    SigPushOnly,
+    PubkeyType,
+    MinimalData,
+    NumOverflow,
+    CleanStack,
+    NullFail,
+    WitnessProgramWrongLength,
+    WitnessProgramWitnessEmpty,
+    WitnessProgramMismatch,
+    WitnessMalleated,
+    WitnessMalleatedP2sh,
+    WitnessUnexpected,
+    WitnessPubkeyType,
+    SchnorrSigSize,
+    SchnorrSigHashType,
+    SchnorrSig,
+    TaprootWrongControlSize,
+    TapscriptUnsupported,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match self {
+            Self::EvalFalse => {
+                "Script evaluated without error but finished with a false/empty top stack element"
+            }
+            Self::OpReturn => "OP_RETURN was encountered",
+            Self::ScriptSize => "Script is too big",
+            Self::PushSize => "Push value size limit exceeded",
+            Self::OpCount => "Operation limit exceeded",
+            Self::StackSize => "Stack size limit exceeded",
+            Self::SigCount => "Signature count negative or greater than pubkey count",
+            Self::PubkeyCount => "Pubkey count negative or limit exceeded",
+            Self::Verify => "Script failed an OP_VERIFY operation",
+            Self::EqualVerify => "Script failed an OP_EQUALVERIFY operation",
+            Self::CheckSigVerify => "Script failed an OP_CHECKSIGVERIFY operation",
+            Self::CheckMultiSigVerify => "Script failed an OP_CHECKMULTISIGVERIFY operation",
+            Self::NumEqualVerify => "Script failed an OP_NUMEQUALVERIFY operation",
+            Self::BadOpcode => "Opcode missing or not understood",
+            Self::DisabledOpcode => "Attempted to use a disabled opcode",
+            Self::InvalidStackOperation => "Operation not valid with the current stack size",
+            Self::InvalidAltstackOperation => "Operation not valid with the current altstack size",
+            Self::UnbalancedConditional => "Invalid OP_IF construction",
+            Self::NegativeLocktime => "Negative locktime",
+            Self::UnsatisfiedLocktime => "Locktime requirement not satisfied",
+            Self::SigHashType => "Signature hash type missing or not understood",
+            Self::SigDer => "Non-canonical DER signature",
+            Self::SigHighS => "Non-canonical signature: S value is unnecessarily high",
+            Self::SigNullDummy => "Dummy CHECKMULTISIG argument must be zero",
+            Self::SigPushOnly => "Only push operators allowed in signatures",
+            Self::PubkeyType => "Public key is neither compressed or uncompressed",
+            Self::MinimalData => "Data push larger than necessary",
+            Self::NumOverflow => "Script number overflow",
+            Self::CleanStack => "Stack size must be exactly one after execution",
+            Self::NullFail => "Signature must be zero for failed CHECK(MULTI)SIG operation",
+            Self::WitnessProgramWrongLength => "Witness program has incorrect length",
+            Self::WitnessProgramWitnessEmpty => "Witness program was passed an empty witness",
+            Self::WitnessProgramMismatch => "Witness program hash mismatch",
+            Self::WitnessMalleated => "Witness requires empty scriptSig",
+            Self::WitnessMalleatedP2sh => "Witness requires only-redeemscript scriptSig",
+            Self::WitnessUnexpected => "Witness provided for non-witness script",
+            Self::WitnessPubkeyType => "Using non-compressed keys in segwit",
+            Self::SchnorrSigSize => "Invalid Schnorr signature size",
+            Self::SchnorrSigHashType => "Invalid Schnorr signature hash type",
+            Self::SchnorrSig => "Invalid Schnorr signature",
+            Self::TaprootWrongControlSize => "Invalid Taproot control block size",
+            Self::TapscriptUnsupported => "Tapscript execution is not supported",
+        };
+        write!(f, "{}", message)
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum SigVersion {
+    Base,
+    WitnessV0,
+}
+
+pub trait SignatureChecker {
+    fn check_ecdsa_signature(
+        &self,
+        signature: &[u8],
+        pubkey: &[u8],
+        script_code: &Script,
+        sig_version: SigVersion,
+    ) -> bool;
+
+    fn check_schnorr_signature(
+        &self,
+        _signature: &[u8],
+        _pubkey: &[u8],
+        _annex: Option<&[u8]>,
+    ) -> bool {
+        false
+    }
+
+    fn check_locktime(&self, _locktime: i64) -> bool {
+        false
+    }
+
+    fn check_sequence(&self, _sequence: i64) -> bool {
+        false
+    }
+}
+
+pub struct MessageChecker(pub U256);
+
+impl SignatureChecker for MessageChecker {
+    #[inline]
+    fn check_ecdsa_signature(
+        &self,
+        signature: &[u8],
+        pubkey: &[u8],
+        _: &Script,
+        _: SigVersion,
+    ) -> bool {
+        match signature.split_last() {
+            Some((_, der)) => verify_ecdsa(self.0, der, pubkey),
+            None => false,
+        }
+    }
+}
+
+pub struct TransactionChecker<'a> {
+    tx: &'a Tx,
+    cache: SighashCache<'a>,
+    input_index: usize,
+    amount: u64,
+}
+
+impl<'a> TransactionChecker<'a> {
+    #[inline]
+    pub fn new(tx: &'a Tx, input_index: usize, amount: u64) -> Self {
+        Self {
+            tx,
+            cache: SighashCache::new(tx),
+            input_index,
+            amount,
+        }
+    }
+
+    #[inline]
+    pub fn with_spent_outputs(tx: &'a Tx, input_index: usize, spent_outputs: &'a [TxOut]) -> Self {
+        Self {
+            tx,
+            cache: SighashCache::with_spent_outputs(tx, spent_outputs),
+            input_index,
+            amount: spent_outputs.get(input_index).map_or(0, TxOut::amount),
+        }
+    }
+}
+
+impl SignatureChecker for TransactionChecker<'_> {
+    fn check_ecdsa_signature(
+        &self,
+        signature: &[u8],
+        pubkey: &[u8],
+        script_code: &Script,
+        sig_version: SigVersion,
+    ) -> bool {
+        let Some((&hash_type, der)) = signature.split_last() else {
+            return false;
+        };
+        let z = match sig_version {
+            SigVersion::Base => {
+                self.tx
+                    .legacy_sighash(self.input_index, script_code, hash_type as u32)
+            }
+            SigVersion::WitnessV0 => {
+                let Ok(z) = self.cache.segwit_v0_sighash(
+                    self.input_index,
+                    script_code,
+                    self.amount,
+                    hash_type as u32,
+                ) else {
+                    return false;
+                };
+                z
+            }
+        };
+        verify_ecdsa(z, der, pubkey)
+    }
+
+    fn check_schnorr_signature(
+        &self,
+        signature: &[u8],
+        pubkey: &[u8],
+        annex: Option<&[u8]>,
+    ) -> bool {
+        let (signature, hash_type) = match signature.split_at_checked(64) {
+            Some((signature, [])) => (signature, SIGHASH_DEFAULT),
+            Some((signature, [hash_type])) => (signature, *hash_type as u32),
+            _ => return false,
+        };
+        let Ok(msg) = self
+            .cache
+            .taproot_key_spend_sighash(self.input_index, hash_type, annex)
+        else {
+            return false;
+        };
+        S256Point::lift_x(pubkey).is_ok_and(|point| point.verify_schnorr(&msg, signature))
+    }
+
+    fn check_locktime(&self, locktime: i64) -> bool {
+        let tx_locktime = self.tx.locktime() as i64;
+        let threshold = LOCKTIME_THRESHOLD as i64;
+        if (tx_locktime < threshold) != (locktime < threshold) || locktime > tx_locktime {
+            return false;
+        }
+        self.tx
+            .inputs()
+            .get(self.input_index)
+            .is_some_and(|input| input.sequence() != SEQUENCE_FINAL)
+    }
+
+    fn check_sequence(&self, sequence: i64) -> bool {
+        let Some(input) = self.tx.inputs().get(self.input_index) else {
+            return false;
+        };
+        let tx_sequence = input.sequence();
+        if self.tx.version() < 2 || tx_sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG != 0 {
+            return false;
+        }
+        let mask = (SEQUENCE_LOCKTIME_TYPE_FLAG | SEQUENCE_LOCKTIME_MASK) as i64;
+        let type_flag = SEQUENCE_LOCKTIME_TYPE_FLAG as i64;
+        let (tx_sequence, sequence) = (tx_sequence as i64 & mask, sequence & mask);
+        (tx_sequence < type_flag) == (sequence < type_flag) && sequence <= tx_sequence
+    }
+}
+
+pub fn verify_ecdsa(z: U256, der: &[u8], pubkey: &[u8]) -> bool {
+    let (Ok(point), Ok(signature)) = (S256Point::parse_sec(pubkey), S256Signature::parse_der(der))
+    else {
+        return false;
+    };
+    let in_range = |n: U256| n > U256::ZERO && n < S256CurveCfg::N;
+    in_range(signature.r().num()) && in_range(signature.s().num()) && point.verify(z, signature)
+}
+
+#[inline]
+pub fn cast_to_bool(bytes: &[u8]) -> bool {
+    match bytes.split_last() {
+        Some((&last, rest)) => rest.iter().any(|&b| b != 0) || (last != 0 && last != 0x80),
+        None => false,
+    }
+}
+
+pub fn decode_num(bytes: &[u8], require_minimal: bool, max_len: usize) -> Result<i64, ScriptError> {
+    if bytes.len() > max_len {
+        return Err(ScriptError::NumOverflow);
+    }
+    let Some((&last, rest)) = bytes.split_last() else {
+        return Ok(0);
+    };
+    if require_minimal && last & 0x7f == 0 && rest.last().is_none_or(|&b| b & 0x80 == 0) {
+        return Err(ScriptError::MinimalData);
+    }
+    let value = bytes
+        .iter()
+        .enumerate()
+        .fold(0i64, |acc, (i, &b)| acc | ((b as i64) << (8 * i)));
+    if last & 0x80 != 0 {
+        Ok(-(value & !(0x80i64 << (8 * rest.len()))))
+    } else {
+        Ok(value)
+    }
+}
+
+pub fn is_valid_signature_encoding(signature: &[u8]) -> bool {
+    let len = signature.len();
+    if !(9..=73).contains(&len) || signature[0] != 0x30 || signature[1] as usize != len - 3 {
+        return false;
+    }
+    let len_r = signature[3] as usize;
+    if 5 + len_r >= len {
+        return false;
+    }
+    let len_s = signature[5 + len_r] as usize;
+    if len_r + len_s + 7 != len {
+        return false;
+    }
+    let valid_integer = |start: usize, integer_len: usize| {
+        signature[start - 2] == 0x02
+            && integer_len != 0
+            && signature[start] & 0x80 == 0
+            && !(integer_len > 1 && signature[start] == 0 && signature[start + 1] & 0x80 == 0)
+    };
+    valid_integer(4, len_r) && valid_integer(len_r + 6, len_s)
+}
+
+fn is_low_der_signature(signature: &[u8]) -> bool {
+    match S256Signature::parse_der(&signature[..signature.len() - 1]) {
+        Ok(signature) => signature.s().num() <= S256CurveCfg::N / U256::TWO,
+        Err(_) => false,
+    }
+}
+
+fn check_signature_encoding(signature: &[u8], flags: u32) -> Result<(), ScriptError> {
+    if signature.is_empty() {
+        return Ok(());
+    }
+    if flags & (VERIFY_DERSIG | VERIFY_LOW_S | VERIFY_STRICTENC) != 0
+        && !is_valid_signature_encoding(signature)
+    {
+        return Err(ScriptError::SigDer);
+    }
+    if flags & VERIFY_LOW_S != 0 && !is_low_der_signature(signature) {
+        return Err(ScriptError::SigHighS);
+    }
+    if flags & VERIFY_STRICTENC != 0 {
+        let hash_type = signature[signature.len() - 1] & !0x80;
+        if !(0x01..=0x03).contains(&hash_type) {
+            return Err(ScriptError::SigHashType);
+        }
+    }
+    Ok(())
+}
+
+fn check_pubkey_encoding(
+    pubkey: &[u8],
+    flags: u32,
+    sig_version: SigVersion,
+) -> Result<(), ScriptError> {
+    let compressed = matches!((pubkey.first(), pubkey.len()), (Some(0x02 | 0x03), 33));
+    let valid = compressed || matches!((pubkey.first(), pubkey.len()), (Some(0x04), 65));
+    if flags & VERIFY_STRICTENC != 0 && !valid {
+        return Err(ScriptError::PubkeyType);
+    }
+    if flags & VERIFY_WITNESS_PUBKEYTYPE != 0 && sig_version == SigVersion::WitnessV0 && !compressed
+    {
+        return Err(ScriptError::WitnessPubkeyType);
+    }
+    Ok(())
+}
+
+#[inline]
+fn push_bool(stack: &mut Vec<Vec<u8>>, value: bool) {
+    stack.push(if value { vec![1] } else { vec![] });
+}
+
+#[inline]
+fn pop(stack: &mut Vec<Vec<u8>>) -> Result<Vec<u8>, ScriptError> {
+    stack.pop().ok_or(ScriptError::InvalidStackOperation)
+}
+
+#[inline]
+fn top(stack: &[Vec<u8>], depth: usize) -> Result<&Vec<u8>, ScriptError> {
+    stack
+        .len()
+        .checked_sub(depth)
+        .map(|i| &stack[i])
+        .ok_or(ScriptError::InvalidStackOperation)
+}
+
+#[inline]
+fn is_disabled(opcode: Opcode) -> bool {
+    use Opcode::*;
+    matches!(
+        opcode,
+        Cat | Substr
+            | Left
+            | Right
+            | Invert
+            | And
+            | Or
+            | Xor
+            | TwoMul
+            | TwoDiv
+            | Mul
+            | Div
+            | Mod
+            | LShift
+            | RShift
+    )
+}
+
+fn serialized_push(data: &[u8]) -> Vec<u8> {
+    let mut result = match data.len() {
+        len @ 0..=0x4b => vec![len as u8],
+        len @ 0x4c..=0xff => vec![0x4c, len as u8],
+        len @ 0x100..=0xffff => [&[0x4d][..], &(len as u16).to_le_bytes()].concat(),
+        len => [&[0x4e][..], &(len as u32).to_le_bytes()].concat(),
+    };
+    result.extend(data);
+    result
+}
+
+pub fn eval_script(
+    stack: &mut Vec<Vec<u8>>,
+    script: &Script,
+    flags: u32,
+    checker: &impl SignatureChecker,
+    sig_version: SigVersion,
+) -> Result<(), ScriptError> {
+    use Opcode::*;
+
+    if script.len() > MAX_SCRIPT_SIZE {
+        return Err(ScriptError::ScriptSize);
+    }
+    let require_minimal = flags & VERIFY_MINIMALDATA != 0;
+    let num = |bytes: &[u8]| decode_num(bytes, require_minimal, 4);
+    let mut alt_stack: Vec<Vec<u8>> = Vec::new();
+    let mut exec_stack: Vec<bool> = Vec::new();
+    let mut op_count = 0;
+    let mut code_separator = 0;
+    let mut instructions = script.instructions();
+
+    while let Some(instruction) = instructions.next() {
+        let executing = exec_stack.iter().all(|&b| b);
+        let instruction = instruction.map_err(|_| ScriptError::BadOpcode)?;
+        let opcode = instruction.opcode();
+
+        if let Instruction::Push(_, data) = &instruction {
+            if data.len() > MAX_SCRIPT_ELEMENT_SIZE {
+                return Err(ScriptError::PushSize);
+            }
+        }
+        if opcode.to_byte() > Op16.to_byte() {
+            op_count += 1;
+            if op_count > MAX_OPS_PER_SCRIPT {
+                return Err(ScriptError::OpCount);
+            }
+        }
+        if is_disabled(opcode) {
+            return Err(ScriptError::DisabledOpcode);
+        }
+
+        if executing && opcode.to_byte() <= PushData4.to_byte() {
+            if require_minimal && !instruction.is_minimal() {
+                return Err(ScriptError::MinimalData);
+            }
+            stack.push(instruction.push_data().unwrap().to_vec());
+        } else if executing || (If.to_byte()..=EndIf.to_byte()).contains(&opcode.to_byte()) {
+            match opcode {
+                OneNegate | Op1 | Op2 | Op3 | Op4 | Op5 | Op6 | Op7 | Op8 | Op9 | Op10 | Op11
+                | Op12 | Op13 | Op14 | Op15 | Op16 => {
+                    stack.push(encode_num(opcode.small_int().unwrap()));
+                }
+                Nop | Nop1 | Nop4 | Nop5 | Nop6 | Nop7 | Nop8 | Nop9 | Nop10 => {}
+                CheckLockTimeVerify => {
+                    if flags & VERIFY_CHECKLOCKTIMEVERIFY != 0 {
+                        let locktime = decode_num(top(stack, 1)?, require_minimal, 5)?;
+                        if locktime < 0 {
+                            return Err(ScriptError::NegativeLocktime);
+                        }
+                        if !checker.check_locktime(locktime) {
+                            return Err(ScriptError::UnsatisfiedLocktime);
+                        }
+                    }
+                }
+                CheckSequenceVerify => {
+                    if flags & VERIFY_CHECKSEQUENCEVERIFY != 0 {
+                        let sequence = decode_num(top(stack, 1)?, require_minimal, 5)?;
+                        if sequence < 0 {
+                            return Err(ScriptError::NegativeLocktime);
+                        }
+                        if sequence & LOCKTIME_DISABLE_FLAG == 0
+                            && !checker.check_sequence(sequence)
+                        {
+                            return Err(ScriptError::UnsatisfiedLocktime);
+                        }
+                    }
+                }
+                If | NotIf => {
+                    let mut value = false;
+                    if executing {
+                        let condition = stack.pop().ok_or(ScriptError::UnbalancedConditional)?;
+                        value = cast_to_bool(&condition) == (opcode == If);
+                    }
+                    exec_stack.push(value);
+                }
+                Else => {
+                    let last = exec_stack
+                        .last_mut()
+                        .ok_or(ScriptError::UnbalancedConditional)?;
+                    *last = !*last;
+                }
+                EndIf => {
+                    exec_stack.pop().ok_or(ScriptError::UnbalancedConditional)?;
+                }
+                Verify => {
+                    if !cast_to_bool(top(stack, 1)?) {
+                        return Err(ScriptError::Verify);
+                    }
+                    stack.pop();
+                }
+                Return => return Err(ScriptError::OpReturn),
+                ToAltStack => alt_stack.push(pop(stack)?),
+                FromAltStack => stack.push(
+                    alt_stack
+                        .pop()
+                        .ok_or(ScriptError::InvalidAltstackOperation)?,
+                ),
+                TwoDrop => {
+                    top(stack, 2)?;
+                    stack.truncate(stack.len() - 2);
+                }
+                TwoDup => {
+                    let items = [top(stack, 2)?.clone(), top(stack, 1)?.clone()];
+                    stack.extend(items);
+                }
+                ThreeDup => {
+                    let items = [
+                        top(stack, 3)?.clone(),
+                        top(stack, 2)?.clone(),
+                        top(stack, 1)?.clone(),
+                    ];
+                    stack.extend(items);
+                }
+                TwoOver => {
+                    let items = [top(stack, 4)?.clone(), top(stack, 3)?.clone()];
+                    stack.extend(items);
+                }
+                TwoRot => {
+                    top(stack, 6)?;
+                    let i = stack.len() - 6;
+                    let items = stack.drain(i..i + 2).collect::<Vec<Vec<u8>>>();
+                    stack.extend(items);
+                }
+                TwoSwap => {
+                    top(stack, 4)?;
+                    let i = stack.len() - 4;
+                    stack.swap(i, i + 2);
+                    stack.swap(i + 1, i + 3);
+                }
+                IfDup => {
+                    let item = top(stack, 1)?.clone();
+                    if cast_to_bool(&item) {
+                        stack.push(item);
+                    }
+                }
+                Depth => stack.push(encode_num(stack.len() as i64)),
+                Drop => {
+                    pop(stack)?;
+                }
+                Dup => stack.push(top(stack, 1)?.clone()),
+                Nip => {
+                    top(stack, 2)?;
+                    stack.remove(stack.len() - 2);
+                }
+                Over => stack.push(top(stack, 2)?.clone()),
+                Pick | Roll => {
+                    let n = num(&pop(stack)?)?;
+                    if n < 0 || n as usize >= stack.len() {
+                        return Err(ScriptError::InvalidStackOperation);
+                    }
+                    let i = stack.len() - 1 - n as usize;
+                    let item = if opcode == Roll {
+                        stack.remove(i)
+                    } else {
+                        stack[i].clone()
+                    };
+                    stack.push(item);
+                }
+                Rot => {
+                    top(stack, 3)?;
+                    let item = stack.remove(stack.len() - 3);
+                    stack.push(item);
+                }
+                Swap => {
+                    top(stack, 2)?;
+                    let len = stack.len();
+                    stack.swap(len - 2, len - 1);
+                }
+                Tuck => {
+                    let item = top(stack, 1)?.clone();
+                    top(stack, 2)?;
+                    stack.insert(stack.len() - 2, item);
+                }
+                Size => stack.push(encode_num(top(stack, 1)?.len() as i64)),
+                Equal | EqualVerify => {
+                    let b = pop(stack)?;
+                    let a = pop(stack)?;
+                    push_bool(stack, a == b);
+                    if opcode == EqualVerify {
+                        if a != b {
+                            return Err(ScriptError::EqualVerify);
+                        }
+                        stack.pop();
+                    }
+                }
+                OneAdd | OneSub | Negate | Abs | Not | ZeroNotEqual => {
+                    let n = num(&pop(stack)?)?;
+                    let result = match opcode {
+                        OneAdd => n + 1,
+                        OneSub => n - 1,
+                        Negate => -n,
+                        Abs => n.abs(),
+                        Not => (n == 0) as i64,
+                        _ => (n != 0) as i64,
+                    };
+                    stack.push(encode_num(result));
+                }
+                Add | Sub | BoolAnd | BoolOr | NumEqual | NumEqualVerify | NumNotEqual
+                | LessThan | GreaterThan | LessThanOrEqual | GreaterThanOrEqual | Min | Max => {
+                    let b = num(&pop(stack)?)?;
+                    let a = num(&pop(stack)?)?;
+                    let result = match opcode {
+                        Add => a + b,
+                        Sub => a - b,
+                        BoolAnd => (a != 0 && b != 0) as i64,
+                        BoolOr => (a != 0 || b != 0) as i64,
+                        NumEqual | NumEqualVerify => (a == b) as i64,
+                        NumNotEqual => (a != b) as i64,
+                        LessThan => (a < b) as i64,
+                        GreaterThan => (a > b) as i64,
+                        LessThanOrEqual => (a <= b) as i64,
+                        GreaterThanOrEqual => (a >= b) as i64,
+                        Min => a.min(b),
+                        _ => a.max(b),
+                    };
+                    stack.push(encode_num(result));
+                    if opcode == NumEqualVerify {
+                        if result == 0 {
+                            return Err(ScriptError::NumEqualVerify);
+                        }
+                        stack.pop();
+                    }
+                }
+                Within => {
+                    let max = num(&pop(stack)?)?;
+                    let min = num(&pop(stack)?)?;
+                    let x = num(&pop(stack)?)?;
+                    push_bool(stack, min <= x && x < max);
+                }
+                Ripemd160 | Sha1 | Sha256 | Hash160 | Hash256 => {
+                    let data = pop(stack)?;
+                    stack.push(match opcode {
+                        Ripemd160 => ripemd::Ripemd160::digest(&data).to_vec(),
+                        Sha1 => sha1::Sha1::digest(&data).to_vec(),
+                        Sha256 => sha2::Sha256::digest(&data).to_vec(),
+                        Hash160 => Sha256Ripemd160::compute(&data),
+                        _ => DoubleSha256::compute(&data),
+                    });
+                }
+                CodeSeparator => code_separator = instructions.position(),
+                CheckSig | CheckSigVerify => {
+                    let pubkey = top(stack, 1)?.clone();
+                    let signature = top(stack, 2)?.clone();
+                    let mut script_code = script.subscript(code_separator);
+                    if sig_version == SigVersion::Base {
+                        script_code = script_code.find_and_delete(&serialized_push(&signature));
+                    }
+                    check_signature_encoding(&signature, flags)?;
+                    check_pubkey_encoding(&pubkey, flags, sig_version)?;
+                    let success = !signature.is_empty()
+                        && checker.check_ecdsa_signature(
+                            &signature,
+                            &pubkey,
+                            &script_code,
+                            sig_version,
+                        );
+                    if !success && flags & VERIFY_NULLFAIL != 0 && !signature.is_empty() {
+                        return Err(ScriptError::NullFail);
+                    }
+                    stack.truncate(stack.len() - 2);
+                    push_bool(stack, success);
+                    if opcode == CheckSigVerify {
+                        if !success {
+                            return Err(ScriptError::CheckSigVerify);
+                        }
+                        stack.pop();
+                    }
+                }
+                CheckMultiSig | CheckMultiSigVerify => {
+                    let mut i = 1;
+                    let mut key_count = num(top(stack, i)?)?;
+                    if !(0..=MAX_PUBKEYS_PER_MULTISIG).contains(&key_count) {
+                        return Err(ScriptError::PubkeyCount);
+                    }
+                    op_count += key_count as usize;
+                    if op_count > MAX_OPS_PER_SCRIPT {
+                        return Err(ScriptError::OpCount);
+                    }
+                    i += 1;
+                    let mut key_index = i;
+                    let mut keys_to_clean = key_count as usize + 2;
+                    i += key_count as usize;
+                    let mut sig_count = num(top(stack, i)?)?;
+                    if sig_count < 0 || sig_count > key_count {
+                        return Err(ScriptError::SigCount);
+                    }
+                    i += 1;
+                    let mut sig_index = i;
+                    i += sig_count as usize;
+                    top(stack, i)?;
+
+                    let mut script_code = script.subscript(code_separator);
+                    for k in 0..sig_count as usize {
+                        let signature = top(stack, sig_index + k)?;
+                        if sig_version == SigVersion::Base {
+                            script_code = script_code.find_and_delete(&serialized_push(signature));
+                        }
+                    }
+
+                    let mut success = true;
+                    while success && sig_count > 0 {
+                        let signature = top(stack, sig_index)?;
+                        let pubkey = top(stack, key_index)?;
+                        check_signature_encoding(signature, flags)?;
+                        check_pubkey_encoding(pubkey, flags, sig_version)?;
+                        if !signature.is_empty()
+                            && checker.check_ecdsa_signature(
+                                signature,
+                                pubkey,
+                                &script_code,
+                                sig_version,
+                            )
+                        {
+                            sig_index += 1;
+                            sig_count -= 1;
+                        }
+                        key_index += 1;
+                        key_count -= 1;
+                        if sig_count > key_count {
+                            success = false;
+                        }
+                    }
+
+                    while i > 1 {
+                        i -= 1;
+                        if !success
+                            && flags & VERIFY_NULLFAIL != 0
+                            && keys_to_clean == 0
+                            && !top(stack, 1)?.is_empty()
+                        {
+                            return Err(ScriptError::NullFail);
+                        }
+                        keys_to_clean = keys_to_clean.saturating_sub(1);
+                        stack.pop();
+                    }
+                    let dummy = pop(stack)?;
+                    if flags & VERIFY_NULLDUMMY != 0 && !dummy.is_empty() {
+                        return Err(ScriptError::SigNullDummy);
+                    }
+                    push_bool(stack, success);
+                    if opcode == CheckMultiSigVerify {
+                        if !success {
+                            return Err(ScriptError::CheckMultiSigVerify);
+                        }
+                        stack.pop();
+                    }
+                }
+                _ => return Err(ScriptError::BadOpcode),
+            }
+        }
+
+        if stack.len() + alt_stack.len() > MAX_STACK_SIZE {
+            return Err(ScriptError::StackSize);
+        }
+    }
+
+    if !exec_stack.is_empty() {
+        return Err(ScriptError::UnbalancedConditional);
+    }
+    Ok(())
+}
+
+fn execute_witness_script(
+    mut stack: Vec<Vec<u8>>,
+    script: &Script,
+    flags: u32,
+    checker: &impl SignatureChecker,
+) -> Result<(), ScriptError> {
+    if stack
+        .iter()
+        .any(|item| item.len() > MAX_SCRIPT_ELEMENT_SIZE)
+    {
+        return Err(ScriptError::PushSize);
+    }
+    eval_script(&mut stack, script, flags, checker, SigVersion::WitnessV0)?;
+    if stack.len() != 1 {
+        return Err(ScriptError::CleanStack);
+    }
+    if !cast_to_bool(&stack[0]) {
+        return Err(ScriptError::EvalFalse);
+    }
+    Ok(())
+}
+
+fn verify_witness_program(
+    witness: &[Vec<u8>],
+    version: u8,
+    program: &[u8],
+    flags: u32,
+    checker: &impl SignatureChecker,
+    is_p2sh: bool,
+) -> Result<(), ScriptError> {
+    if version == 1 && program.len() == 32 && !is_p2sh && flags & VERIFY_TAPROOT != 0 {
+        return verify_taproot(witness, program, checker);
+    }
+    if version != 0 {
+        return Ok(());
+    }
+    match program.len() {
+        32 => {
+            let Some((witness_script, stack)) = witness.split_last() else {
+                return Err(ScriptError::WitnessProgramWitnessEmpty);
+            };
+            if sha2::Sha256::digest(witness_script)[..] != *program {
+                return Err(ScriptError::WitnessProgramMismatch);
+            }
+            let witness_script = Script::from(witness_script.clone());
+            execute_witness_script(stack.to_vec(), &witness_script, flags, checker)
+        }
+        20 => {
+            if witness.len() != 2 {
+                return Err(ScriptError::WitnessProgramMismatch);
+            }
+            let mut script_code = Script::new();
+            script_code
+                .push_opcode(Opcode::Dup)
+                .push_opcode(Opcode::Hash160)
+                .push_data(program)
+                .push_opcode(Opcode::EqualVerify)
+                .push_opcode(Opcode::CheckSig);
+            execute_witness_script(witness.to_vec(), &script_code, flags, checker)
+        }
+        _ => Err(ScriptError::WitnessProgramWrongLength),
+    }
+}
+
+fn verify_taproot(
+    witness: &[Vec<u8>],
+    program: &[u8],
+    checker: &impl SignatureChecker,
+) -> Result<(), ScriptError> {
+    let (stack, annex) = match witness.split_last() {
+        None => return Err(ScriptError::WitnessProgramWitnessEmpty),
+        Some((last, stack)) if !stack.is_empty() && last.first() == Some(&ANNEX_TAG) => {
+            (stack, Some(&last[..]))
+        }
+        Some(_) => (witness, None),
+    };
+    let [.., script, control] = stack else {
+        let signature = &stack[0];
+        match signature.len() {
+            64 => {}
+            65 if matches!(signature[64], 0x01..=0x03 | 0x81..=0x83) => {}
+            65 => return Err(ScriptError::SchnorrSigHashType),
+            _ => return Err(ScriptError::SchnorrSigSize),
+        }
+        if !checker.check_schnorr_signature(signature, program, annex) {
+            return Err(ScriptError::SchnorrSig);
+        }
+        return Ok(());
+    };
+
+    if control.len() < TAPROOT_CONTROL_BASE_SIZE
+        || control.len()
+            > TAPROOT_CONTROL_BASE_SIZE + TAPROOT_CONTROL_NODE_SIZE * TAPROOT_CONTROL_MAX_NODE_COUNT
+        || !(control.len() - TAPROOT_CONTROL_BASE_SIZE).is_multiple_of(TAPROOT_CONTROL_NODE_SIZE)
+    {
+        return Err(ScriptError::TaprootWrongControlSize);
+    }
+    let leaf_version = control[0] & 0xfe;
+    let merkle_root = control[TAPROOT_CONTROL_BASE_SIZE..]
+        .chunks_exact(TAPROOT_CONTROL_NODE_SIZE)
+        .fold(tap_leaf_hash(leaf_version, script), |hash, node| {
+            tap_branch_hash(&hash, node.try_into().unwrap())
+        });
+    let output_key = S256Point::lift_x(&control[1..TAPROOT_CONTROL_BASE_SIZE])
+        .and_then(|internal_key| internal_key.tap_tweak(Some(merkle_root)));
+    if !output_key.is_ok_and(|output_key| {
+        output_key.xonly()[..] == *program && output_key.has_even_y() == (control[0] & 1 == 0)
+    }) {
+        return Err(ScriptError::WitnessProgramMismatch);
+    }
+    match leaf_version {
+        TAPSCRIPT_LEAF_VERSION => Err(ScriptError::TapscriptUnsupported),
+        _ => Ok(()),
+    }
+}
+
+pub fn verify_script(
+    script_sig: &Script,
+    script_pubkey: &Script,
+    witness: &[Vec<u8>],
+    flags: u32,
+    checker: &impl SignatureChecker,
+) -> Result<(), ScriptError> {
+    if flags & VERIFY_SIGPUSHONLY != 0 && !script_sig.is_push_only() {
+        return Err(ScriptError::SigPushOnly);
+    }
+    let mut stack = Vec::new();
+    eval_script(&mut stack, script_sig, flags, checker, SigVersion::Base)?;
+    let mut p2sh_stack = stack.clone();
+    eval_script(&mut stack, script_pubkey, flags, checker, SigVersion::Base)?;
+    if !stack.last().is_some_and(|top| cast_to_bool(top)) {
+        return Err(ScriptError::EvalFalse);
+    }
+
+    let mut had_witness = false;
+    if flags & VERIFY_WITNESS != 0 {
+        if let Some((version, program)) = script_pubkey.witness_program() {
+            had_witness = true;
+            if !script_sig.is_empty() {
+                return Err(ScriptError::WitnessMalleated);
+            }
+            verify_witness_program(witness, version, program, flags, checker, false)?;
+            stack.truncate(1);
+        }
+    }
+
+    if flags & VERIFY_P2SH != 0 && script_pubkey.is_p2sh() {
+        if !script_sig.is_push_only() {
+            return Err(ScriptError::SigPushOnly);
+        }
+        let redeem_script = Script::from(pop(&mut p2sh_stack)?);
+        eval_script(
+            &mut p2sh_stack,
+            &redeem_script,
+            flags,
+            checker,
+            SigVersion::Base,
+        )?;
+        if !p2sh_stack.last().is_some_and(|top| cast_to_bool(top)) {
+            return Err(ScriptError::EvalFalse);
+        }
+        stack = p2sh_stack;
+
+        if flags & VERIFY_WITNESS != 0 {
+            if let Some((version, program)) = redeem_script.witness_program() {
+                had_witness = true;
+                if script_sig[..] != serialized_push(&redeem_script)[..] {
+                    return Err(ScriptError::WitnessMalleatedP2sh);
+                }
+                verify_witness_program(witness, version, program, flags, checker, true)?;
+                stack.truncate(1);
+            }
+        }
+    }
+
+    if flags & VERIFY_CLEANSTACK != 0 && stack.len() != 1 {
+        return Err(ScriptError::CleanStack);
+    }
+    if flags & VERIFY_WITNESS != 0 && !had_witness && !witness.is_empty() {
+        return Err(ScriptError::WitnessUnexpected);
+    }
+    Ok(())
+}
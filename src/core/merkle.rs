@@ -0,0 +1,332 @@
+use super::block::{merkle_parent, Block, BlockHeader};
+use super::tx::Tx;
+use crate::ser::hex;
+use crate::ser::stream::{read_array, read_u32_le, read_vec};
+use crate::ser::varint::{encode_varint, read_varint};
+use std::fmt;
+use std::io::{Cursor, Read};
+use std::str::FromStr;
+
+const MAX_TRANSACTIONS: u32 = 4_000_000 / 240;
+
+type Matches = Vec<([u8; 32], u32)>;
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct MerkleBranch {
+    index: u32,
+    hashes: Vec<[u8; 32]>,
+}
+
+impl MerkleBranch {
+    pub fn new(leaves: &[[u8; 32]], index: u32) -> Result<Self, String> {
+        if index as usize >= leaves.len() {
+            return Err(format!(
+                "Leaf {} out of range for {} leaves.",
+                index,
+                leaves.len()
+            ));
+        }
+        let mut level = leaves.to_vec();
+        let mut position = index as usize;
+        let mut hashes = Vec::new();
+        while level.len() > 1 {
+            if level.len() % 2 == 1 {
+                level.push(level[level.len() - 1]);
+            }
+            hashes.push(level[position ^ 1]);
+            level = level
+                .chunks_exact(2)
+                .map(|pair| merkle_parent(&pair[0], &pair[1]))
+                .collect();
+            position >>= 1;
+        }
+        Ok(Self { index, hashes })
+    }
+
+    #[inline]
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    #[inline]
+    pub fn hashes(&self) -> &[[u8; 32]] {
+        &self.hashes
+    }
+
+    pub fn root(&self, leaf: &[u8; 32]) -> [u8; 32] {
+        let mut hash = *leaf;
+        for (height, sibling) in self.hashes.iter().enumerate() {
+            hash = match (self.index >> height) & 1 {
+                0 => merkle_parent(&hash, sibling),
+                _ => merkle_parent(sibling, &hash),
+            };
+        }
+        hash
+    }
+
+    #[inline]
+    pub fn verify(&self, leaf: &[u8; 32], root: &[u8; 32]) -> bool {
+        let depth = self.hashes.len() as u32;
+        self.index.checked_shr(depth).is_none_or(|rest| rest == 0) && self.root(leaf) == *root
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct PartialMerkleTree {
+    total: u32,
+    hashes: Vec<[u8; 32]>,
+    flags: Vec<bool>,
+}
+
+impl PartialMerkleTree {
+    pub fn from_txids(txids: &[[u8; 32]], matches: &[bool]) -> Result<Self, String> {
+        if txids.is_empty() || txids.len() != matches.len() {
+            return Err("Need one match flag for each of at least one txid.".to_string());
+        }
+        let mut tree = Self {
+            total: txids.len() as u32,
+            hashes: Vec::new(),
+            flags: Vec::new(),
+        };
+        tree.build(tree.height(), 0, txids, matches);
+        Ok(tree)
+    }
+
+    #[inline]
+    pub fn total(&self) -> u32 {
+        self.total
+    }
+
+    #[inline]
+    pub fn hashes(&self) -> &[[u8; 32]] {
+        &self.hashes
+    }
+
+    #[inline]
+    pub fn flags(&self) -> &[bool] {
+        &self.flags
+    }
+
+    #[inline]
+    fn width(&self, height: u32) -> u32 {
+        ((self.total as u64 + (1 << height) - 1) >> height) as u32
+    }
+
+    #[inline]
+    fn height(&self) -> u32 {
+        let mut height = 0;
+        while self.width(height) > 1 {
+            height += 1;
+        }
+        height
+    }
+
+    fn compute_hash(&self, height: u32, position: u32, txids: &[[u8; 32]]) -> [u8; 32] {
+        if height == 0 {
+            return txids[position as usize];
+        }
+        let left = self.compute_hash(height - 1, position * 2, txids);
+        let right = match position * 2 + 1 < self.width(height - 1) {
+            true => self.compute_hash(height - 1, position * 2 + 1, txids),
+            false => left,
+        };
+        merkle_parent(&left, &right)
+    }
+
+    fn build(&mut self, height: u32, position: u32, txids: &[[u8; 32]], matches: &[bool]) {
+        let start = (position as usize) << height;
+        let end = ((position as usize + 1) << height).min(txids.len());
+        let parent_of_match = matches[start..end].iter().any(|matched| *matched);
+        self.flags.push(parent_of_match);
+        if height == 0 || !parent_of_match {
+            self.hashes.push(self.compute_hash(height, position, txids));
+        } else {
+            self.build(height - 1, position * 2, txids, matches);
+            if position * 2 + 1 < self.width(height - 1) {
+                self.build(height - 1, position * 2 + 1, txids, matches);
+            }
+        }
+    }
+
+    fn traverse(
+        &self,
+        height: u32,
+        position: u32,
+        cursor: &mut (usize, usize),
+        matches: &mut Matches,
+    ) -> Result<[u8; 32], String> {
+        let parent_of_match = *self
+            .flags
+            .get(cursor.0)
+            .ok_or("Partial merkle tree ran out of flag bits.")?;
+        cursor.0 += 1;
+        if height == 0 || !parent_of_match {
+            let hash = *self
+                .hashes
+                .get(cursor.1)
+                .ok_or("Partial merkle tree ran out of hashes.")?;
+            cursor.1 += 1;
+            if height == 0 && parent_of_match {
+                matches.push((hash, position));
+            }
+            return Ok(hash);
+        }
+        let left = self.traverse(height - 1, position * 2, cursor, matches)?;
+        let right = if position * 2 + 1 < self.width(height - 1) {
+            let right = self.traverse(height - 1, position * 2 + 1, cursor, matches)?;
+            if right == left {
+                return Err("Partial merkle tree has identical siblings.".to_string());
+            }
+            right
+        } else {
+            left
+        };
+        Ok(merkle_parent(&left, &right))
+    }
+
+    pub fn extract_matches(&self) -> Result<([u8; 32], Matches), String> {
+        if self.total == 0 {
+            return Err("Partial merkle tree has no transactions.".to_string());
+        }
+        if self.total > MAX_TRANSACTIONS {
+            return Err(format!("Too many transactions: {}.", self.total));
+        }
+        if self.hashes.len() > self.total as usize {
+            return Err("More hashes than transactions.".to_string());
+        }
+        if self.flags.len() < self.hashes.len() {
+            return Err("Fewer flag bits than hashes.".to_string());
+        }
+        let mut cursor = (0, 0);
+        let mut matches = Vec::new();
+        let root = self.traverse(self.height(), 0, &mut cursor, &mut matches)?;
+        if cursor.0.div_ceil(8) != self.flags.len().div_ceil(8) {
+            return Err("Unused flag bytes in partial merkle tree.".to_string());
+        }
+        if cursor.1 != self.hashes.len() {
+            return Err("Unused hashes in partial merkle tree.".to_string());
+        }
+        Ok((root, matches))
+    }
+
+    pub fn parse(reader: &mut impl Read) -> Result<Self, String> {
+        let total = read_u32_le(reader)?;
+        let hash_count = read_varint(reader)?;
+        if hash_count > MAX_TRANSACTIONS as u64 {
+            return Err(format!("Too many hashes: {}.", hash_count));
+        }
+        let hashes = (0..hash_count)
+            .map(|_| {
+                let mut hash = read_array::<32>(reader)?;
+                hash.reverse();
+                Ok(hash)
+            })
+            .collect::<Result<Vec<[u8; 32]>, String>>()?;
+        let flag_bytes = read_varint(reader)?;
+        if flag_bytes > MAX_TRANSACTIONS as u64 {
+            return Err(format!("Too many flag bytes: {}.", flag_bytes));
+        }
+        let flags = read_vec(reader, flag_bytes as usize)?
+            .iter()
+            .flat_map(|byte| (0..8).map(move |bit| byte >> bit & 1 == 1))
+            .collect();
+        Ok(Self {
+            total,
+            hashes,
+            flags,
+        })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = self.total.to_le_bytes().to_vec();
+        result.extend(encode_varint(self.hashes.len() as u64));
+        for hash in &self.hashes {
+            result.extend(hash.iter().rev());
+        }
+        let mut flag_bytes = vec![0u8; self.flags.len().div_ceil(8)];
+        for (index, _) in self.flags.iter().enumerate().filter(|(_, flag)| **flag) {
+            flag_bytes[index / 8] |= 1 << (index % 8);
+        }
+        result.extend(encode_varint(flag_bytes.len() as u64));
+        result.extend(flag_bytes);
+        result
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct MerkleBlock {
+    header: BlockHeader,
+    tree: PartialMerkleTree,
+}
+
+impl MerkleBlock {
+    #[inline]
+    pub fn new(header: BlockHeader, tree: PartialMerkleTree) -> Self {
+        Self { header, tree }
+    }
+
+    pub fn from_block(block: &Block, txids: &[[u8; 32]]) -> Result<Self, String> {
+        let hashes: Vec<[u8; 32]> = block.txs().iter().map(Tx::hash).collect();
+        let matches: Vec<bool> = hashes.iter().map(|hash| txids.contains(hash)).collect();
+        Ok(Self {
+            header: *block.header(),
+            tree: PartialMerkleTree::from_txids(&hashes, &matches)?,
+        })
+    }
+
+    #[inline]
+    pub fn header(&self) -> &BlockHeader {
+        &self.header
+    }
+
+    #[inline]
+    pub fn tree(&self) -> &PartialMerkleTree {
+        &self.tree
+    }
+
+    pub fn verify(&self) -> Result<Vec<[u8; 32]>, String> {
+        let (root, matches) = self.tree.extract_matches()?;
+        if root != self.header.merkle_root() {
+            return Err(format!(
+                "Merkle root mismatch: header has {}, tree gives {}.",
+                hex::encode(&self.header.merkle_root()),
+                hex::encode(&root)
+            ));
+        }
+        Ok(matches.into_iter().map(|(txid, _)| txid).collect())
+    }
+
+    pub fn parse(reader: &mut impl Read) -> Result<Self, String> {
+        Ok(Self {
+            header: BlockHeader::parse(reader)?,
+            tree: PartialMerkleTree::parse(reader)?,
+        })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        [&self.header.serialize()[..], &self.tree.serialize()].concat()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let mut cursor = Cursor::new(bytes);
+        let merkle_block = Self::parse(&mut cursor)?;
+        if cursor.position() as usize != bytes.len() {
+            return Err("Trailing data after merkle block.".to_string());
+        }
+        Ok(merkle_block)
+    }
+}
+
+impl FromStr for MerkleBlock {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_bytes(&hex::decode(s)?)
+    }
+}
+
+impl fmt::Display for MerkleBlock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", hex::encode(&self.serialize()))
+    }
+}
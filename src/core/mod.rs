@@ -1,2 +1,20 @@
+pub mod account;
+pub mod address;
+pub mod block;
+pub mod builder;
+pub mod chain;
+pub mod coin_selection;
+pub mod descriptor;
+pub mod hd;
+pub mod interpreter;
+pub mod merkle;
+pub mod network;
+pub mod psbt;
 pub mod s256ecc;
+pub mod script;
 pub mod sha256ser;
+pub mod sighash;
+pub mod taproot;
+pub mod tx;
+pub mod utxo;
+pub mod weight;
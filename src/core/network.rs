@@ -0,0 +1,194 @@
+use super::block::{BlockHeader, MAX_TARGET_BITS};
+use super::hd::{TPRV, TPUB, XPRV, XPUB};
+use crate::ser::hex;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    Testnet4,
+    Signet,
+    Regtest,
+}
+
+impl Network {
+    pub const ALL: [Network; 5] = [
+        Self::Mainnet,
+        Self::Testnet,
+        Self::Testnet4,
+        Self::Signet,
+        Self::Regtest,
+    ];
+
+    #[inline]
+    pub fn is_mainnet(&self) -> bool {
+        *self == Self::Mainnet
+    }
+
+    #[inline]
+    pub fn p2pkh_prefix(&self) -> u8 {
+        match self {
+            Self::Mainnet => 0x00,
+            _ => 0x6f,
+        }
+    }
+
+    #[inline]
+    pub fn p2sh_prefix(&self) -> u8 {
+        match self {
+            Self::Mainnet => 0x05,
+            _ => 0xc4,
+        }
+    }
+
+    #[inline]
+    pub fn wif_prefix(&self) -> u8 {
+        match self {
+            Self::Mainnet => 0x80,
+            _ => 0xef,
+        }
+    }
+
+    #[inline]
+    pub fn bech32_hrp(&self) -> &'static str {
+        match self {
+            Self::Mainnet => "bc",
+            Self::Testnet | Self::Testnet4 | Self::Signet => "tb",
+            Self::Regtest => "bcrt",
+        }
+    }
+
+    #[inline]
+    pub fn xprv_version(&self) -> [u8; 4] {
+        match self {
+            Self::Mainnet => XPRV,
+            _ => TPRV,
+        }
+    }
+
+    #[inline]
+    pub fn xpub_version(&self) -> [u8; 4] {
+        match self {
+            Self::Mainnet => XPUB,
+            _ => TPUB,
+        }
+    }
+
+    #[inline]
+    pub fn coin_type(&self) -> u32 {
+        match self {
+            Self::Mainnet => 0,
+            _ => 1,
+        }
+    }
+
+    #[inline]
+    pub fn magic(&self) -> [u8; 4] {
+        match self {
+            Self::Mainnet => [0xf9, 0xbe, 0xb4, 0xd9],
+            Self::Testnet => [0x0b, 0x11, 0x09, 0x07],
+            Self::Testnet4 => [0x1c, 0x16, 0x3f, 0x28],
+            Self::Signet => [0x0a, 0x03, 0xcf, 0x40],
+            Self::Regtest => [0xfa, 0xbf, 0xb5, 0xda],
+        }
+    }
+
+    #[inline]
+    pub fn default_port(&self) -> u16 {
+        match self {
+            Self::Mainnet => 8333,
+            Self::Testnet => 18333,
+            Self::Testnet4 => 48333,
+            Self::Signet => 38333,
+            Self::Regtest => 18444,
+        }
+    }
+
+    pub fn genesis_hash(&self) -> [u8; 32] {
+        let hash = match self {
+            Self::Mainnet => "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f",
+            Self::Testnet => "000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943",
+            Self::Testnet4 => "00000000da84f2bafbbc53dee25a72ae507ff4914b867c565be350b0da8bf043",
+            Self::Signet => "00000008819873e925422c1ff0f99f7cc9bbb232af63a077a480a3633bee1ef6",
+            Self::Regtest => "0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e2206",
+        };
+        hex::decode(hash).unwrap().try_into().unwrap()
+    }
+
+    pub fn genesis_header(&self) -> BlockHeader {
+        let merkle_root = match self {
+            Self::Testnet4 => "7aa0a7ae1e223414cb807e40cd57e667b718e42aaf9306db9102fe28912b7b4e",
+            _ => "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b",
+        };
+        let (timestamp, bits, nonce) = match self {
+            Self::Mainnet => (1_231_006_505, MAX_TARGET_BITS, 2_083_236_893),
+            Self::Testnet => (1_296_688_602, MAX_TARGET_BITS, 414_098_458),
+            Self::Testnet4 => (1_714_777_860, MAX_TARGET_BITS, 393_743_547),
+            Self::Signet => (1_598_918_400, 0x1e03_77ae, 52_613_770),
+            Self::Regtest => (1_296_688_602, 0x207f_ffff, 2),
+        };
+        let merkle_root = hex::decode(merkle_root).unwrap().try_into().unwrap();
+        BlockHeader::new(1, [0u8; 32], merkle_root, timestamp, bits, nonce)
+    }
+
+    #[inline]
+    pub fn pow_limit_bits(&self) -> u32 {
+        match self {
+            Self::Mainnet | Self::Testnet | Self::Testnet4 => MAX_TARGET_BITS,
+            Self::Signet => 0x1e03_77ae,
+            Self::Regtest => 0x207f_ffff,
+        }
+    }
+
+    #[inline]
+    pub fn allow_min_difficulty_blocks(&self) -> bool {
+        matches!(self, Self::Testnet | Self::Testnet4 | Self::Regtest)
+    }
+
+    #[inline]
+    pub fn no_pow_retargeting(&self) -> bool {
+        *self == Self::Regtest
+    }
+
+    #[inline]
+    pub fn enforce_bip94(&self) -> bool {
+        *self == Self::Testnet4
+    }
+
+    #[inline]
+    pub fn from_magic(magic: [u8; 4]) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|network| network.magic() == magic)
+    }
+}
+
+impl FromStr for Network {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "main" | "mainnet" | "bitcoin" => Ok(Self::Mainnet),
+            "test" | "testnet" | "testnet3" => Ok(Self::Testnet),
+            "testnet4" => Ok(Self::Testnet4),
+            "signet" => Ok(Self::Signet),
+            "regtest" => Ok(Self::Regtest),
+            _ => Err(format!("Unknown network: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Mainnet => "mainnet",
+            Self::Testnet => "testnet",
+            Self::Testnet4 => "testnet4",
+            Self::Signet => "signet",
+            Self::Regtest => "regtest",
+        };
+        write!(f, "{}", name)
+    }
+}
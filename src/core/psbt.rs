@@ -0,0 +1,1420 @@
+use super::address::p2pkh_script;
+use super::descriptor::KeyOrigin;
+use super::hd::{DerivationPath, ExtendedPublicKey};
+use super::s256ecc::{S256Point, S256PrivateKey};
+use super::script::{Instruction, Script};
+use super::sha256ser::Sha256Ripemd160;
+use super::sighash::{SighashCache, SIGHASH_ALL, SIGHASH_DEFAULT};
+use super::tx::{
+    read_var_bytes, serialize_var_bytes, OutPoint, Tx, TxIn, TxOut, LOCKTIME_THRESHOLD,
+    SEQUENCE_FINAL,
+};
+use crate::ser::base64;
+use crate::ser::chained_hash::ChainedCompute;
+use crate::ser::hex;
+use crate::ser::stream::read_array;
+use crate::ser::varint::{encode_varint, read_varint};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::io::{Cursor, Read};
+use std::str::FromStr;
+
+pub const PSBT_MAGIC: [u8; 5] = *b"psbt\xff";
+
+pub const PSBT_GLOBAL_UNSIGNED_TX: u64 = 0x00;
+pub const PSBT_GLOBAL_XPUB: u64 = 0x01;
+pub const PSBT_GLOBAL_TX_VERSION: u64 = 0x02;
+pub const PSBT_GLOBAL_FALLBACK_LOCKTIME: u64 = 0x03;
+pub const PSBT_GLOBAL_INPUT_COUNT: u64 = 0x04;
+pub const PSBT_GLOBAL_OUTPUT_COUNT: u64 = 0x05;
+pub const PSBT_GLOBAL_TX_MODIFIABLE: u64 = 0x06;
+pub const PSBT_GLOBAL_VERSION: u64 = 0xfb;
+
+pub const PSBT_IN_NON_WITNESS_UTXO: u64 = 0x00;
+pub const PSBT_IN_WITNESS_UTXO: u64 = 0x01;
+pub const PSBT_IN_PARTIAL_SIG: u64 = 0x02;
+pub const PSBT_IN_SIGHASH_TYPE: u64 = 0x03;
+pub const PSBT_IN_REDEEM_SCRIPT: u64 = 0x04;
+pub const PSBT_IN_WITNESS_SCRIPT: u64 = 0x05;
+pub const PSBT_IN_BIP32_DERIVATION: u64 = 0x06;
+pub const PSBT_IN_FINAL_SCRIPTSIG: u64 = 0x07;
+pub const PSBT_IN_FINAL_SCRIPTWITNESS: u64 = 0x08;
+pub const PSBT_IN_PREVIOUS_TXID: u64 = 0x0e;
+pub const PSBT_IN_OUTPUT_INDEX: u64 = 0x0f;
+pub const PSBT_IN_SEQUENCE: u64 = 0x10;
+pub const PSBT_IN_REQUIRED_TIME_LOCKTIME: u64 = 0x11;
+pub const PSBT_IN_REQUIRED_HEIGHT_LOCKTIME: u64 = 0x12;
+pub const PSBT_IN_TAP_KEY_SIG: u64 = 0x13;
+pub const PSBT_IN_TAP_INTERNAL_KEY: u64 = 0x17;
+pub const PSBT_IN_TAP_MERKLE_ROOT: u64 = 0x18;
+
+pub const PSBT_OUT_REDEEM_SCRIPT: u64 = 0x00;
+pub const PSBT_OUT_WITNESS_SCRIPT: u64 = 0x01;
+pub const PSBT_OUT_BIP32_DERIVATION: u64 = 0x02;
+pub const PSBT_OUT_AMOUNT: u64 = 0x03;
+pub const PSBT_OUT_SCRIPT: u64 = 0x04;
+pub const PSBT_OUT_TAP_INTERNAL_KEY: u64 = 0x05;
+
+pub const TX_MODIFIABLE_INPUTS: u8 = 0x01;
+pub const TX_MODIFIABLE_OUTPUTS: u8 = 0x02;
+
+type KeyValue = (Vec<u8>, Vec<u8>);
+type TypedKeyValue = (u64, Vec<u8>, Vec<u8>);
+type Stack = Vec<Vec<u8>>;
+
+#[inline]
+fn key(key_type: u64, key_data: &[u8]) -> Vec<u8> {
+    [&encode_varint(key_type)[..], key_data].concat()
+}
+
+fn read_map(reader: &mut impl Read) -> Result<Vec<TypedKeyValue>, String> {
+    let mut seen = HashSet::new();
+    let mut pairs = Vec::new();
+    loop {
+        let key = read_var_bytes(reader)?;
+        if key.is_empty() {
+            return Ok(pairs);
+        }
+        let value = read_var_bytes(reader)?;
+        if !seen.insert(key.clone()) {
+            return Err(format!("Duplicate PSBT key: {}", hex::encode(&key)));
+        }
+        let mut cursor = Cursor::new(&key[..]);
+        let key_type = read_varint(&mut cursor)?;
+        let key_data = key[cursor.position() as usize..].to_vec();
+        pairs.push((key_type, key_data, value));
+    }
+}
+
+fn serialize_map(mut pairs: Vec<KeyValue>) -> Vec<u8> {
+    pairs.sort();
+    let mut result = Vec::new();
+    for (key, value) in pairs {
+        result.extend(serialize_var_bytes(&key));
+        result.extend(serialize_var_bytes(&value));
+    }
+    result.push(0x00);
+    result
+}
+
+fn expect_no_key_data(key_type: u64, key_data: &[u8]) -> Result<(), String> {
+    if !key_data.is_empty() {
+        return Err(format!(
+            "PSBT key type {:#04x} takes no key data.",
+            key_type
+        ));
+    }
+    Ok(())
+}
+
+fn parse_exact<'a, T>(
+    value: &'a [u8],
+    parse: impl FnOnce(&mut Cursor<&'a [u8]>) -> Result<T, String>,
+) -> Result<T, String> {
+    let mut cursor = Cursor::new(value);
+    let result = parse(&mut cursor)?;
+    if cursor.position() as usize != value.len() {
+        return Err("Trailing data in PSBT value.".to_string());
+    }
+    Ok(result)
+}
+
+#[inline]
+fn parse_u32(value: &[u8]) -> Result<u32, String> {
+    parse_exact(value, |reader| Ok(u32::from_le_bytes(read_array(reader)?)))
+}
+
+#[inline]
+fn parse_bytes32(value: &[u8]) -> Result<[u8; 32], String> {
+    value
+        .try_into()
+        .map_err(|_| format!("Expected 32 bytes, got {}.", value.len()))
+}
+
+#[inline]
+fn parse_pubkey(key_data: &[u8]) -> Result<Vec<u8>, String> {
+    S256Point::parse_sec(key_data)?;
+    Ok(key_data.to_vec())
+}
+
+fn parse_key_origin(value: &[u8]) -> Result<KeyOrigin, String> {
+    if value.len() < 4 || !value.len().is_multiple_of(4) {
+        return Err(format!("Invalid BIP32 derivation length: {}", value.len()));
+    }
+    let children = value[4..]
+        .chunks(4)
+        .map(|child| u32::from_le_bytes(child.try_into().unwrap()))
+        .collect();
+    Ok(KeyOrigin::new(
+        value[..4].try_into().unwrap(),
+        DerivationPath::new(children),
+    ))
+}
+
+fn serialize_key_origin(origin: &KeyOrigin) -> Vec<u8> {
+    let mut result = origin.fingerprint().to_vec();
+    for child in origin.path().children() {
+        result.extend(child.to_le_bytes());
+    }
+    result
+}
+
+fn parse_witness(value: &[u8]) -> Result<Vec<Vec<u8>>, String> {
+    parse_exact(value, |reader| {
+        (0..read_varint(reader)?)
+            .map(|_| read_var_bytes(reader))
+            .collect()
+    })
+}
+
+fn serialize_witness(witness: &[Vec<u8>]) -> Vec<u8> {
+    let mut result = encode_varint(witness.len() as u64);
+    for item in witness {
+        result.extend(serialize_var_bytes(item));
+    }
+    result
+}
+
+fn script_from_items(items: &[Vec<u8>]) -> Script {
+    let mut script = Script::new();
+    for item in items {
+        script.push_data(item);
+    }
+    script
+}
+
+fn merge_option<T: Clone>(target: &mut Option<T>, other: &Option<T>) {
+    if target.is_none() {
+        target.clone_from(other);
+    }
+}
+
+fn merge_map<K: Ord + Clone, V: Clone>(target: &mut BTreeMap<K, V>, other: &BTreeMap<K, V>) {
+    for (key, value) in other {
+        target.entry(key.clone()).or_insert_with(|| value.clone());
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct PsbtInput {
+    previous_output: OutPoint,
+    sequence: Option<u32>,
+    required_time_locktime: Option<u32>,
+    required_height_locktime: Option<u32>,
+    non_witness_utxo: Option<Tx>,
+    witness_utxo: Option<TxOut>,
+    partial_sigs: BTreeMap<Vec<u8>, Vec<u8>>,
+    sighash_type: Option<u32>,
+    redeem_script: Option<Script>,
+    witness_script: Option<Script>,
+    bip32_derivations: BTreeMap<Vec<u8>, KeyOrigin>,
+    final_script_sig: Option<Script>,
+    final_script_witness: Option<Vec<Vec<u8>>>,
+    tap_key_sig: Option<Vec<u8>>,
+    tap_internal_key: Option<[u8; 32]>,
+    tap_merkle_root: Option<[u8; 32]>,
+    unknown: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+impl PsbtInput {
+    #[inline]
+    pub fn new(previous_output: OutPoint, sequence: Option<u32>) -> Self {
+        Self {
+            previous_output,
+            sequence,
+            required_time_locktime: None,
+            required_height_locktime: None,
+            non_witness_utxo: None,
+            witness_utxo: None,
+            partial_sigs: BTreeMap::new(),
+            sighash_type: None,
+            redeem_script: None,
+            witness_script: None,
+            bip32_derivations: BTreeMap::new(),
+            final_script_sig: None,
+            final_script_witness: None,
+            tap_key_sig: None,
+            tap_internal_key: None,
+            tap_merkle_root: None,
+            unknown: BTreeMap::new(),
+        }
+    }
+
+    #[inline]
+    pub fn previous_output(&self) -> OutPoint {
+        self.previous_output
+    }
+
+    #[inline]
+    pub fn sequence(&self) -> u32 {
+        self.sequence.unwrap_or(SEQUENCE_FINAL)
+    }
+
+    #[inline]
+    pub fn required_time_locktime(&self) -> Option<u32> {
+        self.required_time_locktime
+    }
+
+    #[inline]
+    pub fn required_height_locktime(&self) -> Option<u32> {
+        self.required_height_locktime
+    }
+
+    #[inline]
+    pub fn non_witness_utxo(&self) -> Option<&Tx> {
+        self.non_witness_utxo.as_ref()
+    }
+
+    #[inline]
+    pub fn witness_utxo(&self) -> Option<&TxOut> {
+        self.witness_utxo.as_ref()
+    }
+
+    #[inline]
+    pub fn partial_sigs(&self) -> &BTreeMap<Vec<u8>, Vec<u8>> {
+        &self.partial_sigs
+    }
+
+    #[inline]
+    pub fn sighash_type(&self) -> Option<u32> {
+        self.sighash_type
+    }
+
+    #[inline]
+    pub fn redeem_script(&self) -> Option<&Script> {
+        self.redeem_script.as_ref()
+    }
+
+    #[inline]
+    pub fn witness_script(&self) -> Option<&Script> {
+        self.witness_script.as_ref()
+    }
+
+    #[inline]
+    pub fn bip32_derivations(&self) -> &BTreeMap<Vec<u8>, KeyOrigin> {
+        &self.bip32_derivations
+    }
+
+    #[inline]
+    pub fn final_script_sig(&self) -> Option<&Script> {
+        self.final_script_sig.as_ref()
+    }
+
+    #[inline]
+    pub fn final_script_witness(&self) -> Option<&[Vec<u8>]> {
+        self.final_script_witness.as_deref()
+    }
+
+    #[inline]
+    pub fn tap_key_sig(&self) -> Option<&[u8]> {
+        self.tap_key_sig.as_deref()
+    }
+
+    #[inline]
+    pub fn tap_internal_key(&self) -> Option<[u8; 32]> {
+        self.tap_internal_key
+    }
+
+    #[inline]
+    pub fn tap_merkle_root(&self) -> Option<[u8; 32]> {
+        self.tap_merkle_root
+    }
+
+    #[inline]
+    pub fn unknown(&self) -> &BTreeMap<Vec<u8>, Vec<u8>> {
+        &self.unknown
+    }
+
+    #[inline]
+    pub fn is_finalized(&self) -> bool {
+        self.final_script_sig.is_some() || self.final_script_witness.is_some()
+    }
+
+    pub fn set_non_witness_utxo(&mut self, tx: Tx) -> Result<(), String> {
+        if tx.hash() != self.previous_output.txid() {
+            return Err(format!(
+                "Non-witness UTXO {} does not match the spent outpoint {}.",
+                tx.id(),
+                self.previous_output
+            ));
+        }
+        if self.previous_output.vout() as usize >= tx.outputs().len() {
+            return Err(format!(
+                "Non-witness UTXO has no output {}.",
+                self.previous_output.vout()
+            ));
+        }
+        self.non_witness_utxo = Some(tx);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn set_witness_utxo(&mut self, output: TxOut) {
+        self.witness_utxo = Some(output);
+    }
+
+    #[inline]
+    pub fn set_sighash_type(&mut self, hash_type: u32) {
+        self.sighash_type = Some(hash_type);
+    }
+
+    #[inline]
+    pub fn set_redeem_script(&mut self, script: Script) {
+        self.redeem_script = Some(script);
+    }
+
+    #[inline]
+    pub fn set_witness_script(&mut self, script: Script) {
+        self.witness_script = Some(script);
+    }
+
+    #[inline]
+    pub fn add_bip32_derivation(&mut self, pubkey: &[u8], origin: KeyOrigin) {
+        self.bip32_derivations.insert(pubkey.to_vec(), origin);
+    }
+
+    #[inline]
+    pub fn set_tap_internal_key(&mut self, xonly: [u8; 32]) {
+        self.tap_internal_key = Some(xonly);
+    }
+
+    #[inline]
+    pub fn set_tap_merkle_root(&mut self, merkle_root: [u8; 32]) {
+        self.tap_merkle_root = Some(merkle_root);
+    }
+
+    pub fn set_required_time_locktime(&mut self, locktime: u32) -> Result<(), String> {
+        if locktime < LOCKTIME_THRESHOLD {
+            return Err(format!("Invalid time-based locktime: {}", locktime));
+        }
+        self.required_time_locktime = Some(locktime);
+        Ok(())
+    }
+
+    pub fn set_required_height_locktime(&mut self, locktime: u32) -> Result<(), String> {
+        if locktime == 0 || locktime >= LOCKTIME_THRESHOLD {
+            return Err(format!("Invalid height-based locktime: {}", locktime));
+        }
+        self.required_height_locktime = Some(locktime);
+        Ok(())
+    }
+
+    pub fn spent_output(&self) -> Option<TxOut> {
+        self.witness_utxo.clone().or_else(|| {
+            self.non_witness_utxo
+                .as_ref()
+                .and_then(|tx| tx.outputs().get(self.previous_output.vout() as usize))
+                .cloned()
+        })
+    }
+
+    fn parse(
+        reader: &mut impl Read,
+        version: u32,
+        tx_input: Option<&TxIn>,
+    ) -> Result<Self, String> {
+        let mut input = Self::new(OutPoint::NULL, None);
+        let mut previous_txid = None;
+        let mut output_index = None;
+        if let Some(tx_input) = tx_input {
+            previous_txid = Some(tx_input.previous_output().txid());
+            output_index = Some(tx_input.previous_output().vout());
+            input.sequence = Some(tx_input.sequence());
+        }
+        for (key_type, key_data, value) in read_map(reader)? {
+            let v2_only = matches!(
+                key_type,
+                PSBT_IN_PREVIOUS_TXID..=PSBT_IN_REQUIRED_HEIGHT_LOCKTIME
+            );
+            if v2_only && version < 2 {
+                return Err(format!(
+                    "PSBT input key type {:#04x} is not allowed in version {}.",
+                    key_type, version
+                ));
+            }
+            match key_type {
+                PSBT_IN_PARTIAL_SIG => {
+                    input.partial_sigs.insert(parse_pubkey(&key_data)?, value);
+                    continue;
+                }
+                PSBT_IN_BIP32_DERIVATION => {
+                    let origin = parse_key_origin(&value)?;
+                    input
+                        .bip32_derivations
+                        .insert(parse_pubkey(&key_data)?, origin);
+                    continue;
+                }
+                PSBT_IN_NON_WITNESS_UTXO..=PSBT_IN_FINAL_SCRIPTWITNESS
+                | PSBT_IN_PREVIOUS_TXID..=PSBT_IN_TAP_KEY_SIG
+                | PSBT_IN_TAP_INTERNAL_KEY
+                | PSBT_IN_TAP_MERKLE_ROOT => expect_no_key_data(key_type, &key_data)?,
+                _ => {
+                    input.unknown.insert(key(key_type, &key_data), value);
+                    continue;
+                }
+            }
+            match key_type {
+                PSBT_IN_NON_WITNESS_UTXO => input.non_witness_utxo = Some(Tx::from_bytes(&value)?),
+                PSBT_IN_WITNESS_UTXO => {
+                    input.witness_utxo = Some(parse_exact(&value, TxOut::parse)?)
+                }
+                PSBT_IN_SIGHASH_TYPE => input.sighash_type = Some(parse_u32(&value)?),
+                PSBT_IN_REDEEM_SCRIPT => input.redeem_script = Some(Script::from(value)),
+                PSBT_IN_WITNESS_SCRIPT => input.witness_script = Some(Script::from(value)),
+                PSBT_IN_FINAL_SCRIPTSIG => input.final_script_sig = Some(Script::from(value)),
+                PSBT_IN_FINAL_SCRIPTWITNESS => {
+                    input.final_script_witness = Some(parse_witness(&value)?)
+                }
+                PSBT_IN_PREVIOUS_TXID => {
+                    let mut txid = parse_bytes32(&value)?;
+                    txid.reverse();
+                    previous_txid = Some(txid);
+                }
+                PSBT_IN_OUTPUT_INDEX => output_index = Some(parse_u32(&value)?),
+                PSBT_IN_SEQUENCE => input.sequence = Some(parse_u32(&value)?),
+                PSBT_IN_REQUIRED_TIME_LOCKTIME => {
+                    input.set_required_time_locktime(parse_u32(&value)?)?
+                }
+                PSBT_IN_REQUIRED_HEIGHT_LOCKTIME => {
+                    input.set_required_height_locktime(parse_u32(&value)?)?
+                }
+                PSBT_IN_TAP_KEY_SIG => {
+                    if value.len() != 64 && value.len() != 65 {
+                        return Err(format!(
+                            "Invalid taproot key signature length: {}",
+                            value.len()
+                        ));
+                    }
+                    input.tap_key_sig = Some(value);
+                }
+                PSBT_IN_TAP_INTERNAL_KEY => {
+                    let xonly = parse_bytes32(&value)?;
+                    S256Point::lift_x(&xonly)?;
+                    input.tap_internal_key = Some(xonly);
+                }
+                _ => input.tap_merkle_root = Some(parse_bytes32(&value)?),
+            }
+        }
+
+        let (Some(txid), Some(vout)) = (previous_txid, output_index) else {
+            return Err("PSBT input is missing its previous txid or output index.".to_string());
+        };
+        input.previous_output = OutPoint::new(txid, vout);
+        if let Some(tx) = input.non_witness_utxo.take() {
+            input.set_non_witness_utxo(tx)?;
+        }
+        Ok(input)
+    }
+
+    fn serialize(&self, version: u32) -> Vec<u8> {
+        let mut pairs: Vec<KeyValue> = Vec::new();
+        let mut push = |key_type: u64, value: Vec<u8>| pairs.push((key(key_type, &[]), value));
+        if let Some(tx) = &self.non_witness_utxo {
+            push(PSBT_IN_NON_WITNESS_UTXO, tx.serialize());
+        }
+        if let Some(output) = &self.witness_utxo {
+            push(PSBT_IN_WITNESS_UTXO, output.serialize());
+        }
+        if let Some(hash_type) = self.sighash_type {
+            push(PSBT_IN_SIGHASH_TYPE, hash_type.to_le_bytes().to_vec());
+        }
+        if let Some(script) = &self.redeem_script {
+            push(PSBT_IN_REDEEM_SCRIPT, script.to_vec());
+        }
+        if let Some(script) = &self.witness_script {
+            push(PSBT_IN_WITNESS_SCRIPT, script.to_vec());
+        }
+        if let Some(script) = &self.final_script_sig {
+            push(PSBT_IN_FINAL_SCRIPTSIG, script.to_vec());
+        }
+        if let Some(witness) = &self.final_script_witness {
+            push(PSBT_IN_FINAL_SCRIPTWITNESS, serialize_witness(witness));
+        }
+        if version >= 2 {
+            let mut txid = self.previous_output.txid();
+            txid.reverse();
+            push(PSBT_IN_PREVIOUS_TXID, txid.to_vec());
+            push(
+                PSBT_IN_OUTPUT_INDEX,
+                self.previous_output.vout().to_le_bytes().to_vec(),
+            );
+            if let Some(sequence) = self.sequence {
+                push(PSBT_IN_SEQUENCE, sequence.to_le_bytes().to_vec());
+            }
+            if let Some(locktime) = self.required_time_locktime {
+                push(
+                    PSBT_IN_REQUIRED_TIME_LOCKTIME,
+                    locktime.to_le_bytes().to_vec(),
+                );
+            }
+            if let Some(locktime) = self.required_height_locktime {
+                push(
+                    PSBT_IN_REQUIRED_HEIGHT_LOCKTIME,
+                    locktime.to_le_bytes().to_vec(),
+                );
+            }
+        }
+        if let Some(signature) = &self.tap_key_sig {
+            push(PSBT_IN_TAP_KEY_SIG, signature.clone());
+        }
+        if let Some(xonly) = self.tap_internal_key {
+            push(PSBT_IN_TAP_INTERNAL_KEY, xonly.to_vec());
+        }
+        if let Some(merkle_root) = self.tap_merkle_root {
+            push(PSBT_IN_TAP_MERKLE_ROOT, merkle_root.to_vec());
+        }
+        for (pubkey, signature) in &self.partial_sigs {
+            pairs.push((key(PSBT_IN_PARTIAL_SIG, pubkey), signature.clone()));
+        }
+        for (pubkey, origin) in &self.bip32_derivations {
+            pairs.push((
+                key(PSBT_IN_BIP32_DERIVATION, pubkey),
+                serialize_key_origin(origin),
+            ));
+        }
+        pairs.extend(self.unknown.clone());
+        serialize_map(pairs)
+    }
+
+    fn combine(&mut self, other: &Self) {
+        merge_option(&mut self.non_witness_utxo, &other.non_witness_utxo);
+        merge_option(&mut self.witness_utxo, &other.witness_utxo);
+        merge_map(&mut self.partial_sigs, &other.partial_sigs);
+        merge_option(&mut self.sighash_type, &other.sighash_type);
+        merge_option(&mut self.redeem_script, &other.redeem_script);
+        merge_option(&mut self.witness_script, &other.witness_script);
+        merge_map(&mut self.bip32_derivations, &other.bip32_derivations);
+        merge_option(&mut self.final_script_sig, &other.final_script_sig);
+        merge_option(&mut self.final_script_witness, &other.final_script_witness);
+        merge_option(
+            &mut self.required_time_locktime,
+            &other.required_time_locktime,
+        );
+        merge_option(
+            &mut self.required_height_locktime,
+            &other.required_height_locktime,
+        );
+        merge_option(&mut self.tap_key_sig, &other.tap_key_sig);
+        merge_option(&mut self.tap_internal_key, &other.tap_internal_key);
+        merge_option(&mut self.tap_merkle_root, &other.tap_merkle_root);
+        merge_map(&mut self.unknown, &other.unknown);
+    }
+
+    fn script_code(&self, script_pubkey: &Script) -> Result<Option<(Script, bool)>, String> {
+        let mut script = script_pubkey.clone();
+        if script.is_p2sh() {
+            let Some(redeem_script) = &self.redeem_script else {
+                return Ok(None);
+            };
+            if Sha256Ripemd160::compute(redeem_script) != script[2..22] {
+                return Err("Redeem script does not match the P2SH output.".to_string());
+            }
+            script = redeem_script.clone();
+        }
+        if script.is_p2wpkh() {
+            return Ok(Some((Script::from(p2pkh_script(&script[2..22])), true)));
+        }
+        if script.is_p2wsh() {
+            let Some(witness_script) = &self.witness_script else {
+                return Ok(None);
+            };
+            if Sha256::digest(witness_script.as_bytes())[..] != script[2..34] {
+                return Err("Witness script does not match the P2WSH program.".to_string());
+            }
+            return Ok(Some((witness_script.clone(), true)));
+        }
+        if script.witness_program().is_some() {
+            return Ok(None);
+        }
+        Ok(Some((script, false)))
+    }
+
+    fn sign(
+        &mut self,
+        cache: &SighashCache,
+        input_index: usize,
+        key: &S256PrivateKey,
+    ) -> Result<bool, String> {
+        let Some(spent_output) = self.spent_output() else {
+            return Ok(false);
+        };
+        let script_pubkey = spent_output.script_pubkey();
+        let point = key.point();
+
+        if script_pubkey.is_p2tr() {
+            let output_key = point.tap_tweak(self.tap_merkle_root)?;
+            if output_key.xonly() != script_pubkey[2..34]
+                || cache.spent_outputs().len() != cache.tx().inputs().len()
+            {
+                return Ok(false);
+            }
+            let hash_type = self.sighash_type.unwrap_or(SIGHASH_DEFAULT);
+            let msg = cache.taproot_key_spend_sighash(input_index, hash_type, None)?;
+            let tweaked = key.tap_tweak(self.tap_merkle_root)?;
+            let mut signature = tweaked.sign_schnorr(&msg, &rand::random()).to_vec();
+            if hash_type != SIGHASH_DEFAULT {
+                signature.push(hash_type as u8);
+            }
+            self.tap_key_sig = Some(signature);
+            self.tap_internal_key.get_or_insert(point.xonly());
+            return Ok(true);
+        }
+
+        let Some((script_code, segwit)) = self.script_code(script_pubkey)? else {
+            return Ok(false);
+        };
+        if !segwit && self.non_witness_utxo.is_none() {
+            return Ok(false);
+        }
+        let candidates = if segwit {
+            vec![point.sec(true)]
+        } else {
+            vec![point.sec(true), point.sec(false)]
+        };
+        let Some(pubkey) = candidates.into_iter().find(|sec| {
+            (script_code.is_p2pkh() && Sha256Ripemd160::compute(sec) == script_code[3..23])
+                || script_code
+                    .instructions()
+                    .any(|i| matches!(i, Ok(Instruction::Push(_, data)) if data == *sec))
+        }) else {
+            return Ok(false);
+        };
+
+        let hash_type = self.sighash_type.unwrap_or(SIGHASH_ALL);
+        if !matches!(hash_type, 0x01..=0x03 | 0x81..=0x83) {
+            return Err(format!("Invalid ECDSA sighash type: {:#04x}.", hash_type));
+        }
+        let z = if segwit {
+            cache.segwit_v0_sighash(input_index, &script_code, spent_output.amount(), hash_type)?
+        } else {
+            cache
+                .tx()
+                .legacy_sighash(input_index, &script_code, hash_type)
+        };
+        let signature = [key.sign(z).der_encoded(), vec![hash_type as u8]].concat();
+        self.partial_sigs.insert(pubkey, signature);
+        Ok(true)
+    }
+
+    fn signature_for_hash(&self, h160: &[u8]) -> Option<(Vec<u8>, Vec<u8>)> {
+        self.partial_sigs
+            .iter()
+            .find(|(pubkey, _)| Sha256Ripemd160::compute(pubkey) == h160)
+            .map(|(pubkey, signature)| (signature.clone(), pubkey.clone()))
+    }
+
+    fn satisfy_script(&self, script: &Script) -> Result<Vec<Vec<u8>>, String> {
+        if script.is_p2pkh() {
+            let (signature, pubkey) = self
+                .signature_for_hash(&script[3..23])
+                .ok_or("Missing signature for P2PKH script.")?;
+            return Ok(vec![signature, pubkey]);
+        }
+        if let Some((threshold, keys)) = script.multisig_keys() {
+            let mut items = vec![Vec::new()];
+            items.extend(
+                keys.iter()
+                    .filter_map(|pubkey| self.partial_sigs.get(pubkey).cloned())
+                    .take(threshold),
+            );
+            if items.len() <= threshold {
+                return Err(format!(
+                    "Multisig needs {} signatures, have {}.",
+                    threshold,
+                    items.len() - 1
+                ));
+            }
+            return Ok(items);
+        }
+        match &script[..] {
+            [len @ (33 | 65), pubkey @ .., 0xac] if pubkey.len() == *len as usize => self
+                .partial_sigs
+                .get(pubkey)
+                .map(|signature| vec![signature.clone()])
+                .ok_or("Missing signature for P2PK script.".to_string()),
+            _ => Err(format!("Unsupported script for finalization: {}", script)),
+        }
+    }
+
+    fn satisfy(&self, script: &Script) -> Result<(Stack, Stack), String> {
+        if script.is_p2wpkh() {
+            let (signature, pubkey) = self
+                .signature_for_hash(&script[2..22])
+                .ok_or("Missing signature for P2WPKH output.")?;
+            return Ok((Vec::new(), vec![signature, pubkey]));
+        }
+        if script.is_p2wsh() {
+            let witness_script = self
+                .witness_script
+                .as_ref()
+                .ok_or("Missing witness script for P2WSH output.")?;
+            let mut witness = self.satisfy_script(witness_script)?;
+            witness.push(witness_script.to_vec());
+            return Ok((Vec::new(), witness));
+        }
+        Ok((self.satisfy_script(script)?, Vec::new()))
+    }
+
+    fn finalize(&mut self) -> Result<(), String> {
+        if self.is_finalized() {
+            return Ok(());
+        }
+        let spent_output = self.spent_output().ok_or("Missing UTXO.")?;
+        let script_pubkey = spent_output.script_pubkey();
+        let (script_sig, witness) = if script_pubkey.is_p2tr() {
+            let signature = self
+                .tap_key_sig
+                .clone()
+                .ok_or("Missing taproot key path signature.")?;
+            (Vec::new(), vec![signature])
+        } else if script_pubkey.is_p2sh() {
+            let redeem_script = self
+                .redeem_script
+                .clone()
+                .ok_or("Missing redeem script for P2SH output.")?;
+            let (mut script_sig, witness) = self.satisfy(&redeem_script)?;
+            script_sig.push(redeem_script.into_bytes());
+            (script_sig, witness)
+        } else {
+            self.satisfy(script_pubkey)?
+        };
+
+        self.final_script_sig = (!script_sig.is_empty()).then(|| script_from_items(&script_sig));
+        self.final_script_witness = (!witness.is_empty()).then_some(witness);
+        self.partial_sigs.clear();
+        self.sighash_type = None;
+        self.redeem_script = None;
+        self.witness_script = None;
+        self.bip32_derivations.clear();
+        self.tap_key_sig = None;
+        self.tap_internal_key = None;
+        self.tap_merkle_root = None;
+        Ok(())
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct PsbtOutput {
+    amount: u64,
+    script_pubkey: Script,
+    redeem_script: Option<Script>,
+    witness_script: Option<Script>,
+    bip32_derivations: BTreeMap<Vec<u8>, KeyOrigin>,
+    tap_internal_key: Option<[u8; 32]>,
+    unknown: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+impl PsbtOutput {
+    #[inline]
+    pub fn new(output: TxOut) -> Self {
+        Self {
+            amount: output.amount(),
+            script_pubkey: output.script_pubkey().clone(),
+            redeem_script: None,
+            witness_script: None,
+            bip32_derivations: BTreeMap::new(),
+            tap_internal_key: None,
+            unknown: BTreeMap::new(),
+        }
+    }
+
+    #[inline]
+    pub fn amount(&self) -> u64 {
+        self.amount
+    }
+
+    #[inline]
+    pub fn script_pubkey(&self) -> &Script {
+        &self.script_pubkey
+    }
+
+    #[inline]
+    pub fn to_tx_out(&self) -> TxOut {
+        TxOut::new(self.amount, self.script_pubkey.clone())
+    }
+
+    #[inline]
+    pub fn redeem_script(&self) -> Option<&Script> {
+        self.redeem_script.as_ref()
+    }
+
+    #[inline]
+    pub fn witness_script(&self) -> Option<&Script> {
+        self.witness_script.as_ref()
+    }
+
+    #[inline]
+    pub fn bip32_derivations(&self) -> &BTreeMap<Vec<u8>, KeyOrigin> {
+        &self.bip32_derivations
+    }
+
+    #[inline]
+    pub fn tap_internal_key(&self) -> Option<[u8; 32]> {
+        self.tap_internal_key
+    }
+
+    #[inline]
+    pub fn unknown(&self) -> &BTreeMap<Vec<u8>, Vec<u8>> {
+        &self.unknown
+    }
+
+    #[inline]
+    pub fn set_redeem_script(&mut self, script: Script) {
+        self.redeem_script = Some(script);
+    }
+
+    #[inline]
+    pub fn set_witness_script(&mut self, script: Script) {
+        self.witness_script = Some(script);
+    }
+
+    #[inline]
+    pub fn add_bip32_derivation(&mut self, pubkey: &[u8], origin: KeyOrigin) {
+        self.bip32_derivations.insert(pubkey.to_vec(), origin);
+    }
+
+    #[inline]
+    pub fn set_tap_internal_key(&mut self, xonly: [u8; 32]) {
+        self.tap_internal_key = Some(xonly);
+    }
+
+    fn parse(
+        reader: &mut impl Read,
+        version: u32,
+        tx_output: Option<&TxOut>,
+    ) -> Result<Self, String> {
+        let mut amount = tx_output.map(TxOut::amount);
+        let mut script_pubkey = tx_output.map(|output| output.script_pubkey().clone());
+        let mut output = Self::new(TxOut::new(0, Script::new()));
+        for (key_type, key_data, value) in read_map(reader)? {
+            match key_type {
+                PSBT_OUT_BIP32_DERIVATION => {
+                    let origin = parse_key_origin(&value)?;
+                    output
+                        .bip32_derivations
+                        .insert(parse_pubkey(&key_data)?, origin);
+                    continue;
+                }
+                PSBT_OUT_AMOUNT | PSBT_OUT_SCRIPT if version < 2 => {
+                    return Err(format!(
+                        "PSBT output key type {:#04x} is not allowed in version {}.",
+                        key_type, version
+                    ));
+                }
+                PSBT_OUT_REDEEM_SCRIPT..=PSBT_OUT_TAP_INTERNAL_KEY => {
+                    expect_no_key_data(key_type, &key_data)?
+                }
+                _ => {
+                    output.unknown.insert(key(key_type, &key_data), value);
+                    continue;
+                }
+            }
+            match key_type {
+                PSBT_OUT_REDEEM_SCRIPT => output.redeem_script = Some(Script::from(value)),
+                PSBT_OUT_WITNESS_SCRIPT => output.witness_script = Some(Script::from(value)),
+                PSBT_OUT_AMOUNT => {
+                    amount = Some(parse_exact(&value, |reader| {
+                        Ok(u64::from_le_bytes(read_array(reader)?))
+                    })?)
+                }
+                PSBT_OUT_SCRIPT => script_pubkey = Some(Script::from(value)),
+                _ => {
+                    let xonly = parse_bytes32(&value)?;
+                    S256Point::lift_x(&xonly)?;
+                    output.tap_internal_key = Some(xonly);
+                }
+            }
+        }
+        let (Some(amount), Some(script_pubkey)) = (amount, script_pubkey) else {
+            return Err("PSBT output is missing its amount or script.".to_string());
+        };
+        output.amount = amount;
+        output.script_pubkey = script_pubkey;
+        Ok(output)
+    }
+
+    fn serialize(&self, version: u32) -> Vec<u8> {
+        let mut pairs: Vec<KeyValue> = Vec::new();
+        if let Some(script) = &self.redeem_script {
+            pairs.push((key(PSBT_OUT_REDEEM_SCRIPT, &[]), script.to_vec()));
+        }
+        if let Some(script) = &self.witness_script {
+            pairs.push((key(PSBT_OUT_WITNESS_SCRIPT, &[]), script.to_vec()));
+        }
+        if version >= 2 {
+            pairs.push((
+                key(PSBT_OUT_AMOUNT, &[]),
+                self.amount.to_le_bytes().to_vec(),
+            ));
+            pairs.push((key(PSBT_OUT_SCRIPT, &[]), self.script_pubkey.to_vec()));
+        }
+        if let Some(xonly) = self.tap_internal_key {
+            pairs.push((key(PSBT_OUT_TAP_INTERNAL_KEY, &[]), xonly.to_vec()));
+        }
+        for (pubkey, origin) in &self.bip32_derivations {
+            pairs.push((
+                key(PSBT_OUT_BIP32_DERIVATION, pubkey),
+                serialize_key_origin(origin),
+            ));
+        }
+        pairs.extend(self.unknown.clone());
+        serialize_map(pairs)
+    }
+
+    fn combine(&mut self, other: &Self) {
+        merge_option(&mut self.redeem_script, &other.redeem_script);
+        merge_option(&mut self.witness_script, &other.witness_script);
+        merge_map(&mut self.bip32_derivations, &other.bip32_derivations);
+        merge_option(&mut self.tap_internal_key, &other.tap_internal_key);
+        merge_map(&mut self.unknown, &other.unknown);
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Psbt {
+    version: u32,
+    explicit_version: bool,
+    tx_version: u32,
+    fallback_locktime: Option<u32>,
+    tx_modifiable: Option<u8>,
+    xpubs: BTreeMap<Vec<u8>, KeyOrigin>,
+    inputs: Vec<PsbtInput>,
+    outputs: Vec<PsbtOutput>,
+    unknown: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+impl Psbt {
+    pub fn from_unsigned_tx(tx: &Tx) -> Result<Self, String> {
+        if tx
+            .inputs()
+            .iter()
+            .any(|input| !input.script_sig().is_empty() || !input.witness().is_empty())
+        {
+            return Err(
+                "Unsigned transaction must have empty scriptSigs and witnesses.".to_string(),
+            );
+        }
+        Ok(Self {
+            version: 0,
+            explicit_version: false,
+            tx_version: tx.version(),
+            fallback_locktime: Some(tx.locktime()),
+            tx_modifiable: None,
+            xpubs: BTreeMap::new(),
+            inputs: tx
+                .inputs()
+                .iter()
+                .map(|input| PsbtInput::new(input.previous_output(), Some(input.sequence())))
+                .collect(),
+            outputs: tx.outputs().iter().cloned().map(PsbtOutput::new).collect(),
+            unknown: BTreeMap::new(),
+        })
+    }
+
+    #[inline]
+    pub fn new_v2(tx_version: u32, fallback_locktime: Option<u32>) -> Self {
+        Self {
+            version: 2,
+            explicit_version: true,
+            tx_version,
+            fallback_locktime,
+            tx_modifiable: Some(TX_MODIFIABLE_INPUTS | TX_MODIFIABLE_OUTPUTS),
+            xpubs: BTreeMap::new(),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            unknown: BTreeMap::new(),
+        }
+    }
+
+    #[inline]
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    #[inline]
+    pub fn tx_version(&self) -> u32 {
+        self.tx_version
+    }
+
+    #[inline]
+    pub fn fallback_locktime(&self) -> Option<u32> {
+        self.fallback_locktime
+    }
+
+    #[inline]
+    pub fn tx_modifiable(&self) -> Option<u8> {
+        self.tx_modifiable
+    }
+
+    #[inline]
+    pub fn inputs(&self) -> &[PsbtInput] {
+        &self.inputs
+    }
+
+    #[inline]
+    pub fn inputs_mut(&mut self) -> &mut [PsbtInput] {
+        &mut self.inputs
+    }
+
+    #[inline]
+    pub fn outputs(&self) -> &[PsbtOutput] {
+        &self.outputs
+    }
+
+    #[inline]
+    pub fn outputs_mut(&mut self) -> &mut [PsbtOutput] {
+        &mut self.outputs
+    }
+
+    #[inline]
+    pub fn unknown(&self) -> &BTreeMap<Vec<u8>, Vec<u8>> {
+        &self.unknown
+    }
+
+    pub fn xpubs(&self) -> Vec<(ExtendedPublicKey, KeyOrigin)> {
+        self.xpubs
+            .iter()
+            .map(|(xpub, origin)| (ExtendedPublicKey::from_bytes(xpub).unwrap(), origin.clone()))
+            .collect()
+    }
+
+    #[inline]
+    pub fn add_xpub(&mut self, xpub: &ExtendedPublicKey, origin: KeyOrigin) {
+        self.xpubs.insert(xpub.serialize(), origin);
+    }
+
+    pub fn add_input(&mut self, input: PsbtInput) -> Result<(), String> {
+        if self.version < 2 || self.tx_modifiable.unwrap_or(0) & TX_MODIFIABLE_INPUTS == 0 {
+            return Err("PSBT inputs are not modifiable.".to_string());
+        }
+        self.inputs.push(input);
+        Ok(())
+    }
+
+    pub fn add_output(&mut self, output: PsbtOutput) -> Result<(), String> {
+        if self.version < 2 || self.tx_modifiable.unwrap_or(0) & TX_MODIFIABLE_OUTPUTS == 0 {
+            return Err("PSBT outputs are not modifiable.".to_string());
+        }
+        self.outputs.push(output);
+        Ok(())
+    }
+
+    pub fn set_version(&mut self, version: u32) -> Result<(), String> {
+        match version {
+            0 => {
+                self.fallback_locktime = Some(self.locktime()?);
+                self.tx_modifiable = None;
+                for input in self.inputs.iter_mut() {
+                    input.sequence.get_or_insert(SEQUENCE_FINAL);
+                    input.required_time_locktime = None;
+                    input.required_height_locktime = None;
+                }
+            }
+            2 => {}
+            _ => return Err(format!("Unsupported PSBT version: {}", version)),
+        }
+        self.version = version;
+        Ok(())
+    }
+
+    pub fn locktime(&self) -> Result<u32, String> {
+        let constrained: Vec<&PsbtInput> = self
+            .inputs
+            .iter()
+            .filter(|input| {
+                input.required_time_locktime.is_some() || input.required_height_locktime.is_some()
+            })
+            .collect();
+        if constrained.is_empty() {
+            return Ok(self.fallback_locktime.unwrap_or(0));
+        }
+        if let Some(heights) = constrained
+            .iter()
+            .map(|input| input.required_height_locktime)
+            .collect::<Option<Vec<u32>>>()
+        {
+            return Ok(heights.into_iter().max().unwrap());
+        }
+        constrained
+            .iter()
+            .map(|input| input.required_time_locktime)
+            .collect::<Option<Vec<u32>>>()
+            .and_then(|times| times.into_iter().max())
+            .ok_or("Inputs require incompatible locktime types.".to_string())
+    }
+
+    pub fn unsigned_tx(&self) -> Result<Tx, String> {
+        let inputs = self
+            .inputs
+            .iter()
+            .map(|input| TxIn::new(input.previous_output, Script::new(), input.sequence()))
+            .collect();
+        let outputs = self.outputs.iter().map(PsbtOutput::to_tx_out).collect();
+        Ok(Tx::new(self.tx_version, inputs, outputs, self.locktime()?))
+    }
+
+    pub fn sign(&mut self, key: &S256PrivateKey) -> Result<usize, String> {
+        let tx = self.unsigned_tx()?;
+        let spent_outputs: Option<Vec<TxOut>> =
+            self.inputs.iter().map(PsbtInput::spent_output).collect();
+        let cache = match &spent_outputs {
+            Some(spent_outputs) => SighashCache::with_spent_outputs(&tx, spent_outputs),
+            None => SighashCache::new(&tx),
+        };
+        let mut signed = 0;
+        let mut errors = Vec::new();
+        for (index, input) in self.inputs.iter_mut().enumerate() {
+            if input.is_finalized() {
+                continue;
+            }
+            match input.sign(&cache, index, key) {
+                Ok(true) => signed += 1,
+                Ok(false) => {}
+                Err(e) => errors.push(format!("input {}: {}", index, e)),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(format!("Cannot sign PSBT, {}", errors.join(", ")));
+        }
+        Ok(signed)
+    }
+
+    pub fn combine(&mut self, other: &Psbt) -> Result<(), String> {
+        if self.version != other.version
+            || self.unsigned_tx()?.hash() != other.unsigned_tx()?.hash()
+        {
+            return Err("Cannot combine PSBTs for different transactions.".to_string());
+        }
+        merge_map(&mut self.xpubs, &other.xpubs);
+        merge_map(&mut self.unknown, &other.unknown);
+        for (input, other) in self.inputs.iter_mut().zip(&other.inputs) {
+            input.combine(other);
+        }
+        for (output, other) in self.outputs.iter_mut().zip(&other.outputs) {
+            output.combine(other);
+        }
+        Ok(())
+    }
+
+    pub fn finalize(&mut self) -> Result<(), String> {
+        let errors: Vec<String> = self
+            .inputs
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, input)| {
+                input
+                    .finalize()
+                    .err()
+                    .map(|e| format!("input {}: {}", index, e))
+            })
+            .collect();
+        if !errors.is_empty() {
+            return Err(format!("Cannot finalize PSBT, {}", errors.join(", ")));
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub fn is_finalized(&self) -> bool {
+        self.inputs.iter().all(PsbtInput::is_finalized)
+    }
+
+    pub fn extract_tx(&self) -> Result<Tx, String> {
+        if !self.is_finalized() {
+            return Err("PSBT is not finalized.".to_string());
+        }
+        let mut tx = self.unsigned_tx()?;
+        for (tx_input, input) in tx.inputs_mut().iter_mut().zip(&self.inputs) {
+            tx_input.set_script_sig(input.final_script_sig.clone().unwrap_or_default());
+            tx_input.set_witness(input.final_script_witness.clone().unwrap_or_default());
+        }
+        Ok(tx)
+    }
+
+    pub fn parse(reader: &mut impl Read) -> Result<Self, String> {
+        if read_array::<5>(reader)? != PSBT_MAGIC {
+            return Err("Invalid PSBT magic bytes.".to_string());
+        }
+        let globals = read_map(reader)?;
+        let explicit_version = globals
+            .iter()
+            .find(|(key_type, _, _)| *key_type == PSBT_GLOBAL_VERSION);
+        let version = match explicit_version {
+            Some((_, key_data, value)) => {
+                expect_no_key_data(PSBT_GLOBAL_VERSION, key_data)?;
+                parse_u32(value)?
+            }
+            None => 0,
+        };
+        let explicit_version = explicit_version.is_some();
+        if version != 0 && version != 2 {
+            return Err(format!("Unsupported PSBT version: {}", version));
+        }
+
+        let mut unsigned_tx = None;
+        let mut tx_version = None;
+        let mut fallback_locktime = None;
+        let mut input_count = None;
+        let mut output_count = None;
+        let mut tx_modifiable = None;
+        let mut xpubs = BTreeMap::new();
+        let mut unknown = BTreeMap::new();
+        for (key_type, key_data, value) in globals {
+            let v2_only = matches!(key_type, PSBT_GLOBAL_TX_VERSION..=PSBT_GLOBAL_TX_MODIFIABLE);
+            if (v2_only && version < 2) || (key_type == PSBT_GLOBAL_UNSIGNED_TX && version >= 2) {
+                return Err(format!(
+                    "PSBT global key type {:#04x} is not allowed in version {}.",
+                    key_type, version
+                ));
+            }
+            match key_type {
+                PSBT_GLOBAL_XPUB => {
+                    ExtendedPublicKey::from_bytes(&key_data)?;
+                    xpubs.insert(key_data, parse_key_origin(&value)?);
+                    continue;
+                }
+                PSBT_GLOBAL_UNSIGNED_TX..=PSBT_GLOBAL_TX_MODIFIABLE | PSBT_GLOBAL_VERSION => {
+                    expect_no_key_data(key_type, &key_data)?
+                }
+                _ => {
+                    unknown.insert(key(key_type, &key_data), value);
+                    continue;
+                }
+            }
+            match key_type {
+                PSBT_GLOBAL_UNSIGNED_TX => {
+                    let tx = Tx::from_bytes(&value)?;
+                    if tx.is_segwit() {
+                        return Err(
+                            "PSBT unsigned transaction must not have witnesses.".to_string()
+                        );
+                    }
+                    unsigned_tx = Some(tx);
+                }
+                PSBT_GLOBAL_TX_VERSION => tx_version = Some(parse_u32(&value)?),
+                PSBT_GLOBAL_FALLBACK_LOCKTIME => fallback_locktime = Some(parse_u32(&value)?),
+                PSBT_GLOBAL_INPUT_COUNT => input_count = Some(parse_exact(&value, read_varint)?),
+                PSBT_GLOBAL_OUTPUT_COUNT => output_count = Some(parse_exact(&value, read_varint)?),
+                PSBT_GLOBAL_TX_MODIFIABLE => {
+                    tx_modifiable = Some(parse_exact(&value, |reader| {
+                        Ok(read_array::<1>(reader)?[0])
+                    })?)
+                }
+                _ => {}
+            }
+        }
+
+        let mut psbt = match (version, unsigned_tx) {
+            (0, Some(tx)) => {
+                let mut psbt = Self::from_unsigned_tx(&tx)?;
+                psbt.inputs = tx
+                    .inputs()
+                    .iter()
+                    .map(|tx_input| PsbtInput::parse(reader, version, Some(tx_input)))
+                    .collect::<Result<Vec<PsbtInput>, String>>()?;
+                psbt.outputs = tx
+                    .outputs()
+                    .iter()
+                    .map(|tx_output| PsbtOutput::parse(reader, version, Some(tx_output)))
+                    .collect::<Result<Vec<PsbtOutput>, String>>()?;
+                psbt
+            }
+            (0, None) => return Err("PSBT is missing the unsigned transaction.".to_string()),
+            _ => {
+                let (Some(tx_version), Some(input_count), Some(output_count)) =
+                    (tx_version, input_count, output_count)
+                else {
+                    return Err("PSBT is missing the transaction version or counts.".to_string());
+                };
+                let mut psbt = Self::new_v2(tx_version, fallback_locktime);
+                psbt.tx_modifiable = tx_modifiable;
+                psbt.inputs = (0..input_count)
+                    .map(|_| PsbtInput::parse(reader, version, None))
+                    .collect::<Result<Vec<PsbtInput>, String>>()?;
+                psbt.outputs = (0..output_count)
+                    .map(|_| PsbtOutput::parse(reader, version, None))
+                    .collect::<Result<Vec<PsbtOutput>, String>>()?;
+                psbt.locktime()?;
+                psbt
+            }
+        };
+        psbt.explicit_version = explicit_version;
+        psbt.xpubs = xpubs;
+        psbt.unknown = unknown;
+        Ok(psbt)
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut pairs: Vec<KeyValue> = Vec::new();
+        if self.version == 0 {
+            let tx = self.unsigned_tx().unwrap();
+            pairs.push((key(PSBT_GLOBAL_UNSIGNED_TX, &[]), tx.serialize_legacy()));
+        } else {
+            pairs.push((
+                key(PSBT_GLOBAL_TX_VERSION, &[]),
+                self.tx_version.to_le_bytes().to_vec(),
+            ));
+            if let Some(locktime) = self.fallback_locktime {
+                pairs.push((
+                    key(PSBT_GLOBAL_FALLBACK_LOCKTIME, &[]),
+                    locktime.to_le_bytes().to_vec(),
+                ));
+            }
+            pairs.push((
+                key(PSBT_GLOBAL_INPUT_COUNT, &[]),
+                encode_varint(self.inputs.len() as u64),
+            ));
+            pairs.push((
+                key(PSBT_GLOBAL_OUTPUT_COUNT, &[]),
+                encode_varint(self.outputs.len() as u64),
+            ));
+            if let Some(flags) = self.tx_modifiable {
+                pairs.push((key(PSBT_GLOBAL_TX_MODIFIABLE, &[]), vec![flags]));
+            }
+        }
+        if self.version != 0 || self.explicit_version {
+            pairs.push((
+                key(PSBT_GLOBAL_VERSION, &[]),
+                self.version.to_le_bytes().to_vec(),
+            ));
+        }
+        for (xpub, origin) in &self.xpubs {
+            pairs.push((key(PSBT_GLOBAL_XPUB, xpub), serialize_key_origin(origin)));
+        }
+        pairs.extend(self.unknown.clone());
+
+        let mut result = PSBT_MAGIC.to_vec();
+        result.extend(serialize_map(pairs));
+        for input in &self.inputs {
+            result.extend(input.serialize(self.version));
+        }
+        for output in &self.outputs {
+            result.extend(output.serialize(self.version));
+        }
+        result
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let mut cursor = Cursor::new(bytes);
+        let psbt = Self::parse(&mut cursor)?;
+        if cursor.position() as usize != bytes.len() {
+            return Err("Trailing data after PSBT.".to_string());
+        }
+        Ok(psbt)
+    }
+}
+
+impl FromStr for Psbt {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_bytes(&base64::decode(s)?)
+    }
+}
+
+impl fmt::Display for Psbt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", base64::encode(&self.serialize()))
+    }
+}
@@ -1,3 +1,4 @@
+use super::network::Network;
 use super::sha256ser::*;
 use crate::ecc::elliptic_curve::EllipticCurve;
 use crate::ecc::finite_field::{FieldElement, Modulus, Sqrt};
@@ -32,16 +33,29 @@ point!(
 );
 
 impl S256Point {
+    pub fn parse_sec(sec: &[u8]) -> Result<Self, String> {
+        match (sec.first(), sec.len()) {
+            (Some(0x02 | 0x03), 33) | (Some(0x04), 65) => {}
+            _ => return Err("Invalid SEC public key encoding.".to_string()),
+        }
+        if sec[1..]
+            .chunks(32)
+            .any(|coordinate| BUint::<4>::from_be_slice(coordinate).unwrap() >= S256FieldCfg::PRIME)
+        {
+            return Err("Invalid SEC public key: coordinate is not a field element.".to_string());
+        }
+        Self::INFINITY.parse(sec.to_vec())
+    }
+
     #[inline]
     pub fn hash160(&self, compressed: bool) -> Vec<u8> {
         Sha256Ripemd160::compute(self.sec(compressed).as_slice())
     }
 
     #[inline]
-    pub fn address(&self, compressed: bool, testnet: bool) -> String {
+    pub fn address(&self, compressed: bool, network: Network) -> String {
         let h160 = self.hash160(compressed);
-        let prefix = if testnet { vec![0x6f] } else { vec![0x00] };
-        let mut payload = prefix;
+        let mut payload = vec![network.p2pkh_prefix()];
         payload.extend_from_slice(&h160);
         Sha256Base58::encode_base58_with_checksum(payload)
     }
@@ -51,8 +65,8 @@ private_key!(S256PrivateKey, S256CurveCfg, S256FieldCfg, 256, Sha256);
 
 impl S256PrivateKey {
     #[inline]
-    pub fn wif(&self, compressed: bool, testnet: bool) -> String {
-        let mut result = if testnet { vec![0xef] } else { vec![0x80] };
+    pub fn wif(&self, compressed: bool, network: Network) -> String {
+        let mut result = vec![network.wif_prefix()];
         result.extend_from_slice(&self.secret().num().to_be_bytes());
         if compressed {
             result.push(0x01);
@@ -0,0 +1,610 @@
+use crate::ser::hex;
+use crate::ser::stream::read_vec;
+use crate::ser::varint::{encode_varint, read_varint};
+use std::fmt;
+use std::io::Read;
+use std::ops::Deref;
+use std::str::FromStr;
+
+macro_rules! opcodes {
+    ($($variant:ident = $code:expr, $name:expr;)*) => {
+        #[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
+        pub enum Opcode {
+            $($variant,)*
+            PushBytes(u8),
+            Invalid(u8),
+        }
+
+        impl Opcode {
+            pub fn from_byte(byte: u8) -> Self {
+                match byte {
+                    $($code => Self::$variant,)*
+                    0x01..=0x4b => Self::PushBytes(byte),
+                    _ => Self::Invalid(byte),
+                }
+            }
+
+            pub fn to_byte(self) -> u8 {
+                match self {
+                    $(Self::$variant => $code,)*
+                    Self::PushBytes(n) | Self::Invalid(n) => n,
+                }
+            }
+
+            pub fn name(&self) -> String {
+                match self {
+                    $(Self::$variant => $name.to_string(),)*
+                    Self::PushBytes(n) => format!("OP_PUSHBYTES_{}", n),
+                    Self::Invalid(n) => format!("OP_UNKNOWN_{:#04x}", n),
+                }
+            }
+
+            fn from_name(name: &str) -> Option<Self> {
+                match name {
+                    $($name => Some(Self::$variant),)*
+                    "OP_FALSE" => Some(Self::Op0),
+                    "OP_TRUE" => Some(Self::Op1),
+                    "OP_NOP2" => Some(Self::CheckLockTimeVerify),
+                    "OP_NOP3" => Some(Self::CheckSequenceVerify),
+                    _ => match name.strip_prefix("OP_PUSHBYTES_") {
+                        Some(n) => n
+                            .parse::<u8>()
+                            .ok()
+                            .filter(|n| (0x01..=0x4b).contains(n))
+                            .map(Self::PushBytes),
+                        None => name
+                            .strip_prefix("OP_UNKNOWN_0x")
+                            .filter(|n| n.len() == 2)
+                            .and_then(|n| u8::from_str_radix(n, 16).ok())
+                            .map(Self::from_byte)
+                            .filter(|opcode| matches!(opcode, Self::Invalid(_))),
+                    },
+                }
+            }
+        }
+    };
+}
+
+opcodes! {
+    Op0 = 0x00, "OP_0";
+    PushData1 = 0x4c, "OP_PUSHDATA1";
+    PushData2 = 0x4d, "OP_PUSHDATA2";
+    PushData4 = 0x4e, "OP_PUSHDATA4";
+    OneNegate = 0x4f, "OP_1NEGATE";
+    Reserved = 0x50, "OP_RESERVED";
+    Op1 = 0x51, "OP_1";
+    Op2 = 0x52, "OP_2";
+    Op3 = 0x53, "OP_3";
+    Op4 = 0x54, "OP_4";
+    Op5 = 0x55, "OP_5";
+    Op6 = 0x56, "OP_6";
+    Op7 = 0x57, "OP_7";
+    Op8 = 0x58, "OP_8";
+    Op9 = 0x59, "OP_9";
+    Op10 = 0x5a, "OP_10";
+    Op11 = 0x5b, "OP_11";
+    Op12 = 0x5c, "OP_12";
+    Op13 = 0x5d, "OP_13";
+    Op14 = 0x5e, "OP_14";
+    Op15 = 0x5f, "OP_15";
+    Op16 = 0x60, "OP_16";
+    Nop = 0x61, "OP_NOP";
+    Ver = 0x62, "OP_VER";
+    If = 0x63, "OP_IF";
+    NotIf = 0x64, "OP_NOTIF";
+    VerIf = 0x65, "OP_VERIF";
+    VerNotIf = 0x66, "OP_VERNOTIF";
+    Else = 0x67, "OP_ELSE";
+    EndIf = 0x68, "OP_ENDIF";
+    Verify = 0x69, "OP_VERIFY";
+    Return = 0x6a, "OP_RETURN";
+    ToAltStack = 0x6b, "OP_TOALTSTACK";
+    FromAltStack = 0x6c, "OP_FROMALTSTACK";
+    TwoDrop = 0x6d, "OP_2DROP";
+    TwoDup = 0x6e, "OP_2DUP";
+    ThreeDup = 0x6f, "OP_3DUP";
+    TwoOver = 0x70, "OP_2OVER";
+    TwoRot = 0x71, "OP_2ROT";
+    TwoSwap = 0x72, "OP_2SWAP";
+    IfDup = 0x73, "OP_IFDUP";
+    Depth = 0x74, "OP_DEPTH";
+    Drop = 0x75, "OP_DROP";
+    Dup = 0x76, "OP_DUP";
+    Nip = 0x77, "OP_NIP";
+    Over = 0x78, "OP_OVER";
+    Pick = 0x79, "OP_PICK";
+    Roll = 0x7a, "OP_ROLL";
+    Rot = 0x7b, "OP_ROT";
+    Swap = 0x7c, "OP_SWAP";
+    Tuck = 0x7d, "OP_TUCK";
+    Cat = 0x7e, "OP_CAT";
+    Substr = 0x7f, "OP_SUBSTR";
+    Left = 0x80, "OP_LEFT";
+    Right = 0x81, "OP_RIGHT";
+    Size = 0x82, "OP_SIZE";
+    Invert = 0x83, "OP_INVERT";
+    And = 0x84, "OP_AND";
+    Or = 0x85, "OP_OR";
+    Xor = 0x86, "OP_XOR";
+    Equal = 0x87, "OP_EQUAL";
+    EqualVerify = 0x88, "OP_EQUALVERIFY";
+    Reserved1 = 0x89, "OP_RESERVED1";
+    Reserved2 = 0x8a, "OP_RESERVED2";
+    OneAdd = 0x8b, "OP_1ADD";
+    OneSub = 0x8c, "OP_1SUB";
+    TwoMul = 0x8d, "OP_2MUL";
+    TwoDiv = 0x8e, "OP_2DIV";
+    Negate = 0x8f, "OP_NEGATE";
+    Abs = 0x90, "OP_ABS";
+    Not = 0x91, "OP_NOT";
+    ZeroNotEqual = 0x92, "OP_0NOTEQUAL";
+    Add = 0x93, "OP_ADD";
+    Sub = 0x94, "OP_SUB";
+    Mul = 0x95, "OP_MUL";
+    Div = 0x96, "OP_DIV";
+    Mod = 0x97, "OP_MOD";
+    LShift = 0x98, "OP_LSHIFT";
+    RShift = 0x99, "OP_RSHIFT";
+    BoolAnd = 0x9a, "OP_BOOLAND";
+    BoolOr = 0x9b, "OP_BOOLOR";
+    NumEqual = 0x9c, "OP_NUMEQUAL";
+    NumEqualVerify = 0x9d, "OP_NUMEQUALVERIFY";
+    NumNotEqual = 0x9e, "OP_NUMNOTEQUAL";
+    LessThan = 0x9f, "OP_LESSTHAN";
+    GreaterThan = 0xa0, "OP_GREATERTHAN";
+    LessThanOrEqual = 0xa1, "OP_LESSTHANOREQUAL";
+    GreaterThanOrEqual = 0xa2, "OP_GREATERTHANOREQUAL";
+    Min = 0xa3, "OP_MIN";
+    Max = 0xa4, "OP_MAX";
+    Within = 0xa5, "OP_WITHIN";
+    Ripemd160 = 0xa6, "OP_RIPEMD160";
+    Sha1 = 0xa7, "OP_SHA1";
+    Sha256 = 0xa8, "OP_SHA256";
+    Hash160 = 0xa9, "OP_HASH160";
+    Hash256 = 0xaa, "OP_HASH256";
+    CodeSeparator = 0xab, "OP_CODESEPARATOR";
+    CheckSig = 0xac, "OP_CHECKSIG";
+    CheckSigVerify = 0xad, "OP_CHECKSIGVERIFY";
+    CheckMultiSig = 0xae, "OP_CHECKMULTISIG";
+    CheckMultiSigVerify = 0xaf, "OP_CHECKMULTISIGVERIFY";
+    Nop1 = 0xb0, "OP_NOP1";
+    CheckLockTimeVerify = 0xb1, "OP_CHECKLOCKTIMEVERIFY";
+    CheckSequenceVerify = 0xb2, "OP_CHECKSEQUENCEVERIFY";
+    Nop4 = 0xb3, "OP_NOP4";
+    Nop5 = 0xb4, "OP_NOP5";
+    Nop6 = 0xb5, "OP_NOP6";
+    Nop7 = 0xb6, "OP_NOP7";
+    Nop8 = 0xb7, "OP_NOP8";
+    Nop9 = 0xb8, "OP_NOP9";
+    Nop10 = 0xb9, "OP_NOP10";
+    CheckSigAdd = 0xba, "OP_CHECKSIGADD";
+}
+
+impl Opcode {
+    #[inline]
+    pub fn is_push(&self) -> bool {
+        matches!(
+            self,
+            Self::PushBytes(_) | Self::PushData1 | Self::PushData2 | Self::PushData4
+        )
+    }
+
+    #[inline]
+    pub fn small_int(&self) -> Option<i64> {
+        match self.to_byte() {
+            0x00 => Some(0),
+            0x4f => Some(-1),
+            byte @ 0x51..=0x60 => Some((byte - 0x50) as i64),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn from_small_int(n: u8) -> Option<Self> {
+        match n {
+            0 => Some(Self::Op0),
+            1..=16 => Some(Self::from_byte(0x50 + n)),
+            _ => None,
+        }
+    }
+
+    pub fn minimal_push(data: &[u8]) -> Self {
+        match data {
+            [] => Self::Op0,
+            [n @ 1..=16] => Self::from_byte(0x50 + n),
+            [0x81] => Self::OneNegate,
+            _ if data.len() <= 0x4b => Self::PushBytes(data.len() as u8),
+            _ if data.len() <= 0xff => Self::PushData1,
+            _ if data.len() <= 0xffff => Self::PushData2,
+            _ => Self::PushData4,
+        }
+    }
+}
+
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+pub fn encode_num(n: i64) -> Vec<u8> {
+    let mut result = Vec::new();
+    let mut abs = n.unsigned_abs();
+    while abs > 0 {
+        result.push((abs & 0xff) as u8);
+        abs >>= 8;
+    }
+    match result.last_mut() {
+        Some(last) if *last & 0x80 != 0 => result.push(if n < 0 { 0x80 } else { 0x00 }),
+        Some(last) if n < 0 => *last |= 0x80,
+        _ => {}
+    }
+    result
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Instruction {
+    Op(Opcode),
+    Push(Opcode, Vec<u8>),
+}
+
+impl Instruction {
+    #[inline]
+    pub fn opcode(&self) -> Opcode {
+        match self {
+            Self::Op(opcode) | Self::Push(opcode, _) => *opcode,
+        }
+    }
+
+    #[inline]
+    pub fn push_data(&self) -> Option<&[u8]> {
+        match self {
+            Self::Op(Opcode::Op0) => Some(&[]),
+            Self::Push(_, data) => Some(data),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn is_minimal(&self) -> bool {
+        match self {
+            Self::Op(_) => true,
+            Self::Push(opcode, data) => *opcode == Opcode::minimal_push(data),
+        }
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        match self {
+            Self::Op(opcode) => vec![opcode.to_byte()],
+            Self::Push(opcode, data) => {
+                let mut result = vec![opcode.to_byte()];
+                match opcode {
+                    Opcode::PushData1 => result.push(data.len() as u8),
+                    Opcode::PushData2 => result.extend((data.len() as u16).to_le_bytes()),
+                    Opcode::PushData4 => result.extend((data.len() as u32).to_le_bytes()),
+                    _ => {}
+                }
+                result.extend_from_slice(data);
+                result
+            }
+        }
+    }
+}
+
+pub struct Instructions<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl Instructions<'_> {
+    #[inline]
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    fn read(&mut self, len: usize) -> Result<&[u8], String> {
+        let end = self.position.saturating_add(len);
+        if end > self.data.len() {
+            return Err(format!(
+                "Truncated push at position {}: expected {} bytes, got {}.",
+                self.position,
+                len,
+                self.data.len() - self.position
+            ));
+        }
+        let result = &self.data[self.position..end];
+        self.position = end;
+        Ok(result)
+    }
+
+    fn read_len(&mut self, width: usize) -> Result<usize, String> {
+        let bytes = self.read(width)?;
+        Ok(bytes
+            .iter()
+            .rev()
+            .fold(0usize, |acc, &b| (acc << 8) | b as usize))
+    }
+}
+
+impl Iterator for Instructions<'_> {
+    type Item = Result<Instruction, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.position >= self.data.len() {
+            return None;
+        }
+        let opcode = Opcode::from_byte(self.data[self.position]);
+        self.position += 1;
+        let len = match opcode {
+            Opcode::PushBytes(n) => Ok(n as usize),
+            Opcode::PushData1 => self.read_len(1),
+            Opcode::PushData2 => self.read_len(2),
+            Opcode::PushData4 => self.read_len(4),
+            _ => return Some(Ok(Instruction::Op(opcode))),
+        };
+        let result = len
+            .and_then(|len| self.read(len).map(|data| data.to_vec()))
+            .map(|data| Instruction::Push(opcode, data));
+        if result.is_err() {
+            self.position = self.data.len();
+        }
+        Some(result)
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Default, Hash)]
+pub struct Script(Vec<u8>);
+
+impl Script {
+    #[inline]
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    #[inline]
+    pub fn from_instructions(instructions: &[Instruction]) -> Self {
+        Self(instructions.iter().flat_map(|i| i.serialize()).collect())
+    }
+
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    #[inline]
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+
+    #[inline]
+    pub fn instructions(&self) -> Instructions<'_> {
+        Instructions {
+            data: &self.0,
+            position: 0,
+        }
+    }
+
+    #[inline]
+    pub fn push_opcode(&mut self, opcode: Opcode) -> &mut Self {
+        self.0.push(opcode.to_byte());
+        self
+    }
+
+    pub fn push_data(&mut self, data: &[u8]) -> &mut Self {
+        let instruction = match Opcode::minimal_push(data) {
+            opcode if opcode.is_push() => Instruction::Push(opcode, data.to_vec()),
+            opcode => Instruction::Op(opcode),
+        };
+        self.0.extend(instruction.serialize());
+        self
+    }
+
+    pub fn push_int(&mut self, n: i64) -> &mut Self {
+        match u8::try_from(n).ok().and_then(Opcode::from_small_int) {
+            Some(opcode) => self.push_opcode(opcode),
+            None if n == -1 => self.push_opcode(Opcode::OneNegate),
+            None => self.push_data(&encode_num(n)),
+        }
+    }
+
+    pub fn find_and_delete(&self, pattern: &[u8]) -> Self {
+        if pattern.is_empty() {
+            return self.clone();
+        }
+        let mut result = Vec::new();
+        let mut instructions = self.instructions();
+        let mut copied_from = 0;
+        loop {
+            let pc = instructions.position;
+            result.extend_from_slice(&self.0[copied_from..pc]);
+            let mut pc = pc;
+            while self.0[pc..].starts_with(pattern) {
+                pc += pattern.len();
+            }
+            copied_from = pc;
+            instructions.position = pc;
+            match instructions.next() {
+                Some(Ok(_)) => {}
+                _ => break,
+            }
+        }
+        result.extend_from_slice(&self.0[copied_from..]);
+        Self(result)
+    }
+
+    #[inline]
+    pub fn subscript(&self, from: usize) -> Self {
+        Self(self.0[from..].to_vec())
+    }
+
+    #[inline]
+    pub fn is_push_only(&self) -> bool {
+        self.instructions().all(|i| match i {
+            Ok(instruction) => instruction.opcode().to_byte() <= 0x60,
+            Err(_) => false,
+        })
+    }
+
+    #[inline]
+    pub fn is_minimal(&self) -> bool {
+        self.instructions()
+            .all(|i| i.map(|i| i.is_minimal()).unwrap_or(false))
+    }
+
+    #[inline]
+    pub fn is_p2pkh(&self) -> bool {
+        matches!(self.0[..], [0x76, 0xa9, 0x14, .., 0x88, 0xac] if self.0.len() == 25)
+    }
+
+    #[inline]
+    pub fn is_p2sh(&self) -> bool {
+        matches!(self.0[..], [0xa9, 0x14, .., 0x87] if self.0.len() == 23)
+    }
+
+    #[inline]
+    pub fn is_op_return(&self) -> bool {
+        self.0.first() == Some(&0x6a)
+    }
+
+    pub fn witness_program(&self) -> Option<(u8, &[u8])> {
+        match self.0[..] {
+            [version @ (0x00 | 0x51..=0x60), len, ref program @ ..]
+                if (2..=40).contains(&program.len()) && len as usize == program.len() =>
+            {
+                let version = if version == 0 { 0 } else { version - 0x50 };
+                Some((version, program))
+            }
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn is_p2wpkh(&self) -> bool {
+        matches!(self.witness_program(), Some((0, program)) if program.len() == 20)
+    }
+
+    #[inline]
+    pub fn is_p2wsh(&self) -> bool {
+        matches!(self.witness_program(), Some((0, program)) if program.len() == 32)
+    }
+
+    #[inline]
+    pub fn is_p2tr(&self) -> bool {
+        matches!(self.witness_program(), Some((1, program)) if program.len() == 32)
+    }
+
+    pub fn multisig_keys(&self) -> Option<(usize, Vec<Vec<u8>>)> {
+        let instructions = self
+            .instructions()
+            .collect::<Result<Vec<Instruction>, String>>()
+            .ok()?;
+        let [threshold, keys @ .., count, Instruction::Op(Opcode::CheckMultiSig)] =
+            &instructions[..]
+        else {
+            return None;
+        };
+        let threshold = threshold.opcode().small_int().filter(|m| *m > 0)? as usize;
+        let count = count.opcode().small_int()? as usize;
+        let keys = keys
+            .iter()
+            .map(|key| match key {
+                Instruction::Push(_, data) if data.len() == 33 || data.len() == 65 => {
+                    Some(data.clone())
+                }
+                _ => None,
+            })
+            .collect::<Option<Vec<Vec<u8>>>>()?;
+        (keys.len() == count && threshold <= count).then_some((threshold, keys))
+    }
+
+    pub fn parse(reader: &mut impl Read) -> Result<Self, String> {
+        let len = read_varint(reader)?;
+        Ok(Self(read_vec(reader, len as usize)?))
+    }
+
+    #[inline]
+    pub fn serialize(&self) -> Vec<u8> {
+        [&encode_varint(self.0.len() as u64)[..], &self.0].concat()
+    }
+}
+
+impl Deref for Script {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<Vec<u8>> for Script {
+    #[inline]
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+}
+
+impl From<&[u8]> for Script {
+    #[inline]
+    fn from(bytes: &[u8]) -> Self {
+        Self(bytes.to_vec())
+    }
+}
+
+impl fmt::Display for Script {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut tokens = Vec::new();
+        for instruction in self.instructions() {
+            match instruction {
+                Ok(Instruction::Op(opcode)) => tokens.push(opcode.name()),
+                Ok(instruction @ Instruction::Push(..)) => {
+                    let data = hex::encode(instruction.push_data().unwrap());
+                    if instruction.is_minimal() {
+                        tokens.push(data);
+                    } else {
+                        tokens.push(format!("{} {}", instruction.opcode().name(), data));
+                    }
+                }
+                Err(_) => tokens.push("[error]".to_string()),
+            }
+        }
+        write!(f, "{}", tokens.join(" "))
+    }
+}
+
+impl FromStr for Script {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut script = Self::new();
+        let mut tokens = s.split_whitespace();
+        while let Some(token) = tokens.next() {
+            match Opcode::from_name(token) {
+                Some(opcode) if opcode.is_push() => {
+                    let data = hex::decode(
+                        tokens
+                            .next()
+                            .ok_or(format!("Missing push data after {}", token))?,
+                    )?;
+                    let instruction = Instruction::Push(opcode, data);
+                    if let Opcode::PushBytes(n) = opcode {
+                        if n as usize != instruction.push_data().unwrap().len() {
+                            return Err(format!("Push data length mismatch for {}", token));
+                        }
+                    }
+                    script.0.extend(instruction.serialize());
+                }
+                Some(opcode) => {
+                    script.push_opcode(opcode);
+                }
+                None if token.starts_with("OP_") => {
+                    return Err(format!("Unknown opcode: {}", token));
+                }
+                None => {
+                    script.push_data(&hex::decode(token)?);
+                }
+            }
+        }
+        Ok(script)
+    }
+}
@@ -0,0 +1,360 @@
+use super::script::{Instruction, Opcode, Script};
+use super::sha256ser::DoubleSha256;
+use super::taproot::tagged_hash;
+use super::tx::{Tx, TxOut};
+use crate::ser::chained_hash::ChainedCompute;
+use crate::ser::varint::encode_varint;
+use bnum::types::U256;
+use sha2::{Digest, Sha256};
+use std::cell::OnceCell;
+
+pub const SIGHASH_DEFAULT: u32 = 0x00;
+pub const SIGHASH_ALL: u32 = 0x01;
+pub const SIGHASH_NONE: u32 = 0x02;
+pub const SIGHASH_SINGLE: u32 = 0x03;
+pub const SIGHASH_ANYONECANPAY: u32 = 0x80;
+
+const SIGHASH_OUTPUT_MASK: u32 = 0x1f;
+
+fn serialize_script_code(script_code: &Script) -> Vec<u8> {
+    let bytes = script_code.as_bytes();
+    let mut body = Vec::new();
+    let mut separators = 0;
+    let mut copied_from = 0;
+    let mut end = 0;
+    let mut instructions = script_code.instructions();
+    while let Some(instruction) = instructions.next() {
+        match instruction {
+            Ok(Instruction::Op(Opcode::CodeSeparator)) => {
+                body.extend_from_slice(&bytes[copied_from..end]);
+                separators += 1;
+                copied_from = instructions.position();
+            }
+            Ok(_) => {}
+            Err(_) => {
+                let width = match Opcode::from_byte(bytes[end]) {
+                    Opcode::PushData1 => 1,
+                    Opcode::PushData2 => 2,
+                    Opcode::PushData4 => 4,
+                    _ => 0,
+                };
+                end += match end + 1 + width <= bytes.len() {
+                    true => 1 + width,
+                    false => 1,
+                };
+                break;
+            }
+        }
+        end = instructions.position();
+    }
+    body.extend_from_slice(&bytes[copied_from..end]);
+    [encode_varint((bytes.len() - separators) as u64), body].concat()
+}
+
+impl Tx {
+    pub fn legacy_sighash(&self, input_index: usize, script_code: &Script, hash_type: u32) -> U256 {
+        let one = U256::ONE << 248;
+        let base_type = hash_type & SIGHASH_OUTPUT_MASK;
+        if input_index >= self.inputs().len()
+            || (base_type == SIGHASH_SINGLE && input_index >= self.outputs().len())
+        {
+            return one;
+        }
+        let anyone_can_pay = hash_type & SIGHASH_ANYONECANPAY != 0;
+        let zero_sequences = base_type == SIGHASH_NONE || base_type == SIGHASH_SINGLE;
+
+        let mut result = self.version().to_le_bytes().to_vec();
+        let signed_inputs = match anyone_can_pay {
+            true => input_index..input_index + 1,
+            false => 0..self.inputs().len(),
+        };
+        result.extend(encode_varint(signed_inputs.len() as u64));
+        for index in signed_inputs {
+            let input = &self.inputs()[index];
+            result.extend(input.previous_output().serialize());
+            if index == input_index {
+                result.extend(serialize_script_code(script_code));
+                result.extend(input.sequence().to_le_bytes());
+            } else {
+                result.push(0x00);
+                let sequence = if zero_sequences { 0 } else { input.sequence() };
+                result.extend(sequence.to_le_bytes());
+            }
+        }
+
+        match base_type {
+            SIGHASH_NONE => result.push(0x00),
+            SIGHASH_SINGLE => {
+                result.extend(encode_varint(input_index as u64 + 1));
+                for _ in 0..input_index {
+                    result.extend(u64::MAX.to_le_bytes());
+                    result.push(0x00);
+                }
+                result.extend(self.outputs()[input_index].serialize());
+            }
+            _ => {
+                result.extend(encode_varint(self.outputs().len() as u64));
+                for output in self.outputs() {
+                    result.extend(output.serialize());
+                }
+            }
+        }
+        result.extend(self.locktime().to_le_bytes());
+        result.extend(hash_type.to_le_bytes());
+        U256::from_be_slice(&DoubleSha256::compute(&result)).unwrap()
+    }
+
+    #[inline]
+    pub fn segwit_v0_sighash(
+        &self,
+        input_index: usize,
+        script_code: &Script,
+        amount: u64,
+        hash_type: u32,
+    ) -> Result<U256, String> {
+        SighashCache::new(self).segwit_v0_sighash(input_index, script_code, amount, hash_type)
+    }
+}
+
+#[inline]
+fn sha256(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+pub struct SighashCache<'a> {
+    tx: &'a Tx,
+    spent_outputs: &'a [TxOut],
+    prevouts: OnceCell<[u8; 32]>,
+    amounts: OnceCell<[u8; 32]>,
+    script_pubkeys: OnceCell<[u8; 32]>,
+    sequences: OnceCell<[u8; 32]>,
+    outputs: OnceCell<[u8; 32]>,
+}
+
+impl<'a> SighashCache<'a> {
+    #[inline]
+    pub fn new(tx: &'a Tx) -> Self {
+        Self::with_spent_outputs(tx, &[])
+    }
+
+    #[inline]
+    pub fn with_spent_outputs(tx: &'a Tx, spent_outputs: &'a [TxOut]) -> Self {
+        Self {
+            tx,
+            spent_outputs,
+            prevouts: OnceCell::new(),
+            amounts: OnceCell::new(),
+            script_pubkeys: OnceCell::new(),
+            sequences: OnceCell::new(),
+            outputs: OnceCell::new(),
+        }
+    }
+
+    #[inline]
+    pub fn tx(&self) -> &'a Tx {
+        self.tx
+    }
+
+    #[inline]
+    pub fn spent_outputs(&self) -> &'a [TxOut] {
+        self.spent_outputs
+    }
+
+    fn sha_prevouts(&self) -> [u8; 32] {
+        *self.prevouts.get_or_init(|| {
+            let inputs = self.tx.inputs().iter();
+            sha256(
+                &inputs
+                    .flat_map(|input| input.previous_output().serialize())
+                    .collect::<Vec<u8>>(),
+            )
+        })
+    }
+
+    fn sha_amounts(&self) -> [u8; 32] {
+        *self.amounts.get_or_init(|| {
+            let outputs = self.spent_outputs.iter();
+            sha256(
+                &outputs
+                    .flat_map(|output| output.amount().to_le_bytes())
+                    .collect::<Vec<u8>>(),
+            )
+        })
+    }
+
+    fn sha_script_pubkeys(&self) -> [u8; 32] {
+        *self.script_pubkeys.get_or_init(|| {
+            let outputs = self.spent_outputs.iter();
+            sha256(
+                &outputs
+                    .flat_map(|output| output.script_pubkey().serialize())
+                    .collect::<Vec<u8>>(),
+            )
+        })
+    }
+
+    fn sha_sequences(&self) -> [u8; 32] {
+        *self.sequences.get_or_init(|| {
+            let inputs = self.tx.inputs().iter();
+            sha256(
+                &inputs
+                    .flat_map(|input| input.sequence().to_le_bytes())
+                    .collect::<Vec<u8>>(),
+            )
+        })
+    }
+
+    fn sha_outputs(&self) -> [u8; 32] {
+        *self.outputs.get_or_init(|| {
+            let outputs = self.tx.outputs().iter();
+            sha256(
+                &outputs
+                    .flat_map(|output| output.serialize())
+                    .collect::<Vec<u8>>(),
+            )
+        })
+    }
+
+    pub fn segwit_v0_sighash(
+        &self,
+        input_index: usize,
+        script_code: &Script,
+        amount: u64,
+        hash_type: u32,
+    ) -> Result<U256, String> {
+        let Some(input) = self.tx.inputs().get(input_index) else {
+            return Err(format!("Input index {} out of range.", input_index));
+        };
+        let base_type = hash_type & SIGHASH_OUTPUT_MASK;
+        let anyone_can_pay = hash_type & SIGHASH_ANYONECANPAY != 0;
+        let commits_outputs = base_type != SIGHASH_NONE && base_type != SIGHASH_SINGLE;
+
+        let hash_prevouts = match anyone_can_pay {
+            false => sha256(&self.sha_prevouts()),
+            true => [0u8; 32],
+        };
+        let hash_sequence = match !anyone_can_pay && commits_outputs {
+            true => sha256(&self.sha_sequences()),
+            false => [0u8; 32],
+        };
+        let hash_outputs = if commits_outputs {
+            sha256(&self.sha_outputs())
+        } else if base_type == SIGHASH_SINGLE && input_index < self.tx.outputs().len() {
+            DoubleSha256::compute(&self.tx.outputs()[input_index].serialize())
+                .try_into()
+                .unwrap()
+        } else {
+            [0u8; 32]
+        };
+
+        let mut result = self.tx.version().to_le_bytes().to_vec();
+        result.extend(hash_prevouts);
+        result.extend(hash_sequence);
+        result.extend(input.previous_output().serialize());
+        result.extend(script_code.serialize());
+        result.extend(amount.to_le_bytes());
+        result.extend(input.sequence().to_le_bytes());
+        result.extend(hash_outputs);
+        result.extend(self.tx.locktime().to_le_bytes());
+        result.extend(hash_type.to_le_bytes());
+        Ok(U256::from_be_slice(&DoubleSha256::compute(&result)).unwrap())
+    }
+
+    #[inline]
+    pub fn taproot_key_spend_sighash(
+        &self,
+        input_index: usize,
+        hash_type: u32,
+        annex: Option<&[u8]>,
+    ) -> Result<[u8; 32], String> {
+        self.taproot_sighash(input_index, hash_type, annex, None)
+    }
+
+    #[inline]
+    pub fn taproot_script_spend_sighash(
+        &self,
+        input_index: usize,
+        hash_type: u32,
+        annex: Option<&[u8]>,
+        leaf_hash: [u8; 32],
+        code_separator_position: u32,
+    ) -> Result<[u8; 32], String> {
+        self.taproot_sighash(
+            input_index,
+            hash_type,
+            annex,
+            Some((leaf_hash, code_separator_position)),
+        )
+    }
+
+    fn taproot_sighash(
+        &self,
+        input_index: usize,
+        hash_type: u32,
+        annex: Option<&[u8]>,
+        script_path: Option<([u8; 32], u32)>,
+    ) -> Result<[u8; 32], String> {
+        if !matches!(hash_type, 0x00..=0x03 | 0x81..=0x83) {
+            return Err(format!("Invalid taproot sighash type: {:#04x}.", hash_type));
+        }
+        if input_index >= self.tx.inputs().len() {
+            return Err(format!("Input index {} out of range.", input_index));
+        }
+        if self.spent_outputs.len() != self.tx.inputs().len() {
+            return Err(format!(
+                "Taproot sighash requires all {} spent outputs, got {}.",
+                self.tx.inputs().len(),
+                self.spent_outputs.len()
+            ));
+        }
+        if annex.is_some_and(|annex| annex.first() != Some(&0x50)) {
+            return Err("Annex must start with 0x50.".to_string());
+        }
+        let base_type = match hash_type {
+            SIGHASH_DEFAULT => SIGHASH_ALL,
+            _ => hash_type & 0x03,
+        };
+        let anyone_can_pay = hash_type & SIGHASH_ANYONECANPAY != 0;
+
+        let mut result = vec![0x00, hash_type as u8];
+        result.extend(self.tx.version().to_le_bytes());
+        result.extend(self.tx.locktime().to_le_bytes());
+        if !anyone_can_pay {
+            result.extend(self.sha_prevouts());
+            result.extend(self.sha_amounts());
+            result.extend(self.sha_script_pubkeys());
+            result.extend(self.sha_sequences());
+        }
+        if base_type != SIGHASH_NONE && base_type != SIGHASH_SINGLE {
+            result.extend(self.sha_outputs());
+        }
+        result.push(script_path.is_some() as u8 * 2 + annex.is_some() as u8);
+        if anyone_can_pay {
+            let input = &self.tx.inputs()[input_index];
+            let spent_output = &self.spent_outputs[input_index];
+            result.extend(input.previous_output().serialize());
+            result.extend(spent_output.serialize());
+            result.extend(input.sequence().to_le_bytes());
+        } else {
+            result.extend((input_index as u32).to_le_bytes());
+        }
+        if let Some(annex) = annex {
+            result.extend(sha256(
+                &[&encode_varint(annex.len() as u64)[..], annex].concat(),
+            ));
+        }
+        if base_type == SIGHASH_SINGLE {
+            let output = self.tx.outputs().get(input_index).ok_or(format!(
+                "SIGHASH_SINGLE input {} has no matching output.",
+                input_index
+            ))?;
+            result.extend(sha256(&output.serialize()));
+        }
+        if let Some((leaf_hash, code_separator_position)) = script_path {
+            result.extend(leaf_hash);
+            result.push(0x00);
+            result.extend(code_separator_position.to_le_bytes());
+        }
+        Ok(tagged_hash("TapSighash", &result))
+    }
+}
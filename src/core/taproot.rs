@@ -0,0 +1,162 @@
+use super::s256ecc::{S256CurveCfg, S256FieldCfg, S256Point, S256PrivateKey};
+use crate::ecc::elliptic_curve::EllipticCurve;
+use crate::ecc::finite_field::Modulus;
+use crate::ser::varint::encode_varint;
+use bnum::types::U256;
+use sha2::{Digest, Sha256};
+
+pub fn tagged_hash(tag: &str, data: &[u8]) -> [u8; 32] {
+    let tag_hash = Sha256::digest(tag.as_bytes());
+    Sha256::new()
+        .chain_update(tag_hash)
+        .chain_update(tag_hash)
+        .chain_update(data)
+        .finalize()
+        .into()
+}
+
+pub const TAPSCRIPT_LEAF_VERSION: u8 = 0xc0;
+
+pub fn tap_leaf_hash(leaf_version: u8, script: &[u8]) -> [u8; 32] {
+    let mut data = vec![leaf_version];
+    data.extend(encode_varint(script.len() as u64));
+    data.extend_from_slice(script);
+    tagged_hash("TapLeaf", &data)
+}
+
+pub fn tap_branch_hash(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let (left, right) = if a <= b { (a, b) } else { (b, a) };
+    tagged_hash("TapBranch", &[&left[..], &right[..]].concat())
+}
+
+impl S256Point {
+    #[inline]
+    pub fn xonly(&self) -> [u8; 32] {
+        self.x().unwrap().num().to_be_bytes()
+    }
+
+    pub fn lift_x(x: &[u8]) -> Result<Self, String> {
+        if x.len() != 32 {
+            return Err(format!(
+                "Invalid x-only public key length: expected 32 bytes, got {}.",
+                x.len()
+            ));
+        }
+        if U256::from_be_slice(x).unwrap() >= S256FieldCfg::PRIME {
+            return Err("Invalid x-only public key: x is not a field element.".to_string());
+        }
+        Self::INFINITY.parse([&[2u8][..], x].concat())
+    }
+
+    #[inline]
+    pub fn has_even_y(&self) -> bool {
+        self.y().unwrap().num() & U256::ONE == U256::ZERO
+    }
+
+    pub fn tap_tweak(&self, merkle_root: Option<[u8; 32]>) -> Result<Self, String> {
+        let internal_key = Self::lift_x(&self.xonly())?;
+        let mut data = internal_key.xonly().to_vec();
+        if let Some(merkle_root) = merkle_root {
+            data.extend_from_slice(&merkle_root);
+        }
+        let tweak = U256::from_be_bytes(tagged_hash("TapTweak", &data));
+        if tweak >= S256CurveCfg::N {
+            return Err("Invalid taproot tweak.".to_string());
+        }
+        Ok(internal_key + *Self::G * tweak)
+    }
+}
+
+#[inline]
+fn add_mod_n(a: U256, b: U256) -> U256 {
+    S256FieldCfg::from_big(
+        (S256FieldCfg::to_big(a) + S256FieldCfg::to_big(b)) % S256FieldCfg::to_big(S256CurveCfg::N),
+    )
+}
+
+#[inline]
+fn mul_mod_n(a: U256, b: U256) -> U256 {
+    S256FieldCfg::from_big(
+        (S256FieldCfg::to_big(a) * S256FieldCfg::to_big(b)) % S256FieldCfg::to_big(S256CurveCfg::N),
+    )
+}
+
+#[inline]
+fn challenge(r: &[u8], xonly: &[u8; 32], msg: &[u8; 32]) -> U256 {
+    let hash = tagged_hash("BIP0340/challenge", &[r, &xonly[..], &msg[..]].concat());
+    U256::from_be_bytes(hash) % S256CurveCfg::N
+}
+
+impl S256Point {
+    pub fn verify_schnorr(&self, msg: &[u8; 32], signature: &[u8]) -> bool {
+        if signature.len() != 64 || self.is_infinity() {
+            return false;
+        }
+        let (r, s) = signature.split_at(32);
+        let r_num = U256::from_be_slice(r).unwrap();
+        let s = U256::from_be_slice(s).unwrap();
+        if r_num >= S256FieldCfg::PRIME || s >= S256CurveCfg::N {
+            return false;
+        }
+        let Ok(point) = Self::lift_x(&self.xonly()) else {
+            return false;
+        };
+        let e = challenge(r, &point.xonly(), msg);
+        let r_point = *Self::G * s + point * ((S256CurveCfg::N - e) % S256CurveCfg::N);
+        !r_point.is_infinity() && r_point.has_even_y() && r_point.xonly()[..] == *r
+    }
+}
+
+impl S256PrivateKey {
+    #[inline]
+    fn even_y_secret(&self) -> U256 {
+        match self.point().has_even_y() {
+            true => self.secret().num(),
+            false => S256CurveCfg::N - self.secret().num(),
+        }
+    }
+
+    pub fn tap_tweak(&self, merkle_root: Option<[u8; 32]>) -> Result<Self, String> {
+        let mut data = self.point().xonly().to_vec();
+        if let Some(merkle_root) = merkle_root {
+            data.extend_from_slice(&merkle_root);
+        }
+        let tweak = U256::from_be_bytes(tagged_hash("TapTweak", &data));
+        if tweak >= S256CurveCfg::N {
+            return Err("Invalid taproot tweak.".to_string());
+        }
+        let secret = add_mod_n(self.even_y_secret(), tweak);
+        if secret == U256::ZERO {
+            return Err("Invalid taproot tweak.".to_string());
+        }
+        Ok(Self::from_value(secret))
+    }
+
+    pub fn sign_schnorr(&self, msg: &[u8; 32], aux_rand: &[u8; 32]) -> [u8; 64] {
+        let secret = self.even_y_secret();
+        let xonly = self.point().xonly();
+        let aux_hash = tagged_hash("BIP0340/aux", aux_rand);
+        let masked: Vec<u8> = secret
+            .to_be_bytes()
+            .iter()
+            .zip(aux_hash)
+            .map(|(a, b)| a ^ b)
+            .collect();
+        let nonce = tagged_hash(
+            "BIP0340/nonce",
+            &[&masked[..], &xonly[..], &msg[..]].concat(),
+        );
+        let k = U256::from_be_bytes(nonce) % S256CurveCfg::N;
+        let r_point = *S256Point::G * k;
+        let k = match r_point.has_even_y() {
+            true => k,
+            false => S256CurveCfg::N - k,
+        };
+        let r = r_point.xonly();
+        let s = add_mod_n(k, mul_mod_n(challenge(&r, &xonly, msg), secret));
+        let mut signature = [0u8; 64];
+        signature[..32].copy_from_slice(&r);
+        signature[32..].copy_from_slice(&s.to_be_bytes());
+        signature
+    }
+}
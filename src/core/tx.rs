@@ -0,0 +1,396 @@
+use super::script::Script;
+use super::sha256ser::DoubleSha256;
+use crate::ser::chained_hash::ChainedCompute;
+use crate::ser::hex;
+use crate::ser::stream::{read_array, read_u32_le, read_u64_le, read_u8, read_vec};
+use crate::ser::varint::{encode_varint, read_varint};
+use std::fmt;
+use std::io::{Cursor, Read};
+use std::str::FromStr;
+
+pub const SEQUENCE_FINAL: u32 = 0xffff_ffff;
+pub const SEQUENCE_LOCKTIME_DISABLE_FLAG: u32 = 1 << 31;
+pub const SEQUENCE_LOCKTIME_TYPE_FLAG: u32 = 1 << 22;
+pub const SEQUENCE_LOCKTIME_MASK: u32 = 0x0000_ffff;
+pub const LOCKTIME_THRESHOLD: u32 = 500_000_000;
+
+#[inline]
+pub fn hash256(data: &[u8]) -> [u8; 32] {
+    let mut hash: [u8; 32] = DoubleSha256::compute(data).try_into().unwrap();
+    hash.reverse();
+    hash
+}
+
+#[inline]
+pub(crate) fn read_var_bytes(reader: &mut impl Read) -> Result<Vec<u8>, String> {
+    let len = read_varint(reader)?;
+    read_vec(reader, len as usize)
+}
+
+#[inline]
+pub(crate) fn serialize_var_bytes(bytes: &[u8]) -> Vec<u8> {
+    [&encode_varint(bytes.len() as u64)[..], bytes].concat()
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash, PartialOrd, Ord)]
+pub struct OutPoint {
+    txid: [u8; 32],
+    vout: u32,
+}
+
+impl OutPoint {
+    pub const NULL: OutPoint = OutPoint {
+        txid: [0u8; 32],
+        vout: 0xffff_ffff,
+    };
+
+    #[inline]
+    pub fn new(txid: [u8; 32], vout: u32) -> Self {
+        Self { txid, vout }
+    }
+
+    #[inline]
+    pub fn txid(&self) -> [u8; 32] {
+        self.txid
+    }
+
+    #[inline]
+    pub fn vout(&self) -> u32 {
+        self.vout
+    }
+
+    #[inline]
+    pub fn is_null(&self) -> bool {
+        *self == Self::NULL
+    }
+
+    pub fn parse(reader: &mut impl Read) -> Result<Self, String> {
+        let mut txid = read_array::<32>(reader)?;
+        txid.reverse();
+        let vout = read_u32_le(reader)?;
+        Ok(Self { txid, vout })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = self.txid.to_vec();
+        result.reverse();
+        result.extend(self.vout.to_le_bytes());
+        result
+    }
+}
+
+impl fmt::Display for OutPoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", hex::encode(&self.txid), self.vout)
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct TxIn {
+    previous_output: OutPoint,
+    script_sig: Script,
+    sequence: u32,
+    witness: Vec<Vec<u8>>,
+}
+
+impl TxIn {
+    #[inline]
+    pub fn new(previous_output: OutPoint, script_sig: Script, sequence: u32) -> Self {
+        Self {
+            previous_output,
+            script_sig,
+            sequence,
+            witness: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn previous_output(&self) -> OutPoint {
+        self.previous_output
+    }
+
+    #[inline]
+    pub fn script_sig(&self) -> &Script {
+        &self.script_sig
+    }
+
+    #[inline]
+    pub fn sequence(&self) -> u32 {
+        self.sequence
+    }
+
+    #[inline]
+    pub fn set_script_sig(&mut self, script_sig: Script) {
+        self.script_sig = script_sig;
+    }
+
+    #[inline]
+    pub fn set_sequence(&mut self, sequence: u32) {
+        self.sequence = sequence;
+    }
+
+    #[inline]
+    pub fn witness(&self) -> &[Vec<u8>] {
+        &self.witness
+    }
+
+    #[inline]
+    pub fn set_witness(&mut self, witness: Vec<Vec<u8>>) {
+        self.witness = witness;
+    }
+
+    pub fn parse_witness(&mut self, reader: &mut impl Read) -> Result<(), String> {
+        self.witness = (0..read_varint(reader)?)
+            .map(|_| read_var_bytes(reader))
+            .collect::<Result<Vec<Vec<u8>>, String>>()?;
+        Ok(())
+    }
+
+    pub fn serialize_witness(&self) -> Vec<u8> {
+        let mut result = encode_varint(self.witness.len() as u64);
+        for item in &self.witness {
+            result.extend(serialize_var_bytes(item));
+        }
+        result
+    }
+
+    pub fn parse(reader: &mut impl Read) -> Result<Self, String> {
+        let previous_output = OutPoint::parse(reader)?;
+        let script_sig = Script::parse(reader)?;
+        let sequence = read_u32_le(reader)?;
+        Ok(Self {
+            previous_output,
+            script_sig,
+            sequence,
+            witness: Vec::new(),
+        })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = self.previous_output.serialize();
+        result.extend(self.script_sig.serialize());
+        result.extend(self.sequence.to_le_bytes());
+        result
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct TxOut {
+    amount: u64,
+    script_pubkey: Script,
+}
+
+impl TxOut {
+    #[inline]
+    pub fn new(amount: u64, script_pubkey: Script) -> Self {
+        Self {
+            amount,
+            script_pubkey,
+        }
+    }
+
+    #[inline]
+    pub fn amount(&self) -> u64 {
+        self.amount
+    }
+
+    #[inline]
+    pub fn script_pubkey(&self) -> &Script {
+        &self.script_pubkey
+    }
+
+    pub fn parse(reader: &mut impl Read) -> Result<Self, String> {
+        let amount = read_u64_le(reader)?;
+        let script_pubkey = Script::parse(reader)?;
+        Ok(Self {
+            amount,
+            script_pubkey,
+        })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = self.amount.to_le_bytes().to_vec();
+        result.extend(self.script_pubkey.serialize());
+        result
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Tx {
+    version: u32,
+    inputs: Vec<TxIn>,
+    outputs: Vec<TxOut>,
+    locktime: u32,
+}
+
+impl Tx {
+    #[inline]
+    pub fn new(version: u32, inputs: Vec<TxIn>, outputs: Vec<TxOut>, locktime: u32) -> Self {
+        Self {
+            version,
+            inputs,
+            outputs,
+            locktime,
+        }
+    }
+
+    #[inline]
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    #[inline]
+    pub fn inputs(&self) -> &[TxIn] {
+        &self.inputs
+    }
+
+    #[inline]
+    pub fn inputs_mut(&mut self) -> &mut [TxIn] {
+        &mut self.inputs
+    }
+
+    #[inline]
+    pub fn outputs(&self) -> &[TxOut] {
+        &self.outputs
+    }
+
+    #[inline]
+    pub fn locktime(&self) -> u32 {
+        self.locktime
+    }
+
+    #[inline]
+    pub fn is_coinbase(&self) -> bool {
+        self.inputs.len() == 1 && self.inputs[0].previous_output.is_null()
+    }
+
+    #[inline]
+    pub fn is_segwit(&self) -> bool {
+        self.inputs.iter().any(|input| !input.witness.is_empty())
+    }
+
+    #[inline]
+    pub fn output_value(&self) -> u64 {
+        self.outputs.iter().map(|output| output.amount).sum()
+    }
+
+    pub fn parse(reader: &mut impl Read) -> Result<Self, String> {
+        let version = read_u32_le(reader)?;
+        let mut input_count = read_varint(reader)?;
+        let segwit = input_count == 0;
+        if segwit {
+            let flag = read_u8(reader)?;
+            if flag != 0x01 {
+                return Err(format!("Invalid segwit flag: {:#04x}", flag));
+            }
+            input_count = read_varint(reader)?;
+        }
+        let mut inputs = (0..input_count)
+            .map(|_| TxIn::parse(reader))
+            .collect::<Result<Vec<TxIn>, String>>()?;
+        let outputs = (0..read_varint(reader)?)
+            .map(|_| TxOut::parse(reader))
+            .collect::<Result<Vec<TxOut>, String>>()?;
+        if segwit {
+            for input in inputs.iter_mut() {
+                input.parse_witness(reader)?;
+            }
+            if inputs.iter().all(|input| input.witness.is_empty()) {
+                return Err("Segwit transaction has no witness data.".to_string());
+            }
+        }
+        let locktime = read_u32_le(reader)?;
+        Ok(Self {
+            version,
+            inputs,
+            outputs,
+            locktime,
+        })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        if !self.is_segwit() {
+            return self.serialize_legacy();
+        }
+        let mut result = self.version.to_le_bytes().to_vec();
+        result.extend([0x00, 0x01]);
+        result.extend(self.serialize_body());
+        for input in &self.inputs {
+            result.extend(input.serialize_witness());
+        }
+        result.extend(self.locktime.to_le_bytes());
+        result
+    }
+
+    pub fn serialize_legacy(&self) -> Vec<u8> {
+        let mut result = self.version.to_le_bytes().to_vec();
+        result.extend(self.serialize_body());
+        result.extend(self.locktime.to_le_bytes());
+        result
+    }
+
+    fn serialize_body(&self) -> Vec<u8> {
+        let mut result = encode_varint(self.inputs.len() as u64);
+        for input in &self.inputs {
+            result.extend(input.serialize());
+        }
+        result.extend(encode_varint(self.outputs.len() as u64));
+        for output in &self.outputs {
+            result.extend(output.serialize());
+        }
+        result
+    }
+
+    #[inline]
+    pub fn hash(&self) -> [u8; 32] {
+        hash256(&self.serialize_legacy())
+    }
+
+    #[inline]
+    pub fn witness_hash(&self) -> [u8; 32] {
+        hash256(&self.serialize())
+    }
+
+    #[inline]
+    pub fn wid(&self) -> String {
+        hex::encode(&self.witness_hash())
+    }
+
+    #[inline]
+    pub fn weight(&self) -> usize {
+        self.serialize_legacy().len() * 3 + self.serialize().len()
+    }
+
+    #[inline]
+    pub fn vsize(&self) -> usize {
+        self.weight().div_ceil(4)
+    }
+
+    #[inline]
+    pub fn id(&self) -> String {
+        hex::encode(&self.hash())
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let mut cursor = Cursor::new(bytes);
+        let tx = Self::parse(&mut cursor)?;
+        if cursor.position() as usize != bytes.len() {
+            return Err("Trailing data after transaction.".to_string());
+        }
+        Ok(tx)
+    }
+}
+
+impl FromStr for Tx {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_bytes(&hex::decode(s)?)
+    }
+}
+
+impl fmt::Display for Tx {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", hex::encode(&self.serialize()))
+    }
+}
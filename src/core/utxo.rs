@@ -0,0 +1,600 @@
+use super::block::Block;
+use super::builder::Utxo;
+use super::interpreter::MAX_SCRIPT_SIZE;
+use super::script::Script;
+use super::sha256ser::DoubleSha256;
+use super::tx::{OutPoint, Tx, TxOut};
+use crate::ser::chained_hash::ChainedCompute;
+use crate::ser::stream::{read_array, read_u32_le, read_u8};
+use crate::ser::varint::{encode_varint, read_varint};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+pub const COINBASE_MATURITY: u32 = 100;
+pub const MAX_MONEY: u64 = 21_000_000 * 100_000_000;
+
+const RECORD_SPEND: u8 = 0;
+const RECORD_ADD: u8 = 1;
+const RECORD_COMMIT: u8 = 2;
+const BATCH_CHECKSUM_LEN: usize = 4;
+const BATCH_HEADER_LEN: usize = 4 + BATCH_CHECKSUM_LEN;
+
+#[inline]
+fn money_sum(amounts: impl IntoIterator<Item = u64>) -> Option<u64> {
+    amounts.into_iter().try_fold(0u64, |total, amount| {
+        total
+            .checked_add(amount)
+            .filter(|total| amount <= MAX_MONEY && *total <= MAX_MONEY)
+    })
+}
+
+#[inline]
+fn output_value(tx: &Tx) -> Result<u64, String> {
+    money_sum(tx.outputs().iter().map(TxOut::amount)).ok_or(format!(
+        "Transaction {} output value out of range.",
+        tx.id()
+    ))
+}
+
+#[inline]
+fn batch_checksum(data: &[u8]) -> [u8; BATCH_CHECKSUM_LEN] {
+    DoubleSha256::compute(data)[..BATCH_CHECKSUM_LEN]
+        .try_into()
+        .unwrap()
+}
+
+#[inline]
+fn is_unspendable(script_pubkey: &Script) -> bool {
+    script_pubkey.is_op_return() || script_pubkey.as_bytes().len() > MAX_SCRIPT_SIZE
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Coin {
+    output: TxOut,
+    height: u32,
+    is_coinbase: bool,
+}
+
+impl Coin {
+    #[inline]
+    pub fn new(output: TxOut, height: u32, is_coinbase: bool) -> Self {
+        Self {
+            output,
+            height,
+            is_coinbase,
+        }
+    }
+
+    #[inline]
+    pub fn output(&self) -> &TxOut {
+        &self.output
+    }
+
+    #[inline]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    #[inline]
+    pub fn is_coinbase(&self) -> bool {
+        self.is_coinbase
+    }
+
+    #[inline]
+    pub fn is_mature(&self, spend_height: u32) -> bool {
+        !self.is_coinbase || spend_height >= self.height + COINBASE_MATURITY
+    }
+
+    pub fn parse(reader: &mut impl Read) -> Result<Self, String> {
+        let code = read_u32_le(reader)?;
+        Ok(Self {
+            output: TxOut::parse(reader)?,
+            height: code >> 1,
+            is_coinbase: code & 1 == 1,
+        })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let code = self.height << 1 | self.is_coinbase as u32;
+        let mut result = code.to_le_bytes().to_vec();
+        result.extend(self.output.serialize());
+        result
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct BlockUndo {
+    spent: Vec<Coin>,
+}
+
+impl BlockUndo {
+    #[inline]
+    pub fn spent(&self) -> &[Coin] {
+        &self.spent
+    }
+
+    pub fn parse(reader: &mut impl Read) -> Result<Self, String> {
+        let spent = (0..read_varint(reader)?)
+            .map(|_| Coin::parse(reader))
+            .collect::<Result<Vec<Coin>, String>>()?;
+        Ok(Self { spent })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = encode_varint(self.spent.len() as u64);
+        for coin in &self.spent {
+            result.extend(coin.serialize());
+        }
+        result
+    }
+}
+
+pub trait UtxoStore {
+    fn tip(&self) -> Option<([u8; 32], u32)>;
+
+    fn get(&mut self, outpoint: &OutPoint) -> Result<Option<Coin>, String>;
+
+    fn coins(&mut self) -> Result<Vec<(OutPoint, Coin)>, String>;
+
+    fn write(
+        &mut self,
+        changes: &[(OutPoint, Option<Coin>)],
+        tip: ([u8; 32], u32),
+    ) -> Result<(), String>;
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct MemoryStore {
+    coins: HashMap<OutPoint, Coin>,
+    tip: Option<([u8; 32], u32)>,
+}
+
+impl MemoryStore {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl UtxoStore for MemoryStore {
+    #[inline]
+    fn tip(&self) -> Option<([u8; 32], u32)> {
+        self.tip
+    }
+
+    #[inline]
+    fn get(&mut self, outpoint: &OutPoint) -> Result<Option<Coin>, String> {
+        Ok(self.coins.get(outpoint).cloned())
+    }
+
+    fn coins(&mut self) -> Result<Vec<(OutPoint, Coin)>, String> {
+        Ok(self
+            .coins
+            .iter()
+            .map(|(outpoint, coin)| (*outpoint, coin.clone()))
+            .collect())
+    }
+
+    fn write(
+        &mut self,
+        changes: &[(OutPoint, Option<Coin>)],
+        tip: ([u8; 32], u32),
+    ) -> Result<(), String> {
+        for (outpoint, coin) in changes {
+            match coin {
+                Some(coin) => self.coins.insert(*outpoint, coin.clone()),
+                None => self.coins.remove(outpoint),
+            };
+        }
+        self.tip = Some(tip);
+        Ok(())
+    }
+}
+
+enum Record {
+    Spend(OutPoint),
+    Add(OutPoint, u64),
+    Commit([u8; 32], u32),
+}
+
+impl Record {
+    fn parse(cursor: &mut Cursor<&[u8]>) -> Result<Self, String> {
+        match read_u8(cursor)? {
+            RECORD_SPEND => Ok(Record::Spend(OutPoint::parse(cursor)?)),
+            RECORD_ADD => {
+                let outpoint = OutPoint::parse(cursor)?;
+                let offset = cursor.position();
+                Coin::parse(cursor)?;
+                Ok(Record::Add(outpoint, offset))
+            }
+            RECORD_COMMIT => Ok(Record::Commit(read_array(cursor)?, read_u32_le(cursor)?)),
+            kind => Err(format!("Unknown UTXO log record {}.", kind)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct LogStore {
+    file: File,
+    len: u64,
+    index: HashMap<OutPoint, u64>,
+    tip: Option<([u8; 32], u32)>,
+}
+
+impl LogStore {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let corrupt = |e: String| format!("Corrupt UTXO log {}: {}", path.display(), e);
+        let mut index = HashMap::new();
+        let mut tip = None;
+        let mut committed = 0;
+        while committed < bytes.len() {
+            let batch = &bytes[committed..];
+            let Some(header) = batch.get(..BATCH_HEADER_LEN) else {
+                break;
+            };
+            let (len, checksum) = header.split_at(4);
+            if batch_checksum(len) != checksum {
+                return Err(corrupt(format!(
+                    "Bad batch header at offset {}.",
+                    committed
+                )));
+            }
+            let records_end =
+                BATCH_HEADER_LEN + u32::from_le_bytes(len.try_into().unwrap()) as usize;
+            let Some((records, checksum)) = batch
+                .get(BATCH_HEADER_LEN..records_end)
+                .zip(batch.get(records_end..records_end + BATCH_CHECKSUM_LEN))
+            else {
+                break;
+            };
+            if batch_checksum(records) != checksum {
+                return Err(corrupt(format!("Bad checksum at offset {}.", committed)));
+            }
+            let mut cursor = Cursor::new(records);
+            let mut pending = Vec::new();
+            loop {
+                match Record::parse(&mut cursor).map_err(corrupt)? {
+                    Record::Spend(outpoint) => pending.push((outpoint, None)),
+                    Record::Add(outpoint, offset) => {
+                        let offset = (committed + BATCH_HEADER_LEN) as u64 + offset;
+                        pending.push((outpoint, Some(offset)))
+                    }
+                    Record::Commit(hash, height) => {
+                        tip = Some((hash, height));
+                        break;
+                    }
+                }
+            }
+            if cursor.position() != records.len() as u64 {
+                return Err(corrupt(format!(
+                    "Data after commit at offset {}.",
+                    committed
+                )));
+            }
+            for (outpoint, offset) in pending {
+                match offset {
+                    Some(offset) => index.insert(outpoint, offset),
+                    None => index.remove(&outpoint),
+                };
+            }
+            committed += records_end + BATCH_CHECKSUM_LEN;
+        }
+        if committed != bytes.len() {
+            file.set_len(committed as u64)
+                .map_err(|e| format!("Failed to truncate {}: {}", path.display(), e))?;
+        }
+        Ok(Self {
+            file,
+            len: committed as u64,
+            index,
+            tip,
+        })
+    }
+
+    fn read_coin(&mut self, offset: u64) -> Result<Coin, String> {
+        self.file
+            .seek(SeekFrom::Start(offset))
+            .map_err(|e| e.to_string())?;
+        Coin::parse(&mut self.file)
+    }
+}
+
+impl UtxoStore for LogStore {
+    #[inline]
+    fn tip(&self) -> Option<([u8; 32], u32)> {
+        self.tip
+    }
+
+    fn get(&mut self, outpoint: &OutPoint) -> Result<Option<Coin>, String> {
+        match self.index.get(outpoint) {
+            Some(offset) => self.read_coin(*offset).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn coins(&mut self) -> Result<Vec<(OutPoint, Coin)>, String> {
+        let entries: Vec<(OutPoint, u64)> = self
+            .index
+            .iter()
+            .map(|(outpoint, offset)| (*outpoint, *offset))
+            .collect();
+        entries
+            .into_iter()
+            .map(|(outpoint, offset)| Ok((outpoint, self.read_coin(offset)?)))
+            .collect()
+    }
+
+    fn write(
+        &mut self,
+        changes: &[(OutPoint, Option<Coin>)],
+        tip: ([u8; 32], u32),
+    ) -> Result<(), String> {
+        let mut records = Vec::new();
+        let mut offsets = Vec::with_capacity(changes.len());
+        for (outpoint, coin) in changes {
+            match coin {
+                Some(coin) => {
+                    records.push(RECORD_ADD);
+                    records.extend(outpoint.serialize());
+                    offsets.push(Some(self.len + (BATCH_HEADER_LEN + records.len()) as u64));
+                    records.extend(coin.serialize());
+                }
+                None => {
+                    records.push(RECORD_SPEND);
+                    records.extend(outpoint.serialize());
+                    offsets.push(None);
+                }
+            }
+        }
+        records.push(RECORD_COMMIT);
+        records.extend(tip.0);
+        records.extend(tip.1.to_le_bytes());
+        let len = (records.len() as u32).to_le_bytes();
+        let batch = [
+            &len[..],
+            &batch_checksum(&len),
+            &records,
+            &batch_checksum(&records),
+        ]
+        .concat();
+        if let Err(e) = self
+            .file
+            .write_all(&batch)
+            .and_then(|_| self.file.sync_data())
+        {
+            return Err(match self.file.set_len(self.len) {
+                Ok(_) => format!("Failed to write UTXO log: {}", e),
+                Err(rollback) => format!(
+                    "Failed to write UTXO log: {}, rolling it back failed: {}",
+                    e, rollback
+                ),
+            });
+        }
+        self.len += batch.len() as u64;
+        for ((outpoint, _), offset) in changes.iter().zip(offsets) {
+            match offset {
+                Some(offset) => self.index.insert(*outpoint, offset),
+                None => self.index.remove(outpoint),
+            };
+        }
+        self.tip = Some(tip);
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct UtxoSet<S: UtxoStore> {
+    store: S,
+}
+
+impl<S: UtxoStore> UtxoSet<S> {
+    #[inline]
+    pub fn new(store: S) -> Self {
+        Self { store }
+    }
+
+    #[inline]
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    #[inline]
+    pub fn tip(&self) -> Option<([u8; 32], u32)> {
+        self.store.tip()
+    }
+
+    #[inline]
+    fn next_height(&self) -> u32 {
+        self.tip().map_or(0, |(_, height)| height + 1)
+    }
+
+    #[inline]
+    pub fn get(&mut self, outpoint: &OutPoint) -> Result<Option<Coin>, String> {
+        self.store.get(outpoint)
+    }
+
+    fn lookup(
+        &mut self,
+        changes: &HashMap<OutPoint, Option<Coin>>,
+        outpoint: &OutPoint,
+    ) -> Result<Option<Coin>, String> {
+        match changes.get(outpoint) {
+            Some(coin) => Ok(coin.clone()),
+            None => self.store.get(outpoint),
+        }
+    }
+
+    fn spend_inputs(
+        &mut self,
+        changes: &mut HashMap<OutPoint, Option<Coin>>,
+        tx: &Tx,
+        height: u32,
+    ) -> Result<(Vec<Coin>, u64), String> {
+        let mut spent = Vec::with_capacity(tx.inputs().len());
+        for input in tx.inputs() {
+            let outpoint = input.previous_output();
+            let coin = self
+                .lookup(changes, &outpoint)?
+                .ok_or(format!("Missing or spent output {}.", outpoint))?;
+            if !coin.is_mature(height) {
+                return Err(format!(
+                    "Coinbase output {} spent at height {} before maturity.",
+                    outpoint, height
+                ));
+            }
+            changes.insert(outpoint, None);
+            spent.push(coin);
+        }
+        let input_value = money_sum(spent.iter().map(|coin| coin.output().amount()))
+            .ok_or(format!("Transaction {} input value out of range.", tx.id()))?;
+        let output_value = output_value(tx)?;
+        let fee = input_value.checked_sub(output_value).ok_or(format!(
+            "Transaction {} spends {} but creates {}.",
+            tx.id(),
+            input_value,
+            output_value
+        ))?;
+        Ok((spent, fee))
+    }
+
+    pub fn check_tx(&mut self, tx: &Tx) -> Result<u64, String> {
+        if tx.is_coinbase() {
+            return Err("Coinbase transactions are only valid in blocks.".to_string());
+        }
+        let height = self.next_height();
+        let mut changes = HashMap::new();
+        self.spend_inputs(&mut changes, tx, height)
+            .map(|(_, fee)| fee)
+    }
+
+    pub fn connect_block(&mut self, block: &Block) -> Result<BlockUndo, String> {
+        match self.tip() {
+            Some((hash, _)) if block.header().prev_block() != hash => {
+                return Err(format!("Block {} does not build on the tip.", block.id()))
+            }
+            None if block.header().prev_block() != [0; 32] => {
+                return Err(format!("Block {} is not a genesis block.", block.id()))
+            }
+            _ => {}
+        }
+        block.check_merkle_root()?;
+        let height = self.next_height();
+        let mut changes: HashMap<OutPoint, Option<Coin>> = HashMap::new();
+        let mut undo = BlockUndo::default();
+        for tx in block.txs() {
+            if tx.is_coinbase() {
+                output_value(tx)?;
+                if height == 0 {
+                    continue;
+                }
+            } else {
+                let (spent, _) = self.spend_inputs(&mut changes, tx, height)?;
+                undo.spent.extend(spent);
+            }
+            let txid = tx.hash();
+            for (vout, output) in tx.outputs().iter().enumerate() {
+                if is_unspendable(output.script_pubkey()) {
+                    continue;
+                }
+                let outpoint = OutPoint::new(txid, vout as u32);
+                if !tx.is_coinbase() && self.lookup(&changes, &outpoint)?.is_some() {
+                    return Err(format!("Output {} already exists.", outpoint));
+                }
+                let coin = Coin::new(output.clone(), height, tx.is_coinbase());
+                changes.insert(outpoint, Some(coin));
+            }
+        }
+        let changes: Vec<(OutPoint, Option<Coin>)> = changes.into_iter().collect();
+        self.store.write(&changes, (block.hash(), height))?;
+        Ok(undo)
+    }
+
+    pub fn disconnect_block(&mut self, block: &Block, undo: &BlockUndo) -> Result<(), String> {
+        let height = match self.tip() {
+            Some((hash, height)) if hash == block.hash() => height,
+            _ => return Err(format!("Block {} is not the tip.", block.id())),
+        };
+        if height == 0 {
+            return Err("Cannot disconnect the first block.".to_string());
+        }
+        if block.txs().is_empty() {
+            return Err(format!("Block {} has no transactions.", block.id()));
+        }
+        block.check_merkle_root()?;
+        let input_count: usize = block.txs()[1..].iter().map(|tx| tx.inputs().len()).sum();
+        if undo.spent.len() != input_count {
+            return Err(format!(
+                "Undo data has {} coins for {} inputs.",
+                undo.spent.len(),
+                input_count
+            ));
+        }
+        let mut changes: HashMap<OutPoint, Option<Coin>> = HashMap::new();
+        let mut spent = undo.spent.iter().rev();
+        for tx in block.txs().iter().rev() {
+            let txid = tx.hash();
+            for (vout, output) in tx.outputs().iter().enumerate() {
+                if is_unspendable(output.script_pubkey()) {
+                    continue;
+                }
+                let outpoint = OutPoint::new(txid, vout as u32);
+                if self.lookup(&changes, &outpoint)?.is_none() {
+                    return Err(format!("Output {} of the block is missing.", outpoint));
+                }
+                changes.insert(outpoint, None);
+            }
+            if tx.is_coinbase() {
+                continue;
+            }
+            for input in tx.inputs().iter().rev() {
+                let coin = spent.next().unwrap();
+                changes.insert(input.previous_output(), Some(coin.clone()));
+            }
+        }
+        let changes: Vec<(OutPoint, Option<Coin>)> = changes.into_iter().collect();
+        self.store
+            .write(&changes, (block.header().prev_block(), height - 1))
+    }
+
+    pub fn spendable(&mut self, script_pubkey: &Script) -> Result<Vec<Utxo>, String> {
+        let height = self.next_height();
+        Ok(self
+            .store
+            .coins()?
+            .into_iter()
+            .filter(|(_, coin)| {
+                coin.output().script_pubkey() == script_pubkey && coin.is_mature(height)
+            })
+            .map(|(outpoint, coin)| {
+                Utxo::new(outpoint, coin.output().amount(), script_pubkey.clone())
+            })
+            .collect())
+    }
+
+    pub fn balance(&mut self, script_pubkey: &Script) -> Result<(u64, u64), String> {
+        let height = self.next_height();
+        Ok(self
+            .store
+            .coins()?
+            .iter()
+            .filter(|(_, coin)| coin.output().script_pubkey() == script_pubkey)
+            .fold((0, 0), |(mature, immature), (_, coin)| {
+                if coin.is_mature(height) {
+                    (mature + coin.output().amount(), immature)
+                } else {
+                    (mature, immature + coin.output().amount())
+                }
+            }))
+    }
+}
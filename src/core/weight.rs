@@ -0,0 +1,147 @@
+use super::script::{encode_num, Script};
+use super::tx::TxOut;
+use crate::ser::varint::encode_varint;
+
+const MAX_DER_R_LEN: u64 = 33;
+const MAX_DER_LOW_S_LEN: u64 = 32;
+
+pub const MAX_DER_SIGNATURE_LEN: u64 = 2 + (2 + MAX_DER_R_LEN) + (2 + MAX_DER_LOW_S_LEN);
+pub const MAX_ECDSA_SIGNATURE_LEN: u64 = MAX_DER_SIGNATURE_LEN + 1;
+pub const SCHNORR_SIGNATURE_LEN: u64 = 64;
+pub const COMPRESSED_PUBKEY_LEN: u64 = 33;
+pub const UNCOMPRESSED_PUBKEY_LEN: u64 = 65;
+pub const WITNESS_SCALE_FACTOR: u64 = 4;
+
+const INPUT_BASE_LEN: u64 = 36 + 4;
+const TX_BASE_LEN: u64 = 4 + 4;
+const SEGWIT_HEADER_WEIGHT: u64 = 2;
+
+#[inline]
+fn varint_len(n: u64) -> u64 {
+    encode_varint(n).len() as u64
+}
+
+#[inline]
+fn push_len(data_len: u64) -> u64 {
+    data_len
+        + match data_len {
+            0..=0x4b => 1,
+            0x4c..=0xff => 2,
+            0x100..=0xffff => 3,
+            _ => 5,
+        }
+}
+
+#[inline]
+fn push_int_len(n: usize) -> u64 {
+    match n {
+        0..=16 => 1,
+        _ => push_len(encode_num(n as i64).len() as u64),
+    }
+}
+
+#[inline]
+fn multisig_script_len(threshold: usize, keys: usize) -> u64 {
+    push_int_len(threshold) + keys as u64 * push_len(COMPRESSED_PUBKEY_LEN) + push_int_len(keys) + 1
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum InputType {
+    P2pkh,
+    P2pkhUncompressed,
+    P2wpkh,
+    P2shP2wpkh,
+    P2tr,
+    P2shMultisig(usize, usize),
+    P2wshMultisig(usize, usize),
+    P2shP2wshMultisig(usize, usize),
+}
+
+impl InputType {
+    pub fn from_script_pubkey(script_pubkey: &Script) -> Option<Self> {
+        if script_pubkey.is_p2pkh() {
+            Some(Self::P2pkh)
+        } else if script_pubkey.is_p2wpkh() {
+            Some(Self::P2wpkh)
+        } else if script_pubkey.is_p2tr() {
+            Some(Self::P2tr)
+        } else {
+            None
+        }
+    }
+
+    pub fn script_sig_len(&self) -> u64 {
+        match *self {
+            Self::P2pkh => push_len(MAX_ECDSA_SIGNATURE_LEN) + push_len(COMPRESSED_PUBKEY_LEN),
+            Self::P2pkhUncompressed => {
+                push_len(MAX_ECDSA_SIGNATURE_LEN) + push_len(UNCOMPRESSED_PUBKEY_LEN)
+            }
+            Self::P2wpkh | Self::P2tr | Self::P2wshMultisig(..) => 0,
+            Self::P2shP2wpkh => push_len(22),
+            Self::P2shP2wshMultisig(..) => push_len(34),
+            Self::P2shMultisig(threshold, keys) => {
+                1 + threshold as u64 * push_len(MAX_ECDSA_SIGNATURE_LEN)
+                    + push_len(multisig_script_len(threshold, keys))
+            }
+        }
+    }
+
+    pub fn witness_len(&self) -> u64 {
+        let items = |items: &[u64]| {
+            varint_len(items.len() as u64)
+                + items.iter().map(|len| varint_len(*len) + len).sum::<u64>()
+        };
+        match *self {
+            Self::P2pkh | Self::P2pkhUncompressed | Self::P2shMultisig(..) => 0,
+            Self::P2wpkh | Self::P2shP2wpkh => {
+                items(&[MAX_ECDSA_SIGNATURE_LEN, COMPRESSED_PUBKEY_LEN])
+            }
+            Self::P2tr => items(&[SCHNORR_SIGNATURE_LEN]),
+            Self::P2wshMultisig(threshold, keys) | Self::P2shP2wshMultisig(threshold, keys) => {
+                let mut stack = vec![0];
+                stack.extend(vec![MAX_ECDSA_SIGNATURE_LEN; threshold]);
+                stack.push(multisig_script_len(threshold, keys));
+                items(&stack)
+            }
+        }
+    }
+
+    #[inline]
+    pub fn is_segwit(&self) -> bool {
+        self.witness_len() > 0
+    }
+
+    #[inline]
+    pub fn weight(&self) -> u64 {
+        let script_sig_len = self.script_sig_len();
+        (INPUT_BASE_LEN + varint_len(script_sig_len) + script_sig_len) * WITNESS_SCALE_FACTOR
+            + self.witness_len()
+    }
+}
+
+#[inline]
+pub fn output_weight(output: &TxOut) -> u64 {
+    output.serialize().len() as u64 * WITNESS_SCALE_FACTOR
+}
+
+pub fn estimate_weight(inputs: &[InputType], outputs: &[TxOut]) -> u64 {
+    let base = TX_BASE_LEN + varint_len(inputs.len() as u64) + varint_len(outputs.len() as u64);
+    let mut weight = base * WITNESS_SCALE_FACTOR
+        + inputs.iter().map(InputType::weight).sum::<u64>()
+        + outputs.iter().map(output_weight).sum::<u64>();
+    if inputs.iter().any(InputType::is_segwit) {
+        let legacy_inputs = inputs.iter().filter(|input| !input.is_segwit()).count();
+        weight += SEGWIT_HEADER_WEIGHT + legacy_inputs as u64;
+    }
+    weight
+}
+
+#[inline]
+pub fn estimate_vsize(inputs: &[InputType], outputs: &[TxOut]) -> u64 {
+    estimate_weight(inputs, outputs).div_ceil(WITNESS_SCALE_FACTOR)
+}
+
+#[inline]
+pub fn estimate_fee(inputs: &[InputType], outputs: &[TxOut], sat_per_vbyte: u64) -> u64 {
+    estimate_vsize(inputs, outputs) * sat_per_vbyte
+}
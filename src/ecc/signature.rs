@@ -1,6 +1,7 @@
 use super::finite_field::{FieldElement, Modulus};
 use bnum::BUint;
 
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub struct Signature<M, const N: usize>
 where
     M: Modulus<N>,
@@ -59,6 +60,47 @@ where
         }
         result
     }
+
+    #[inline]
+    pub fn der_encoded(&self) -> Vec<u8>
+    where
+        [(); BUint::<N>::BYTES_USIZE]:,
+    {
+        let der = self.der();
+        [&[0x30u8, der.len() as u8][..], &der].concat()
+    }
+
+    pub fn parse_der(der: &[u8]) -> Result<Self, String> {
+        if der.len() < 8 || der[0] != 0x30 || der[1] as usize != der.len() - 2 {
+            return Err("Invalid DER signature: bad sequence header.".to_string());
+        }
+        let (r, rest) = Self::parse_der_integer(&der[2..])?;
+        let (s, rest) = Self::parse_der_integer(rest)?;
+        if !rest.is_empty() {
+            return Err("Invalid DER signature: trailing data.".to_string());
+        }
+        Ok(Self::from_values(r, s))
+    }
+
+    fn parse_der_integer(data: &[u8]) -> Result<(BUint<N>, &[u8]), String> {
+        if data.len() < 2 || data[0] != 0x02 {
+            return Err("Invalid DER signature: expected integer.".to_string());
+        }
+        let len = data[1] as usize;
+        if len == 0 || data.len() < 2 + len {
+            return Err("Invalid DER signature: bad integer length.".to_string());
+        }
+        let bytes = &data[2..2 + len];
+        if bytes[0] & 0x80 != 0 {
+            return Err("Invalid DER signature: negative integer.".to_string());
+        }
+        if len > 1 && bytes[0] == 0 && bytes[1] & 0x80 == 0 {
+            return Err("Invalid DER signature: integer not minimally encoded.".to_string());
+        }
+        let value = BUint::<N>::from_be_slice(bytes)
+            .ok_or("Invalid DER signature: integer too large.".to_string())?;
+        Ok((value, &data[2 + len..]))
+    }
 }
 
 macro_rules! signature {
@@ -7,6 +7,7 @@ mod ser;
 #[macro_use]
 mod ecc;
 mod core;
+mod network;
 #[cfg(test)]
 mod tests;
 
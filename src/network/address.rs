@@ -0,0 +1,216 @@
+use crate::core::tx::serialize_var_bytes;
+use crate::ser::stream::{read_array, read_u16_be, read_u32_le, read_u64_le, read_u8, read_vec};
+use crate::ser::varint::{encode_varint, read_varint};
+use std::io::Read;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+pub const NODE_NETWORK: u64 = 1;
+pub const NODE_BLOOM: u64 = 1 << 2;
+pub const NODE_WITNESS: u64 = 1 << 3;
+pub const NODE_COMPACT_FILTERS: u64 = 1 << 6;
+pub const NODE_NETWORK_LIMITED: u64 = 1 << 10;
+
+pub const MAX_ADDRV2_SIZE: usize = 512;
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
+pub struct NetAddress {
+    services: u64,
+    ip: IpAddr,
+    port: u16,
+}
+
+impl NetAddress {
+    #[inline]
+    pub fn new(services: u64, ip: IpAddr, port: u16) -> Self {
+        Self { services, ip, port }
+    }
+
+    #[inline]
+    pub fn from_socket_addr(services: u64, addr: SocketAddr) -> Self {
+        Self::new(services, addr.ip(), addr.port())
+    }
+
+    #[inline]
+    pub fn services(&self) -> u64 {
+        self.services
+    }
+
+    #[inline]
+    pub fn ip(&self) -> IpAddr {
+        self.ip
+    }
+
+    #[inline]
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    #[inline]
+    pub fn socket_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.ip, self.port)
+    }
+
+    pub fn parse(reader: &mut impl Read) -> Result<Self, String> {
+        let services = read_u64_le(reader)?;
+        let ip = Ipv6Addr::from(read_array::<16>(reader)?);
+        let ip = match ip.to_ipv4_mapped() {
+            Some(ipv4) => IpAddr::V4(ipv4),
+            None => IpAddr::V6(ip),
+        };
+        let port = read_u16_be(reader)?;
+        Ok(Self { services, ip, port })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let ip = match self.ip {
+            IpAddr::V4(ipv4) => ipv4.to_ipv6_mapped(),
+            IpAddr::V6(ipv6) => ipv6,
+        };
+        let mut result = self.services.to_le_bytes().to_vec();
+        result.extend(ip.octets());
+        result.extend(self.port.to_be_bytes());
+        result
+    }
+}
+
+impl Default for NetAddress {
+    #[inline]
+    fn default() -> Self {
+        Self::new(0, IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0)
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Hash)]
+pub enum AddrV2Address {
+    Ipv4(Ipv4Addr),
+    Ipv6(Ipv6Addr),
+    TorV3([u8; 32]),
+    I2p([u8; 32]),
+    Cjdns(Ipv6Addr),
+    Unknown(u8, Vec<u8>),
+}
+
+impl AddrV2Address {
+    #[inline]
+    pub fn network_id(&self) -> u8 {
+        match self {
+            Self::Ipv4(_) => 1,
+            Self::Ipv6(_) => 2,
+            Self::TorV3(_) => 4,
+            Self::I2p(_) => 5,
+            Self::Cjdns(_) => 6,
+            Self::Unknown(id, _) => *id,
+        }
+    }
+
+    pub fn parse(reader: &mut impl Read) -> Result<Self, String> {
+        let network_id = read_u8(reader)?;
+        let len = read_varint(reader)?;
+        if len as usize > MAX_ADDRV2_SIZE {
+            return Err(format!("Address of {} bytes is too long.", len));
+        }
+        let bytes = read_vec(reader, len as usize)?;
+        let invalid_len = || {
+            format!(
+                "Invalid length {} for address network {}.",
+                bytes.len(),
+                network_id
+            )
+        };
+        Ok(match network_id {
+            1 => Self::Ipv4(
+                <[u8; 4]>::try_from(&bytes[..])
+                    .map_err(|_| invalid_len())?
+                    .into(),
+            ),
+            2 => Self::Ipv6(
+                <[u8; 16]>::try_from(&bytes[..])
+                    .map_err(|_| invalid_len())?
+                    .into(),
+            ),
+            4 => Self::TorV3(bytes[..].try_into().map_err(|_| invalid_len())?),
+            5 => Self::I2p(bytes[..].try_into().map_err(|_| invalid_len())?),
+            6 => Self::Cjdns(
+                <[u8; 16]>::try_from(&bytes[..])
+                    .map_err(|_| invalid_len())?
+                    .into(),
+            ),
+            _ => Self::Unknown(network_id, bytes),
+        })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let bytes = match self {
+            Self::Ipv4(ip) => ip.octets().to_vec(),
+            Self::Ipv6(ip) | Self::Cjdns(ip) => ip.octets().to_vec(),
+            Self::TorV3(key) | Self::I2p(key) => key.to_vec(),
+            Self::Unknown(_, bytes) => bytes.clone(),
+        };
+        [vec![self.network_id()], serialize_var_bytes(&bytes)].concat()
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Hash)]
+pub struct AddrV2 {
+    time: u32,
+    services: u64,
+    address: AddrV2Address,
+    port: u16,
+}
+
+impl AddrV2 {
+    #[inline]
+    pub fn new(time: u32, services: u64, address: AddrV2Address, port: u16) -> Self {
+        Self {
+            time,
+            services,
+            address,
+            port,
+        }
+    }
+
+    #[inline]
+    pub fn time(&self) -> u32 {
+        self.time
+    }
+
+    #[inline]
+    pub fn services(&self) -> u64 {
+        self.services
+    }
+
+    #[inline]
+    pub fn address(&self) -> &AddrV2Address {
+        &self.address
+    }
+
+    #[inline]
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn socket_addr(&self) -> Option<SocketAddr> {
+        match self.address {
+            AddrV2Address::Ipv4(ip) => Some(SocketAddr::new(IpAddr::V4(ip), self.port)),
+            AddrV2Address::Ipv6(ip) => Some(SocketAddr::new(IpAddr::V6(ip), self.port)),
+            _ => None,
+        }
+    }
+
+    pub fn parse(reader: &mut impl Read) -> Result<Self, String> {
+        Ok(Self {
+            time: read_u32_le(reader)?,
+            services: read_varint(reader)?,
+            address: AddrV2Address::parse(reader)?,
+            port: read_u16_be(reader)?,
+        })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = self.time.to_le_bytes().to_vec();
+        result.extend(encode_varint(self.services));
+        result.extend(self.address.serialize());
+        result.extend(self.port.to_be_bytes());
+        result
+    }
+}
@@ -0,0 +1,226 @@
+use crate::core::script::{Instruction, Opcode, Script};
+use crate::core::sha256ser::Sha256Ripemd160;
+use crate::core::tx::{read_var_bytes, serialize_var_bytes, OutPoint, Tx};
+use crate::ser::chained_hash::ChainedCompute;
+use crate::ser::stream::{read_u32_le, read_u8};
+use std::f64::consts::LN_2;
+use std::io::Read;
+
+pub const MAX_BLOOM_FILTER_SIZE: usize = 36_000;
+pub const MAX_HASH_FUNCS: u32 = 50;
+pub const MAX_FILTER_ADD_SIZE: usize = 520;
+
+pub const BLOOM_UPDATE_NONE: u8 = 0;
+pub const BLOOM_UPDATE_ALL: u8 = 1;
+pub const BLOOM_UPDATE_P2PUBKEY_ONLY: u8 = 2;
+pub const BLOOM_UPDATE_MASK: u8 = 3;
+
+const HASH_SEED_STEP: u32 = 0xfba4_c795;
+
+pub fn murmur3(data: &[u8], seed: u32) -> u32 {
+    const C1: u32 = 0xcc9e_2d51;
+    const C2: u32 = 0x1b87_3593;
+    let mix = |k: u32| k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+    let mut hash = seed;
+    let blocks = data.chunks_exact(4);
+    let tail = blocks.remainder();
+    for block in blocks {
+        hash ^= mix(u32::from_le_bytes(block.try_into().unwrap()));
+        hash = hash
+            .rotate_left(13)
+            .wrapping_mul(5)
+            .wrapping_add(0xe654_6b64);
+    }
+    if !tail.is_empty() {
+        let k = tail
+            .iter()
+            .rev()
+            .fold(0u32, |acc, byte| (acc << 8) | *byte as u32);
+        hash ^= mix(k);
+    }
+    hash ^= data.len() as u32;
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85eb_ca6b);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xc2b2_ae35);
+    hash ^ (hash >> 16)
+}
+
+fn is_p2pk(script: &Script) -> bool {
+    matches!(
+        script.instructions().collect::<Result<Vec<Instruction>, String>>().as_deref(),
+        Ok([Instruction::Push(_, key), Instruction::Op(Opcode::CheckSig)])
+            if key.len() == 33 || key.len() == 65
+    )
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct BloomFilter {
+    data: Vec<u8>,
+    hash_funcs: u32,
+    tweak: u32,
+    flags: u8,
+}
+
+impl BloomFilter {
+    pub fn new(elements: u32, fp_rate: f64, tweak: u32, flags: u8) -> Self {
+        let elements = elements.max(1);
+        let bits = (-1.0 / (LN_2 * LN_2) * elements as f64 * fp_rate.ln()) as usize;
+        let data = vec![0u8; bits.min(MAX_BLOOM_FILTER_SIZE * 8) / 8];
+        let hash_funcs = ((data.len() * 8 / elements as usize) as f64 * LN_2) as u32;
+        Self {
+            data,
+            hash_funcs: hash_funcs.min(MAX_HASH_FUNCS),
+            tweak,
+            flags,
+        }
+    }
+
+    #[inline]
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    #[inline]
+    pub fn hash_funcs(&self) -> u32 {
+        self.hash_funcs
+    }
+
+    #[inline]
+    pub fn tweak(&self) -> u32 {
+        self.tweak
+    }
+
+    #[inline]
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    #[inline]
+    fn bit_index(&self, n: u32, element: &[u8]) -> usize {
+        let seed = n.wrapping_mul(HASH_SEED_STEP).wrapping_add(self.tweak);
+        murmur3(element, seed) as usize % (self.data.len() * 8)
+    }
+
+    pub fn insert(&mut self, element: &[u8]) -> &mut Self {
+        if self.data.is_empty() {
+            return self;
+        }
+        for n in 0..self.hash_funcs {
+            let index = self.bit_index(n, element);
+            self.data[index >> 3] |= 1 << (index & 7);
+        }
+        self
+    }
+
+    pub fn insert_pubkey(&mut self, pubkey: &[u8]) -> &mut Self {
+        self.insert(pubkey);
+        self.insert(&Sha256Ripemd160::compute(pubkey))
+    }
+
+    #[inline]
+    pub fn insert_hash160(&mut self, hash160: &[u8; 20]) -> &mut Self {
+        self.insert(hash160)
+    }
+
+    #[inline]
+    pub fn insert_outpoint(&mut self, outpoint: &OutPoint) -> &mut Self {
+        self.insert(&outpoint.serialize())
+    }
+
+    pub fn insert_txid(&mut self, txid: &[u8; 32]) -> &mut Self {
+        let mut hash = *txid;
+        hash.reverse();
+        self.insert(&hash)
+    }
+
+    pub fn contains(&self, element: &[u8]) -> bool {
+        self.data.is_empty()
+            || (0..self.hash_funcs).all(|n| {
+                let index = self.bit_index(n, element);
+                self.data[index >> 3] & (1 << (index & 7)) != 0
+            })
+    }
+
+    #[inline]
+    pub fn contains_outpoint(&self, outpoint: &OutPoint) -> bool {
+        self.contains(&outpoint.serialize())
+    }
+
+    pub fn contains_txid(&self, txid: &[u8; 32]) -> bool {
+        let mut hash = *txid;
+        hash.reverse();
+        self.contains(&hash)
+    }
+
+    fn matches_script(&self, script: &Script) -> bool {
+        script
+            .instructions()
+            .map_while(Result::ok)
+            .any(|instruction| match instruction.push_data() {
+                Some(data) => !data.is_empty() && self.contains(data),
+                None => false,
+            })
+    }
+
+    pub fn is_relevant_and_update(&mut self, tx: &Tx) -> bool {
+        if self.data.is_empty() {
+            return true;
+        }
+        let txid = tx.hash();
+        let mut found = self.contains_txid(&txid);
+        for (vout, output) in tx.outputs().iter().enumerate() {
+            let script_pubkey = output.script_pubkey();
+            if !self.matches_script(script_pubkey) {
+                continue;
+            }
+            found = true;
+            let update = match self.flags & BLOOM_UPDATE_MASK {
+                BLOOM_UPDATE_ALL => true,
+                BLOOM_UPDATE_P2PUBKEY_ONLY => {
+                    is_p2pk(script_pubkey) || script_pubkey.multisig_keys().is_some()
+                }
+                _ => false,
+            };
+            if update {
+                self.insert_outpoint(&OutPoint::new(txid, vout as u32));
+            }
+        }
+        found
+            || tx.inputs().iter().any(|input| {
+                self.contains_outpoint(&input.previous_output())
+                    || self.matches_script(input.script_sig())
+            })
+    }
+
+    pub fn parse(reader: &mut impl Read) -> Result<Self, String> {
+        let data = read_var_bytes(reader)?;
+        if data.len() > MAX_BLOOM_FILTER_SIZE {
+            return Err(format!(
+                "Bloom filter of {} bytes is too large.",
+                data.len()
+            ));
+        }
+        let hash_funcs = read_u32_le(reader)?;
+        if hash_funcs > MAX_HASH_FUNCS {
+            return Err(format!(
+                "Too many bloom filter hash functions: {}.",
+                hash_funcs
+            ));
+        }
+        Ok(Self {
+            data,
+            hash_funcs,
+            tweak: read_u32_le(reader)?,
+            flags: read_u8(reader)?,
+        })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = serialize_var_bytes(&self.data);
+        result.extend(self.hash_funcs.to_le_bytes());
+        result.extend(self.tweak.to_le_bytes());
+        result.push(self.flags);
+        result
+    }
+}
@@ -0,0 +1,579 @@
+use super::message::{read_hash, serialize_hash};
+use crate::core::block::Block;
+use crate::core::chain::HeaderChain;
+use crate::core::script::Script;
+use crate::core::sha256ser::DoubleSha256;
+use crate::core::tx::{read_var_bytes, serialize_var_bytes};
+use crate::ser::chained_hash::ChainedCompute;
+use crate::ser::hex;
+use crate::ser::stream::{read_u32_le, read_u8};
+use crate::ser::varint::{encode_varint, read_varint};
+use std::collections::BTreeSet;
+use std::io::{Cursor, Read};
+
+pub const FILTER_TYPE_BASIC: u8 = 0;
+pub const BASIC_FILTER_P: u8 = 19;
+pub const BASIC_FILTER_M: u64 = 784_931;
+pub const MAX_GETCFILTERS_SIZE: u32 = 1000;
+pub const MAX_GETCFHEADERS_SIZE: u32 = 2000;
+pub const CFCHECKPT_INTERVAL: u32 = 1000;
+
+pub fn siphash24(k0: u64, k1: u64, data: &[u8]) -> u64 {
+    let mut v = [
+        k0 ^ 0x736f_6d65_7073_6575,
+        k1 ^ 0x646f_7261_6e64_6f6d,
+        k0 ^ 0x6c79_6765_6e65_7261,
+        k1 ^ 0x7465_6462_7974_6573,
+    ];
+    let round = |v: &mut [u64; 4]| {
+        v[0] = v[0].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(13) ^ v[0];
+        v[0] = v[0].rotate_left(32);
+        v[2] = v[2].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(16) ^ v[2];
+        v[0] = v[0].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(21) ^ v[0];
+        v[2] = v[2].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(17) ^ v[2];
+        v[2] = v[2].rotate_left(32);
+    };
+    let compress = |v: &mut [u64; 4], word: u64| {
+        v[3] ^= word;
+        round(v);
+        round(v);
+        v[0] ^= word;
+    };
+    let words = data.chunks_exact(8);
+    let tail = words.remainder();
+    for word in words {
+        compress(&mut v, u64::from_le_bytes(word.try_into().unwrap()));
+    }
+    let mut last = (data.len() as u64 & 0xff) << 56;
+    for (index, byte) in tail.iter().enumerate() {
+        last |= (*byte as u64) << (8 * index);
+    }
+    compress(&mut v, last);
+    v[2] ^= 0xff;
+    for _ in 0..4 {
+        round(&mut v);
+    }
+    v[0] ^ v[1] ^ v[2] ^ v[3]
+}
+
+#[inline]
+fn double_sha256(data: &[u8]) -> [u8; 32] {
+    DoubleSha256::compute(data).try_into().unwrap()
+}
+
+pub fn filter_header(filter_hash: &[u8; 32], prev_header: &[u8; 32]) -> [u8; 32] {
+    let mut data: Vec<u8> = serialize_hash(filter_hash).copied().collect();
+    data.extend(serialize_hash(prev_header));
+    let mut header = double_sha256(&data);
+    header.reverse();
+    header
+}
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    used: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u64, bits: u8) {
+        for bit in (0..bits).rev() {
+            if self.used == 0 {
+                self.bytes.push(0);
+            }
+            let last = self.bytes.len() - 1;
+            self.bytes[last] |= ((value >> bit & 1) as u8) << (7 - self.used);
+            self.used = (self.used + 1) % 8;
+        }
+    }
+}
+
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl BitReader<'_> {
+    fn read(&mut self, bits: u8) -> Result<u64, String> {
+        let mut value = 0;
+        for _ in 0..bits {
+            let byte = self
+                .bytes
+                .get(self.position / 8)
+                .ok_or("Filter ended in the middle of a value.")?;
+            value = value << 1 | (byte >> (7 - self.position % 8) & 1) as u64;
+            self.position += 1;
+        }
+        Ok(value)
+    }
+
+    fn read_golomb_rice(&mut self, p: u8) -> Result<u64, String> {
+        let mut quotient = 0u64;
+        while self.read(1)? == 1 {
+            quotient += 1;
+        }
+        Ok(quotient << p | self.read(p)?)
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct BlockFilter {
+    block_hash: [u8; 32],
+    content: Vec<u8>,
+}
+
+impl BlockFilter {
+    pub fn new<'a>(block_hash: [u8; 32], elements: impl IntoIterator<Item = &'a [u8]>) -> Self {
+        let elements: BTreeSet<&[u8]> = elements.into_iter().collect();
+        let mut filter = Self {
+            block_hash,
+            content: encode_varint(elements.len() as u64),
+        };
+        let mut values = filter.hashed_values(elements.iter().copied(), elements.len() as u64);
+        values.sort_unstable();
+        let mut writer = BitWriter {
+            bytes: Vec::new(),
+            used: 0,
+        };
+        let mut last = 0;
+        for value in values {
+            let delta = value - last;
+            last = value;
+            for _ in 0..delta >> BASIC_FILTER_P {
+                writer.write(1, 1);
+            }
+            writer.write(0, 1);
+            writer.write(delta, BASIC_FILTER_P);
+        }
+        filter.content.extend(writer.bytes);
+        filter
+    }
+
+    pub fn from_block(block: &Block, spent_scripts: &[Script]) -> Result<Self, String> {
+        let inputs: usize = block
+            .txs()
+            .iter()
+            .filter(|tx| !tx.is_coinbase())
+            .map(|tx| tx.inputs().len())
+            .sum();
+        if inputs != spent_scripts.len() {
+            return Err(format!(
+                "Block has {} inputs to filter, got {} spent scripts.",
+                inputs,
+                spent_scripts.len()
+            ));
+        }
+        let outputs = block
+            .txs()
+            .iter()
+            .flat_map(|tx| tx.outputs())
+            .map(|output| output.script_pubkey())
+            .filter(|script| !script.is_op_return());
+        let elements = outputs
+            .chain(spent_scripts)
+            .map(Script::as_bytes)
+            .filter(|bytes| !bytes.is_empty());
+        Ok(Self::new(block.hash(), elements))
+    }
+
+    #[inline]
+    pub fn from_content(block_hash: [u8; 32], content: Vec<u8>) -> Self {
+        Self {
+            block_hash,
+            content,
+        }
+    }
+
+    #[inline]
+    pub fn block_hash(&self) -> [u8; 32] {
+        self.block_hash
+    }
+
+    #[inline]
+    pub fn content(&self) -> &[u8] {
+        &self.content
+    }
+
+    pub fn hash(&self) -> [u8; 32] {
+        let mut hash = double_sha256(&self.content);
+        hash.reverse();
+        hash
+    }
+
+    #[inline]
+    pub fn header(&self, prev_header: &[u8; 32]) -> [u8; 32] {
+        filter_header(&self.hash(), prev_header)
+    }
+
+    fn hashed_values<'a>(&self, elements: impl IntoIterator<Item = &'a [u8]>, n: u64) -> Vec<u64> {
+        let mut key = self.block_hash;
+        key.reverse();
+        let k0 = u64::from_le_bytes(key[..8].try_into().unwrap());
+        let k1 = u64::from_le_bytes(key[8..16].try_into().unwrap());
+        let range = n * BASIC_FILTER_M;
+        elements
+            .into_iter()
+            .map(|element| ((siphash24(k0, k1, element) as u128 * range as u128) >> 64) as u64)
+            .collect()
+    }
+
+    pub fn match_any<'a>(
+        &self,
+        elements: impl IntoIterator<Item = &'a [u8]>,
+    ) -> Result<bool, String> {
+        let mut cursor = Cursor::new(&self.content);
+        let n = read_varint(&mut cursor)?;
+        if n == 0 {
+            return Ok(false);
+        }
+        if n >= 1 << 32 {
+            return Err(format!("Filter has {} elements, N must be <2^32.", n));
+        }
+        let mut queries = self.hashed_values(elements, n);
+        queries.sort_unstable();
+        let mut queries = queries.into_iter().peekable();
+        let mut reader = BitReader {
+            bytes: &self.content[cursor.position() as usize..],
+            position: 0,
+        };
+        let mut value = 0u64;
+        for _ in 0..n {
+            value += reader.read_golomb_rice(BASIC_FILTER_P)?;
+            while queries.next_if(|query| *query < value).is_some() {}
+            match queries.peek() {
+                Some(query) if *query == value => return Ok(true),
+                Some(_) => {}
+                None => return Ok(false),
+            }
+        }
+        Ok(false)
+    }
+
+    #[inline]
+    pub fn matches(&self, element: &[u8]) -> Result<bool, String> {
+        self.match_any([element])
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct FilterRequest {
+    filter_type: u8,
+    start_height: u32,
+    stop_hash: [u8; 32],
+}
+
+impl FilterRequest {
+    #[inline]
+    pub fn new(filter_type: u8, start_height: u32, stop_hash: [u8; 32]) -> Self {
+        Self {
+            filter_type,
+            start_height,
+            stop_hash,
+        }
+    }
+
+    #[inline]
+    pub fn filter_type(&self) -> u8 {
+        self.filter_type
+    }
+
+    #[inline]
+    pub fn start_height(&self) -> u32 {
+        self.start_height
+    }
+
+    #[inline]
+    pub fn stop_hash(&self) -> [u8; 32] {
+        self.stop_hash
+    }
+
+    pub fn parse(reader: &mut impl Read) -> Result<Self, String> {
+        Ok(Self {
+            filter_type: read_u8(reader)?,
+            start_height: read_u32_le(reader)?,
+            stop_hash: read_hash(reader)?,
+        })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = vec![self.filter_type];
+        result.extend(self.start_height.to_le_bytes());
+        result.extend(serialize_hash(&self.stop_hash));
+        result
+    }
+}
+
+pub(super) fn parse_cfilter(reader: &mut impl Read) -> Result<BlockFilter, String> {
+    let filter_type = read_u8(reader)?;
+    if filter_type != FILTER_TYPE_BASIC {
+        return Err(format!("Unsupported filter type {}.", filter_type));
+    }
+    let block_hash = read_hash(reader)?;
+    Ok(BlockFilter::from_content(
+        block_hash,
+        read_var_bytes(reader)?,
+    ))
+}
+
+pub(super) fn serialize_cfilter(filter: &BlockFilter) -> Vec<u8> {
+    let mut result = vec![FILTER_TYPE_BASIC];
+    result.extend(serialize_hash(&filter.block_hash));
+    result.extend(serialize_var_bytes(&filter.content));
+    result
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct CFHeadersMessage {
+    filter_type: u8,
+    stop_hash: [u8; 32],
+    prev_header: [u8; 32],
+    filter_hashes: Vec<[u8; 32]>,
+}
+
+impl CFHeadersMessage {
+    #[inline]
+    pub fn new(
+        filter_type: u8,
+        stop_hash: [u8; 32],
+        prev_header: [u8; 32],
+        filter_hashes: Vec<[u8; 32]>,
+    ) -> Self {
+        Self {
+            filter_type,
+            stop_hash,
+            prev_header,
+            filter_hashes,
+        }
+    }
+
+    #[inline]
+    pub fn filter_type(&self) -> u8 {
+        self.filter_type
+    }
+
+    #[inline]
+    pub fn stop_hash(&self) -> [u8; 32] {
+        self.stop_hash
+    }
+
+    #[inline]
+    pub fn prev_header(&self) -> [u8; 32] {
+        self.prev_header
+    }
+
+    #[inline]
+    pub fn filter_hashes(&self) -> &[[u8; 32]] {
+        &self.filter_hashes
+    }
+
+    pub fn headers(&self) -> Vec<[u8; 32]> {
+        let mut prev = self.prev_header;
+        self.filter_hashes
+            .iter()
+            .map(|filter_hash| {
+                prev = filter_header(filter_hash, &prev);
+                prev
+            })
+            .collect()
+    }
+
+    pub fn parse(reader: &mut impl Read) -> Result<Self, String> {
+        let filter_type = read_u8(reader)?;
+        let stop_hash = read_hash(reader)?;
+        let prev_header = read_hash(reader)?;
+        Ok(Self {
+            filter_type,
+            stop_hash,
+            prev_header,
+            filter_hashes: read_hashes(reader, MAX_GETCFHEADERS_SIZE)?,
+        })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = vec![self.filter_type];
+        result.extend(serialize_hash(&self.stop_hash));
+        result.extend(serialize_hash(&self.prev_header));
+        result.extend(serialize_hashes(&self.filter_hashes));
+        result
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct CFCheckptMessage {
+    filter_type: u8,
+    stop_hash: [u8; 32],
+    headers: Vec<[u8; 32]>,
+}
+
+impl CFCheckptMessage {
+    #[inline]
+    pub fn new(filter_type: u8, stop_hash: [u8; 32], headers: Vec<[u8; 32]>) -> Self {
+        Self {
+            filter_type,
+            stop_hash,
+            headers,
+        }
+    }
+
+    #[inline]
+    pub fn filter_type(&self) -> u8 {
+        self.filter_type
+    }
+
+    #[inline]
+    pub fn stop_hash(&self) -> [u8; 32] {
+        self.stop_hash
+    }
+
+    #[inline]
+    pub fn headers(&self) -> &[[u8; 32]] {
+        &self.headers
+    }
+
+    pub fn parse(reader: &mut impl Read) -> Result<Self, String> {
+        let filter_type = read_u8(reader)?;
+        let stop_hash = read_hash(reader)?;
+        Ok(Self {
+            filter_type,
+            stop_hash,
+            headers: read_hashes(reader, u32::MAX)?,
+        })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = vec![self.filter_type];
+        result.extend(serialize_hash(&self.stop_hash));
+        result.extend(serialize_hashes(&self.headers));
+        result
+    }
+}
+
+fn read_hashes(reader: &mut impl Read, max: u32) -> Result<Vec<[u8; 32]>, String> {
+    let count = read_varint(reader)?;
+    if count > max as u64 {
+        return Err(format!("Too many filter hashes: {}.", count));
+    }
+    (0..count).map(|_| read_hash(reader)).collect()
+}
+
+fn serialize_hashes(hashes: &[[u8; 32]]) -> Vec<u8> {
+    let mut result = encode_varint(hashes.len() as u64);
+    for hash in hashes {
+        result.extend(serialize_hash(hash));
+    }
+    result
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct FilterHeaderChain {
+    headers: Vec<[u8; 32]>,
+    checkpoints: Vec<[u8; 32]>,
+}
+
+impl FilterHeaderChain {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn len(&self) -> u32 {
+        self.headers.len() as u32
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty()
+    }
+
+    #[inline]
+    pub fn header_at(&self, height: u32) -> Option<[u8; 32]> {
+        self.headers.get(height as usize).copied()
+    }
+
+    #[inline]
+    fn prev_header(&self, height: u32) -> Option<[u8; 32]> {
+        match height {
+            0 => Some([0; 32]),
+            _ => self.header_at(height - 1),
+        }
+    }
+
+    pub fn set_checkpoints(&mut self, checkpoint: &CFCheckptMessage) -> Result<(), String> {
+        for (index, header) in checkpoint.headers().iter().enumerate() {
+            let height = (index as u32 + 1) * CFCHECKPT_INTERVAL;
+            if self.header_at(height).is_some_and(|known| known != *header) {
+                return Err(format!(
+                    "Checkpoint at height {} conflicts with known headers.",
+                    height
+                ));
+            }
+        }
+        self.checkpoints = checkpoint.headers().to_vec();
+        Ok(())
+    }
+
+    pub fn accept(
+        &mut self,
+        message: &CFHeadersMessage,
+        chain: &HeaderChain,
+    ) -> Result<(), String> {
+        if message.filter_type() != FILTER_TYPE_BASIC {
+            return Err(format!(
+                "Unsupported filter type {}.",
+                message.filter_type()
+            ));
+        }
+        let start = self.len();
+        if message.prev_header() != self.prev_header(start).unwrap() {
+            return Err(format!(
+                "Filter headers don't continue from height {}.",
+                start as i64 - 1
+            ));
+        }
+        let stop_height = (start + message.filter_hashes().len() as u32)
+            .checked_sub(1)
+            .ok_or("Empty filter headers.")?;
+        if chain.hash_at(stop_height) != Some(message.stop_hash()) {
+            return Err(format!(
+                "Stop hash {} is not the best block at height {}.",
+                hex::encode(&message.stop_hash()),
+                stop_height
+            ));
+        }
+        let headers = message.headers();
+        for (offset, header) in headers.iter().enumerate() {
+            let height = start + offset as u32;
+            if height > 0 && height.is_multiple_of(CFCHECKPT_INTERVAL) {
+                let checkpoint = self
+                    .checkpoints
+                    .get((height / CFCHECKPT_INTERVAL - 1) as usize);
+                if checkpoint.is_some_and(|checkpoint| checkpoint != header) {
+                    return Err(format!(
+                        "Filter header at height {} fails checkpoint.",
+                        height
+                    ));
+                }
+            }
+        }
+        self.headers.extend(headers);
+        Ok(())
+    }
+
+    pub fn check_filter(&self, filter: &BlockFilter, height: u32) -> Result<(), String> {
+        let (Some(prev), Some(expected)) = (self.prev_header(height), self.header_at(height))
+        else {
+            return Err(format!("No filter header at height {}.", height));
+        };
+        if filter.header(&prev) != expected {
+            return Err(format!(
+                "Filter for block {} does not match its header.",
+                hex::encode(&filter.block_hash())
+            ));
+        }
+        Ok(())
+    }
+}
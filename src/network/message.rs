@@ -0,0 +1,689 @@
+use super::address::{AddrV2, NetAddress};
+use super::bloom::{BloomFilter, MAX_FILTER_ADD_SIZE};
+use super::filter::{
+    parse_cfilter, serialize_cfilter, BlockFilter, CFCheckptMessage, CFHeadersMessage,
+    FilterRequest,
+};
+use crate::core::block::{Block, BlockHeader};
+use crate::core::merkle::MerkleBlock;
+use crate::core::network::Network;
+use crate::core::sha256ser::DoubleSha256;
+use crate::core::tx::{read_var_bytes, serialize_var_bytes, Tx};
+use crate::ser::chained_hash::ChainedCompute;
+use crate::ser::hex;
+use crate::ser::stream::{read_array, read_u32_le, read_u64_le, read_u8, read_vec};
+use crate::ser::varint::{encode_varint, read_varint};
+use std::io::{Cursor, Read};
+
+pub const PROTOCOL_VERSION: u32 = 70016;
+pub const SENDHEADERS_VERSION: u32 = 70012;
+pub const FEEFILTER_VERSION: u32 = 70013;
+pub const SHORT_IDS_BLOCKS_VERSION: u32 = 70014;
+pub const WTXID_RELAY_VERSION: u32 = 70016;
+pub const USER_AGENT: &str = "/bitcoin-rs:0.1.0/";
+pub const HEADER_SIZE: usize = 24;
+pub const MAX_MESSAGE_SIZE: usize = 4_000_000;
+pub const MAX_INV_SIZE: u64 = 50_000;
+pub const MAX_HEADERS: u64 = 2000;
+pub const MAX_ADDR: u64 = 1000;
+pub const MAX_LOCATOR_SIZE: u64 = 101;
+pub const MAX_USER_AGENT_LEN: usize = 256;
+
+pub const INV_ERROR: u32 = 0;
+pub const INV_TX: u32 = 1;
+pub const INV_BLOCK: u32 = 2;
+pub const INV_FILTERED_BLOCK: u32 = 3;
+pub const INV_CMPCT_BLOCK: u32 = 4;
+pub const INV_WTX: u32 = 5;
+pub const INV_WITNESS_FLAG: u32 = 1 << 30;
+
+pub const REJECT_MALFORMED: u8 = 0x01;
+pub const REJECT_INVALID: u8 = 0x10;
+pub const REJECT_OBSOLETE: u8 = 0x11;
+pub const REJECT_DUPLICATE: u8 = 0x12;
+pub const REJECT_NONSTANDARD: u8 = 0x40;
+pub const REJECT_DUST: u8 = 0x41;
+pub const REJECT_INSUFFICIENT_FEE: u8 = 0x42;
+pub const REJECT_CHECKPOINT: u8 = 0x43;
+
+#[inline]
+fn checksum(payload: &[u8]) -> [u8; 4] {
+    DoubleSha256::compute(payload)[..4].try_into().unwrap()
+}
+
+#[inline]
+pub(super) fn read_hash(reader: &mut impl Read) -> Result<[u8; 32], String> {
+    let mut hash = read_array::<32>(reader)?;
+    hash.reverse();
+    Ok(hash)
+}
+
+#[inline]
+pub(super) fn serialize_hash(hash: &[u8; 32]) -> impl Iterator<Item = &u8> {
+    hash.iter().rev()
+}
+
+fn read_count(reader: &mut impl Read, max: u64, what: &str) -> Result<u64, String> {
+    let count = read_varint(reader)?;
+    if count > max {
+        return Err(format!("Too many {}: {} exceeds {}.", what, count, max));
+    }
+    Ok(count)
+}
+
+fn read_var_string(reader: &mut impl Read, max_len: usize) -> Result<String, String> {
+    let bytes = read_var_bytes(reader)?;
+    if bytes.len() > max_len {
+        return Err(format!("String of {} bytes is too long.", bytes.len()));
+    }
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct RawMessage {
+    command: String,
+    payload: Vec<u8>,
+}
+
+impl RawMessage {
+    pub fn new(command: &str, payload: Vec<u8>) -> Result<Self, String> {
+        if command.len() > 12 || !command.bytes().all(|byte| byte.is_ascii_graphic()) {
+            return Err(format!("Invalid command: {:?}", command));
+        }
+        Ok(Self {
+            command: command.to_string(),
+            payload,
+        })
+    }
+
+    #[inline]
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    #[inline]
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    pub fn parse(reader: &mut impl Read, network: Network) -> Result<Self, String> {
+        let magic = read_array::<4>(reader)?;
+        if magic != network.magic() {
+            return Err(format!(
+                "Expected {} magic, got {}.",
+                network,
+                hex::encode(&magic)
+            ));
+        }
+        let command = read_array::<12>(reader)?;
+        let len = command.iter().position(|byte| *byte == 0).unwrap_or(12);
+        if command[len..].iter().any(|byte| *byte != 0)
+            || !command[..len].iter().all(u8::is_ascii_graphic)
+        {
+            return Err(format!("Invalid command: {}", hex::encode(&command)));
+        }
+        let command = String::from_utf8(command[..len].to_vec()).unwrap();
+        let payload_len = read_u32_le(reader)? as usize;
+        if payload_len > MAX_MESSAGE_SIZE {
+            return Err(format!(
+                "Payload of {} bytes exceeds the maximum message size.",
+                payload_len
+            ));
+        }
+        let expected_checksum = read_array::<4>(reader)?;
+        let payload = read_vec(reader, payload_len)?;
+        if checksum(&payload) != expected_checksum {
+            return Err(format!("Checksum mismatch in {} message.", command));
+        }
+        Ok(Self { command, payload })
+    }
+
+    pub fn serialize(&self, network: Network) -> Vec<u8> {
+        let mut command = [0u8; 12];
+        command[..self.command.len()].copy_from_slice(self.command.as_bytes());
+        let mut result = network.magic().to_vec();
+        result.extend(command);
+        result.extend((self.payload.len() as u32).to_le_bytes());
+        result.extend(checksum(&self.payload));
+        result.extend(&self.payload);
+        result
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct VersionMessage {
+    version: u32,
+    services: u64,
+    timestamp: i64,
+    receiver: NetAddress,
+    sender: NetAddress,
+    nonce: u64,
+    user_agent: String,
+    start_height: i32,
+    relay: bool,
+}
+
+impl VersionMessage {
+    pub fn new(timestamp: i64, nonce: u64, start_height: i32) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            services: 0,
+            timestamp,
+            receiver: NetAddress::default(),
+            sender: NetAddress::default(),
+            nonce,
+            user_agent: USER_AGENT.to_string(),
+            start_height,
+            relay: false,
+        }
+    }
+
+    #[inline]
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    #[inline]
+    pub fn services(&self) -> u64 {
+        self.services
+    }
+
+    #[inline]
+    pub fn timestamp(&self) -> i64 {
+        self.timestamp
+    }
+
+    #[inline]
+    pub fn receiver(&self) -> &NetAddress {
+        &self.receiver
+    }
+
+    #[inline]
+    pub fn sender(&self) -> &NetAddress {
+        &self.sender
+    }
+
+    #[inline]
+    pub fn nonce(&self) -> u64 {
+        self.nonce
+    }
+
+    #[inline]
+    pub fn user_agent(&self) -> &str {
+        &self.user_agent
+    }
+
+    #[inline]
+    pub fn start_height(&self) -> i32 {
+        self.start_height
+    }
+
+    #[inline]
+    pub fn relay(&self) -> bool {
+        self.relay
+    }
+
+    #[inline]
+    pub fn set_version(&mut self, version: u32) -> &mut Self {
+        self.version = version;
+        self
+    }
+
+    #[inline]
+    pub fn set_services(&mut self, services: u64) -> &mut Self {
+        self.services = services;
+        self
+    }
+
+    #[inline]
+    pub fn set_receiver(&mut self, receiver: NetAddress) -> &mut Self {
+        self.receiver = receiver;
+        self
+    }
+
+    #[inline]
+    pub fn set_sender(&mut self, sender: NetAddress) -> &mut Self {
+        self.sender = sender;
+        self
+    }
+
+    #[inline]
+    pub fn set_user_agent(&mut self, user_agent: &str) -> &mut Self {
+        self.user_agent = user_agent.to_string();
+        self
+    }
+
+    #[inline]
+    pub fn set_relay(&mut self, relay: bool) -> &mut Self {
+        self.relay = relay;
+        self
+    }
+
+    pub fn parse(reader: &mut impl Read) -> Result<Self, String> {
+        let version = read_u32_le(reader)?;
+        let services = read_u64_le(reader)?;
+        let timestamp = read_u64_le(reader)? as i64;
+        let receiver = NetAddress::parse(reader)?;
+        let sender = NetAddress::parse(reader)?;
+        let nonce = read_u64_le(reader)?;
+        let user_agent = read_var_string(reader, MAX_USER_AGENT_LEN)?;
+        let start_height = read_u32_le(reader)? as i32;
+        let mut relay = [1u8; 1];
+        let relay = match reader.read(&mut relay) {
+            Ok(1) => relay[0] != 0,
+            _ => true,
+        };
+        Ok(Self {
+            version,
+            services,
+            timestamp,
+            receiver,
+            sender,
+            nonce,
+            user_agent,
+            start_height,
+            relay,
+        })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = self.version.to_le_bytes().to_vec();
+        result.extend(self.services.to_le_bytes());
+        result.extend(self.timestamp.to_le_bytes());
+        result.extend(self.receiver.serialize());
+        result.extend(self.sender.serialize());
+        result.extend(self.nonce.to_le_bytes());
+        result.extend(serialize_var_bytes(self.user_agent.as_bytes()));
+        result.extend(self.start_height.to_le_bytes());
+        result.push(self.relay as u8);
+        result
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
+pub struct Inventory {
+    kind: u32,
+    hash: [u8; 32],
+}
+
+impl Inventory {
+    #[inline]
+    pub fn new(kind: u32, hash: [u8; 32]) -> Self {
+        Self { kind, hash }
+    }
+
+    #[inline]
+    pub fn kind(&self) -> u32 {
+        self.kind
+    }
+
+    #[inline]
+    pub fn hash(&self) -> [u8; 32] {
+        self.hash
+    }
+
+    pub fn parse(reader: &mut impl Read) -> Result<Self, String> {
+        Ok(Self {
+            kind: read_u32_le(reader)?,
+            hash: read_hash(reader)?,
+        })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = self.kind.to_le_bytes().to_vec();
+        result.extend(serialize_hash(&self.hash));
+        result
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct GetHeadersMessage {
+    version: u32,
+    locator: Vec<[u8; 32]>,
+    stop_hash: [u8; 32],
+}
+
+impl GetHeadersMessage {
+    #[inline]
+    pub fn new(locator: Vec<[u8; 32]>, stop_hash: [u8; 32]) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            locator,
+            stop_hash,
+        }
+    }
+
+    #[inline]
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    #[inline]
+    pub fn locator(&self) -> &[[u8; 32]] {
+        &self.locator
+    }
+
+    #[inline]
+    pub fn stop_hash(&self) -> [u8; 32] {
+        self.stop_hash
+    }
+
+    pub fn parse(reader: &mut impl Read) -> Result<Self, String> {
+        let version = read_u32_le(reader)?;
+        let locator = (0..read_count(reader, MAX_LOCATOR_SIZE, "locator hashes")?)
+            .map(|_| read_hash(reader))
+            .collect::<Result<Vec<[u8; 32]>, String>>()?;
+        Ok(Self {
+            version,
+            locator,
+            stop_hash: read_hash(reader)?,
+        })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = self.version.to_le_bytes().to_vec();
+        result.extend(encode_varint(self.locator.len() as u64));
+        for hash in &self.locator {
+            result.extend(serialize_hash(hash));
+        }
+        result.extend(serialize_hash(&self.stop_hash));
+        result
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct RejectMessage {
+    message: String,
+    code: u8,
+    reason: String,
+    data: Vec<u8>,
+}
+
+impl RejectMessage {
+    #[inline]
+    pub fn new(message: &str, code: u8, reason: &str, data: Vec<u8>) -> Self {
+        Self {
+            message: message.to_string(),
+            code,
+            reason: reason.to_string(),
+            data,
+        }
+    }
+
+    #[inline]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    #[inline]
+    pub fn code(&self) -> u8 {
+        self.code
+    }
+
+    #[inline]
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+
+    #[inline]
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn parse(reader: &mut impl Read) -> Result<Self, String> {
+        let message = read_var_string(reader, 12)?;
+        let code = read_u8(reader)?;
+        let reason = read_var_string(reader, 111)?;
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).map_err(|e| e.to_string())?;
+        Ok(Self {
+            message,
+            code,
+            reason,
+            data,
+        })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = serialize_var_bytes(self.message.as_bytes());
+        result.push(self.code);
+        result.extend(serialize_var_bytes(self.reason.as_bytes()));
+        result.extend(&self.data);
+        result
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum NetworkMessage {
+    Version(VersionMessage),
+    Verack,
+    Ping(u64),
+    Pong(u64),
+    GetHeaders(GetHeadersMessage),
+    Headers(Vec<BlockHeader>),
+    Inv(Vec<Inventory>),
+    GetData(Vec<Inventory>),
+    NotFound(Vec<Inventory>),
+    Tx(Tx),
+    Block(Block),
+    GetAddr,
+    Addr(Vec<(u32, NetAddress)>),
+    SendAddrV2,
+    AddrV2(Vec<AddrV2>),
+    SendHeaders,
+    SendCmpct(bool, u64),
+    WtxidRelay,
+    FeeFilter(u64),
+    FilterLoad(BloomFilter),
+    FilterAdd(Vec<u8>),
+    FilterClear,
+    MerkleBlock(MerkleBlock),
+    GetCFilters(FilterRequest),
+    CFilter(BlockFilter),
+    GetCFHeaders(FilterRequest),
+    CFHeaders(CFHeadersMessage),
+    GetCFCheckpt(u8, [u8; 32]),
+    CFCheckpt(CFCheckptMessage),
+    Reject(RejectMessage),
+    Unknown(RawMessage),
+}
+
+impl NetworkMessage {
+    pub fn command(&self) -> &str {
+        match self {
+            Self::Version(_) => "version",
+            Self::Verack => "verack",
+            Self::Ping(_) => "ping",
+            Self::Pong(_) => "pong",
+            Self::GetHeaders(_) => "getheaders",
+            Self::Headers(_) => "headers",
+            Self::Inv(_) => "inv",
+            Self::GetData(_) => "getdata",
+            Self::NotFound(_) => "notfound",
+            Self::Tx(_) => "tx",
+            Self::Block(_) => "block",
+            Self::GetAddr => "getaddr",
+            Self::Addr(_) => "addr",
+            Self::SendAddrV2 => "sendaddrv2",
+            Self::AddrV2(_) => "addrv2",
+            Self::SendHeaders => "sendheaders",
+            Self::SendCmpct(..) => "sendcmpct",
+            Self::WtxidRelay => "wtxidrelay",
+            Self::FeeFilter(_) => "feefilter",
+            Self::FilterLoad(_) => "filterload",
+            Self::FilterAdd(_) => "filteradd",
+            Self::FilterClear => "filterclear",
+            Self::MerkleBlock(_) => "merkleblock",
+            Self::GetCFilters(_) => "getcfilters",
+            Self::CFilter(_) => "cfilter",
+            Self::GetCFHeaders(_) => "getcfheaders",
+            Self::CFHeaders(_) => "cfheaders",
+            Self::GetCFCheckpt(..) => "getcfcheckpt",
+            Self::CFCheckpt(_) => "cfcheckpt",
+            Self::Reject(_) => "reject",
+            Self::Unknown(raw) => raw.command(),
+        }
+    }
+
+    fn parse_inventory(reader: &mut impl Read) -> Result<Vec<Inventory>, String> {
+        (0..read_count(reader, MAX_INV_SIZE, "inventory entries")?)
+            .map(|_| Inventory::parse(reader))
+            .collect()
+    }
+
+    fn serialize_inventory(inventory: &[Inventory]) -> Vec<u8> {
+        let mut result = encode_varint(inventory.len() as u64);
+        for item in inventory {
+            result.extend(item.serialize());
+        }
+        result
+    }
+
+    fn parse_headers(reader: &mut impl Read) -> Result<Vec<BlockHeader>, String> {
+        (0..read_count(reader, MAX_HEADERS, "headers")?)
+            .map(|_| {
+                let header = BlockHeader::parse(reader)?;
+                if read_varint(reader)? != 0 {
+                    return Err("Header has a non-zero transaction count.".to_string());
+                }
+                Ok(header)
+            })
+            .collect()
+    }
+
+    pub fn from_raw(raw: &RawMessage) -> Result<Self, String> {
+        let reader = &mut Cursor::new(raw.payload());
+        let message = match raw.command() {
+            "version" => Self::Version(VersionMessage::parse(reader)?),
+            "verack" => Self::Verack,
+            "ping" => Self::Ping(read_u64_le(reader)?),
+            "pong" => Self::Pong(read_u64_le(reader)?),
+            "getheaders" => Self::GetHeaders(GetHeadersMessage::parse(reader)?),
+            "headers" => Self::Headers(Self::parse_headers(reader)?),
+            "inv" => Self::Inv(Self::parse_inventory(reader)?),
+            "getdata" => Self::GetData(Self::parse_inventory(reader)?),
+            "notfound" => Self::NotFound(Self::parse_inventory(reader)?),
+            "tx" => Self::Tx(Tx::parse(reader)?),
+            "block" => Self::Block(Block::parse(reader)?),
+            "getaddr" => Self::GetAddr,
+            "addr" => Self::Addr(
+                (0..read_count(reader, MAX_ADDR, "addresses")?)
+                    .map(|_| Ok((read_u32_le(reader)?, NetAddress::parse(reader)?)))
+                    .collect::<Result<Vec<(u32, NetAddress)>, String>>()?,
+            ),
+            "sendaddrv2" => Self::SendAddrV2,
+            "addrv2" => Self::AddrV2(
+                (0..read_count(reader, MAX_ADDR, "addresses")?)
+                    .map(|_| AddrV2::parse(reader))
+                    .collect::<Result<Vec<AddrV2>, String>>()?,
+            ),
+            "sendheaders" => Self::SendHeaders,
+            "sendcmpct" => Self::SendCmpct(read_u8(reader)? != 0, read_u64_le(reader)?),
+            "wtxidrelay" => Self::WtxidRelay,
+            "feefilter" => Self::FeeFilter(read_u64_le(reader)?),
+            "filterload" => Self::FilterLoad(BloomFilter::parse(reader)?),
+            "filteradd" => {
+                let element = read_var_bytes(reader)?;
+                if element.len() > MAX_FILTER_ADD_SIZE {
+                    return Err(format!(
+                        "Filter element of {} bytes is too large.",
+                        element.len()
+                    ));
+                }
+                Self::FilterAdd(element)
+            }
+            "filterclear" => Self::FilterClear,
+            "merkleblock" => Self::MerkleBlock(MerkleBlock::parse(reader)?),
+            "getcfilters" => Self::GetCFilters(FilterRequest::parse(reader)?),
+            "cfilter" => Self::CFilter(parse_cfilter(reader)?),
+            "getcfheaders" => Self::GetCFHeaders(FilterRequest::parse(reader)?),
+            "cfheaders" => Self::CFHeaders(CFHeadersMessage::parse(reader)?),
+            "getcfcheckpt" => Self::GetCFCheckpt(read_u8(reader)?, read_hash(reader)?),
+            "cfcheckpt" => Self::CFCheckpt(CFCheckptMessage::parse(reader)?),
+            "reject" => Self::Reject(RejectMessage::parse(reader)?),
+            _ => return Ok(Self::Unknown(raw.clone())),
+        };
+        if reader.position() as usize != raw.payload().len() {
+            return Err(format!("Trailing data in {} message.", raw.command()));
+        }
+        Ok(message)
+    }
+
+    pub fn payload(&self) -> Vec<u8> {
+        match self {
+            Self::Version(version) => version.serialize(),
+            Self::Verack
+            | Self::GetAddr
+            | Self::SendAddrV2
+            | Self::SendHeaders
+            | Self::WtxidRelay
+            | Self::FilterClear => Vec::new(),
+            Self::Ping(nonce) | Self::Pong(nonce) => nonce.to_le_bytes().to_vec(),
+            Self::GetHeaders(get_headers) => get_headers.serialize(),
+            Self::Headers(headers) => {
+                let mut result = encode_varint(headers.len() as u64);
+                for header in headers {
+                    result.extend(header.serialize());
+                    result.push(0);
+                }
+                result
+            }
+            Self::Inv(inventory) | Self::GetData(inventory) | Self::NotFound(inventory) => {
+                Self::serialize_inventory(inventory)
+            }
+            Self::Tx(tx) => tx.serialize(),
+            Self::Block(block) => block.serialize(),
+            Self::Addr(addresses) => {
+                let mut result = encode_varint(addresses.len() as u64);
+                for (time, address) in addresses {
+                    result.extend(time.to_le_bytes());
+                    result.extend(address.serialize());
+                }
+                result
+            }
+            Self::AddrV2(addresses) => {
+                let mut result = encode_varint(addresses.len() as u64);
+                for address in addresses {
+                    result.extend(address.serialize());
+                }
+                result
+            }
+            Self::SendCmpct(announce, version) => {
+                [&[*announce as u8][..], &version.to_le_bytes()].concat()
+            }
+            Self::FeeFilter(fee_rate) => fee_rate.to_le_bytes().to_vec(),
+            Self::FilterLoad(filter) => filter.serialize(),
+            Self::FilterAdd(element) => serialize_var_bytes(element),
+            Self::MerkleBlock(merkle_block) => merkle_block.serialize(),
+            Self::GetCFilters(request) | Self::GetCFHeaders(request) => request.serialize(),
+            Self::CFilter(filter) => serialize_cfilter(filter),
+            Self::CFHeaders(cfheaders) => cfheaders.serialize(),
+            Self::GetCFCheckpt(filter_type, stop_hash) => {
+                let mut result = vec![*filter_type];
+                result.extend(serialize_hash(stop_hash));
+                result
+            }
+            Self::CFCheckpt(cfcheckpt) => cfcheckpt.serialize(),
+            Self::Reject(reject) => reject.serialize(),
+            Self::Unknown(raw) => raw.payload().to_vec(),
+        }
+    }
+
+    #[inline]
+    pub fn to_raw(&self) -> RawMessage {
+        RawMessage {
+            command: self.command().to_string(),
+            payload: self.payload(),
+        }
+    }
+
+    #[inline]
+    pub fn parse(reader: &mut impl Read, network: Network) -> Result<Self, String> {
+        Self::from_raw(&RawMessage::parse(reader, network)?)
+    }
+
+    #[inline]
+    pub fn serialize(&self, network: Network) -> Vec<u8> {
+        self.to_raw().serialize(network)
+    }
+}
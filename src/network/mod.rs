@@ -0,0 +1,6 @@
+pub mod address;
+pub mod bloom;
+pub mod filter;
+pub mod message;
+pub mod peer;
+pub mod spv;
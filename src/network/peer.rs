@@ -0,0 +1,372 @@
+use super::address::{NetAddress, NODE_BLOOM, NODE_COMPACT_FILTERS, NODE_NETWORK, NODE_WITNESS};
+use super::message::{
+    NetworkMessage, VersionMessage, PROTOCOL_VERSION, SENDHEADERS_VERSION,
+    SHORT_IDS_BLOCKS_VERSION, USER_AGENT, WTXID_RELAY_VERSION,
+};
+use crate::core::network::Network;
+use std::io::{BufReader, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub const MIN_PEER_PROTO_VERSION: u32 = 31800;
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+pub const CMPCT_VERSION: u64 = 2;
+
+#[derive(Debug, Clone)]
+pub struct PeerConfig {
+    network: Network,
+    services: u64,
+    required_services: u64,
+    user_agent: String,
+    start_height: i32,
+    relay: bool,
+    timeout: Duration,
+}
+
+impl PeerConfig {
+    pub fn new(network: Network) -> Self {
+        Self {
+            network,
+            services: 0,
+            required_services: NODE_NETWORK | NODE_WITNESS,
+            user_agent: USER_AGENT.to_string(),
+            start_height: 0,
+            relay: false,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    #[inline]
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    #[inline]
+    pub fn services(&mut self, services: u64) -> &mut Self {
+        self.services = services;
+        self
+    }
+
+    #[inline]
+    pub fn required_services(&mut self, services: u64) -> &mut Self {
+        self.required_services = services;
+        self
+    }
+
+    #[inline]
+    pub fn user_agent(&mut self, user_agent: &str) -> &mut Self {
+        self.user_agent = user_agent.to_string();
+        self
+    }
+
+    #[inline]
+    pub fn start_height(&mut self, start_height: i32) -> &mut Self {
+        self.start_height = start_height;
+        self
+    }
+
+    #[inline]
+    pub fn relay(&mut self, relay: bool) -> &mut Self {
+        self.relay = relay;
+        self
+    }
+
+    #[inline]
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn version_message(&self, receiver: SocketAddr, nonce: u64) -> VersionMessage {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs() as i64);
+        let mut version = VersionMessage::new(timestamp, nonce, self.start_height);
+        version
+            .set_services(self.services)
+            .set_receiver(NetAddress::from_socket_addr(0, receiver))
+            .set_user_agent(&self.user_agent)
+            .set_relay(self.relay);
+        version
+    }
+}
+
+#[derive(Debug)]
+pub struct Peer {
+    network: Network,
+    addr: SocketAddr,
+    stream: TcpStream,
+    reader: BufReader<TcpStream>,
+    timeout: Duration,
+    remote: VersionMessage,
+    version: u32,
+    wtxid_relay: bool,
+    addrv2: bool,
+    send_headers: bool,
+    compact_blocks: Option<bool>,
+    fee_filter: u64,
+    ping: Option<(u64, Instant)>,
+    latency: Option<Duration>,
+    disconnected: Option<String>,
+}
+
+impl Peer {
+    pub fn connect(addr: SocketAddr, config: &PeerConfig) -> Result<Self, String> {
+        let stream = TcpStream::connect_timeout(&addr, config.timeout)
+            .map_err(|e| format!("Failed to connect to {}: {}", addr, e))?;
+        Self::handshake(stream, config)
+    }
+
+    pub fn handshake(stream: TcpStream, config: &PeerConfig) -> Result<Self, String> {
+        let addr = stream.peer_addr().map_err(|e| e.to_string())?;
+        stream
+            .set_read_timeout(Some(config.timeout))
+            .and_then(|_| stream.set_write_timeout(Some(config.timeout)))
+            .and_then(|_| stream.set_nodelay(true))
+            .map_err(|e| e.to_string())?;
+        let reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+        let mut peer = Self {
+            network: config.network,
+            addr,
+            stream,
+            reader,
+            timeout: config.timeout,
+            remote: VersionMessage::new(0, 0, 0),
+            version: 0,
+            wtxid_relay: false,
+            addrv2: false,
+            send_headers: false,
+            compact_blocks: None,
+            fee_filter: 0,
+            ping: None,
+            latency: None,
+            disconnected: None,
+        };
+        peer.negotiate(config)
+            .map_err(|reason| peer.disconnect(&reason))?;
+        Ok(peer)
+    }
+
+    fn negotiate(&mut self, config: &PeerConfig) -> Result<(), String> {
+        let nonce = rand::random::<u64>();
+        let deadline = Instant::now() + config.timeout;
+        self.send_raw(&NetworkMessage::Version(
+            config.version_message(self.addr, nonce),
+        ))?;
+        let mut remote = None;
+        let mut verack = false;
+        while remote.is_none() || !verack {
+            if Instant::now() > deadline {
+                return Err("Handshake timed out.".to_string());
+            }
+            let message = NetworkMessage::parse(&mut self.reader, self.network)?;
+            match (message, &remote) {
+                (NetworkMessage::Version(version), None) => {
+                    if version.nonce() == nonce {
+                        return Err("Connected to self.".to_string());
+                    }
+                    if version.version() < MIN_PEER_PROTO_VERSION {
+                        return Err(format!("Obsolete protocol version {}.", version.version()));
+                    }
+                    if version.services() & config.required_services != config.required_services {
+                        return Err(format!(
+                            "Peer services {:#x} lack required {:#x}.",
+                            version.services(),
+                            config.required_services
+                        ));
+                    }
+                    self.version = version.version().min(PROTOCOL_VERSION);
+                    if self.version >= WTXID_RELAY_VERSION {
+                        self.send_raw(&NetworkMessage::WtxidRelay)?;
+                    }
+                    self.send_raw(&NetworkMessage::SendAddrV2)?;
+                    self.send_raw(&NetworkMessage::Verack)?;
+                    remote = Some(version);
+                }
+                (NetworkMessage::Version(_), Some(_)) => {
+                    return Err("Duplicate version message.".to_string())
+                }
+                (message, None) => {
+                    return Err(format!("Received {} before version.", message.command()))
+                }
+                (NetworkMessage::Verack, Some(_)) => verack = true,
+                (NetworkMessage::WtxidRelay, Some(_)) if !verack => {
+                    self.wtxid_relay = self.version >= WTXID_RELAY_VERSION;
+                }
+                (NetworkMessage::SendAddrV2, Some(_)) if !verack => self.addrv2 = true,
+                (NetworkMessage::WtxidRelay | NetworkMessage::SendAddrV2, Some(_)) => {
+                    return Err("Feature negotiation after verack.".to_string())
+                }
+                _ => {}
+            }
+        }
+        self.remote = remote.unwrap();
+        if self.version >= SENDHEADERS_VERSION {
+            self.send_raw(&NetworkMessage::SendHeaders)?;
+        }
+        if self.version >= SHORT_IDS_BLOCKS_VERSION {
+            self.send_raw(&NetworkMessage::SendCmpct(false, CMPCT_VERSION))?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    #[inline]
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    #[inline]
+    pub fn remote(&self) -> &VersionMessage {
+        &self.remote
+    }
+
+    #[inline]
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    #[inline]
+    pub fn services(&self) -> u64 {
+        self.remote.services()
+    }
+
+    #[inline]
+    pub fn wtxid_relay(&self) -> bool {
+        self.wtxid_relay
+    }
+
+    #[inline]
+    pub fn addrv2(&self) -> bool {
+        self.addrv2
+    }
+
+    #[inline]
+    pub fn send_headers(&self) -> bool {
+        self.send_headers
+    }
+
+    #[inline]
+    pub fn compact_blocks(&self) -> Option<bool> {
+        self.compact_blocks
+    }
+
+    #[inline]
+    pub fn fee_filter(&self) -> u64 {
+        self.fee_filter
+    }
+
+    #[inline]
+    pub fn latency(&self) -> Option<Duration> {
+        self.latency
+    }
+
+    #[inline]
+    pub fn is_connected(&self) -> bool {
+        self.disconnected.is_none()
+    }
+
+    pub fn disconnect(&mut self, reason: &str) -> String {
+        if self.disconnected.is_none() {
+            let _ = self.stream.shutdown(Shutdown::Both);
+            self.disconnected = Some(format!("Disconnected from {}: {}", self.addr, reason));
+        }
+        self.disconnected.clone().unwrap()
+    }
+
+    fn send_raw(&mut self, message: &NetworkMessage) -> Result<(), String> {
+        self.stream
+            .write_all(&message.serialize(self.network))
+            .map_err(|e| format!("Failed to send {}: {}", message.command(), e))
+    }
+
+    pub fn send(&mut self, message: &NetworkMessage) -> Result<(), String> {
+        if let Some(reason) = &self.disconnected {
+            return Err(reason.clone());
+        }
+        if matches!(message, NetworkMessage::AddrV2(_)) && !self.addrv2 {
+            return Err("Peer did not ask for addrv2.".to_string());
+        }
+        let filter = matches!(
+            message,
+            NetworkMessage::FilterLoad(_)
+                | NetworkMessage::FilterAdd(_)
+                | NetworkMessage::FilterClear
+        );
+        if filter && self.services() & NODE_BLOOM == 0 {
+            return Err("Peer does not serve bloom filters.".to_string());
+        }
+        let compact_filters = matches!(
+            message,
+            NetworkMessage::GetCFilters(_)
+                | NetworkMessage::GetCFHeaders(_)
+                | NetworkMessage::GetCFCheckpt(..)
+        );
+        if compact_filters && self.services() & NODE_COMPACT_FILTERS == 0 {
+            return Err("Peer does not serve compact block filters.".to_string());
+        }
+        self.send_raw(message).map_err(|e| self.disconnect(&e))
+    }
+
+    pub fn ping(&mut self) -> Result<(), String> {
+        if self.ping.is_some() {
+            return Ok(());
+        }
+        let nonce = rand::random::<u64>();
+        self.send(&NetworkMessage::Ping(nonce))?;
+        self.ping = Some((nonce, Instant::now()));
+        Ok(())
+    }
+
+    pub fn receive(&mut self) -> Result<NetworkMessage, String> {
+        loop {
+            if let Some(reason) = &self.disconnected {
+                return Err(reason.clone());
+            }
+            if self
+                .ping
+                .is_some_and(|(_, sent)| sent.elapsed() > self.timeout)
+            {
+                return Err(self.disconnect("Ping timed out."));
+            }
+            let message = NetworkMessage::parse(&mut self.reader, self.network)
+                .map_err(|e| self.disconnect(&e))?;
+            match message {
+                NetworkMessage::Ping(nonce) => self.send(&NetworkMessage::Pong(nonce))?,
+                NetworkMessage::Pong(nonce) => {
+                    if let Some((expected, sent)) = self.ping {
+                        if nonce == expected {
+                            self.latency = Some(sent.elapsed());
+                            self.ping = None;
+                        }
+                    }
+                }
+                NetworkMessage::Version(_) => {
+                    return Err(self.disconnect("Duplicate version message."))
+                }
+                NetworkMessage::WtxidRelay | NetworkMessage::SendAddrV2 => {
+                    return Err(self.disconnect("Feature negotiation after verack."))
+                }
+                NetworkMessage::Verack => {}
+                NetworkMessage::SendHeaders => self.send_headers = true,
+                NetworkMessage::SendCmpct(announce, version) => {
+                    if version == CMPCT_VERSION {
+                        self.compact_blocks = Some(announce);
+                    }
+                }
+                NetworkMessage::FeeFilter(fee_rate) => self.fee_filter = fee_rate,
+                message => return Ok(message),
+            }
+        }
+    }
+}
+
+impl Drop for Peer {
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
@@ -0,0 +1,151 @@
+use super::message::{GetHeadersMessage, NetworkMessage, INV_BLOCK, INV_WITNESS_FLAG, MAX_HEADERS};
+use super::peer::Peer;
+use crate::core::block::{BlockHeader, BLOCK_HEADER_SIZE};
+use crate::core::chain::{ChainUpdate, HeaderChain};
+use crate::core::network::Network;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[inline]
+fn now() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as u32)
+}
+
+#[derive(Debug)]
+pub struct SpvClient {
+    chain: HeaderChain,
+    store: File,
+}
+
+impl SpvClient {
+    pub fn open(network: Network, path: &Path) -> Result<Self, String> {
+        let mut store = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        let mut bytes = Vec::new();
+        store
+            .read_to_end(&mut bytes)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let complete = bytes.len() - bytes.len() % BLOCK_HEADER_SIZE;
+        if complete != bytes.len() {
+            store
+                .set_len(complete as u64)
+                .map_err(|e| format!("Failed to truncate {}: {}", path.display(), e))?;
+        }
+        let mut chain = HeaderChain::new(network);
+        for chunk in bytes[..complete].chunks_exact(BLOCK_HEADER_SIZE) {
+            chain
+                .accept_header(BlockHeader::from_bytes(chunk)?, u32::MAX)
+                .map_err(|e| format!("Corrupt header file {}: {}", path.display(), e))?;
+        }
+        Ok(Self { chain, store })
+    }
+
+    #[inline]
+    pub fn chain(&self) -> &HeaderChain {
+        &self.chain
+    }
+
+    pub fn process_headers(
+        &mut self,
+        headers: &[BlockHeader],
+        now: u32,
+    ) -> Result<Vec<ChainUpdate>, String> {
+        if headers
+            .windows(2)
+            .any(|pair| pair[1].prev_block() != pair[0].hash())
+        {
+            return Err("Non-continuous headers sequence.".to_string());
+        }
+        let mut updates = Vec::new();
+        let result = headers.iter().try_for_each(|header| {
+            let update = self.chain.accept_header(*header, now)?;
+            if update != ChainUpdate::AlreadyKnown {
+                self.store
+                    .write_all(&header.serialize())
+                    .map_err(|e| format!("Failed to store header {}: {}", header.id(), e))?;
+                updates.push(update);
+            }
+            Ok::<(), String>(())
+        });
+        self.store.sync_data().map_err(|e| e.to_string())?;
+        result?;
+        Ok(updates)
+    }
+
+    pub fn request_headers(&self, peer: &mut Peer) -> Result<(), String> {
+        peer.send(&NetworkMessage::GetHeaders(GetHeadersMessage::new(
+            self.chain.locator(),
+            [0; 32],
+        )))
+    }
+
+    fn accept_from(
+        &mut self,
+        peer: &mut Peer,
+        headers: &[BlockHeader],
+    ) -> Result<(Vec<ChainUpdate>, bool), String> {
+        let updates = self
+            .process_headers(headers, now())
+            .map_err(|e| peer.disconnect(&e))?;
+        let more = headers.len() as u64 == MAX_HEADERS && !updates.is_empty();
+        Ok((updates, more))
+    }
+
+    pub fn sync(&mut self, peer: &mut Peer) -> Result<Vec<ChainUpdate>, String> {
+        let mut updates = Vec::new();
+        loop {
+            self.request_headers(peer)?;
+            let headers = loop {
+                if let NetworkMessage::Headers(headers) = peer.receive()? {
+                    break headers;
+                }
+            };
+            let (batch, more) = self.accept_from(peer, &headers)?;
+            updates.extend(batch);
+            if !more {
+                return Ok(updates);
+            }
+        }
+    }
+
+    pub fn handle_message(
+        &mut self,
+        peer: &mut Peer,
+        message: &NetworkMessage,
+    ) -> Result<Vec<ChainUpdate>, String> {
+        match message {
+            NetworkMessage::Headers(headers) => {
+                if headers
+                    .first()
+                    .is_some_and(|header| !self.chain.contains(&header.prev_block()))
+                {
+                    self.request_headers(peer)?;
+                    return Ok(Vec::new());
+                }
+                let (updates, more) = self.accept_from(peer, headers)?;
+                if more {
+                    self.request_headers(peer)?;
+                }
+                Ok(updates)
+            }
+            NetworkMessage::Inv(inventory) => {
+                if inventory.iter().any(|item| {
+                    item.kind() & !INV_WITNESS_FLAG == INV_BLOCK
+                        && !self.chain.contains(&item.hash())
+                }) {
+                    self.request_headers(peer)?;
+                }
+                Ok(Vec::new())
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
+}
@@ -38,6 +38,38 @@ where
         data.extend_from_slice(&checksum[..4]);
         Self::encode_base58(&data)
     }
+
+    fn decode_base58(s: &str) -> Result<Vec<u8>, String> {
+        let num_58 = BUint::<N>::from_digit(58);
+        let zeros = s.chars().take_while(|&c| c == '1').count();
+
+        let mut num = BUint::<N>::ZERO;
+        for c in s.chars() {
+            let digit = Self::BASE58_ALPHABET
+                .find(c)
+                .ok_or(format!("Invalid base58 character: {}", c))?;
+            num = num
+                .checked_mul(num_58)
+                .and_then(|n| n.checked_add(BUint::<N>::from_digit(digit as u64)))
+                .ok_or("Base58 string is too long to decode.".to_string())?;
+        }
+
+        let mut result = vec![0u8; zeros];
+        result.extend(num.to_be_bytes().iter().skip_while(|&&b| b == 0));
+        Ok(result)
+    }
+
+    fn decode_base58_with_checksum(s: &str) -> Result<Vec<u8>, String> {
+        let data = Self::decode_base58(s)?;
+        if data.len() < 4 {
+            return Err("Base58 string is too short to contain a checksum.".to_string());
+        }
+        let (payload, checksum) = data.split_at(data.len() - 4);
+        if C::compute(payload)[..4] != *checksum {
+            return Err("Invalid base58 checksum.".to_string());
+        }
+        Ok(payload.to_vec())
+    }
 }
 
 pub struct Base58ChainedHasher<C: ChainedCompute<R, H, F>, const R: usize, H, F, const N: usize>
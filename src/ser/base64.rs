@@ -0,0 +1,49 @@
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const PADDING: u8 = b'=';
+
+pub fn encode(data: &[u8]) -> String {
+    let mut result = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let group = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |acc, (i, byte)| acc | (*byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                result.push(ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                result.push(PADDING as char);
+            }
+        }
+    }
+    result
+}
+
+pub fn decode(s: &str) -> Result<Vec<u8>, String> {
+    let bytes = s.as_bytes();
+    if !bytes.len().is_multiple_of(4) {
+        return Err("Base64 string length must be a multiple of 4.".to_string());
+    }
+    let mut result = Vec::with_capacity(bytes.len() / 4 * 3);
+    for (index, chunk) in bytes.chunks(4).enumerate() {
+        let last = index == bytes.len() / 4 - 1;
+        let padding = chunk.iter().rev().take_while(|c| **c == PADDING).count();
+        if padding > 2 || (padding > 0 && !last) {
+            return Err("Invalid base64 padding.".to_string());
+        }
+        let mut group = 0u32;
+        for (i, c) in chunk[..4 - padding].iter().enumerate() {
+            let value = ALPHABET
+                .iter()
+                .position(|a| a == c)
+                .ok_or(format!("Invalid base64 character: {:?}", *c as char))?;
+            group |= (value as u32) << (18 - 6 * i);
+        }
+        let len = 3 - padding;
+        if group & ((1 << (8 * padding)) - 1) != 0 {
+            return Err("Non-canonical base64 encoding.".to_string());
+        }
+        result.extend_from_slice(&group.to_be_bytes()[1..1 + len]);
+    }
+    Ok(result)
+}
@@ -0,0 +1,278 @@
+use std::fmt;
+
+const CHARSET: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+const BECH32_CONST: u32 = 1;
+const BECH32M_CONST: u32 = 0x2bc830a3;
+const CHECKSUM_LEN: usize = 6;
+const MAX_LEN: usize = 90;
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Variant {
+    Bech32,
+    Bech32m,
+}
+
+impl Variant {
+    #[inline]
+    fn constant(&self) -> u32 {
+        match self {
+            Self::Bech32 => BECH32_CONST,
+            Self::Bech32m => BECH32M_CONST,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Bech32Error {
+    InvalidLength(usize),
+    MixedCase,
+    MissingSeparator,
+    InvalidHrp,
+    InvalidCharacter(usize),
+    InvalidChecksum(Vec<usize>),
+    InvalidPadding,
+    InvalidWitnessVersion(u8),
+    InvalidProgramLength(usize),
+    WrongVariant,
+    HrpMismatch(String),
+}
+
+impl fmt::Display for Bech32Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidLength(len) => write!(f, "Invalid bech32 string length: {}", len),
+            Self::MixedCase => write!(f, "Bech32 string mixes upper and lower case."),
+            Self::MissingSeparator => write!(f, "Bech32 string has no separator."),
+            Self::InvalidHrp => write!(f, "Invalid bech32 human-readable part."),
+            Self::InvalidCharacter(position) => {
+                write!(f, "Invalid bech32 character at position {}.", position)
+            }
+            Self::InvalidChecksum(positions) if positions.is_empty() => {
+                write!(f, "Invalid bech32 checksum.")
+            }
+            Self::InvalidChecksum(positions) => {
+                write!(
+                    f,
+                    "Invalid bech32 checksum, error at positions {:?}.",
+                    positions
+                )
+            }
+            Self::InvalidPadding => write!(f, "Invalid padding in bech32 data."),
+            Self::InvalidWitnessVersion(version) => {
+                write!(f, "Invalid witness version: {}", version)
+            }
+            Self::InvalidProgramLength(len) => {
+                write!(f, "Invalid witness program length: {}", len)
+            }
+            Self::WrongVariant => write!(f, "Wrong bech32 variant for witness version."),
+            Self::HrpMismatch(hrp) => write!(f, "Unexpected human-readable part: {}", hrp),
+        }
+    }
+}
+
+fn polymod(values: &[u8]) -> u32 {
+    let mut chk = 1u32;
+    for &value in values {
+        let top = chk >> 25;
+        chk = ((chk & 0x1ffffff) << 5) ^ value as u32;
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                chk ^= generator;
+            }
+        }
+    }
+    chk
+}
+
+fn hrp_expand(hrp: &str) -> Vec<u8> {
+    let mut result = hrp.bytes().map(|b| b >> 5).collect::<Vec<u8>>();
+    result.push(0);
+    result.extend(hrp.bytes().map(|b| b & 31));
+    result
+}
+
+fn create_checksum(hrp: &str, data: &[u8], variant: Variant) -> Vec<u8> {
+    let mut values = hrp_expand(hrp);
+    values.extend_from_slice(data);
+    values.extend([0u8; CHECKSUM_LEN]);
+    let polymod = polymod(&values) ^ variant.constant();
+    (0..CHECKSUM_LEN)
+        .map(|i| ((polymod >> (5 * (5 - i))) & 31) as u8)
+        .collect()
+}
+
+fn verify_checksum(hrp: &str, data: &[u8]) -> Option<Variant> {
+    let mut values = hrp_expand(hrp);
+    values.extend_from_slice(data);
+    match polymod(&values) {
+        BECH32_CONST => Some(Variant::Bech32),
+        BECH32M_CONST => Some(Variant::Bech32m),
+        _ => None,
+    }
+}
+
+fn locate_errors(hrp: &str, data: &[u8], separator: usize) -> Vec<usize> {
+    let mut positions = Vec::new();
+    let mut candidate = data.to_vec();
+    for i in 0..data.len() {
+        for value in 0..32u8 {
+            if value == data[i] {
+                continue;
+            }
+            candidate[i] = value;
+            if verify_checksum(hrp, &candidate).is_some() {
+                positions.push(separator + 1 + i);
+                break;
+            }
+        }
+        candidate[i] = data[i];
+    }
+    if positions.len() == 1 {
+        positions
+    } else {
+        Vec::new()
+    }
+}
+
+pub fn encode(hrp: &str, data: &[u8], variant: Variant) -> Result<String, Bech32Error> {
+    if hrp.is_empty() || hrp.bytes().any(|b| !(33..=126).contains(&b)) {
+        return Err(Bech32Error::InvalidHrp);
+    }
+    if hrp.bytes().any(|b| b.is_ascii_uppercase()) {
+        return Err(Bech32Error::MixedCase);
+    }
+    if data.iter().any(|&d| d > 31) {
+        return Err(Bech32Error::InvalidPadding);
+    }
+    let len = hrp.len() + 1 + data.len() + CHECKSUM_LEN;
+    if len > MAX_LEN {
+        return Err(Bech32Error::InvalidLength(len));
+    }
+    let checksum = create_checksum(hrp, data, variant);
+    let mut result = format!("{}1", hrp);
+    result.extend(
+        data.iter()
+            .chain(checksum.iter())
+            .map(|&d| CHARSET.as_bytes()[d as usize] as char),
+    );
+    Ok(result)
+}
+
+pub fn decode(s: &str) -> Result<(String, Vec<u8>, Variant), Bech32Error> {
+    if s.len() > MAX_LEN {
+        return Err(Bech32Error::InvalidLength(s.len()));
+    }
+    if let Some(position) = s.bytes().position(|b| !(33..=126).contains(&b)) {
+        return Err(Bech32Error::InvalidCharacter(position));
+    }
+    if s.bytes().any(|b| b.is_ascii_lowercase()) && s.bytes().any(|b| b.is_ascii_uppercase()) {
+        return Err(Bech32Error::MixedCase);
+    }
+    let s = s.to_lowercase();
+    let separator = s.rfind('1').ok_or(Bech32Error::MissingSeparator)?;
+    if separator == 0 {
+        return Err(Bech32Error::InvalidHrp);
+    }
+    if separator + 1 + CHECKSUM_LEN > s.len() {
+        return Err(Bech32Error::InvalidLength(s.len()));
+    }
+    let hrp = &s[..separator];
+    let data = s[separator + 1..]
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            CHARSET
+                .find(c)
+                .map(|d| d as u8)
+                .ok_or(Bech32Error::InvalidCharacter(separator + 1 + i))
+        })
+        .collect::<Result<Vec<u8>, Bech32Error>>()?;
+    let variant = verify_checksum(hrp, &data)
+        .ok_or_else(|| Bech32Error::InvalidChecksum(locate_errors(hrp, &data, separator)))?;
+    Ok((
+        hrp.to_string(),
+        data[..data.len() - CHECKSUM_LEN].to_vec(),
+        variant,
+    ))
+}
+
+pub fn convert_bits(data: &[u8], from: u32, to: u32, pad: bool) -> Result<Vec<u8>, Bech32Error> {
+    let mut acc = 0u32;
+    let mut bits = 0u32;
+    let max_value = (1u32 << to) - 1;
+    let max_acc = (1u32 << (from + to - 1)) - 1;
+    let mut result = Vec::new();
+    for &value in data {
+        if (value as u32) >> from != 0 {
+            return Err(Bech32Error::InvalidPadding);
+        }
+        acc = ((acc << from) | value as u32) & max_acc;
+        bits += from;
+        while bits >= to {
+            bits -= to;
+            result.push(((acc >> bits) & max_value) as u8);
+        }
+    }
+    if pad {
+        if bits > 0 {
+            result.push(((acc << (to - bits)) & max_value) as u8);
+        }
+    } else if bits >= from || (acc << (to - bits)) & max_value != 0 {
+        return Err(Bech32Error::InvalidPadding);
+    }
+    Ok(result)
+}
+
+pub fn encode_segwit_address(
+    hrp: &str,
+    version: u8,
+    program: &[u8],
+) -> Result<String, Bech32Error> {
+    if version > 16 {
+        return Err(Bech32Error::InvalidWitnessVersion(version));
+    }
+    if program.len() < 2
+        || program.len() > 40
+        || (version == 0 && program.len() != 20 && program.len() != 32)
+    {
+        return Err(Bech32Error::InvalidProgramLength(program.len()));
+    }
+    let variant = if version == 0 {
+        Variant::Bech32
+    } else {
+        Variant::Bech32m
+    };
+    let mut data = vec![version];
+    data.extend(convert_bits(program, 8, 5, true)?);
+    encode(hrp, &data, variant)
+}
+
+pub fn decode_segwit_address(hrp: &str, address: &str) -> Result<(u8, Vec<u8>), Bech32Error> {
+    let (decoded_hrp, data, variant) = decode(address)?;
+    if decoded_hrp != hrp {
+        return Err(Bech32Error::HrpMismatch(decoded_hrp));
+    }
+    let (&version, data) = data
+        .split_first()
+        .ok_or(Bech32Error::InvalidProgramLength(0))?;
+    if version > 16 {
+        return Err(Bech32Error::InvalidWitnessVersion(version));
+    }
+    let program = convert_bits(data, 5, 8, false)?;
+    if program.len() < 2
+        || program.len() > 40
+        || (version == 0 && program.len() != 20 && program.len() != 32)
+    {
+        return Err(Bech32Error::InvalidProgramLength(program.len()));
+    }
+    let expected = if version == 0 {
+        Variant::Bech32
+    } else {
+        Variant::Bech32m
+    };
+    if variant != expected {
+        return Err(Bech32Error::WrongVariant);
+    }
+    Ok((version, program))
+}
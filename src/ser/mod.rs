@@ -1,4 +1,9 @@
 #[macro_use]
 pub mod base58;
+pub mod base64;
+pub mod bech32;
 #[macro_use]
 pub mod chained_hash;
+pub mod hex;
+pub mod stream;
+pub mod varint;
@@ -0,0 +1,52 @@
+use std::io::Read;
+
+#[inline]
+pub fn read_array<const N: usize>(reader: &mut impl Read) -> Result<[u8; N], String> {
+    let mut buf = [0u8; N];
+    reader
+        .read_exact(&mut buf)
+        .map_err(|e| format!("Unexpected end of data: {}", e))?;
+    Ok(buf)
+}
+
+#[inline]
+pub fn read_vec(reader: &mut impl Read, len: usize) -> Result<Vec<u8>, String> {
+    let mut buf = Vec::new();
+    reader
+        .take(len as u64)
+        .read_to_end(&mut buf)
+        .map_err(|e| e.to_string())?;
+    if buf.len() != len {
+        return Err(format!(
+            "Unexpected end of data: expected {} bytes, got {}.",
+            len,
+            buf.len()
+        ));
+    }
+    Ok(buf)
+}
+
+#[inline]
+pub fn read_u8(reader: &mut impl Read) -> Result<u8, String> {
+    Ok(read_array::<1>(reader)?[0])
+}
+
+#[inline]
+pub fn read_u16_le(reader: &mut impl Read) -> Result<u16, String> {
+    Ok(u16::from_le_bytes(read_array(reader)?))
+}
+
+#[inline]
+pub fn read_u32_le(reader: &mut impl Read) -> Result<u32, String> {
+    Ok(u32::from_le_bytes(read_array(reader)?))
+}
+
+#[inline]
+pub fn read_u64_le(reader: &mut impl Read) -> Result<u64, String> {
+    Ok(u64::from_le_bytes(read_array(reader)?))
+}
+
+#[inline]
+pub fn read_u16_be(reader: &mut impl Read) -> Result<u16, String> {
+    Ok(u16::from_be_bytes(read_array(reader)?))
+}
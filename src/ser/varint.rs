@@ -0,0 +1,24 @@
+use super::stream::{read_u16_le, read_u32_le, read_u64_le, read_u8};
+use std::io::Read;
+
+pub fn encode_varint(n: u64) -> Vec<u8> {
+    match n {
+        0..=0xfc => vec![n as u8],
+        0xfd..=0xffff => [&[0xfd][..], &(n as u16).to_le_bytes()].concat(),
+        0x10000..=0xffff_ffff => [&[0xfe][..], &(n as u32).to_le_bytes()].concat(),
+        _ => [&[0xff][..], &n.to_le_bytes()].concat(),
+    }
+}
+
+pub fn read_varint(reader: &mut impl Read) -> Result<u64, String> {
+    let (n, min) = match read_u8(reader)? {
+        0xfd => (read_u16_le(reader)? as u64, 0xfd),
+        0xfe => (read_u32_le(reader)? as u64, 0x10000),
+        0xff => (read_u64_le(reader)?, 0x1_0000_0000),
+        n => return Ok(n as u64),
+    };
+    if n < min {
+        return Err(format!("Non-canonical varint encoding of {}.", n));
+    }
+    Ok(n)
+}
@@ -0,0 +1,147 @@
+use crate::core::account::{Account, AccountPreset, ScriptType};
+use crate::core::hd::{ExtendedPrivateKey, XPRV, ZPUB};
+use crate::core::network::Network;
+use crate::ser::hex;
+
+fn master() -> ExtendedPrivateKey {
+    let seed = hex::decode(
+        "5eb00bbddcf069084889a8ab9155568165f5c453ccb85e70811aaed6f6da5fc19a5ac40b389cd370d086206dec8aa6c43daea6690f20ad3d8d48b2d2ce9e38e4",
+    )
+    .unwrap();
+    ExtendedPrivateKey::from_seed(&seed, XPRV).unwrap()
+}
+
+#[test]
+fn test_account_xpubs() {
+    let master = master();
+    let vectors = [
+        (
+            AccountPreset::Bip44,
+            Network::Mainnet,
+            "xpub6BosfCnifzxcFwrSzQiqu2DBVTshkCXacvNsWGYJVVhhawA7d4R5WSWGFNbi8Aw6ZRc1brxMyWMzG3DSSSSoekkudhUd9yLb6qx39T9nMdj",
+        ),
+        (
+            AccountPreset::Bip49,
+            Network::Mainnet,
+            "ypub6Ww3ibxVfGzLrAH1PNcjyAWenMTbbAosGNB6VvmSEgytSER9azLDWCxoJwW7Ke7icmizBMXrzBx9979FfaHxHcrArf3zbeJJJUZPf663zsP",
+        ),
+        (
+            AccountPreset::Bip84,
+            Network::Mainnet,
+            "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs",
+        ),
+        (
+            AccountPreset::Bip86,
+            Network::Mainnet,
+            "xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ",
+        ),
+        (
+            AccountPreset::Bip49,
+            Network::Testnet,
+            "upub5EFU65HtV5TeiSHmZZm7FUffBGy8UKeqp7vw43jYbvZPpoVsgU93oac7Wk3u6moKegAEWtGNF8DehrnHtv21XXEMYRUocHqguyjknFHYfgY",
+        ),
+        (
+            AccountPreset::Bip84,
+            Network::Testnet,
+            "vpub5Y6cjg78GGuNLsaPhmYsiw4gYX3HoQiRBiSwDaBXKUafCt9bNwWQiitDk5VZ5BVxYnQdwoTyXSs2JHRPAgjAvtbBrf8ZhDYe2jWAqvZVnsc",
+        ),
+    ];
+    for (preset, network, expected) in vectors {
+        let account = Account::from_master(&master, preset, 0, network).unwrap();
+        assert_eq!(account.to_string(), expected);
+    }
+}
+
+#[test]
+fn test_account_xprv() {
+    let xprv = AccountPreset::Bip84
+        .account_xprv(&master(), 0, Network::Mainnet)
+        .unwrap();
+    let expected = "zprvAdG4iTXWBoARxkkzNpNh8r6Qag3irQB8PzEMkAFeTRXxHpbF9z4QgEvBRmfvqWvGp42t42nvgGpNgYSJA9iefm1yYNZKEm7z6qUWCroSQnE";
+    assert_eq!(xprv.to_string(), expected);
+
+    let path = AccountPreset::Bip84
+        .path(0x7fff_ffff, Network::Mainnet)
+        .unwrap();
+    assert_eq!(path.to_string(), "m/84'/0'/2147483647'");
+    assert!(AccountPreset::Bip84
+        .path(0x8000_0000, Network::Mainnet)
+        .is_err());
+    assert!(AccountPreset::Bip84
+        .account_xprv(&master(), u32::MAX, Network::Mainnet)
+        .is_err());
+}
+
+#[test]
+fn test_parse_account() {
+    let zpub = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs";
+    let account = zpub.parse::<Account>().unwrap();
+    assert_eq!(account.preset(), AccountPreset::Bip84);
+    assert_eq!(account.script_type(), ScriptType::P2wpkh);
+    assert_eq!(account.network(), Network::Mainnet);
+    assert_eq!(
+        hex::encode(&account.script_pubkey(false, 0).unwrap()),
+        "0014c0cebcd6c3d3ca8c75dc5ec62ebe55330ef910e2"
+    );
+    assert_eq!(account.to_string(), zpub);
+
+    let upub = "upub5EFU65HtV5TeiSHmZZm7FUffBGy8UKeqp7vw43jYbvZPpoVsgU93oac7Wk3u6moKegAEWtGNF8DehrnHtv21XXEMYRUocHqguyjknFHYfgY";
+    let account = upub.parse::<Account>().unwrap();
+    assert_eq!(account.preset(), AccountPreset::Bip49);
+    assert_eq!(account.network(), Network::Testnet);
+    let account = Account::parse(upub, Network::Regtest).unwrap();
+    assert_eq!(account.preset(), AccountPreset::Bip49);
+    assert_eq!(account.network(), Network::Regtest);
+    assert_eq!(account.to_string(), upub);
+    assert!(Account::parse(upub, Network::Mainnet).is_err());
+    assert_eq!(
+        AccountPreset::from_public_version(ZPUB, Network::Mainnet),
+        Some(AccountPreset::Bip84)
+    );
+    assert_eq!(
+        AccountPreset::from_public_version(ZPUB, Network::Signet),
+        None
+    );
+}
+
+#[test]
+fn test_account_addresses() {
+    let master = master();
+    let account = Account::from_master(&master, AccountPreset::Bip44, 0, Network::Mainnet).unwrap();
+    assert_eq!(
+        account.address(false, 0).unwrap(),
+        "1LqBGSKuX5yYUonjxT5qGfpUsXKYYWeabA"
+    );
+    assert_eq!(
+        account.address(false, 1).unwrap(),
+        "1Ak8PffB2meyfYnbXZR9EGfLfFZVpzJvQP"
+    );
+    let account = Account::from_master(&master, AccountPreset::Bip49, 0, Network::Mainnet).unwrap();
+    assert_eq!(
+        account.address(false, 0).unwrap(),
+        "37VucYSaXLCAsxYyAPfbSi9eh4iEcbShgf"
+    );
+    let account = Account::from_master(&master, AccountPreset::Bip49, 0, Network::Testnet).unwrap();
+    assert_eq!(
+        account.address(false, 0).unwrap(),
+        "2Mww8dCYPUpKHofjgcXcBCEGmniw9CoaiD2"
+    );
+    let account = Account::from_master(&master, AccountPreset::Bip84, 0, Network::Mainnet).unwrap();
+    assert_eq!(
+        account.address(false, 0).unwrap(),
+        "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+    );
+    assert_eq!(
+        account.address(true, 0).unwrap(),
+        "bc1q8c6fshw2dlwun7ekn9qwf37cu2rn755upcp6el"
+    );
+    let account = Account::from_master(&master, AccountPreset::Bip86, 0, Network::Mainnet).unwrap();
+    assert_eq!(
+        hex::encode(&account.script_pubkey(false, 0).unwrap()),
+        "5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c"
+    );
+    assert_eq!(
+        account.address(false, 0).unwrap(),
+        "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr"
+    );
+}
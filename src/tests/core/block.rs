@@ -0,0 +1,244 @@
+use crate::core::block::{
+    bits_to_target, merkle_root, target_to_bits, Block, BlockHeader, MAX_TARGET_BITS,
+};
+use crate::core::tx::Tx;
+use crate::ser::hex;
+use bnum::types::U256;
+
+const GENESIS: &str = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c";
+
+const BLOCK_1: &str = "010000006fe28c0ab6f1b372c1a6a246ae63f74f931e8365e15a089c68d6190000000000982051fd1e4ba744bbbe680e1fee14677ba1a3c3540bf7b1cdb606e857233e0e61bc6649ffff001d01e36299";
+
+const BLOCK_471744: &str = "020000208ec39428b17323fa0ddec8e887b4a7c53b8c0a0a220cfd0000000000000000005b0750fce0a889502d40508d39576821155e9c9e3f5c3157f961db38fd8b25be1e77a759e93c0118a4ffd71d";
+
+const GENESIS_COINBASE: &str = "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000";
+
+// Testnet block 926485, a coinbase committing to the wtxid of a P2SH-P2WSH spend
+const SEGWIT_BLOCK: &str = "0000002060bbab0edbf3ef8a49608ee326f8fd75c473b7e3982095e2d100000000000000c30134f8c9b6d2470488d7a67a888f6fa12f8692e0c3411fbfb92f0f68f67eedae03ca57ef13021acc22dc4105010000000001010000000000000000000000000000000000000000000000000000000000000000ffffffff2f0315230e0004ae03ca57043e3d1e1d0c8796bf579aef0c0000000000122f4e696e6a61506f6f6c2f5345475749542fffffffff038427a112000000001976a914876fbb82ec05caa6af7a3b5e5a983aae6c6cc6d688ac0000000000000000266a24aa21a9ed5c748e121c0fe146d973a4ac26fa4a68b0549d46ee22d25f50a5e46fe1b377ee00000000000000002952534b424c4f434b3acd16772ad61a3c5f00287480b720f6035d5e54c9efc71be94bb5e3727f10909001200000000000000000000000000000000000000000000000000000000000000000000000000100000000010145310e878941a1b2bc2d33797ee4d89d95eaaf2e13488063a2aa9a74490f510a0100000023220020b6744de4f6ec63cc92f7c220cdefeeb1b1bed2b66c8e5706d80ec247d37e65a1ffffffff01002d3101000000001976a9143ebc40e411ed3c76f86711507ab952300890397288ac0400473044022001dd489a5d4e2fbd8a3ade27177f6b49296ba7695c40dbbe650ea83f106415fd02200b23a0602d8ff1bdf79dee118205fc7e9b40672bf31563e5741feb53fb86388501483045022100f88f040e90cc5dc6c6189d04718376ac19ed996bf9e4a3c29c3718d90ffd27180220761711f16c9e3a44f71aab55cbc0634907a1fa8bb635d971a9a01d368727bea10169522103b3623117e988b76aaabe3d63f56a4fc88b228a71e64c4cc551d1204822fe85cb2103dd823066e096f72ed617a41d3ca56717db335b1ea47a1b4c5c9dbdd0963acba621033d7c89bd9da29fa8d44db7906a9778b53121f72191184a9fee785c39180e4be153ae00000000010000000120925534261de4dcebb1ed5ab1b62bfe7a3ef968fb111dc2c910adfebc6e3bdf010000006b483045022100f50198f5ae66211a4f485190abe4dc7accdabe3bc214ebc9ea7069b97097d46e0220316a70a03014887086e335fc1b48358d46cd6bdc9af3b57c109c94af76fc915101210316cff587a01a2736d5e12e53551b18d73780b83c3bfb4fcf209c869b11b6415effffffff0220a10700000000001976a91450333046115eaa0ac9e0216565f945070e44573988ac2e7cd01a000000001976a914c01a7ca16b47be50cbdbc60724f701d52d75156688ac00000000010000000203a25f58630d7a1ea52550365fd2156683f56daf6ca73a4b4bbd097e66516322010000006a47304402204efc3d70e4ca3049c2a425025edf22d5ca355f9ec899dbfbbeeb2268533a0f2b02204780d3739653035af4814ea52e1396d021953f948c29754edd0ee537364603dc012103f7a897e4dbecab2264b21917f90664ea8256189ea725d28740cf7ba5d85b5763ffffffff03a25f58630d7a1ea52550365fd2156683f56daf6ca73a4b4bbd097e66516322000000006a47304402202d96defdc5b4af71d6ba28c9a6042c2d5ee7bc6de565d4db84ef517445626e03022022da80320e9e489c8f41b74833dfb6a54a4eb5087cdb46eb663eef0b25caa526012103f7a897e4dbecab2264b21917f90664ea8256189ea725d28740cf7ba5d85b5763ffffffff0200e1f5050000000017a914b7e6f7ff8658b2d1fb107e3d7be7af4742e6b1b3876f88fc00000000001976a914913bcc2be49cb534c20474c4dee1e9c4c317e7eb88ac0000000001000000043ffd60d3818431c495b89be84afac205d5d1ed663009291c560758bbd0a66df5010000006b483045022100f344607de9df42049688dcae8ff1db34c0c7cd25ec05516e30d2bc8f12ac9b2f022060b648f6a21745ea6d9782e17bcc4277b5808326488a1f40d41e125879723d3a012103f7a897e4dbecab2264b21917f90664ea8256189ea725d28740cf7ba5d85b5763ffffffffa5379401cce30f84731ef1ba65ce27edf2cc7ce57704507ebe8714aa16a96b92010000006a473044022020c37a63bf4d7f564c2192528709b6a38ab8271bd96898c6c2e335e5208661580220435c6f1ad4d9305d2c0a818b2feb5e45d443f2f162c0f61953a14d097fd07064012103f7a897e4dbecab2264b21917f90664ea8256189ea725d28740cf7ba5d85b5763ffffffff70e731e193235ff12c3184510895731a099112ffca4b00246c60003c40f843ce000000006a473044022053760f74c29a879e30a17b5f03a5bb057a5751a39f86fa6ecdedc36a1b7db04c022041d41c9b95f00d2d10a0373322a9025dba66c942196bc9d8adeb0e12d3024728012103f7a897e4dbecab2264b21917f90664ea8256189ea725d28740cf7ba5d85b5763ffffffff66b7a71b3e50379c8e85fc18fe3f1a408fc985f257036c34702ba205cef09f6f000000006a4730440220499bf9e2db3db6e930228d0661395f65431acae466634d098612fd80b08459ee022040e069fc9e3c60009f521cef54c38aadbd1251aee37940e6018aadb10f194d6a012103f7a897e4dbecab2264b21917f90664ea8256189ea725d28740cf7ba5d85b5763ffffffff0200e1f5050000000017a9148fc37ad460fdfbd2b44fe446f6e3071a4f64faa6878f447f0b000000001976a914913bcc2be49cb534c20474c4dee1e9c4c317e7eb88ac00000000";
+
+// BIP143 P2WPKH example
+const SEGWIT_TX: &str = "01000000000102fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f00000000494830450221008b9d1dc26ba6a9cb62127b02742fa9d754cd3bebf337f7a55d114c8e5cdd30be022040529b194ba3f9281a99f2b1c0a19c0489bc22ede944ccf4ecbab4cc618ef3ed01eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac000247304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01cc44a0220573a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee0121025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee635711000000";
+
+fn hash(hex_str: &str) -> [u8; 32] {
+    hex::decode(hex_str).unwrap().try_into().unwrap()
+}
+
+fn target(hex_str: &str) -> U256 {
+    U256::parse_str_radix(hex_str, 16)
+}
+
+#[test]
+fn test_parse_genesis() {
+    let header: BlockHeader = GENESIS.parse().unwrap();
+    assert_eq!(header.version(), 1);
+    assert_eq!(header.prev_block(), [0u8; 32]);
+    assert_eq!(
+        hex::encode(&header.merkle_root()),
+        "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b"
+    );
+    assert_eq!(header.timestamp(), 1_231_006_505);
+    assert_eq!(header.bits(), MAX_TARGET_BITS);
+    assert_eq!(header.nonce(), 2_083_236_893);
+    assert_eq!(
+        header.id(),
+        "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
+    );
+    assert_eq!(header.to_string(), GENESIS);
+    assert_eq!(header.difficulty(), 1.0);
+    assert!(header.check_pow());
+}
+
+#[test]
+fn test_header_chain_link() {
+    let genesis: BlockHeader = GENESIS.parse().unwrap();
+    let block_1: BlockHeader = BLOCK_1.parse().unwrap();
+    assert_eq!(block_1.prev_block(), genesis.hash());
+    assert_eq!(
+        block_1.id(),
+        "00000000839a8e6886ab5951d76f411475428afc90947ee320161bbf18eb6048"
+    );
+    assert!(block_1.check_pow());
+    let serialized = block_1.serialize();
+    assert_eq!(BlockHeader::from_bytes(&serialized).unwrap(), block_1);
+    assert!(BlockHeader::from_bytes(&serialized[1..]).is_err());
+    assert!(BlockHeader::from_bytes(&[&serialized[..], &[0]].concat()).is_err());
+}
+
+#[test]
+fn test_mainnet_header() {
+    let mut header: BlockHeader = BLOCK_471744.parse().unwrap();
+    assert_eq!(header.version(), 0x2000_0002);
+    assert_eq!(
+        header.id(),
+        "0000000000000000007e9e4c586439b0cdbe13b1370bdd9435d76a644d047523"
+    );
+    assert_eq!(header.bits(), 0x1801_3ce9);
+    assert_eq!(
+        header.target().unwrap(),
+        target("13ce9000000000000000000000000000000000000000000")
+    );
+    assert!((header.difficulty() - 888_171_856_257.320_6).abs() < 0.001);
+    assert!(header.check_pow());
+
+    header.set_nonce(header.nonce() + 1);
+    assert!(!header.check_pow());
+}
+
+#[test]
+fn test_bits_and_target() {
+    assert_eq!(
+        bits_to_target(MAX_TARGET_BITS).unwrap(),
+        target("ffff0000000000000000000000000000000000000000000000000000")
+    );
+    // Regtest limit
+    assert_eq!(
+        bits_to_target(0x207f_ffff).unwrap(),
+        target("7fffff0000000000000000000000000000000000000000000000000000000000")
+    );
+    for bits in [
+        MAX_TARGET_BITS,
+        0x1801_3ce9,
+        0x207f_ffff,
+        0x1b04_04cb,
+        0x0300_8000,
+    ] {
+        assert_eq!(target_to_bits(bits_to_target(bits).unwrap()), bits);
+    }
+
+    // Small exponents shift the mantissa right
+    assert_eq!(bits_to_target(0x0112_3456).unwrap(), U256::from(0x12u8));
+    assert_eq!(bits_to_target(0x0212_3456).unwrap(), U256::from(0x1234u16));
+    assert_eq!(bits_to_target(0x0000_0000).unwrap(), U256::ZERO);
+    assert_eq!(bits_to_target(0x0480_0000).unwrap(), U256::ZERO);
+
+    // A set high bit would make the mantissa negative
+    assert_eq!(target_to_bits(U256::from(0x80u8)), 0x0200_8000);
+    assert_eq!(target_to_bits(U256::from(0x1234_5678u32)), 0x0412_3456);
+    assert_eq!(target_to_bits(U256::ZERO), 0);
+
+    assert!(bits_to_target(0x0492_3456).is_err());
+    assert!(bits_to_target(0x2301_0000).is_err());
+    assert!(bits_to_target(0x2200_0100).is_err());
+    assert!(bits_to_target(0x2100_0001).is_ok());
+
+    let genesis: BlockHeader = GENESIS.parse().unwrap();
+    let header = BlockHeader::new(
+        genesis.version(),
+        genesis.prev_block(),
+        genesis.merkle_root(),
+        genesis.timestamp(),
+        0x0180_0001,
+        genesis.nonce(),
+    );
+    assert!(!header.check_pow());
+}
+
+#[test]
+fn test_merkle_root() {
+    // Block 100000
+    let txids = [
+        "8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87",
+        "fff2525b8931402dd09222c50775608f75787bd2b87e56995a7bdd30f79702c4",
+        "6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4",
+        "e9a66845e05d5abc0ad04ec80f774a7e585c6e8db975962d069a522137b80c1d",
+    ]
+    .map(hash);
+    let root = hash("f3e94742aca4b5ef85488dc37c06c3282295ffec960994b2c0d5ac2a25a95766");
+    assert_eq!(merkle_root(&txids), (root, false));
+    assert_eq!(merkle_root(&txids[..1]), (txids[0], false));
+    assert_eq!(merkle_root(&[]), ([0u8; 32], false));
+
+    // Repeating the last leaf of an odd level gives the same root, but is flagged
+    let (odd_root, mutated) = merkle_root(&txids[..3]);
+    assert!(!mutated);
+    let duplicated = [txids[0], txids[1], txids[2], txids[2]];
+    assert_eq!(merkle_root(&duplicated), (odd_root, true));
+}
+
+#[test]
+fn test_genesis_block() {
+    let hex_str = format!("{}01{}", GENESIS, GENESIS_COINBASE);
+    let block: Block = hex_str.parse().unwrap();
+    assert_eq!(block.txs().len(), 1);
+    assert_eq!(block.hash(), block.header().hash());
+    assert_eq!(block.txs()[0].hash(), block.header().merkle_root());
+    assert_eq!(block.to_string(), hex_str);
+    assert_eq!(block.weight(), hex_str.len() / 2 * 4);
+    assert!(block.check_merkle_root().is_ok());
+    assert!(block.witness_commitment().is_none());
+    assert!(block.check_witness_commitment().is_ok());
+    assert!(Block::from_bytes(&hex::decode(&format!("{}00", hex_str)).unwrap()).is_err());
+    assert!(Block::from_bytes(&hex::decode(&hex_str[..hex_str.len() - 2]).unwrap()).is_err());
+}
+
+#[test]
+fn test_witness_commitment() {
+    let block: Block = SEGWIT_BLOCK.parse().unwrap();
+    assert_eq!(
+        block.id(),
+        "000000000000015d6077a411a8f5cc95caf775ccf11c54e27df75ce58d187313"
+    );
+    assert!(block.txs()[1].is_segwit());
+    assert_eq!(
+        block.witness_commitment(),
+        Some(hash(
+            "5c748e121c0fe146d973a4ac26fa4a68b0549d46ee22d25f50a5e46fe1b377ee"
+        ))
+    );
+    assert!(block.check_merkle_root().is_ok());
+    assert!(block.check_witness_commitment().is_ok());
+    assert_eq!(block.to_string(), SEGWIT_BLOCK);
+
+    // The txid tree ignores witnesses, the commitment does not
+    let with_reserved = |reserved: Vec<u8>| {
+        let mut txs = block.txs().to_vec();
+        txs[0].inputs_mut()[0].set_witness(vec![reserved]);
+        Block::new(*block.header(), txs)
+    };
+    let changed = with_reserved(vec![1; 32]);
+    assert!(changed.check_merkle_root().is_ok());
+    assert!(changed.check_witness_commitment().is_err());
+    assert!(with_reserved(vec![0; 31])
+        .check_witness_commitment()
+        .is_err());
+    let mut txs = block.txs().to_vec();
+    txs[1].inputs_mut()[0].set_witness(Vec::new());
+    let stripped = Block::new(*block.header(), txs);
+    assert!(stripped.check_merkle_root().is_ok());
+    assert!(stripped.check_witness_commitment().is_err());
+
+    // Witness data needs a commitment
+    let coinbase: Tx = GENESIS_COINBASE.parse().unwrap();
+    let txs = vec![coinbase, SEGWIT_TX.parse().unwrap()];
+    let block = Block::new(*block.header(), txs);
+    assert!(block.check_witness_commitment().is_err());
+}
+
+#[test]
+fn test_check_merkle_root() {
+    let coinbase: Tx = GENESIS_COINBASE.parse().unwrap();
+    let spend: Tx = SEGWIT_TX.parse().unwrap();
+    let header = |txs: &[Tx]| {
+        let hashes: Vec<[u8; 32]> = txs.iter().map(Tx::hash).collect();
+        BlockHeader::new(1, [0; 32], merkle_root(&hashes).0, 0, MAX_TARGET_BITS, 0)
+    };
+
+    let txs = vec![
+        coinbase.clone(),
+        spend.clone(),
+        spend.clone(),
+        spend.clone(),
+    ];
+    let block = Block::new(header(&txs), txs);
+    assert!(block.check_merkle_root().unwrap_err().contains("Duplicate"));
+
+    let txs = vec![coinbase.clone(), spend.clone()];
+    let block = Block::new(header(&txs[..1]), txs);
+    assert!(block.check_merkle_root().unwrap_err().contains("mismatch"));
+
+    let txs = vec![spend.clone(), coinbase.clone()];
+    assert!(Block::new(header(&txs), txs).check_merkle_root().is_err());
+    let txs = vec![coinbase.clone(), coinbase];
+    assert!(Block::new(header(&txs), txs).check_merkle_root().is_err());
+    assert!(Block::new(header(&[]), vec![]).check_merkle_root().is_err());
+}
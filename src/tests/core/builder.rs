@@ -0,0 +1,153 @@
+use crate::core::address::script_from_address;
+use crate::core::builder::{sign_tx, TxBuilder, Utxo, DUST_LIMIT, SEQUENCE_RBF};
+use crate::core::interpreter::{verify_script, TransactionChecker, STANDARD_VERIFY_FLAGS};
+use crate::core::network::Network;
+use crate::core::s256ecc::S256PrivateKey;
+use crate::core::script::Script;
+use crate::core::tx::{OutPoint, Tx, TxIn, TxOut};
+use crate::tests::util::keys;
+
+const NETWORK: Network = Network::Regtest;
+
+fn utxo(vout: u32, amount: u64, address: &str) -> Utxo {
+    let script_pubkey = Script::from(script_from_address(address, NETWORK).unwrap());
+    Utxo::new(OutPoint::new([0x42; 32], vout), amount, script_pubkey)
+}
+
+fn utxos(keys: &[S256PrivateKey]) -> Vec<Utxo> {
+    vec![
+        utxo(0, 40_000, &keys[0].point().address(true, NETWORK)),
+        utxo(1, 30_000, &keys[1].point().address(false, NETWORK)),
+        utxo(2, 20_000, &keys[2].point().p2wpkh_address(NETWORK)),
+        utxo(3, 15_000, &keys[3].point().p2sh_p2wpkh_address(NETWORK)),
+        utxo(4, 10_000, &keys[4].point().p2tr_address(NETWORK).unwrap()),
+    ]
+}
+
+fn verify_inputs(tx: &Tx, spent: &[TxOut]) {
+    for (index, (input, prevout)) in tx.inputs().iter().zip(spent).enumerate() {
+        let checker = TransactionChecker::with_spent_outputs(tx, index, spent);
+        verify_script(
+            input.script_sig(),
+            prevout.script_pubkey(),
+            input.witness(),
+            STANDARD_VERIFY_FLAGS,
+            &checker,
+        )
+        .unwrap();
+    }
+}
+
+#[test]
+fn test_build_and_sign() {
+    let keys = keys(5);
+    let utxos = utxos(&keys);
+    let recipient = keys[0].point().p2wpkh_address(NETWORK);
+    let change = keys[1].point().p2tr_address(NETWORK).unwrap();
+    let mut builder = TxBuilder::new(NETWORK);
+    for utxo in &utxos {
+        builder.add_utxo(utxo.clone());
+    }
+    builder
+        .add_recipient(&recipient, 60_000)
+        .unwrap()
+        .change_address(&change)
+        .unwrap()
+        .fee_rate(5);
+    let tx = builder.build_and_sign(&keys).unwrap();
+
+    assert_eq!(tx.version(), 2);
+    assert!(tx.is_segwit());
+    assert!(tx
+        .inputs()
+        .iter()
+        .all(|input| input.sequence() == SEQUENCE_RBF));
+    assert_eq!(tx.outputs().len(), 2);
+    assert_eq!(tx.outputs()[0].amount(), 60_000);
+    assert_eq!(
+        tx.outputs()[1].script_pubkey().as_bytes(),
+        script_from_address(&change, NETWORK).unwrap()
+    );
+    let fee = 115_000 - tx.output_value();
+    assert!(fee >= tx.vsize() as u64 * 5);
+    assert!(fee <= (tx.vsize() as u64 + 4) * 5);
+
+    let spent: Vec<TxOut> = utxos.iter().map(Utxo::to_tx_out).collect();
+    verify_inputs(&tx, &spent);
+    assert_eq!(Tx::from_bytes(&tx.serialize()).unwrap(), tx);
+}
+
+#[test]
+fn test_build_without_change() {
+    let keys = keys(5);
+    let utxos = utxos(&keys);
+    let recipient = keys[2].point().address(true, NETWORK);
+    let mut builder = TxBuilder::new(NETWORK);
+    builder
+        .add_utxo(utxos[2].clone())
+        .add_recipient(&recipient, 19_500)
+        .unwrap()
+        .change_address(&recipient)
+        .unwrap()
+        .version(1)
+        .locktime(500)
+        .sequence(0xffff_fffe);
+    let tx = builder.build_and_sign(&keys).unwrap();
+    // Leftover value is below the dust limit, so it goes to the fee
+    assert_eq!(tx.outputs().len(), 1);
+    assert_eq!(tx.version(), 1);
+    assert_eq!(tx.locktime(), 500);
+    assert_eq!(tx.inputs()[0].sequence(), 0xffff_fffe);
+    verify_inputs(&tx, &[utxos[2].to_tx_out()]);
+}
+
+#[test]
+fn test_build_errors() {
+    let keys = keys(5);
+    let utxos = utxos(&keys);
+    let recipient = keys[0].point().p2wpkh_address(NETWORK);
+
+    let mut builder = TxBuilder::new(NETWORK);
+    assert!(builder.add_recipient(&recipient, DUST_LIMIT - 1).is_err());
+    assert!(builder.add_recipient("bc1qinvalid", 10_000).is_err());
+    assert!(builder.build_and_sign(&keys).is_err());
+
+    builder
+        .add_utxo(utxos[0].clone())
+        .add_recipient(&recipient, 40_000)
+        .unwrap();
+    assert!(builder
+        .build_and_sign(&keys)
+        .unwrap_err()
+        .contains("Insufficient funds"));
+
+    let mut builder = TxBuilder::new(NETWORK);
+    builder
+        .add_utxo(utxos[3].clone())
+        .add_recipient(&recipient, 10_000)
+        .unwrap();
+    assert!(builder
+        .build_and_sign(&keys[..3])
+        .unwrap_err()
+        .contains("No key for input 0"));
+}
+
+#[test]
+fn test_sign_tx() {
+    let keys = keys(5);
+    let utxos = utxos(&keys);
+    let inputs = utxos
+        .iter()
+        .map(|utxo| TxIn::new(utxo.outpoint(), Script::new(), SEQUENCE_RBF))
+        .collect();
+    let output = TxOut::new(100_000, utxos[0].script_pubkey().clone());
+    let mut tx = Tx::new(2, inputs, vec![output], 0);
+    let spent: Vec<TxOut> = utxos.iter().map(Utxo::to_tx_out).collect();
+    assert!(sign_tx(&mut tx, &spent[1..], &keys).is_err());
+    sign_tx(&mut tx, &spent, &keys).unwrap();
+    verify_inputs(&tx, &spent);
+
+    let mut unsupported = spent.clone();
+    unsupported[0] = TxOut::new(1_000, Script::from(vec![0x51]));
+    assert!(sign_tx(&mut tx, &unsupported, &keys).is_err());
+}
@@ -0,0 +1,248 @@
+use crate::core::block::{BlockHeader, MAX_TARGET_BITS};
+use crate::core::chain::{
+    block_work, calculate_next_bits, ChainUpdate, HeaderChain, DIFFICULTY_ADJUSTMENT_INTERVAL,
+    MAX_FUTURE_BLOCK_TIME,
+};
+use crate::core::network::Network;
+use crate::tests::util::{extend, mine, REGTEST_BITS};
+use bnum::types::U256;
+
+const NOW: u32 = 2_000_000_000;
+
+#[test]
+fn test_genesis() {
+    for network in Network::ALL {
+        let chain = HeaderChain::new(network);
+        assert_eq!(chain.network(), network);
+        assert_eq!(chain.height(), 0);
+        assert_eq!(chain.tip().hash(), network.genesis_hash());
+        assert!(chain.tip().header().check_pow());
+        assert_eq!(chain.tip().header().bits(), network.pow_limit_bits());
+        assert_eq!(chain.locator(), vec![network.genesis_hash()]);
+    }
+    let chain = HeaderChain::new(Network::Mainnet);
+    assert_eq!(chain.tip().chainwork(), U256::from(0x1_0001_0001u64));
+}
+
+#[test]
+fn test_block_work() {
+    assert_eq!(block_work(MAX_TARGET_BITS), U256::from(0x1_0001_0001u64));
+    assert_eq!(block_work(REGTEST_BITS), U256::from(2u8));
+    assert_eq!(
+        block_work(0x1801_3ce9),
+        U256::parse_str_radix("3814727283971761869673", 10)
+    );
+    assert_eq!(block_work(0), U256::ZERO);
+    assert_eq!(block_work(0x0480_0001), U256::ZERO);
+}
+
+#[test]
+fn test_calculate_next_bits() {
+    // Bitcoin Core pow_tests
+    assert_eq!(
+        calculate_next_bits(
+            MAX_TARGET_BITS,
+            1_261_130_161,
+            1_262_152_739,
+            MAX_TARGET_BITS
+        ),
+        0x1d00_d86a
+    );
+    assert_eq!(
+        calculate_next_bits(
+            MAX_TARGET_BITS,
+            1_231_006_505,
+            1_233_061_996,
+            MAX_TARGET_BITS
+        ),
+        MAX_TARGET_BITS
+    );
+    assert_eq!(
+        calculate_next_bits(0x1c05_a3f4, 1_279_008_237, 1_279_297_671, MAX_TARGET_BITS),
+        0x1c01_68fd
+    );
+    assert_eq!(
+        calculate_next_bits(0x1c38_7f6f, 1_263_163_443, 1_269_211_443, MAX_TARGET_BITS),
+        0x1d00_e1fd
+    );
+    // Timestamps going backwards clamp to the fastest adjustment
+    assert_eq!(
+        calculate_next_bits(0x1c05_a3f4, 1_279_297_671, 1_279_008_237, MAX_TARGET_BITS),
+        0x1c01_68fd
+    );
+}
+
+#[test]
+fn test_extend_and_reorg() {
+    let mut chain = HeaderChain::new(Network::Regtest);
+    let genesis = *chain.tip().header();
+    let main = extend(&genesis, 1, 5);
+    for (height, header) in main.iter().enumerate() {
+        assert_eq!(
+            chain.accept_header(*header, NOW).unwrap(),
+            ChainUpdate::Extended
+        );
+        assert_eq!(chain.height(), height as u32 + 1);
+    }
+    assert_eq!(
+        chain.accept_header(main[2], NOW).unwrap(),
+        ChainUpdate::AlreadyKnown
+    );
+    assert_eq!(chain.tip().hash(), main[4].hash());
+    assert_eq!(chain.tip().chainwork(), U256::from(12u8));
+
+    // A fork from height 2 needs more work than the main chain to take over
+    let fork = extend(&main[1], 2, 4);
+    for header in &fork[..3] {
+        assert_eq!(
+            chain.accept_header(*header, NOW).unwrap(),
+            ChainUpdate::SideChain
+        );
+    }
+    assert_eq!(chain.tip().hash(), main[4].hash());
+    assert!(!chain.is_in_best_chain(&fork[0].hash()));
+    assert_eq!(chain.get(&fork[2].hash()).unwrap().height(), 5);
+    assert_eq!(
+        chain.ancestor(&fork[2].hash(), 3).unwrap().hash(),
+        fork[0].hash()
+    );
+    assert_eq!(
+        chain.ancestor(&fork[2].hash(), 1).unwrap().hash(),
+        main[0].hash()
+    );
+    assert!(chain.ancestor(&fork[2].hash(), 6).is_none());
+
+    assert_eq!(
+        chain.accept_header(fork[3], NOW).unwrap(),
+        ChainUpdate::Reorg {
+            disconnected: main[2..].iter().rev().map(BlockHeader::hash).collect(),
+            connected: fork.iter().map(BlockHeader::hash).collect(),
+        }
+    );
+    assert_eq!(chain.height(), 6);
+    assert_eq!(chain.tip().hash(), fork[3].hash());
+    assert_eq!(chain.hash_at(2), Some(main[1].hash()));
+    assert_eq!(chain.hash_at(3), Some(fork[0].hash()));
+    assert!(chain.hash_at(7).is_none());
+    assert!(!chain.is_in_best_chain(&main[4].hash()));
+    assert!(chain.contains(&main[4].hash()));
+
+    // The old chain can come back
+    let revived = extend(&main[4], 3, 2);
+    assert_eq!(
+        chain.accept_header(revived[0], NOW).unwrap(),
+        ChainUpdate::SideChain
+    );
+    let ChainUpdate::Reorg {
+        disconnected,
+        connected,
+    } = chain.accept_header(revived[1], NOW).unwrap()
+    else {
+        panic!("expected a reorg");
+    };
+    assert_eq!(disconnected.len(), 4);
+    assert_eq!(connected.len(), 5);
+    assert_eq!(chain.tip().hash(), revived[1].hash());
+}
+
+#[test]
+fn test_invalid_headers() {
+    let mut chain = HeaderChain::new(Network::Regtest);
+    let genesis = *chain.tip().header();
+    let headers = extend(&genesis, 1, 11);
+    for header in &headers {
+        chain.accept_header(*header, NOW).unwrap();
+    }
+    let tip = *chain.tip().header();
+
+    let orphan = BlockHeader::new(4, [7; 32], [9; 32], tip.timestamp() + 600, REGTEST_BITS, 0);
+    assert!(chain
+        .accept_header(orphan, NOW)
+        .unwrap_err()
+        .contains("unknown block"));
+
+    let mut bad_pow = mine(&tip, 2, tip.timestamp() + 600, REGTEST_BITS);
+    while bad_pow.check_pow() {
+        bad_pow.set_nonce(bad_pow.nonce() + 1);
+    }
+    assert!(chain
+        .accept_header(bad_pow, NOW)
+        .unwrap_err()
+        .contains("proof of work"));
+
+    let wrong_bits = mine(&tip, 2, tip.timestamp() + 600, 0x207f_0000);
+    assert!(chain
+        .accept_header(wrong_bits, NOW)
+        .unwrap_err()
+        .contains("bits"));
+
+    // Median of the last eleven timestamps is the sixth newest
+    let median = chain.median_time_past(&tip.hash()).unwrap();
+    assert_eq!(median, headers[5].timestamp());
+    let stale = mine(&tip, 2, median, REGTEST_BITS);
+    assert!(chain
+        .accept_header(stale, NOW)
+        .unwrap_err()
+        .contains("median time past"));
+    let older_than_parent = mine(&tip, 2, median + 1, REGTEST_BITS);
+    assert_eq!(
+        chain.accept_header(older_than_parent, NOW).unwrap(),
+        ChainUpdate::Extended
+    );
+
+    let tip = *chain.tip().header();
+    let now = tip.timestamp();
+    let future = mine(&tip, 2, now + MAX_FUTURE_BLOCK_TIME + 1, REGTEST_BITS);
+    assert!(chain
+        .accept_header(future, now)
+        .unwrap_err()
+        .contains("future"));
+    let near_future = mine(&tip, 2, now + MAX_FUTURE_BLOCK_TIME, REGTEST_BITS);
+    assert_eq!(
+        chain.accept_header(near_future, now).unwrap(),
+        ChainUpdate::Extended
+    );
+}
+
+#[test]
+fn test_next_bits() {
+    let chain = HeaderChain::new(Network::Mainnet);
+    let genesis = chain.tip();
+    assert_eq!(chain.next_bits(genesis, u32::MAX), MAX_TARGET_BITS);
+
+    // Testnet allows minimum difficulty blocks after twenty minutes
+    let chain = HeaderChain::new(Network::Testnet);
+    let genesis = chain.tip();
+    let late = genesis.header().timestamp() + 1201;
+    assert_eq!(chain.next_bits(genesis, late), MAX_TARGET_BITS);
+
+    // Regtest never retargets
+    let mut chain = HeaderChain::new(Network::Regtest);
+    let genesis = *chain.tip().header();
+    let headers = extend(&genesis, 1, DIFFICULTY_ADJUSTMENT_INTERVAL as usize - 1);
+    for header in &headers {
+        chain.accept_header(*header, NOW).unwrap();
+    }
+    let tip = chain.tip();
+    assert_eq!(tip.height(), DIFFICULTY_ADJUSTMENT_INTERVAL - 1);
+    assert_eq!(
+        chain.next_bits(tip, tip.header().timestamp() + 1),
+        REGTEST_BITS
+    );
+}
+
+#[test]
+fn test_locator() {
+    let mut chain = HeaderChain::new(Network::Regtest);
+    let genesis = *chain.tip().header();
+    let headers = extend(&genesis, 1, 30);
+    for header in &headers {
+        chain.accept_header(*header, NOW).unwrap();
+    }
+    let heights: Vec<u32> = (20..=30).rev().chain([18, 14, 6, 0]).collect();
+    let expected: Vec<[u8; 32]> = heights
+        .iter()
+        .map(|height| chain.hash_at(*height).unwrap())
+        .collect();
+    assert_eq!(chain.locator(), expected);
+}
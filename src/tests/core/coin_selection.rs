@@ -0,0 +1,265 @@
+use crate::core::builder::{TxBuilder, Utxo, DUST_LIMIT};
+use crate::core::coin_selection::{
+    branch_and_bound, fee_for_weight, knapsack, largest_first, select_coins, single_random_draw,
+    CoinSelectionAlgorithm, Selection, SelectionParams, WeightedUtxo, P2WPKH_INPUT_WEIGHT,
+};
+use crate::core::network::Network;
+use crate::core::s256ecc::S256PrivateKey;
+use crate::core::script::Script;
+use crate::core::tx::OutPoint;
+use bnum::types::U256;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+const CENT: u64 = 100_000;
+
+fn candidates(amounts: &[u64], input_weight: u64) -> Vec<WeightedUtxo> {
+    amounts
+        .iter()
+        .enumerate()
+        .map(|(vout, amount)| {
+            let utxo = Utxo::new(OutPoint::new([1; 32], vout as u32), *amount, Script::new());
+            WeightedUtxo::new(utxo, input_weight)
+        })
+        .collect()
+}
+
+fn amounts(selection: &Selection) -> Vec<u64> {
+    let mut amounts: Vec<u64> = selection
+        .inputs()
+        .iter()
+        .map(|utxo| utxo.utxo().amount())
+        .collect();
+    amounts.sort();
+    amounts
+}
+
+fn free_params() -> SelectionParams {
+    let mut params = SelectionParams::new(0);
+    params.long_term_fee_rate(0);
+    params
+}
+
+#[test]
+fn test_fee_and_effective_value() {
+    assert_eq!(fee_for_weight(272, 1), 68);
+    assert_eq!(fee_for_weight(230, 1), 58);
+    assert_eq!(fee_for_weight(230, 3), 173);
+    let utxo = &candidates(&[1_000, 680], P2WPKH_INPUT_WEIGHT)[..];
+    assert_eq!(utxo[0].fee(10), 680);
+    assert_eq!(utxo[0].effective_value(10), Some(320));
+    assert_eq!(utxo[1].effective_value(10), None);
+    assert_eq!(utxo[1].effective_value(9), Some(68));
+
+    let params = SelectionParams::new(5);
+    assert_eq!(params.change_fee(), 155);
+    assert_eq!(params.cost_of_change(), 155 + 680);
+}
+
+#[test]
+fn test_branch_and_bound() {
+    let pool = candidates(&[CENT, 2 * CENT, 3 * CENT, 4 * CENT], 0);
+    let params = free_params();
+
+    let selection = branch_and_bound(&pool, 5 * CENT, &params).unwrap();
+    assert_eq!(selection.effective_value(), 5 * CENT);
+    assert_eq!(selection.change(), None);
+    assert_eq!(selection.waste(), 0);
+    assert_eq!(
+        selection.algorithm(),
+        CoinSelectionAlgorithm::BranchAndBound
+    );
+
+    let selection = branch_and_bound(&pool, 10 * CENT, &params).unwrap();
+    assert_eq!(amounts(&selection), [CENT, 2 * CENT, 3 * CENT, 4 * CENT]);
+
+    let selection = branch_and_bound(&pool, 7 * CENT, &params).unwrap();
+    assert_eq!(selection.effective_value(), 7 * CENT);
+
+    // No subset lands within the cost of change
+    let mut params = free_params();
+    params.change_weights(0, 0);
+    assert!(branch_and_bound(&pool, CENT + 1, &params).is_err());
+    assert!(branch_and_bound(&pool, 11 * CENT, &params)
+        .unwrap_err()
+        .contains("Insufficient funds"));
+
+    // Excess below the cost of change is accepted and counted as waste
+    let mut params = SelectionParams::new(1);
+    params.long_term_fee_rate(1);
+    let pool = candidates(&[CENT + 68 + 50, 3 * CENT], P2WPKH_INPUT_WEIGHT);
+    let selection = branch_and_bound(&pool, CENT, &params).unwrap();
+    assert_eq!(amounts(&selection), [CENT + 118]);
+    assert_eq!(selection.waste(), 50);
+
+    // The whole excess goes to fees even when it could pay for a change output
+    let mut params = SelectionParams::new(1);
+    params.long_term_fee_rate(10);
+    let excess = params.change_fee() + DUST_LIMIT + 20;
+    assert!(excess <= params.cost_of_change());
+    let pool = candidates(&[CENT + 68 + excess], P2WPKH_INPUT_WEIGHT);
+    let selection = branch_and_bound(&pool, CENT, &params).unwrap();
+    assert_eq!(selection.change(), None);
+    assert_eq!(selection.waste(), 68 - 680 + excess as i64);
+}
+
+#[test]
+fn test_branch_and_bound_prefers_low_waste() {
+    // At a high fee rate fewer inputs waste less, at a low one consolidation wins
+    let pool = candidates(
+        &[4 * CENT, 2 * CENT, 2 * CENT, 5 * CENT],
+        P2WPKH_INPUT_WEIGHT,
+    );
+    let mut params = SelectionParams::new(50);
+    params.long_term_fee_rate(10);
+    let fee = fee_for_weight(P2WPKH_INPUT_WEIGHT, 50);
+    let selection = branch_and_bound(&pool, 4 * CENT - fee, &params).unwrap();
+    assert_eq!(amounts(&selection), [4 * CENT]);
+
+    let mut params = SelectionParams::new(2);
+    params.long_term_fee_rate(10);
+    let fee = fee_for_weight(P2WPKH_INPUT_WEIGHT, 2);
+    let selection = branch_and_bound(&pool, 4 * CENT - 2 * fee, &params).unwrap();
+    assert_eq!(amounts(&selection), [2 * CENT, 2 * CENT]);
+    assert!(selection.waste() < 0);
+}
+
+#[test]
+fn test_knapsack() {
+    let mut rng = StdRng::seed_from_u64(42);
+    let params = free_params();
+
+    let pool = candidates(&[CENT, 2 * CENT, 5 * CENT, 20 * CENT], 0);
+    let selection = knapsack(&pool, 5 * CENT, &params, &mut rng).unwrap();
+    assert_eq!(amounts(&selection), [5 * CENT]);
+    assert_eq!(selection.algorithm(), CoinSelectionAlgorithm::Knapsack);
+
+    let selection = knapsack(&pool, 8 * CENT, &params, &mut rng).unwrap();
+    assert_eq!(amounts(&selection), [CENT, 2 * CENT, 5 * CENT]);
+
+    // Smaller inputs can't cover target plus change, the lowest larger one is used
+    let selection = knapsack(&pool, 9 * CENT, &params, &mut rng).unwrap();
+    assert_eq!(amounts(&selection), [20 * CENT]);
+    assert_eq!(selection.change(), Some(11 * CENT));
+
+    let pool = candidates(&[CENT, 2 * CENT, 3 * CENT, 4 * CENT, 30 * CENT], 0);
+    let selection = knapsack(&pool, 6 * CENT, &params, &mut rng).unwrap();
+    assert!(selection.effective_value() >= 6 * CENT);
+    assert!(selection.effective_value() < 30 * CENT);
+
+    let pool = candidates(&[CENT, 2 * CENT], 0);
+    assert!(knapsack(&pool, 4 * CENT, &params, &mut rng).is_err());
+}
+
+#[test]
+fn test_largest_first_and_single_random_draw() {
+    let pool = candidates(&[CENT, 7 * CENT, 3 * CENT, 2 * CENT], P2WPKH_INPUT_WEIGHT);
+    let params = SelectionParams::new(1);
+    let fee = fee_for_weight(P2WPKH_INPUT_WEIGHT, 1);
+
+    let selection = largest_first(&pool, 8 * CENT, &params).unwrap();
+    assert_eq!(amounts(&selection), [3 * CENT, 7 * CENT]);
+    assert_eq!(
+        selection.change(),
+        Some(10 * CENT - 2 * fee - 8 * CENT - params.change_fee())
+    );
+    assert_eq!(selection.algorithm(), CoinSelectionAlgorithm::LargestFirst);
+
+    // Everything is needed and the leftover is too small for change
+    let selection = largest_first(&pool, 13 * CENT - 4 * fee - 100, &params).unwrap();
+    assert_eq!(selection.inputs().len(), 4);
+    assert_eq!(selection.change(), None);
+    assert_eq!(selection.waste(), 4 * fee as i64 - 4 * 680 + 100);
+    assert!(largest_first(&pool, 13 * CENT, &params).is_err());
+
+    let mut rng = StdRng::seed_from_u64(7);
+    for _ in 0..20 {
+        let selection = single_random_draw(&pool, 4 * CENT, &params, &mut rng).unwrap();
+        assert!(selection.effective_value() >= 4 * CENT + params.change_fee() + DUST_LIMIT);
+        assert!(selection.change().is_some());
+        assert_eq!(
+            selection.algorithm(),
+            CoinSelectionAlgorithm::SingleRandomDraw
+        );
+    }
+}
+
+#[test]
+fn test_select_coins() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let params = SelectionParams::new(1);
+    let fee = fee_for_weight(P2WPKH_INPUT_WEIGHT, 1);
+    let pool = candidates(&[CENT, 2 * CENT, 3 * CENT, 10 * CENT], P2WPKH_INPUT_WEIGHT);
+
+    let selection = select_coins(&pool, 5 * CENT - 2 * fee, &params, &mut rng).unwrap();
+    assert_eq!(
+        selection.algorithm(),
+        CoinSelectionAlgorithm::BranchAndBound
+    );
+    assert_eq!(amounts(&selection), [2 * CENT, 3 * CENT]);
+    assert_eq!(selection.change(), None);
+
+    let selection = select_coins(&pool, 12 * CENT, &params, &mut rng).unwrap();
+    assert!(selection.effective_value() >= 12 * CENT);
+    assert!(selection.change().is_some());
+    for algorithm_result in [
+        largest_first(&pool, 12 * CENT, &params),
+        knapsack(&pool, 12 * CENT, &params, &mut rng),
+    ] {
+        assert!(selection.waste() <= algorithm_result.unwrap().waste());
+    }
+
+    // Uneconomical inputs are never selected
+    let mut params = SelectionParams::new(10);
+    params.long_term_fee_rate(10);
+    let pool = candidates(&[500, 600, 5_000], P2WPKH_INPUT_WEIGHT);
+    assert!(select_coins(&pool, 4_400, &params, &mut rng)
+        .unwrap_err()
+        .contains("Insufficient funds"));
+    let selection = select_coins(&pool, 4_000, &params, &mut rng).unwrap();
+    assert_eq!(amounts(&selection), [5_000]);
+}
+
+#[test]
+fn test_builder_select_utxos() {
+    let network = Network::Regtest;
+    let keys: Vec<S256PrivateKey> = (1u32..=6)
+        .map(|secret| S256PrivateKey::from_value(U256::from(secret * 7_777)))
+        .collect();
+    let pool: Vec<WeightedUtxo> = keys
+        .iter()
+        .enumerate()
+        .map(|(vout, key)| {
+            let address = key.point().p2wpkh_address(network);
+            let script_pubkey =
+                Script::from(crate::core::address::script_from_address(&address, network).unwrap());
+            let amount = (vout as u64 + 1) * 25_000;
+            let utxo = Utxo::new(OutPoint::new([9; 32], vout as u32), amount, script_pubkey);
+            WeightedUtxo::new(utxo, P2WPKH_INPUT_WEIGHT)
+        })
+        .collect();
+    let recipient = keys[0].point().p2tr_address(network).unwrap();
+    let change = keys[1].point().p2wpkh_address(network);
+    let mut rng = StdRng::seed_from_u64(3);
+
+    let mut builder = TxBuilder::new(network);
+    builder
+        .add_recipient(&recipient, 120_000)
+        .unwrap()
+        .change_address(&change)
+        .unwrap()
+        .fee_rate(3);
+    let selection = builder.select_utxos(&pool, &mut rng).unwrap();
+    let tx = builder.build_and_sign(&keys).unwrap();
+    assert_eq!(tx.inputs().len(), selection.inputs().len());
+    let fee = selection.input_value() - tx.output_value();
+    assert!(fee >= tx.vsize() as u64 * 3);
+    assert_eq!(
+        tx.outputs().len(),
+        1 + selection.change().is_some() as usize
+    );
+
+    let mut builder = TxBuilder::new(network);
+    builder.add_recipient(&recipient, 1_000_000).unwrap();
+    assert!(builder.select_utxos(&pool, &mut rng).is_err());
+}
@@ -0,0 +1,148 @@
+use crate::core::descriptor::{descriptor_checksum, Descriptor};
+use crate::core::network::Network;
+use crate::ser::hex;
+
+const XPUB: &str = "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL";
+
+fn script_hex(descriptor: &str, index: u32) -> String {
+    let descriptor = descriptor.parse::<Descriptor>().unwrap();
+    hex::encode(&descriptor.script_pubkey(index).unwrap())
+}
+
+#[test]
+fn test_checksum() {
+    assert_eq!(descriptor_checksum("raw(deadbeef)").unwrap(), "89f8spxm");
+    let descriptor = format!("pkh([d34db33f/44'/0'/0']{}/1/*)", XPUB);
+    assert_eq!(descriptor_checksum(&descriptor).unwrap(), "ml40v0wf");
+    assert!(format!("{}#ml40v0wf", descriptor)
+        .parse::<Descriptor>()
+        .is_ok());
+    assert!(format!("{}#ml40v0wg", descriptor)
+        .parse::<Descriptor>()
+        .is_err());
+}
+
+#[test]
+fn test_single_key_scripts() {
+    let vectors = [
+        (
+            "pk(0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798)",
+            "210279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798ac",
+        ),
+        (
+            "pkh(02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5)",
+            "76a91406afd46bcdfd22ef94ac122aa11f241244a37ecc88ac",
+        ),
+        (
+            "sh(wpkh(03fff97bd5755eeea420453a14355235d382f6472f8568a18b2f057a1460297556))",
+            "a914cc6ffbc0bf31af759451068f90ba7a0272b6b33287",
+        ),
+        (
+            "sh(wsh(pkh(02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5)))",
+            "a9141def75e1dd672e63f5fd8490c197e08c360784e487",
+        ),
+    ];
+    for (descriptor, expected) in vectors {
+        assert_eq!(script_hex(descriptor, 0), expected);
+    }
+}
+
+#[test]
+fn test_multisig_scripts() {
+    let descriptor = "sh(multi(2,0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798,02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5))";
+    assert_eq!(
+        script_hex(descriptor, 0),
+        "a91412fcac201d73f5b5dba0f1f22c40f02da17bb4a487"
+    );
+    assert_eq!(
+        descriptor
+            .parse::<Descriptor>()
+            .unwrap()
+            .address(0, Network::Mainnet)
+            .unwrap(),
+        "33RQmypKhD6f4tMquiR5a3C6dRT7eBpaiG"
+    );
+    let descriptor = "wsh(sortedmulti(1,03fff97bd5755eeea420453a14355235d382f6472f8568a18b2f057a1460297556,0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798))";
+    assert_eq!(
+        script_hex(descriptor, 0),
+        "00201b0013a2fe259d7df333527ce896fa7788b00387c42b794ca5301db45d0d901f"
+    );
+    assert!("multi(3,0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798,02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5)"
+        .parse::<Descriptor>()
+        .is_err());
+}
+
+#[test]
+fn test_ranged_descriptor() {
+    let descriptor = format!("pkh([d34db33f/44'/0'/0']{}/1/*)", XPUB)
+        .parse::<Descriptor>()
+        .unwrap();
+    assert!(descriptor.is_ranged());
+    assert_eq!(
+        descriptor.addresses(0..3, Network::Mainnet).unwrap(),
+        [
+            "14qCH92HCyDDBFFZdhDt1WMfrMDYnBFYMF",
+            "17igj1BanXgMbEgnLrfYhKHtGPZeBj9CfX",
+            "1HcUHrp8YyDfssqXecCnfxd6ZLdF1y2m4d",
+        ]
+    );
+    assert_eq!(
+        descriptor.to_string(),
+        format!("pkh([d34db33f/44'/0'/0']{}/1/*)#ml40v0wf", XPUB)
+    );
+
+    let descriptor = format!("wpkh([d34db33f/84h/0h/0h]{}/0/*)", XPUB)
+        .parse::<Descriptor>()
+        .unwrap();
+    let scripts = descriptor
+        .script_pubkeys(0..2)
+        .unwrap()
+        .iter()
+        .map(|script| hex::encode(script))
+        .collect::<Vec<String>>();
+    assert_eq!(
+        scripts,
+        [
+            "00143099ad49dfdd021bf3748f7f858e0d1fa0b4f6f8",
+            "0014fcb408d9c05b3dd4bd4cce49e9c271350d1e66ed",
+        ]
+    );
+    assert_eq!(
+        descriptor.to_string(),
+        format!("wpkh([d34db33f/84'/0'/0']{}/0/*)#yq904q8l", XPUB)
+    );
+}
+
+#[test]
+fn test_taproot_descriptor() {
+    let descriptor = "tr([73c5da0a/86'/0'/0']xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ/0/*)#rg247h69";
+    assert_eq!(
+        script_hex(descriptor, 0),
+        "5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c"
+    );
+    let descriptor = "tr(79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798,{pk(c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5),pk(fff97bd5755eeea420453a14355235d382f6472f8568a18b2f057a1460297556)})#gm33ql02";
+    assert_eq!(
+        script_hex(descriptor, 0),
+        "51201c5145cbc501645d40d9e1a1050675378c456da7afdb7c420993d645755a8e61"
+    );
+    assert_eq!(
+        descriptor.parse::<Descriptor>().unwrap().to_string(),
+        descriptor
+    );
+}
+
+#[test]
+fn test_invalid_descriptors() {
+    let invalid = [
+        "wpkh(04a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd5b8dec5235a0fa8722476c7709c02559e3aa73aa03918ba2d492eea75abea235)",
+        "sh(sh(pk(0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798)))",
+        "wsh(wpkh(0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798))",
+        "sh(tr(79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798))",
+        "pk(79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798)",
+        "pkh(xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/1'/*)",
+        "pkh(0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+    ];
+    for descriptor in invalid {
+        assert!(descriptor.parse::<Descriptor>().is_err(), "{}", descriptor);
+    }
+}
@@ -0,0 +1,84 @@
+use crate::core::hd::{DerivationPath, ExtendedPrivateKey, ExtendedPublicKey, HARDENED, XPRV};
+use crate::ser::hex;
+
+#[test]
+fn test_derivation_path() {
+    let path = "m/84'/0'/0h/1/5".parse::<DerivationPath>().unwrap();
+    assert_eq!(path.children(), &[84 + HARDENED, HARDENED, HARDENED, 1, 5]);
+    assert_eq!(path.to_string(), "m/84'/0'/0'/1/5");
+    assert_eq!("m".parse::<DerivationPath>().unwrap().children(), &[]);
+    assert!("m/2147483648".parse::<DerivationPath>().is_err());
+    assert!("m/x'".parse::<DerivationPath>().is_err());
+}
+
+#[test]
+fn test_bip32_vector_1() {
+    let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+    let master = ExtendedPrivateKey::from_seed(&seed, XPRV).unwrap();
+    let vectors = [
+        (
+            "m",
+            "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi",
+            "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8",
+        ),
+        (
+            "m/0'",
+            "xprv9uHRZZhk6KAJC1avXpDAp4MDc3sQKNxDiPvvkX8Br5ngLNv1TxvUxt4cV1rGL5hj6KCesnDYUhd7oWgT11eZG7XnxHrnYeSvkzY7d2bhkJ7",
+            "xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw",
+        ),
+        (
+            "m/0'/1",
+            "xprv9wTYmMFdV23N2TdNG573QoEsfRrWKQgWeibmLntzniatZvR9BmLnvSxqu53Kw1UmYPxLgboyZQaXwTCg8MSY3H2EU4pWcQDnRnrVA1xe8fs",
+            "xpub6ASuArnXKPbfEwhqN6e3mwBcDTgzisQN1wXN9BJcM47sSikHjJf3UFHKkNAWbWMiGj7Wf5uMash7SyYq527Hqck2AxYysAA7xmALppuCkwQ",
+        ),
+        (
+            "m/0'/1/2'/2/1000000000",
+            "xprvA41z7zogVVwxVSgdKUHDy1SKmdb533PjDz7J6N6mV6uS3ze1ai8FHa8kmHScGpWmj4WggLyQjgPie1rFSruoUihUZREPSL39UNdE3BBDu76",
+            "xpub6H1LXWLaKsWFhvm6RVpEL9P4KfRZSW7abD2ttkWP3SSQvnyA8FSVqNTEcYFgJS2UaFcxupHiYkro49S8yGasTvXEYBVPamhGW6cFJodrTHy",
+        ),
+    ];
+    for (path, xprv, xpub) in vectors {
+        let key = master
+            .derive_path(&path.parse::<DerivationPath>().unwrap())
+            .unwrap();
+        assert_eq!(key.to_string(), xprv);
+        assert_eq!(key.extended_public_key().unwrap().to_string(), xpub);
+        assert_eq!(xprv.parse::<ExtendedPrivateKey>().unwrap(), key);
+        assert_eq!(
+            xpub.parse::<ExtendedPublicKey>().unwrap(),
+            key.extended_public_key().unwrap()
+        );
+    }
+}
+
+#[test]
+fn test_public_derivation() {
+    let xpub = "xpub6ASuArnXKPbfEwhqN6e3mwBcDTgzisQN1wXN9BJcM47sSikHjJf3UFHKkNAWbWMiGj7Wf5uMash7SyYq527Hqck2AxYysAA7xmALppuCkwQ"
+        .parse::<ExtendedPublicKey>()
+        .unwrap();
+    let expected = "xpub6H1LXWLaKsWFhvm6RVpEL9P4KfRZSW7abD2ttkWP3SSQvnyA8FSVqNTEcYFgJS2UaFcxupHiYkro49S8yGasTvXEYBVPamhGW6cFJodrTHy";
+    assert!(xpub.derive_child(2 + HARDENED).is_err());
+    let xprv = "xprv9wTYmMFdV23N2TdNG573QoEsfRrWKQgWeibmLntzniatZvR9BmLnvSxqu53Kw1UmYPxLgboyZQaXwTCg8MSY3H2EU4pWcQDnRnrVA1xe8fs"
+        .parse::<ExtendedPrivateKey>()
+        .unwrap();
+    let child = xprv
+        .derive_child(2 + HARDENED)
+        .unwrap()
+        .extended_public_key()
+        .unwrap();
+    assert_eq!(
+        child
+            .derive_path(&"2/1000000000".parse::<DerivationPath>().unwrap())
+            .unwrap()
+            .to_string(),
+        expected
+    );
+}
+
+#[test]
+fn test_invalid_extended_key() {
+    let xpub = "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet7";
+    assert!(xpub.parse::<ExtendedPublicKey>().is_err());
+    let xprv = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi";
+    assert!(xprv.parse::<ExtendedPublicKey>().is_err());
+}
@@ -0,0 +1,501 @@
+use crate::core::address::{p2pkh_script, p2sh_script, p2tr_script};
+use crate::core::interpreter::{
+    decode_num, verify_script, MessageChecker, ScriptError, SigVersion, SignatureChecker,
+    TransactionChecker, ANNEX_TAG, STANDARD_VERIFY_FLAGS, VERIFY_LOW_S, VERIFY_MINIMALDATA,
+    VERIFY_NONE, VERIFY_NULLDUMMY, VERIFY_P2SH, VERIFY_TAPROOT,
+};
+use crate::core::s256ecc::{S256PrivateKey, S256Signature};
+use crate::core::script::{encode_num, Opcode, Script};
+use crate::core::sha256ser::Sha256Ripemd160;
+use crate::core::sighash::{SighashCache, SIGHASH_ALL, SIGHASH_DEFAULT};
+use crate::core::taproot::{tap_branch_hash, tap_leaf_hash, TAPSCRIPT_LEAF_VERSION};
+use crate::core::tx::{OutPoint, Tx, TxIn, TxOut, SEQUENCE_FINAL};
+use crate::ser::chained_hash::ChainedCompute;
+use crate::ser::hex;
+use bnum::types::U256;
+use std::cell::RefCell;
+
+fn run(asm: &str, flags: u32) -> Result<(), ScriptError> {
+    let script_pubkey = asm.parse::<Script>().unwrap();
+    verify_script(
+        &Script::new(),
+        &script_pubkey,
+        &[],
+        flags,
+        &MessageChecker(U256::ZERO),
+    )
+}
+
+fn sign(pk: &S256PrivateKey, z: U256) -> Vec<u8> {
+    [pk.sign(z).der_encoded(), vec![0x01]].concat()
+}
+
+struct ScriptCodeChecker(RefCell<Vec<Script>>);
+
+impl SignatureChecker for ScriptCodeChecker {
+    fn check_ecdsa_signature(
+        &self,
+        _: &[u8],
+        _: &[u8],
+        script_code: &Script,
+        _: SigVersion,
+    ) -> bool {
+        self.0.borrow_mut().push(script_code.clone());
+        true
+    }
+}
+
+#[test]
+fn test_script_num() {
+    assert_eq!(encode_num(0), Vec::<u8>::new());
+    assert_eq!(encode_num(-1), vec![0x81]);
+    assert_eq!(encode_num(128), vec![0x80, 0x00]);
+    assert_eq!(encode_num(-128), vec![0x80, 0x80]);
+    assert_eq!(encode_num(-255), vec![0xff, 0x80]);
+    for n in [0, 1, -1, 127, 128, -128, 255, 256, 0x7fffffff, -0x7fffffff] {
+        assert_eq!(decode_num(&encode_num(n), true, 4), Ok(n));
+    }
+    assert_eq!(decode_num(&[0x00], true, 4), Err(ScriptError::MinimalData));
+    assert_eq!(decode_num(&[0x00], false, 4), Ok(0));
+    assert_eq!(
+        decode_num(&[0x01, 0x80], true, 4),
+        Err(ScriptError::MinimalData)
+    );
+    assert_eq!(
+        decode_num(&[0, 0, 0, 0, 1], false, 4),
+        Err(ScriptError::NumOverflow)
+    );
+}
+
+#[test]
+fn test_arithmetic_and_stack() {
+    assert_eq!(run("OP_2 OP_3 OP_ADD OP_5 OP_EQUAL", VERIFY_NONE), Ok(()));
+    assert_eq!(
+        run("OP_2 OP_3 OP_SUB OP_1NEGATE OP_NUMEQUAL", VERIFY_NONE),
+        Ok(())
+    );
+    assert_eq!(
+        run(
+            "OP_1 OP_2 OP_SWAP OP_1 OP_EQUALVERIFY OP_2 OP_EQUAL",
+            VERIFY_NONE
+        ),
+        Ok(())
+    );
+    assert_eq!(
+        run(
+            "OP_1 OP_2 OP_3 OP_ROT OP_1 OP_EQUALVERIFY OP_DEPTH OP_2 OP_EQUAL",
+            VERIFY_NONE
+        ),
+        Ok(())
+    );
+    assert_eq!(
+        run(
+            "OP_5 OP_2 OP_10 OP_WITHIN OP_VERIFY OP_7 OP_3 OP_MIN",
+            VERIFY_NONE
+        ),
+        Ok(())
+    );
+    assert_eq!(
+        run("OP_1 OP_2 OP_EQUALVERIFY", VERIFY_NONE),
+        Err(ScriptError::EqualVerify)
+    );
+    assert_eq!(run("OP_0", VERIFY_NONE), Err(ScriptError::EvalFalse));
+    assert_eq!(
+        run("OP_DROP", VERIFY_NONE),
+        Err(ScriptError::InvalidStackOperation)
+    );
+    assert_eq!(
+        run("OP_FROMALTSTACK", VERIFY_NONE),
+        Err(ScriptError::InvalidAltstackOperation)
+    );
+}
+
+#[test]
+fn test_conditionals() {
+    assert_eq!(
+        run(
+            "OP_1 OP_IF OP_2 OP_ELSE OP_3 OP_ENDIF OP_2 OP_EQUAL",
+            VERIFY_NONE
+        ),
+        Ok(())
+    );
+    assert_eq!(
+        run(
+            "OP_0 OP_IF OP_2 OP_ELSE OP_3 OP_ENDIF OP_3 OP_EQUAL",
+            VERIFY_NONE
+        ),
+        Ok(())
+    );
+    assert_eq!(
+        run(
+            "OP_0 OP_NOTIF OP_0 OP_IF OP_RETURN OP_ENDIF OP_1 OP_ENDIF",
+            VERIFY_NONE
+        ),
+        Ok(())
+    );
+    assert_eq!(
+        run("OP_1 OP_IF OP_1", VERIFY_NONE),
+        Err(ScriptError::UnbalancedConditional)
+    );
+    assert_eq!(
+        run("OP_1 OP_ENDIF", VERIFY_NONE),
+        Err(ScriptError::UnbalancedConditional)
+    );
+    assert_eq!(
+        run("OP_0 OP_IF OP_CAT OP_ENDIF OP_1", VERIFY_NONE),
+        Err(ScriptError::DisabledOpcode)
+    );
+    assert_eq!(
+        run("OP_0 OP_IF OP_VERIF OP_ENDIF OP_1", VERIFY_NONE),
+        Err(ScriptError::BadOpcode)
+    );
+    assert_eq!(run("OP_0 OP_IF OP_VER OP_ENDIF OP_1", VERIFY_NONE), Ok(()));
+    assert_eq!(
+        run("OP_1 OP_RETURN", VERIFY_NONE),
+        Err(ScriptError::OpReturn)
+    );
+}
+
+#[test]
+fn test_limits() {
+    let nops = vec!["OP_NOP"; 201].join(" ");
+    assert_eq!(run(&format!("{} OP_1", nops), VERIFY_NONE), Ok(()));
+    assert_eq!(
+        run(&format!("{} OP_NOP OP_1", nops), VERIFY_NONE),
+        Err(ScriptError::OpCount)
+    );
+    let mut script = Script::new();
+    script.push_data(&[0xab; 521]);
+    assert_eq!(
+        run(&script.to_string(), VERIFY_NONE),
+        Err(ScriptError::PushSize)
+    );
+    let pushes = vec!["OP_1"; 1001].join(" ");
+    assert_eq!(run(&pushes[4..], VERIFY_NONE), Ok(()));
+    assert_eq!(run(&pushes, VERIFY_NONE), Err(ScriptError::StackSize));
+    assert_eq!(run("OP_PUSHDATA1 01", VERIFY_NONE), Ok(()));
+    assert_eq!(
+        run("OP_PUSHDATA1 01", VERIFY_MINIMALDATA),
+        Err(ScriptError::MinimalData)
+    );
+}
+
+#[test]
+fn test_hash_ops() {
+    let sec = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+    let asm = format!(
+        "{} OP_HASH160 751e76e8199196d454941c45d1b3a323f1433bd6 OP_EQUAL",
+        sec
+    );
+    assert_eq!(run(&asm, VERIFY_NONE), Ok(()));
+    assert_eq!(
+        run(
+            "OP_0 OP_SHA256 e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855 OP_EQUAL",
+            VERIFY_NONE
+        ),
+        Ok(())
+    );
+    assert_eq!(
+        run(
+            "OP_0 OP_SHA1 da39a3ee5e6b4b0d3255bfef95601890afd80709 OP_EQUAL",
+            VERIFY_NONE
+        ),
+        Ok(())
+    );
+    assert_eq!(
+        run(
+            "OP_0 OP_HASH256 5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456 OP_EQUAL",
+            VERIFY_NONE
+        ),
+        Ok(())
+    );
+}
+
+#[test]
+fn test_checksig() {
+    let z = U256::parse_str_radix(
+        "7c076ff316692a3d7eb3c3bb0f8b1488cf72e1afcd929e29307032997a838a3d",
+        16,
+    );
+    let script_sig = "3045022000eff69ef2b1bd93a66ed5219add4fb51e11a840f404876325a1e8ffe0529a2c022100c7207fee197d27c618aea621406f6bf5ef6fca38681d82b2f06fddbdce6feab601 04887387e452b8eacc4acfde10d9aaf7f6d9a0f975aabb10d006e4da568744d06c61de6d95231cd89026e286df3b6ae4a894a3378e393e93a0f45b666329a0ae34"
+        .parse::<Script>()
+        .unwrap();
+    let script_pubkey = "OP_CHECKSIG".parse::<Script>().unwrap();
+    let checker = MessageChecker(z);
+    assert_eq!(
+        verify_script(&script_sig, &script_pubkey, &[], VERIFY_NONE, &checker),
+        Ok(())
+    );
+    assert_eq!(
+        verify_script(&script_sig, &script_pubkey, &[], VERIFY_LOW_S, &checker),
+        Err(ScriptError::SigHighS)
+    );
+    assert_eq!(
+        verify_script(
+            &script_sig,
+            &script_pubkey,
+            &[],
+            VERIFY_NONE,
+            &MessageChecker(z + U256::ONE)
+        ),
+        Err(ScriptError::EvalFalse)
+    );
+}
+
+#[test]
+fn test_p2pkh_spend() {
+    let pk = S256PrivateKey::from_value(U256::from(12345u32));
+    let z = U256::from(0xdeadbeefu32);
+    let point = pk.point();
+    let mut script_sig = Script::new();
+    script_sig
+        .push_data(&sign(&pk, z))
+        .push_data(&point.sec(true));
+    let script_pubkey = Script::from(p2pkh_script(&point.hash160(true)));
+    assert_eq!(
+        verify_script(
+            &script_sig,
+            &script_pubkey,
+            &[],
+            STANDARD_VERIFY_FLAGS,
+            &MessageChecker(z)
+        ),
+        Ok(())
+    );
+    assert_eq!(
+        verify_script(
+            &script_sig,
+            &script_pubkey,
+            &[],
+            STANDARD_VERIFY_FLAGS,
+            &MessageChecker(z + U256::ONE)
+        ),
+        Err(ScriptError::NullFail)
+    );
+    let mut wrong_key = Script::new();
+    wrong_key
+        .push_data(&sign(&pk, z))
+        .push_data(&point.sec(false));
+    assert_eq!(
+        verify_script(
+            &wrong_key,
+            &script_pubkey,
+            &[],
+            STANDARD_VERIFY_FLAGS,
+            &MessageChecker(z)
+        ),
+        Err(ScriptError::EqualVerify)
+    );
+}
+
+#[test]
+fn test_p2sh_multisig_spend() {
+    let keys = [1u32, 2, 3].map(|k| S256PrivateKey::from_value(U256::from(k * 1000)));
+    let z = U256::from(42u32);
+    let mut redeem_script = Script::new();
+    redeem_script.push_int(2);
+    for key in &keys {
+        redeem_script.push_data(&key.point().sec(true));
+    }
+    redeem_script.push_int(3).push_opcode(Opcode::CheckMultiSig);
+    let script_pubkey = Script::from(p2sh_script(&Sha256Ripemd160::compute(&redeem_script)));
+
+    let mut script_sig = Script::new();
+    script_sig
+        .push_opcode(Opcode::Op0)
+        .push_data(&sign(&keys[0], z))
+        .push_data(&sign(&keys[2], z))
+        .push_data(&redeem_script);
+    let checker = MessageChecker(z);
+    assert_eq!(
+        verify_script(
+            &script_sig,
+            &script_pubkey,
+            &[],
+            STANDARD_VERIFY_FLAGS,
+            &checker
+        ),
+        Ok(())
+    );
+
+    let mut out_of_order = Script::new();
+    out_of_order
+        .push_opcode(Opcode::Op0)
+        .push_data(&sign(&keys[2], z))
+        .push_data(&sign(&keys[0], z))
+        .push_data(&redeem_script);
+    assert_eq!(
+        verify_script(&out_of_order, &script_pubkey, &[], VERIFY_P2SH, &checker),
+        Err(ScriptError::EvalFalse)
+    );
+
+    let mut bad_dummy = Script::new();
+    bad_dummy
+        .push_opcode(Opcode::Op1)
+        .push_data(&sign(&keys[0], z))
+        .push_data(&sign(&keys[1], z))
+        .push_data(&redeem_script);
+    assert_eq!(
+        verify_script(&bad_dummy, &script_pubkey, &[], VERIFY_P2SH, &checker),
+        Ok(())
+    );
+    assert_eq!(
+        verify_script(
+            &bad_dummy,
+            &script_pubkey,
+            &[],
+            VERIFY_P2SH | VERIFY_NULLDUMMY,
+            &checker
+        ),
+        Err(ScriptError::SigNullDummy)
+    );
+}
+
+#[test]
+fn test_der_round_trip() {
+    let pk = S256PrivateKey::from_value(U256::from(12345u32));
+    let signature = pk.sign(U256::from(99u32));
+    let der = signature.der_encoded();
+    assert_eq!(der[0], 0x30);
+    assert_eq!(der[1] as usize, der.len() - 2);
+    assert_eq!(der[2..], signature.der()[..]);
+    assert_eq!(S256Signature::parse_der(&der).unwrap(), signature);
+    assert!(S256Signature::parse_der(&der[..der.len() - 1]).is_err());
+    assert!(S256Signature::parse_der(&hex::decode("300602010002010f").unwrap()).is_ok());
+    assert!(S256Signature::parse_der(&hex::decode("3007020200010201ff").unwrap()).is_err());
+}
+
+#[test]
+fn test_find_and_delete_small_signatures() {
+    let pubkey = format!("2102{}", hex::encode(&[0x11; 32]));
+    for (script_sig, script_pubkey) in [
+        ("000101", format!("51{}51ae", pubkey)),
+        ("0181", format!("{}4f75ac", pubkey)),
+    ] {
+        let script_pubkey = Script::from(hex::decode(&script_pubkey).unwrap());
+        let checker = ScriptCodeChecker(RefCell::new(Vec::new()));
+        verify_script(
+            &Script::from(hex::decode(script_sig).unwrap()),
+            &script_pubkey,
+            &[],
+            VERIFY_NONE,
+            &checker,
+        )
+        .unwrap();
+        // The signature is not OP_1 or OP_1NEGATE, so nothing is deleted
+        assert_eq!(checker.0.into_inner(), vec![script_pubkey]);
+    }
+}
+
+#[test]
+fn test_taproot_spend() {
+    let internal_key = S256PrivateKey::from_value(U256::from(8675309u32));
+    let tapscript_leaf = tap_leaf_hash(TAPSCRIPT_LEAF_VERSION, &[0x51]);
+    let future_leaf = tap_leaf_hash(0xc2, &[0x51]);
+    let merkle_root = tap_branch_hash(&tapscript_leaf, &future_leaf);
+    let output_key = internal_key.point().tap_tweak(Some(merkle_root)).unwrap();
+    let script_pubkey = Script::from(p2tr_script(&output_key.xonly()));
+    let spent = vec![TxOut::new(50_000, script_pubkey.clone())];
+    let tx = Tx::new(
+        2,
+        vec![TxIn::new(
+            OutPoint::new([1; 32], 0),
+            Script::new(),
+            SEQUENCE_FINAL,
+        )],
+        vec![TxOut::new(40_000, Script::from(vec![0x51]))],
+        0,
+    );
+    let checker = TransactionChecker::with_spent_outputs(&tx, 0, &spent);
+    let verify = |witness: &[Vec<u8>], flags: u32| {
+        verify_script(&Script::new(), &script_pubkey, witness, flags, &checker)
+    };
+
+    // Key path
+    let tweaked = internal_key.tap_tweak(Some(merkle_root)).unwrap();
+    let cache = SighashCache::with_spent_outputs(&tx, &spent);
+    let msg = cache
+        .taproot_key_spend_sighash(0, SIGHASH_DEFAULT, None)
+        .unwrap();
+    let signature = tweaked.sign_schnorr(&msg, &[0u8; 32]).to_vec();
+    assert_eq!(
+        verify(std::slice::from_ref(&signature), STANDARD_VERIFY_FLAGS),
+        Ok(())
+    );
+    let msg = cache
+        .taproot_key_spend_sighash(0, SIGHASH_ALL, None)
+        .unwrap();
+    let signature_all = [
+        &tweaked.sign_schnorr(&msg, &[0u8; 32])[..],
+        &[SIGHASH_ALL as u8],
+    ]
+    .concat();
+    assert_eq!(verify(&[signature_all], STANDARD_VERIFY_FLAGS), Ok(()));
+
+    let mut tampered = signature.clone();
+    tampered[10] ^= 1;
+    assert_eq!(
+        verify(&[tampered.clone()], STANDARD_VERIFY_FLAGS),
+        Err(ScriptError::SchnorrSig)
+    );
+    assert_eq!(
+        verify(&[tampered], STANDARD_VERIFY_FLAGS & !VERIFY_TAPROOT),
+        Ok(())
+    );
+    for hash_type in [0x00, 0x04, 0x80, 0x84] {
+        assert_eq!(
+            verify(
+                &[[&signature[..], &[hash_type]].concat()],
+                STANDARD_VERIFY_FLAGS
+            ),
+            Err(ScriptError::SchnorrSigHashType)
+        );
+    }
+    assert_eq!(
+        verify(&[signature[..63].to_vec()], STANDARD_VERIFY_FLAGS),
+        Err(ScriptError::SchnorrSigSize)
+    );
+    assert_eq!(
+        verify(&[], STANDARD_VERIFY_FLAGS),
+        Err(ScriptError::WitnessProgramWitnessEmpty)
+    );
+    // The signature commits to the annex
+    assert_eq!(
+        verify(&[signature, vec![ANNEX_TAG]], STANDARD_VERIFY_FLAGS),
+        Err(ScriptError::SchnorrSig)
+    );
+
+    // Script path
+    let parity = !output_key.has_even_y() as u8;
+    let xonly = internal_key.point().xonly();
+    let tapscript_control = [&[TAPSCRIPT_LEAF_VERSION | parity][..], &xonly, &future_leaf].concat();
+    let future_control = [&[0xc2 | parity][..], &xonly, &tapscript_leaf].concat();
+    assert_eq!(
+        verify(&[vec![0x51], future_control.clone()], STANDARD_VERIFY_FLAGS),
+        Ok(())
+    );
+    assert_eq!(
+        verify(
+            &[vec![0x51], tapscript_control.clone()],
+            STANDARD_VERIFY_FLAGS
+        ),
+        Err(ScriptError::TapscriptUnsupported)
+    );
+    let mut wrong_parity = future_control.clone();
+    wrong_parity[0] ^= 1;
+    assert_eq!(
+        verify(&[vec![0x51], wrong_parity], STANDARD_VERIFY_FLAGS),
+        Err(ScriptError::WitnessProgramMismatch)
+    );
+    assert_eq!(
+        verify(&[vec![0x52], future_control.clone()], STANDARD_VERIFY_FLAGS),
+        Err(ScriptError::WitnessProgramMismatch)
+    );
+    assert_eq!(
+        verify(
+            &[vec![0x51], future_control[..64].to_vec()],
+            STANDARD_VERIFY_FLAGS
+        ),
+        Err(ScriptError::TaprootWrongControlSize)
+    );
+}
@@ -0,0 +1,205 @@
+use crate::core::block::{merkle_root, Block, BlockHeader};
+use crate::core::merkle::{MerkleBlock, MerkleBranch, PartialMerkleTree};
+use crate::core::tx::Tx;
+use crate::ser::hex;
+
+// Programming Bitcoin merkleblock, one match out of 3519 transactions
+const MERKLE_BLOCK: &str = "00000020df3b053dc46f162a9b00c7f0d5124e2676d47bbe7c5d0793a500000000000000ef445fef2ed495c275892206ca533e7411907971013ab83e3b47bd0d692d14d4dc7c835b67d8001ac157e670bf0d00000aba412a0d1480e370173072c9562becffe87aa661c1e4a6dbc305d38ec5dc088a7cf92e6458aca7b32edae818f9c2c98c37e06bf72ae0ce80649a38655ee1e27d34d9421d940b16732f24b94023e9d572a7f9ab8023434a4feb532d2adfc8c2c2158785d1bd04eb99df2e86c54bc13e139862897217400def5d72c280222c4cbaee7261831e1550dbb8fa82853e9fe506fc5fda3f7b919d8fe74b6282f92763cef8e625f977af7c8619c32a369b832bc2d051ecd9c73c51e76370ceabd4f25097c256597fa898d404ed53425de608ac6bfe426f6e2bb457f1c554866eb69dcb8d6bf6f880e9a59b3cd053e6c7060eeacaacf4dac6697dac20e4bd3f38a2ea2543d1ab7953e3430790a9f81e1c67f5b58c825acf46bd02848384eebe9af917274cdfbb1a28a5d58a23a17977def0de10d644258d9c54f886d47d293a411cb6226103b55635";
+
+const BOOK_TX: &str = "0100000001813f79011acb80925dfe69b3def355fe914bd1d96a3f5f71bf8303c6a989c7d1000000006b483045022100ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320b0277457c98f02207a986d955c6e0cb35d446a89d3f56100f4d7f67801c31967743a9c8e10615bed01210349fc4e631e3624a545de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278afeffffff02a135ef01000000001976a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88ac99c39800000000001976a9141c4bc762dd5423e332166702cb75f40df79fea1288ac19430600";
+
+fn hash(hex_str: &str) -> [u8; 32] {
+    hex::decode(hex_str).unwrap().try_into().unwrap()
+}
+
+fn leaves(count: u8) -> Vec<[u8; 32]> {
+    (1..=count).map(|n| [n; 32]).collect()
+}
+
+#[test]
+fn test_merkle_branch() {
+    // Block 100000
+    let txids = [
+        "8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87",
+        "fff2525b8931402dd09222c50775608f75787bd2b87e56995a7bdd30f79702c4",
+        "6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4",
+        "e9a66845e05d5abc0ad04ec80f774a7e585c6e8db975962d069a522137b80c1d",
+    ]
+    .map(hash);
+    let root = hash("f3e94742aca4b5ef85488dc37c06c3282295ffec960994b2c0d5ac2a25a95766");
+    for (index, txid) in txids.iter().enumerate() {
+        let branch = MerkleBranch::new(&txids, index as u32).unwrap();
+        assert_eq!(branch.index(), index as u32);
+        assert_eq!(branch.hashes().len(), 2);
+        assert!(branch.verify(txid, &root));
+        assert!(!branch.verify(&txids[(index + 1) % 4], &root));
+    }
+    let branch = MerkleBranch::new(&txids, 2).unwrap();
+    assert_eq!(branch.hashes()[0], txids[3]);
+
+    // Odd levels pair the last node with itself
+    for count in [1, 5, 7, 11] {
+        let leaves = leaves(count);
+        let root = merkle_root(&leaves).0;
+        for (index, leaf) in leaves.iter().enumerate() {
+            assert!(MerkleBranch::new(&leaves, index as u32)
+                .unwrap()
+                .verify(leaf, &root));
+        }
+    }
+    assert!(MerkleBranch::new(&txids, 4).is_err());
+    assert!(MerkleBranch::new(&[], 0).is_err());
+}
+
+#[test]
+fn test_parse_merkle_block() {
+    let merkle_block: MerkleBlock = MERKLE_BLOCK.parse().unwrap();
+    assert_eq!(
+        merkle_block.header().id(),
+        "00000000000000cac712b726e4326e596170574c01a16001692510c44025eb30"
+    );
+    assert_eq!(merkle_block.tree().total(), 3519);
+    assert_eq!(merkle_block.tree().hashes().len(), 10);
+    assert_eq!(merkle_block.tree().flags().len(), 24);
+    assert_eq!(merkle_block.to_string(), MERKLE_BLOCK);
+
+    let (root, matches) = merkle_block.tree().extract_matches().unwrap();
+    assert_eq!(root, merkle_block.header().merkle_root());
+    assert_eq!(
+        matches,
+        vec![(
+            hash("6122b61c413a297dd486f8549c8d2544d610def0de7779a1238ad5a5281abbdf"),
+            3518
+        )]
+    );
+    assert_eq!(merkle_block.verify().unwrap(), vec![matches[0].0]);
+
+    let bytes = hex::decode(MERKLE_BLOCK).unwrap();
+    assert!(MerkleBlock::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    assert!(MerkleBlock::from_bytes(&[&bytes[..], &[0]].concat()).is_err());
+
+    // Any bit flip in the proof breaks it
+    let mut bad_hash = bytes.clone();
+    bad_hash[100] ^= 1;
+    assert!(MerkleBlock::from_bytes(&bad_hash)
+        .unwrap()
+        .verify()
+        .is_err());
+    let mut bad_flags = bytes.clone();
+    let last = bad_flags.len() - 1;
+    bad_flags[last] ^= 0x01;
+    assert!(MerkleBlock::from_bytes(&bad_flags)
+        .unwrap()
+        .verify()
+        .is_err());
+}
+
+#[test]
+fn test_build_partial_merkle_tree() {
+    for count in [1, 2, 3, 7, 16, 17, 40] {
+        let txids = leaves(count);
+        let root = merkle_root(&txids).0;
+        for pattern in [0usize, 1, 0b101, 0b1100_0011, usize::MAX] {
+            let matches: Vec<bool> = (0..txids.len())
+                .map(|i| pattern >> (i % 32) & 1 == 1)
+                .collect();
+            let tree = PartialMerkleTree::from_txids(&txids, &matches).unwrap();
+            let (tree_root, found) = tree.extract_matches().unwrap();
+            assert_eq!(tree_root, root);
+            let expected: Vec<([u8; 32], u32)> = txids
+                .iter()
+                .zip(&matches)
+                .enumerate()
+                .filter(|(_, (_, matched))| **matched)
+                .map(|(index, (txid, _))| (*txid, index as u32))
+                .collect();
+            assert_eq!(found, expected);
+
+            let parsed = PartialMerkleTree::parse(&mut &tree.serialize()[..]).unwrap();
+            assert_eq!(parsed.extract_matches().unwrap(), (tree_root, found));
+            assert_eq!(parsed.serialize(), tree.serialize());
+        }
+    }
+    assert!(PartialMerkleTree::from_txids(&[], &[]).is_err());
+    assert!(PartialMerkleTree::from_txids(&leaves(2), &[true]).is_err());
+}
+
+#[test]
+fn test_invalid_partial_merkle_tree() {
+    let tree =
+        PartialMerkleTree::from_txids(&leaves(5), &[false, true, false, false, true]).unwrap();
+    let serialized = tree.serialize();
+
+    // Total of zero
+    let mut bytes = serialized.clone();
+    bytes[..4].copy_from_slice(&0u32.to_le_bytes());
+    let parsed = PartialMerkleTree::parse(&mut &bytes[..]).unwrap();
+    assert!(parsed.extract_matches().is_err());
+
+    // A different transaction count changes the tree shape
+    let mut bytes = serialized.clone();
+    bytes[..4].copy_from_slice(&6u32.to_le_bytes());
+    let parsed = PartialMerkleTree::parse(&mut &bytes[..]).unwrap();
+    assert!(
+        parsed.extract_matches().map(|(root, _)| root)
+            != tree.extract_matches().map(|(root, _)| root)
+    );
+
+    // Missing and extra flag bytes
+    let flag_count = serialized.len() - 1 - tree.flags().len().div_ceil(8);
+    let mut bytes = serialized[..flag_count].to_vec();
+    bytes.push(0);
+    assert!(PartialMerkleTree::parse(&mut &bytes[..])
+        .unwrap()
+        .extract_matches()
+        .is_err());
+    let mut bytes = serialized.clone();
+    bytes[flag_count] += 1;
+    bytes.push(0);
+    assert!(PartialMerkleTree::parse(&mut &bytes[..])
+        .unwrap()
+        .extract_matches()
+        .is_err());
+
+    // An extra hash is never consumed
+    let mut bytes = serialized[..4].to_vec();
+    bytes.push(tree.hashes().len() as u8 + 1);
+    bytes.extend(&serialized[5..flag_count]);
+    bytes.extend([0x42; 32]);
+    bytes.extend(&serialized[flag_count..]);
+    assert!(PartialMerkleTree::parse(&mut &bytes[..])
+        .unwrap()
+        .extract_matches()
+        .is_err());
+
+    // Duplicated siblings are rejected even though they hash to a valid root
+    let mut txids = leaves(3);
+    txids.push(txids[2]);
+    let tree = PartialMerkleTree::from_txids(&txids, &[false, false, true, true]).unwrap();
+    assert!(tree
+        .extract_matches()
+        .unwrap_err()
+        .contains("identical siblings"));
+}
+
+#[test]
+fn test_merkle_block_from_block() {
+    let coinbase: Tx = "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff0704ffff001d0104ffffffff0100f2052a0100000043410496b538e853519c726a2c91e61ec11600ae1390813a627c66fb8be7947be63c52da7589379515d4e0a604f8141781e62294721166bf621e73a82cbf2342c858eeac00000000".parse().unwrap();
+    let payment: Tx = BOOK_TX.parse().unwrap();
+    let txs = vec![coinbase.clone(), payment.clone()];
+    let hashes: Vec<[u8; 32]> = txs.iter().map(Tx::hash).collect();
+    let header = BlockHeader::new(1, [0; 32], merkle_root(&hashes).0, 0, 0x207f_ffff, 0);
+    let block = Block::new(header, txs);
+
+    let merkle_block = MerkleBlock::from_block(&block, &[payment.hash()]).unwrap();
+    assert_eq!(merkle_block.verify().unwrap(), vec![payment.hash()]);
+    let reparsed: MerkleBlock = merkle_block.to_string().parse().unwrap();
+    assert_eq!(reparsed.verify().unwrap(), vec![payment.hash()]);
+
+    let merkle_block = MerkleBlock::from_block(&block, &[]).unwrap();
+    assert!(merkle_block.verify().unwrap().is_empty());
+
+    let other = BlockHeader::new(1, [0; 32], coinbase.hash(), 0, 0x207f_ffff, 0);
+    let mismatched = MerkleBlock::new(other, merkle_block.tree().clone());
+    assert!(mismatched.verify().unwrap_err().contains("mismatch"));
+}
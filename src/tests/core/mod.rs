@@ -1 +1,18 @@
+mod account;
+mod block;
+mod builder;
+mod chain;
+mod coin_selection;
+mod descriptor;
+mod hd;
+mod interpreter;
+mod merkle;
+mod network;
+mod psbt;
 mod s256ecc;
+mod script;
+mod sighash;
+mod taproot;
+mod tx;
+mod utxo;
+mod weight;
@@ -0,0 +1,61 @@
+use crate::core::address::{address_from_script, script_from_address};
+use crate::core::network::Network;
+use crate::core::s256ecc::S256PrivateKey;
+use crate::ser::hex;
+use bnum::types::U256;
+
+#[test]
+fn test_network_names() {
+    for network in Network::ALL {
+        assert_eq!(network.to_string().parse::<Network>().unwrap(), network);
+        assert_eq!(Network::from_magic(network.magic()), Some(network));
+    }
+    assert_eq!("testnet3".parse::<Network>().unwrap(), Network::Testnet);
+    assert!("litecoin".parse::<Network>().is_err());
+}
+
+#[test]
+fn test_network_parameters() {
+    assert_eq!(Network::Mainnet.default_port(), 8333);
+    assert_eq!(Network::Regtest.default_port(), 18444);
+    assert_eq!(Network::Signet.bech32_hrp(), "tb");
+    assert_eq!(
+        hex::encode(&Network::Mainnet.genesis_hash()),
+        "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
+    );
+}
+
+#[test]
+fn test_regtest_encoding() {
+    let pk = S256PrivateKey::from_value(U256::ONE);
+    let point = pk.point();
+    assert_eq!(
+        point.p2wpkh_address(Network::Regtest),
+        "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080"
+    );
+    assert_eq!(
+        point.address(true, Network::Regtest),
+        "mrCDrCybB6J1vRfbwM5hemdJz73FwDBC8r"
+    );
+    assert_eq!(
+        pk.wif(true, Network::Signet),
+        "cMahea7zqjxrtgAbB7LSGbcQUr1uX1ojuat9jZodMN87JcbXMTcA"
+    );
+    let script = script_from_address(
+        "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080",
+        Network::Regtest,
+    )
+    .unwrap();
+    assert_eq!(
+        hex::encode(&script),
+        "0014751e76e8199196d454941c45d1b3a323f1433bd6"
+    );
+    assert!(address_from_script(&script, Network::Mainnet)
+        .unwrap()
+        .starts_with("bc1q"));
+    assert!(script_from_address(
+        "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080",
+        Network::Mainnet
+    )
+    .is_err());
+}
@@ -0,0 +1,471 @@
+use crate::core::address::{p2pkh_script, p2sh_script, p2tr_script, p2wpkh_script, p2wsh_script};
+use crate::core::descriptor::KeyOrigin;
+use crate::core::hd::ExtendedPrivateKey;
+use crate::core::interpreter::{verify_script, TransactionChecker, STANDARD_VERIFY_FLAGS};
+use crate::core::psbt::{Psbt, PsbtInput, PsbtOutput, PSBT_MAGIC};
+use crate::core::s256ecc::S256PrivateKey;
+use crate::core::script::Script;
+use crate::core::sha256ser::Sha256Ripemd160;
+use crate::core::tx::{OutPoint, Tx, TxIn, TxOut, SEQUENCE_FINAL};
+use crate::ser::base64;
+use crate::ser::chained_hash::ChainedCompute;
+use crate::ser::hex;
+use crate::ser::varint::encode_varint;
+use crate::tests::util::{keys, multisig_script};
+use bnum::types::U256;
+use sha2::{Digest, Sha256};
+
+// BIP174 creator test vector
+const CREATOR_PSBT: &str = "cHNidP8BAJoCAAAAAljoeiG1ba8MI76OcHBFbDNvfLqlyHV5JPVFiHuyq911AAAAAAD/////g40EJ9DsZQpoqka7CwmK6kQiwHGyyng1Kgd5WdB86h0BAAAAAP////8CcKrwCAAAAAAWABTYXCtx0AYLCcmIauuBXlCZHdoSTQDh9QUAAAAAFgAUAK6pouXw+HaliN9VRuh0LR2HAI8AAAAAAAAAAAA=";
+
+// BIP174 valid vector: one P2PKH input with a non-witness UTXO, outputs are empty
+const P2PKH_PSBT: &str = "cHNidP8BAHUCAAAAASaBcTce3/KF6Tet7qSze3gADAVmy7OtZGQXE8pCFxv2AAAAAAD+////AtPf9QUAAAAAGXapFNDFmQPFusKGh2DpD9UhpGZap2UgiKwA4fUFAAAAABepFDVF5uM7gyxHBQ8k0+65PJwDlIvHh7MuEwAAAQD9pQEBAAAAAAECiaPHHqtNIOA3G7ukzGmPopXJRjr6Ljl/hTPMti+VZ+UBAAAAFxYAFL4Y0VKpsBIDna89p95PUzSe7LmF/////4b4qkOnHf8USIk6UwpyN+9rRgi7st0tAXHmOuxqSJC0AQAAABcWABT+Pp7xp0XpdNkCxDVZQ6vLNL1TU/////8CAMLrCwAAAAAZdqkUhc/xCX/Z4Ai7NK9wnGIZeziXikiIrHL++E4sAAAAF6kUM5cluiHv1irHU6m80GfWx6ajnQWHAkcwRAIgJxK+IuAnDzlPVoMR3HyppolwuAJf3TskAinwf4pfOiQCIAGLONfc0xTnNMkna9b7QPZzMlvEuqFEyADS8vAtsnZcASED0uFWdJQbrUqZY3LLh+GFbTZSYG2YVi/jnF6efkE/IQUCSDBFAiEA0SuFLYXc2WHS9fSrZgZU327tzHlMDDPOXMMJ/7X85Y0CIGczio4OFyXBl/saiK9Z9R5E5CVbIBZ8hoQDHAXR8lkqASECI7cr7vCWXRC+B3jv7NYfysb3mk6haTkzgHNEZPhPKrMAAAAAAAAA";
+
+// BIP174 valid vector: a finalized P2PKH input and a P2SH-P2WPKH input with its redeem script
+const FINALIZED_PSBT: &str = "cHNidP8BAKACAAAAAqsJSaCMWvfEm4IS9Bfi8Vqz9cM9zxU4IagTn4d6W3vkAAAAAAD+////qwlJoIxa98SbghL0F+LxWrP1wz3PFTghqBOfh3pbe+QBAAAAAP7///8CYDvqCwAAAAAZdqkUdopAu9dAy+gdmI5x3ipNXHE5ax2IrI4kAAAAAAAAGXapFG9GILVT+glechue4O/p+gOcykWXiKwAAAAAAAEHakcwRAIgR1lmF5fAGwNrJZKJSGhiGDR9iYZLcZ4ff89X0eURZYcCIFMJ6r9Wqk2Ikf/REf3xM286KdqGbX+EhtdVRs7tr5MZASEDXNxh/HupccC1AaZGoqg7ECy0OIEhfKaC3Ibi1z+ogpIAAQEgAOH1BQAAAAAXqRQ1RebjO4MsRwUPJNPuuTycA5SLx4cBBBYAFIXRNTfy4mVAWjTbr6nj3aAfuCMIAAAA";
+
+fn txid(display_hex: &str) -> [u8; 32] {
+    hex::decode(display_hex).unwrap().try_into().unwrap()
+}
+
+fn script(hex_str: &str) -> Script {
+    Script::from(hex::decode(hex_str).unwrap())
+}
+
+#[test]
+fn test_creator() {
+    let inputs = [
+        (
+            "75ddabb27b8845f5247975c8a5ba7c6f336c4570708ebe230caf6db5217ae858",
+            0,
+        ),
+        (
+            "1dea7cd05979072a3578cab271c02244ea8a090bbb46aa680a65ecd027048d83",
+            1,
+        ),
+    ]
+    .map(|(id, vout)| TxIn::new(OutPoint::new(txid(id), vout), Script::new(), SEQUENCE_FINAL));
+    let outputs = vec![
+        TxOut::new(
+            149_990_000,
+            script("0014d85c2b71d0060b09c9886aeb815e50991dda124d"),
+        ),
+        TxOut::new(
+            100_000_000,
+            script("001400aea9a2e5f0f876a588df5546e8742d1d87008f"),
+        ),
+    ];
+    let tx = Tx::new(2, inputs.to_vec(), outputs, 0);
+    let psbt = Psbt::from_unsigned_tx(&tx).unwrap();
+    assert_eq!(psbt.to_string(), CREATOR_PSBT);
+    assert_eq!(CREATOR_PSBT.parse::<Psbt>().unwrap(), psbt);
+    assert_eq!(psbt.unsigned_tx().unwrap(), tx);
+
+    // An explicit PSBT_GLOBAL_VERSION of 0 survives a round trip
+    let unsigned_tx = tx.serialize_legacy();
+    let global_end = PSBT_MAGIC.len() + 2 + encode_varint(unsigned_tx.len() as u64).len();
+    let mut bytes = psbt.serialize();
+    bytes.splice(
+        global_end + unsigned_tx.len()..global_end + unsigned_tx.len(),
+        [0x01, 0xfb, 0x04, 0x00, 0x00, 0x00, 0x00],
+    );
+    let explicit = Psbt::from_bytes(&bytes).unwrap();
+    assert_eq!(explicit.version(), 0);
+    assert_eq!(explicit.serialize(), bytes);
+}
+
+#[test]
+fn test_parse_valid_vectors() {
+    let psbt: Psbt = P2PKH_PSBT.parse().unwrap();
+    assert_eq!(psbt.version(), 0);
+    assert_eq!(psbt.to_string(), P2PKH_PSBT);
+    let input = &psbt.inputs()[0];
+    assert_eq!(
+        hex::encode(&input.previous_output().txid()),
+        "f61b1742ca13176464adb3cb66050c00787bb3a4eead37e985f2df1e37718126"
+    );
+    assert_eq!(input.sequence(), 0xffff_fffe);
+    assert_eq!(input.spent_output().unwrap().amount(), 200_000_000);
+    assert!(input.witness_utxo().is_none());
+    assert!(!input.is_finalized());
+    assert_eq!(psbt.outputs().len(), 2);
+    assert_eq!(psbt.unsigned_tx().unwrap().locktime(), 1_257_139);
+
+    let psbt: Psbt = FINALIZED_PSBT.parse().unwrap();
+    assert_eq!(psbt.to_string(), FINALIZED_PSBT);
+    assert!(psbt.inputs()[0].is_finalized());
+    assert!(!psbt.inputs()[1].is_finalized());
+    assert!(!psbt.is_finalized());
+    assert!(psbt.extract_tx().is_err());
+    let redeem_script = psbt.inputs()[1].redeem_script().unwrap();
+    let witness_utxo = psbt.inputs()[1].witness_utxo().unwrap();
+    assert_eq!(
+        witness_utxo.script_pubkey().as_bytes(),
+        p2sh_script(&Sha256Ripemd160::compute(redeem_script))
+    );
+}
+
+#[test]
+fn test_parse_invalid() {
+    let valid = base64::decode(P2PKH_PSBT).unwrap();
+    let unsigned_tx = hex::decode("0200000001268171371edff285e937adeea4b37b78000c0566cbb3ad64641713ca42171bf60000000000feffffff02d3dff505000000001976a914d0c59903c5bac2868760e90fd521a4665aa7652088ac00e1f5050000000017a9143545e6e33b832c47050f24d3eeb93c9c03948bc787b32e1300").unwrap();
+    let psbt_with = |global: &[u8], rest: &[u8]| [&PSBT_MAGIC[..], global, rest].concat();
+    let global = |tx: &[u8]| [&[0x01, 0x00][..], &[tx.len() as u8], tx, &[0x00]].concat();
+    let empty_maps = [0x00, 0x00, 0x00];
+
+    assert!(Psbt::from_bytes(&psbt_with(&global(&unsigned_tx), &empty_maps)).is_ok());
+    let invalid: Vec<Vec<u8>> = vec![
+        // Network transaction instead of a PSBT
+        unsigned_tx.clone(),
+        // Missing output maps
+        valid[..valid.len() - 2].to_vec(),
+        // Trailing data
+        [&valid[..], &[0x00]].concat(),
+        // No unsigned transaction
+        psbt_with(&[0x00], &empty_maps),
+        // Unsigned transaction with a scriptSig
+        {
+            let mut tx = unsigned_tx.clone();
+            tx[41] = 0x01;
+            tx.insert(42, 0x51);
+            psbt_with(&global(&tx), &empty_maps)
+        },
+        // Duplicate global key
+        psbt_with(
+            &[
+                &global(&unsigned_tx)[..global(&unsigned_tx).len() - 1],
+                &global(&unsigned_tx),
+            ]
+            .concat(),
+            &empty_maps,
+        ),
+        // Unsigned transaction key with key data
+        psbt_with(
+            &[&[0x02, 0x00, 0x01][..], &global(&unsigned_tx)[2..]].concat(),
+            &empty_maps,
+        ),
+        // Unsupported PSBT version
+        psbt_with(
+            &[
+                &global(&unsigned_tx)[..global(&unsigned_tx).len() - 1],
+                &[0x01, 0xfb, 0x04, 0x01, 0, 0, 0, 0x00],
+            ]
+            .concat(),
+            &empty_maps,
+        ),
+        // Version 2 fields in a version 0 PSBT
+        psbt_with(
+            &[
+                &global(&unsigned_tx)[..global(&unsigned_tx).len() - 1],
+                &[0x01, 0x02, 0x04, 0x02, 0, 0, 0, 0x00],
+            ]
+            .concat(),
+            &empty_maps,
+        ),
+        psbt_with(
+            &global(&unsigned_tx),
+            &[0x01, 0x10, 0x04, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00],
+        ),
+        // Partial signature with an invalid public key
+        psbt_with(
+            &global(&unsigned_tx),
+            &[
+                &[0x22, 0x02, 0x05][..],
+                &[0x11; 32],
+                &[0x01, 0x30, 0x00, 0x00, 0x00],
+            ]
+            .concat(),
+        ),
+        // Sighash type of the wrong length
+        psbt_with(
+            &global(&unsigned_tx),
+            &[0x01, 0x03, 0x02, 0x01, 0x00, 0x00, 0x00, 0x00],
+        ),
+        // Non-witness UTXO that isn't the spent transaction
+        psbt_with(
+            &global(&unsigned_tx),
+            &[
+                &[0x01, 0x00, unsigned_tx.len() as u8][..],
+                &unsigned_tx,
+                &[0x00, 0x00, 0x00],
+            ]
+            .concat(),
+        ),
+    ];
+    for bytes in invalid {
+        assert!(Psbt::from_bytes(&bytes).is_err(), "{}", hex::encode(&bytes));
+    }
+    assert!("cHNidP8".parse::<Psbt>().is_err());
+}
+
+#[test]
+fn test_sign_combine_finalize_extract() {
+    let keys = keys(7);
+    let multisig = multisig_script(&keys[3..6], 2);
+    let funding_outputs = vec![
+        TxOut::new(
+            50_000,
+            Script::from(p2pkh_script(&keys[0].point().hash160(false))),
+        ),
+        TxOut::new(
+            60_000,
+            Script::from(p2wpkh_script(&keys[1].point().hash160(true))),
+        ),
+        TxOut::new(
+            70_000,
+            Script::from(p2sh_script(&Sha256Ripemd160::compute(
+                &keys[2].point().p2sh_p2wpkh_redeem_script(),
+            ))),
+        ),
+        TxOut::new(
+            80_000,
+            Script::from(p2wsh_script(&Sha256::digest(multisig.as_bytes()))),
+        ),
+        TxOut::new(
+            90_000,
+            Script::from(p2tr_script(
+                &keys[6].point().tap_tweak(None).unwrap().xonly(),
+            )),
+        ),
+    ];
+    let funding = Tx::new(
+        2,
+        vec![TxIn::new(
+            OutPoint::new([7; 32], 0),
+            Script::new(),
+            SEQUENCE_FINAL,
+        )],
+        funding_outputs.clone(),
+        0,
+    );
+    let inputs = (0..5)
+        .map(|vout| {
+            TxIn::new(
+                OutPoint::new(funding.hash(), vout),
+                Script::new(),
+                0xffff_fffd,
+            )
+        })
+        .collect();
+    let payment = TxOut::new(340_000, Script::from(p2wpkh_script(&[0x55; 20])));
+    let unsigned = Tx::new(2, inputs, vec![payment], 800_000);
+
+    // Updater
+    let mut psbt = Psbt::from_unsigned_tx(&unsigned).unwrap();
+    psbt.inputs_mut()[0]
+        .set_non_witness_utxo(funding.clone())
+        .unwrap();
+    for (index, output) in funding_outputs.iter().enumerate().skip(1) {
+        psbt.inputs_mut()[index].set_witness_utxo(output.clone());
+    }
+    psbt.inputs_mut()[2]
+        .set_redeem_script(Script::from(keys[2].point().p2sh_p2wpkh_redeem_script()));
+    psbt.inputs_mut()[3].set_witness_script(multisig.clone());
+    let mut wrong = funding.clone();
+    wrong.inputs_mut()[0].set_sequence(0);
+    assert!(psbt.inputs_mut()[1].set_non_witness_utxo(wrong).is_err());
+
+    // Missing UTXOs only block taproot inputs, which commit to every spent output
+    let mut partial = Psbt::from_unsigned_tx(&unsigned).unwrap();
+    partial.inputs_mut()[1].set_witness_utxo(funding_outputs[1].clone());
+    partial.inputs_mut()[4].set_witness_utxo(funding_outputs[4].clone());
+    assert_eq!(partial.sign(&keys[1]).unwrap(), 1);
+    assert_eq!(partial.sign(&keys[6]).unwrap(), 0);
+    for hash_type in [0x00, 0x04, 0x80, 0x84, 0x101] {
+        let mut invalid = partial.clone();
+        invalid.inputs_mut()[1].set_sighash_type(hash_type);
+        assert!(invalid.sign(&keys[1]).is_err());
+    }
+    partial.inputs_mut()[1].set_sighash_type(0x83);
+    assert_eq!(partial.sign(&keys[1]).unwrap(), 1);
+
+    // Two signers work on their own copies
+    let mut first = psbt.clone();
+    let mut second: Psbt = psbt.to_string().parse().unwrap();
+    assert_eq!(first.sign(&keys[0]).unwrap(), 1);
+    assert_eq!(first.sign(&keys[1]).unwrap(), 1);
+    assert_eq!(first.sign(&keys[3]).unwrap(), 1);
+    assert_eq!(second.sign(&keys[2]).unwrap(), 1);
+    assert_eq!(second.sign(&keys[5]).unwrap(), 1);
+    assert_eq!(second.sign(&keys[6]).unwrap(), 1);
+    assert_eq!(
+        second
+            .sign(&S256PrivateKey::from_value(U256::from(99u32)))
+            .unwrap(),
+        0
+    );
+    assert!(first.clone().finalize().is_err());
+
+    // Combiner
+    let mut combined: Psbt = first.to_string().parse().unwrap();
+    combined.combine(&second).unwrap();
+    assert_eq!(combined.inputs()[3].partial_sigs().len(), 2);
+    assert!(combined
+        .combine(&Psbt::from_unsigned_tx(&funding).unwrap())
+        .is_err());
+
+    // Finalizer and extractor
+    combined.finalize().unwrap();
+    assert!(combined.is_finalized());
+    assert!(combined.inputs()[3].partial_sigs().is_empty());
+    assert!(combined.inputs()[3].witness_script().is_none());
+    let reparsed: Psbt = combined.to_string().parse().unwrap();
+    assert_eq!(reparsed, combined);
+    let tx = combined.extract_tx().unwrap();
+    assert_eq!(tx.outputs(), unsigned.outputs());
+    assert_eq!(tx.locktime(), unsigned.locktime());
+    assert!(tx.inputs()[0].witness().is_empty());
+    assert_eq!(tx.inputs()[3].witness().len(), 4);
+
+    for (index, (input, spent)) in tx.inputs().iter().zip(&funding_outputs).enumerate() {
+        let checker = TransactionChecker::with_spent_outputs(&tx, index, &funding_outputs);
+        verify_script(
+            input.script_sig(),
+            spent.script_pubkey(),
+            input.witness(),
+            STANDARD_VERIFY_FLAGS,
+            &checker,
+        )
+        .unwrap();
+    }
+}
+
+#[test]
+fn test_version_2() {
+    let keys = keys(7);
+    let spent = TxOut::new(
+        25_000,
+        Script::from(p2wpkh_script(&keys[0].point().hash160(true))),
+    );
+    let mut psbt = Psbt::new_v2(2, Some(100));
+    let mut input = PsbtInput::new(OutPoint::new([3; 32], 1), None);
+    input.set_witness_utxo(spent.clone());
+    psbt.add_input(input).unwrap();
+    psbt.add_output(PsbtOutput::new(TxOut::new(
+        24_000,
+        spent.script_pubkey().clone(),
+    )))
+    .unwrap();
+    assert_eq!(psbt.locktime().unwrap(), 100);
+    assert_eq!(psbt.inputs()[0].sequence(), SEQUENCE_FINAL);
+
+    let reparsed: Psbt = psbt.to_string().parse().unwrap();
+    assert_eq!(reparsed, psbt);
+    assert_eq!(reparsed.version(), 2);
+
+    // Required locktimes override the fallback, heights win when both are possible
+    let mut second = PsbtInput::new(OutPoint::new([4; 32], 0), Some(0xffff_fffe));
+    second.set_witness_utxo(spent.clone());
+    second.set_required_height_locktime(840_000).unwrap();
+    second.set_required_time_locktime(1_700_000_000).unwrap();
+    assert!(second.set_required_height_locktime(LOCKTIME_TIME).is_err());
+    assert!(second.set_required_time_locktime(840_000).is_err());
+    psbt.add_input(second).unwrap();
+    assert_eq!(psbt.locktime().unwrap(), 840_000);
+    psbt.inputs_mut()[0]
+        .set_required_time_locktime(1_600_000_000)
+        .unwrap();
+    assert_eq!(psbt.locktime().unwrap(), 1_700_000_000);
+    let reparsed: Psbt = psbt.to_string().parse().unwrap();
+    assert_eq!(reparsed, psbt);
+
+    assert_eq!(psbt.sign(&keys[0]).unwrap(), 2);
+    psbt.finalize().unwrap();
+    let tx = psbt.extract_tx().unwrap();
+    assert_eq!(tx.locktime(), 1_700_000_000);
+    for index in 0..2 {
+        let checker = TransactionChecker::new(&tx, index, spent.amount());
+        let input = &tx.inputs()[index];
+        verify_script(
+            input.script_sig(),
+            spent.script_pubkey(),
+            input.witness(),
+            STANDARD_VERIFY_FLAGS,
+            &checker,
+        )
+        .unwrap();
+    }
+
+    // Converting to version 0 pins the locktime into the unsigned transaction
+    let mut v0 = psbt.clone();
+    v0.set_version(0).unwrap();
+    assert_eq!(v0.unsigned_tx().unwrap().locktime(), 1_700_000_000);
+    assert!(v0.add_output(PsbtOutput::new(spent.clone())).is_err());
+    let reparsed: Psbt = v0.to_string().parse().unwrap();
+    assert_eq!(reparsed, v0);
+    assert_eq!(reparsed.extract_tx().unwrap(), tx);
+    assert!(v0.set_version(1).is_err());
+
+    // Version 2 PSBTs must have the transaction version and counts
+    let bytes = psbt.serialize();
+    assert!(Psbt::from_bytes(&bytes).is_ok());
+    let missing_version = [&PSBT_MAGIC[..], &bytes[12..]].concat();
+    assert!(Psbt::from_bytes(&missing_version).is_err());
+}
+
+const LOCKTIME_TIME: u32 = 500_000_000;
+
+#[test]
+fn test_bip32_derivations_and_xpubs() {
+    let master = ExtendedPrivateKey::from_seed(&[0x42; 32], [0x04, 0x35, 0x83, 0x94]).unwrap();
+    let path = "m/84'/1'/0'/0/3".parse().unwrap();
+    let child = master.derive_path(&path).unwrap();
+    let account_path = "m/84'/1'/0'".parse().unwrap();
+    let account = master
+        .derive_path(&account_path)
+        .unwrap()
+        .extended_public_key()
+        .unwrap();
+    let origin = KeyOrigin::new(master.fingerprint(), path);
+
+    let spent = TxOut::new(
+        10_000,
+        Script::from(p2wpkh_script(&child.point().hash160(true))),
+    );
+    let tx = Tx::new(
+        2,
+        vec![TxIn::new(
+            OutPoint::new([1; 32], 0),
+            Script::new(),
+            SEQUENCE_FINAL,
+        )],
+        vec![TxOut::new(9_000, spent.script_pubkey().clone())],
+        0,
+    );
+    let mut psbt = Psbt::from_unsigned_tx(&tx).unwrap();
+    psbt.add_xpub(&account, KeyOrigin::new(master.fingerprint(), account_path));
+    psbt.inputs_mut()[0].set_witness_utxo(spent);
+    psbt.inputs_mut()[0].add_bip32_derivation(&child.point().sec(true), origin.clone());
+    psbt.outputs_mut()[0].add_bip32_derivation(&child.point().sec(true), origin.clone());
+
+    let reparsed: Psbt = psbt.to_string().parse().unwrap();
+    assert_eq!(reparsed, psbt);
+    let xpubs = reparsed.xpubs();
+    assert_eq!(xpubs.len(), 1);
+    assert_eq!(xpubs[0].0, account);
+    assert_eq!(
+        xpubs[0].1.to_string(),
+        format!("{}/84'/1'/0'", hex::encode(&master.fingerprint()))
+    );
+    assert_eq!(
+        reparsed.inputs()[0]
+            .bip32_derivations()
+            .get(&child.point().sec(true)),
+        Some(&origin)
+    );
+
+    // A signer finds its key through the recorded derivation
+    let (pubkey, origin) = reparsed.inputs()[0]
+        .bip32_derivations()
+        .iter()
+        .next()
+        .unwrap();
+    assert_eq!(origin.fingerprint(), master.fingerprint());
+    let key = master.derive_path(origin.path()).unwrap();
+    assert_eq!(&key.point().sec(true), pubkey);
+    let mut signed = reparsed.clone();
+    assert_eq!(signed.sign(&key.private_key()).unwrap(), 1);
+    signed.finalize().unwrap();
+    assert!(signed.inputs()[0].bip32_derivations().is_empty());
+    assert_eq!(signed.outputs()[0].bip32_derivations().len(), 1);
+}
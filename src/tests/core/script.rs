@@ -0,0 +1,129 @@
+use crate::core::script::{Instruction, Opcode, Script};
+use crate::core::tx::Tx;
+use crate::ser::hex;
+
+fn script(hex_str: &str) -> Script {
+    Script::from(hex::decode(hex_str).unwrap())
+}
+
+#[test]
+fn test_opcode_bytes() {
+    for byte in 0..=255u8 {
+        assert_eq!(Opcode::from_byte(byte).to_byte(), byte);
+    }
+    assert_eq!(Opcode::from_byte(0x76), Opcode::Dup);
+    assert_eq!(Opcode::from_byte(0x14), Opcode::PushBytes(20));
+    assert_eq!(Opcode::from_byte(0xba), Opcode::CheckSigAdd);
+    assert_eq!(Opcode::from_byte(0xff), Opcode::Invalid(0xff));
+    assert_eq!(Opcode::CheckLockTimeVerify.name(), "OP_CHECKLOCKTIMEVERIFY");
+    assert_eq!(Opcode::from_small_int(0), Some(Opcode::Op0));
+    assert_eq!(Opcode::from_small_int(16).unwrap().small_int(), Some(16));
+    assert_eq!(Opcode::from_small_int(17), None);
+}
+
+#[test]
+fn test_script_asm() {
+    let p2pkh = script("76a9141c4bc762dd5423e332166702cb75f40df79fea1288ac");
+    let asm =
+        "OP_DUP OP_HASH160 1c4bc762dd5423e332166702cb75f40df79fea12 OP_EQUALVERIFY OP_CHECKSIG";
+    assert_eq!(p2pkh.to_string(), asm);
+    assert_eq!(asm.parse::<Script>().unwrap(), p2pkh);
+    assert!(p2pkh.is_p2pkh());
+    assert!(!p2pkh.is_push_only());
+
+    let multisig = "OP_2 0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798 02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5 OP_2 OP_CHECKMULTISIG";
+    let script = multisig.parse::<Script>().unwrap();
+    assert_eq!(script.len(), 71);
+    assert_eq!(script.to_string(), multisig);
+    assert!("OP_FOO".parse::<Script>().is_err());
+    assert!("OP_PUSHBYTES_2 00".parse::<Script>().is_err());
+
+    let unknown = Script::from(vec![0xbb, 0xff, 0x51]);
+    assert_eq!(unknown.to_string(), "OP_UNKNOWN_0xbb OP_UNKNOWN_0xff OP_1");
+    assert_eq!(unknown.to_string().parse::<Script>().unwrap(), unknown);
+    assert!("OP_UNKNOWN_0xba".parse::<Script>().is_err());
+    assert!("OP_UNKNOWN_0x100".parse::<Script>().is_err());
+}
+
+#[test]
+fn test_script_sig() {
+    let tx = "0100000001813f79011acb80925dfe69b3def355fe914bd1d96a3f5f71bf8303c6a989c7d1000000006b483045022100ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320b0277457c98f02207a986d955c6e0cb35d446a89d3f56100f4d7f67801c31967743a9c8e10615bed01210349fc4e631e3624a545de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278afeffffff02a135ef01000000001976a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88ac99c39800000000001976a9141c4bc762dd5423e332166702cb75f40df79fea1288ac19430600"
+        .parse::<Tx>()
+        .unwrap();
+    let script_sig = tx.inputs()[0].script_sig();
+    let instructions = script_sig
+        .instructions()
+        .collect::<Result<Vec<Instruction>, String>>()
+        .unwrap();
+    assert_eq!(instructions.len(), 2);
+    assert_eq!(instructions[0].opcode(), Opcode::PushBytes(72));
+    assert_eq!(
+        hex::encode(instructions[1].push_data().unwrap()),
+        "0349fc4e631e3624a545de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278a"
+    );
+    assert!(script_sig.is_push_only());
+    assert!(script_sig.is_minimal());
+}
+
+#[test]
+fn test_push_data() {
+    let mut script = Script::new();
+    script
+        .push_data(&[])
+        .push_data(&[0x07])
+        .push_data(&[0x81])
+        .push_data(&[0xab; 20])
+        .push_data(&[0xcd; 100])
+        .push_data(&[0xef; 300]);
+    let opcodes = script
+        .instructions()
+        .map(|i| i.unwrap().opcode())
+        .collect::<Vec<Opcode>>();
+    assert_eq!(
+        opcodes,
+        vec![
+            Opcode::Op0,
+            Opcode::Op7,
+            Opcode::OneNegate,
+            Opcode::PushBytes(20),
+            Opcode::PushData1,
+            Opcode::PushData2,
+        ]
+    );
+    assert!(script.is_minimal());
+    assert_eq!(script.to_string().parse::<Script>().unwrap(), script);
+}
+
+#[test]
+fn test_non_minimal_push() {
+    let pushdata = script("4c0101");
+    assert!(!pushdata.is_minimal());
+    assert_eq!(pushdata.to_string(), "OP_PUSHDATA1 01");
+    assert_eq!(pushdata.to_string().parse::<Script>().unwrap(), pushdata);
+    assert!(!script("0107").is_minimal());
+    assert!(!script("4c00").is_minimal());
+}
+
+#[test]
+fn test_truncated_push() {
+    let truncated = script("76a94c05abcd");
+    let instructions = truncated.instructions().collect::<Vec<_>>();
+    assert_eq!(instructions.len(), 3);
+    assert!(instructions[2].is_err());
+    assert_eq!(truncated.to_string(), "OP_DUP OP_HASH160 [error]");
+    assert!(!truncated.is_push_only());
+    assert!(script("4d01").instructions().next().unwrap().is_err());
+}
+
+#[test]
+fn test_witness_programs() {
+    let p2wpkh = script("0014751e76e8199196d454941c45d1b3a323f1433bd6");
+    assert!(p2wpkh.is_p2wpkh());
+    assert_eq!(p2wpkh.witness_program().unwrap().0, 0);
+    let p2tr = script("512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798");
+    assert!(p2tr.is_p2tr());
+    assert!(!p2tr.is_p2wsh());
+    assert!(script("6a0568656c6c6f").is_op_return());
+    assert!(script("a914cc6ffbc0bf31af759451068f90ba7a0272b6b33287").is_p2sh());
+    assert!(script("0001ff").witness_program().is_none());
+}
@@ -0,0 +1,665 @@
+use crate::core::address::{p2pkh_script, p2sh_script, p2wpkh_script, p2wsh_script};
+use crate::core::interpreter::{
+    verify_script, ScriptError, TransactionChecker, STANDARD_VERIFY_FLAGS,
+};
+use crate::core::s256ecc::S256PrivateKey;
+use crate::core::script::{Opcode, Script};
+use crate::core::sha256ser::Sha256Ripemd160;
+use crate::core::sighash::{
+    SighashCache, SIGHASH_ALL, SIGHASH_ANYONECANPAY, SIGHASH_DEFAULT, SIGHASH_NONE, SIGHASH_SINGLE,
+};
+use crate::core::taproot::{tap_leaf_hash, TAPSCRIPT_LEAF_VERSION};
+use crate::core::tx::{OutPoint, Tx, TxIn, TxOut, SEQUENCE_FINAL};
+use crate::ser::chained_hash::ChainedCompute;
+use crate::ser::hex;
+use bnum::types::U256;
+use sha2::{Digest, Sha256};
+
+const BOOK_TX: &str = "0100000001813f79011acb80925dfe69b3def355fe914bd1d96a3f5f71bf8303c6a989c7d1000000006b483045022100ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320b0277457c98f02207a986d955c6e0cb35d446a89d3f56100f4d7f67801c31967743a9c8e10615bed01210349fc4e631e3624a545de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278afeffffff02a135ef01000000001976a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88ac99c39800000000001976a9141c4bc762dd5423e332166702cb75f40df79fea1288ac19430600";
+
+const CORE_TX: &str = "907c2bc503ade11cc3b04eb2918b6f547b0630ab569273824748c87ea14b0696526c66ba740200000004ab65ababfd1f9bdd4ef073c7afc4ae00da8a66f429c917a0081ad1e1dabce28d373eab81d8628de802000000096aab5253ab52000052ad042b5f25efb33beec9f3364e8a9139e8439d9d7e26529c3c30b6c3fd89f8684cfd68ea0200000009ab53526500636a52ab599ac2fe02a526ed040000000008535300516352515164370e010000000003006300ab2ec229";
+
+// Rows of Bitcoin Core's sighash.json: raw tx, script code, input index, hash type, sighash
+const CORE_VECTORS: [(&str, &str, usize, i32, &str); 5] = [
+    (
+        CORE_TX,
+        "",
+        2,
+        1864164639,
+        "31af167a6cf3f9d5f6875caa4d31704ceb0eba078d132b78dab52c3b8997317e",
+    ),
+    (
+        "a0aa3126041621a6dea5b800141aa696daf28408959dfb2df96095db9fa425ad3f427f2f6103000000015360290e9c6063fa26912c2e7fb6a0ad80f1c5fea1771d42f12976092e7a85a4229fdb6e890000000001abc109f6e47688ac0e4682988785744602b8c87228fcef0695085edf19088af1a9db126e93000000000665516aac536affffffff8fe53e0806e12dfd05d67ac68f4768fdbe23fc48ace22a5aa8ba04c96d58e2750300000009ac51abac63ab5153650524aa680455ce7b000000000000499e50030000000008636a00ac526563ac5051ee030000000003abacabd2b6fe000000000003516563910fb6b5",
+        "65",
+        0,
+        -1391424484,
+        "48d6a1bd2cd9eec54eb866fc71209418a950402b5d7e52363bfb75c98e141175",
+    ),
+    (
+        "6e7e9d4b04ce17afa1e8546b627bb8d89a6a7fefd9d892ec8a192d79c2ceafc01694a6a7e7030000000953ac6a51006353636a33bced1544f797f08ceed02f108da22cd24c9e7809a446c61eb3895914508ac91f07053a01000000055163ab516affffffff11dc54eee8f9e4ff0bcf6b1a1a35b1cd10d63389571375501af7444073bcec3c02000000046aab53514a821f0ce3956e235f71e4c69d91abe1e93fb703bd33039ac567249ed339bf0ba0883ef300000000090063ab65000065ac654bec3cc504bcf499020000000005ab6a52abac64eb060100000000076a6a5351650053bbbc130100000000056a6aab53abd6e1380100000000026a51c4e509b8",
+        "acab655151",
+        0,
+        479279909,
+        "2a3d95b09237b72034b23f2d2bb29fa32a58ab5c6aa72f6aafdfa178ab1dd01c",
+    ),
+    (
+        "73107cbd025c22ebc8c3e0a47b2a760739216a528de8d4dab5d45cbeb3051cebae73b01ca10200000007ab6353656a636affffffffe26816dffc670841e6a6c8c61c586da401df1261a330a6c6b3dd9f9a0789bc9e000000000800ac6552ac6aac51ffffffff0174a8f0010000000004ac52515100000000",
+        "5163ac63635151ac",
+        1,
+        1190874345,
+        "06e328de263a87b09beabe222a21627a6ea5c7f560030da31610c4611f4a46bc",
+    ),
+    (
+        "50818f4c01b464538b1e7e7f5ae4ed96ad23c68c830e78da9a845bc19b5c3b0b20bb82e5e9030000000763526a63655352ffffffff023b3f9c040000000008630051516a6a5163a83caf01000000000553ab65510000000000",
+        "6aac",
+        0,
+        946795545,
+        "746306f322de2b4b58ffe7faae83f6a72433c22f88062cdde881d4dd8a5a4e2d",
+    ),
+];
+
+fn core_hex(z: U256) -> String {
+    let mut bytes = z.to_be_bytes();
+    bytes.reverse();
+    hex::encode(&bytes)
+}
+
+#[test]
+fn test_core_vectors() {
+    for (raw_tx, script_code, index, hash_type, expected) in CORE_VECTORS {
+        let tx = raw_tx.parse::<Tx>().unwrap();
+        let script_code = Script::from(hex::decode(script_code).unwrap());
+        let z = tx.legacy_sighash(index, &script_code, hash_type as u32);
+        assert_eq!(
+            core_hex(z),
+            expected,
+            "{} {} {}",
+            script_code,
+            index,
+            hash_type
+        );
+    }
+}
+
+#[test]
+fn test_single_bug() {
+    let tx = CORE_TX.parse::<Tx>().unwrap();
+    assert_eq!(tx.outputs().len(), 2);
+    let one = U256::ONE << 248;
+    assert_eq!(tx.legacy_sighash(2, &Script::new(), SIGHASH_SINGLE), one);
+    assert_eq!(tx.legacy_sighash(3, &Script::new(), SIGHASH_ALL), one);
+    assert_ne!(tx.legacy_sighash(1, &Script::new(), SIGHASH_SINGLE), one);
+}
+
+#[test]
+fn test_book_sighash() {
+    let tx = BOOK_TX.parse::<Tx>().unwrap();
+    let script_pubkey =
+        Script::from(hex::decode("76a914a802fc56c704ce87c42d7c92eb75e7896bdc41ae88ac").unwrap());
+    assert_eq!(
+        tx.legacy_sighash(0, &script_pubkey, SIGHASH_ALL),
+        U256::parse_str_radix(
+            "27e0c5994dec7824e56dec6b2fcb342eb7cdb0d0957c2fce9882f715e85d81a6",
+            16
+        )
+    );
+    let checker = TransactionChecker::new(&tx, 0, 0);
+    let script_sig = tx.inputs()[0].script_sig();
+    assert_eq!(
+        verify_script(
+            script_sig,
+            &script_pubkey,
+            &[],
+            STANDARD_VERIFY_FLAGS,
+            &checker
+        ),
+        Ok(())
+    );
+    let mut tampered = tx.clone();
+    tampered.inputs_mut()[0].set_sequence(SEQUENCE_FINAL);
+    assert_eq!(
+        verify_script(
+            script_sig,
+            &script_pubkey,
+            &[],
+            STANDARD_VERIFY_FLAGS,
+            &TransactionChecker::new(&tampered, 0, 0)
+        ),
+        Err(ScriptError::NullFail)
+    );
+}
+
+fn spend(inputs: usize, outputs: usize) -> Tx {
+    let inputs = (0..inputs)
+        .map(|i| {
+            TxIn::new(
+                OutPoint::new([i as u8 + 1; 32], i as u32),
+                Script::new(),
+                0xfffffffd,
+            )
+        })
+        .collect();
+    let outputs = (0..outputs)
+        .map(|i| TxOut::new(10_000 * (i as u64 + 1), Script::from(vec![0x51])))
+        .collect();
+    Tx::new(2, inputs, outputs, 0)
+}
+
+#[test]
+fn test_sign_hash_types() {
+    let pk = S256PrivateKey::from_value(U256::from(8675309u32));
+    let sec = pk.point().sec(true);
+    let script_pubkey = Script::from(p2pkh_script(&pk.point().hash160(true)));
+    for hash_type in [
+        SIGHASH_ALL,
+        SIGHASH_NONE,
+        SIGHASH_SINGLE,
+        SIGHASH_ALL | SIGHASH_ANYONECANPAY,
+        SIGHASH_NONE | SIGHASH_ANYONECANPAY,
+        SIGHASH_SINGLE | SIGHASH_ANYONECANPAY,
+    ] {
+        let mut tx = spend(3, 3);
+        let z = tx.legacy_sighash(1, &script_pubkey, hash_type);
+        let signature = [pk.sign(z).der_encoded(), vec![hash_type as u8]].concat();
+        let mut script_sig = Script::new();
+        script_sig.push_data(&signature).push_data(&sec);
+        tx.inputs_mut()[1].set_script_sig(script_sig.clone());
+        let verify = |tx: &Tx| {
+            verify_script(
+                &script_sig,
+                &script_pubkey,
+                &[],
+                STANDARD_VERIFY_FLAGS,
+                &TransactionChecker::new(tx, 1, 0),
+            )
+        };
+        assert_eq!(verify(&tx), Ok(()));
+
+        // Other inputs' sequences are only committed to by SIGHASH_ALL
+        let mut modified = tx.clone();
+        modified.inputs_mut()[0].set_sequence(0);
+        assert_eq!(
+            verify(&modified).is_ok(),
+            hash_type & 0x1f != SIGHASH_ALL || hash_type & SIGHASH_ANYONECANPAY != 0
+        );
+
+        // Dropping the last output only matters to SIGHASH_ALL
+        let modified = Tx::new(
+            tx.version(),
+            tx.inputs().to_vec(),
+            tx.outputs()[..2].to_vec(),
+            tx.locktime(),
+        );
+        assert_eq!(verify(&modified).is_ok(), hash_type & 0x1f != SIGHASH_ALL);
+    }
+}
+
+#[test]
+fn test_locktime_checks() {
+    let run = |asm: &str, tx: &Tx| {
+        verify_script(
+            &Script::new(),
+            &asm.parse::<Script>().unwrap(),
+            &[],
+            STANDARD_VERIFY_FLAGS,
+            &TransactionChecker::new(tx, 0, 0),
+        )
+    };
+    let mut tx = spend(1, 1);
+    tx = Tx::new(2, tx.inputs().to_vec(), tx.outputs().to_vec(), 700_000);
+    let cltv = |n: i64| {
+        let mut script = Script::new();
+        script
+            .push_int(n)
+            .push_opcode(Opcode::CheckLockTimeVerify)
+            .push_opcode(Opcode::Drop)
+            .push_opcode(Opcode::Op1);
+        script.to_string()
+    };
+    assert_eq!(run(&cltv(700_000), &tx), Ok(()));
+    assert_eq!(
+        run(&cltv(700_001), &tx),
+        Err(ScriptError::UnsatisfiedLocktime)
+    );
+    assert_eq!(
+        run(&cltv(1_600_000_000), &tx),
+        Err(ScriptError::UnsatisfiedLocktime)
+    );
+    assert_eq!(run(&cltv(-1), &tx), Err(ScriptError::NegativeLocktime));
+    let mut final_tx = tx.clone();
+    final_tx.inputs_mut()[0].set_sequence(SEQUENCE_FINAL);
+    assert_eq!(
+        run(&cltv(1), &final_tx),
+        Err(ScriptError::UnsatisfiedLocktime)
+    );
+
+    tx.inputs_mut()[0].set_sequence(10);
+    assert_eq!(run("0a OP_CHECKSEQUENCEVERIFY", &tx), Ok(()));
+    assert_eq!(
+        run("0b OP_CHECKSEQUENCEVERIFY", &tx),
+        Err(ScriptError::UnsatisfiedLocktime)
+    );
+    assert_eq!(
+        run("0a0040 OP_CHECKSEQUENCEVERIFY", &tx),
+        Err(ScriptError::UnsatisfiedLocktime)
+    );
+    let v1 = Tx::new(1, tx.inputs().to_vec(), tx.outputs().to_vec(), 0);
+    assert_eq!(
+        run("0a OP_CHECKSEQUENCEVERIFY", &v1),
+        Err(ScriptError::UnsatisfiedLocktime)
+    );
+}
+
+const BIP143_P2WPKH_TX: &str = "0100000002fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f0000000000eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac11000000";
+
+const BIP143_P2WPKH_SIGNED_TX: &str = "01000000000102fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f00000000494830450221008b9d1dc26ba6a9cb62127b02742fa9d754cd3bebf337f7a55d114c8e5cdd30be022040529b194ba3f9281a99f2b1c0a19c0489bc22ede944ccf4ecbab4cc618ef3ed01eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac000247304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01cc44a0220573a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee0121025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee635711000000";
+
+const BIP143_P2SH_P2WPKH_TX: &str = "0100000001db6b1b20aa0fd7b23880be2ecbd4a98130974cf4748fb66092ac4d3ceb1a54770100000000feffffff02b8b4eb0b000000001976a914a457b684d7f0d539a46a45bbc043f35b59d0d96388ac0008af2f000000001976a914fd270b1ee6abcaea97fea7ad0402e8bd8ad6d77c88ac92040000";
+
+fn script(hex: &str) -> Script {
+    Script::from(hex::decode(hex).unwrap())
+}
+
+fn z(hex: &str) -> U256 {
+    U256::parse_str_radix(hex, 16)
+}
+
+#[test]
+fn test_bip143_vectors() {
+    let tx = BIP143_P2WPKH_TX.parse::<Tx>().unwrap();
+    let script_code = script("76a9141d0f172a0ecb48aee1be1f2687d2963ae33f71a188ac");
+    let expected = z("c37af31116d1b27caf68aae9e3ac82f1477929014d5b917657d0eb49478cb670");
+    assert_eq!(
+        tx.segwit_v0_sighash(1, &script_code, 600_000_000, SIGHASH_ALL),
+        Ok(expected)
+    );
+    let cache = SighashCache::new(&tx);
+    for _ in 0..2 {
+        assert_eq!(
+            cache.segwit_v0_sighash(1, &script_code, 600_000_000, SIGHASH_ALL),
+            Ok(expected)
+        );
+    }
+    assert_ne!(
+        cache.segwit_v0_sighash(1, &script_code, 600_000_001, SIGHASH_ALL),
+        Ok(expected)
+    );
+    assert!(cache
+        .segwit_v0_sighash(2, &script_code, 600_000_000, SIGHASH_ALL)
+        .is_err());
+
+    let tx = BIP143_P2SH_P2WPKH_TX.parse::<Tx>().unwrap();
+    let script_code = script("76a91479091972186c449eb1ded22b78e40d009bdf008988ac");
+    assert_eq!(
+        tx.segwit_v0_sighash(0, &script_code, 1_000_000_000, SIGHASH_ALL),
+        Ok(z(
+            "64f3b0f4dd2bb3aa1ce8566d220cc74dda9df97d8490cc81d89d735c92e59fb6"
+        ))
+    );
+}
+
+#[test]
+fn test_verify_bip143_tx() {
+    let tx = BIP143_P2WPKH_SIGNED_TX.parse::<Tx>().unwrap();
+    let spent = [
+        (
+            script("2103c9f4836b9a4f77fc0d81f7bcb01b7f1b35916864b9476c241ce9fc198bd25432ac"),
+            625_000_000,
+        ),
+        (
+            script("00141d0f172a0ecb48aee1be1f2687d2963ae33f71a1"),
+            600_000_000,
+        ),
+    ];
+    for (index, (script_pubkey, amount)) in spent.iter().enumerate() {
+        let input = &tx.inputs()[index];
+        let verify = |amount: u64| {
+            verify_script(
+                input.script_sig(),
+                script_pubkey,
+                input.witness(),
+                STANDARD_VERIFY_FLAGS,
+                &TransactionChecker::new(&tx, index, amount),
+            )
+        };
+        assert_eq!(verify(*amount), Ok(()));
+        // Legacy signatures don't commit to the amount, segwit ones do
+        assert_eq!(verify(amount + 1).is_ok(), index == 0);
+    }
+}
+
+fn witness_signature(
+    tx: &Tx,
+    index: usize,
+    pk: &S256PrivateKey,
+    script_code: &Script,
+    amount: u64,
+    hash_type: u32,
+) -> Vec<u8> {
+    let z = tx
+        .segwit_v0_sighash(index, script_code, amount, hash_type)
+        .unwrap();
+    [pk.sign(z).der_encoded(), vec![hash_type as u8]].concat()
+}
+
+#[test]
+fn test_sign_p2wpkh() {
+    let pk = S256PrivateKey::from_value(U256::from(8675309u32));
+    let sec = pk.point().sec(true);
+    let h160 = pk.point().hash160(true);
+    let script_code = Script::from(p2pkh_script(&h160));
+    let p2wpkh = Script::from(p2wpkh_script(&h160));
+    let amount = 50_000;
+
+    let mut tx = spend(2, 2);
+    let signature = witness_signature(&tx, 1, &pk, &script_code, amount, SIGHASH_ALL);
+    tx.inputs_mut()[1].set_witness(vec![signature.clone(), sec.clone()]);
+    let verify = |tx: &Tx, script_sig: &Script, script_pubkey: &Script| {
+        verify_script(
+            script_sig,
+            script_pubkey,
+            tx.inputs()[1].witness(),
+            STANDARD_VERIFY_FLAGS,
+            &TransactionChecker::new(tx, 1, amount),
+        )
+    };
+    assert_eq!(verify(&tx, &Script::new(), &p2wpkh), Ok(()));
+
+    let mut malleated = Script::new();
+    malleated.push_opcode(Opcode::Op0);
+    assert_eq!(
+        verify(&tx, &malleated, &p2wpkh),
+        Err(ScriptError::WitnessMalleated)
+    );
+    assert_eq!(
+        verify(&tx, &Script::new(), &Script::from(p2pkh_script(&h160))),
+        Err(ScriptError::InvalidStackOperation)
+    );
+    let z = tx.legacy_sighash(1, &script_code, SIGHASH_ALL);
+    let mut unexpected = Script::new();
+    unexpected
+        .push_data(&[pk.sign(z).der_encoded(), vec![SIGHASH_ALL as u8]].concat())
+        .push_data(&sec);
+    assert_eq!(
+        verify(&tx, &unexpected, &script_code),
+        Err(ScriptError::WitnessUnexpected)
+    );
+
+    // P2SH-P2WPKH: the scriptSig is a single push of the witness program
+    let p2sh = Script::from(p2sh_script(&Sha256Ripemd160::compute(&p2wpkh)));
+    let mut script_sig = Script::new();
+    script_sig.push_data(&p2wpkh);
+    tx.inputs_mut()[1].set_script_sig(script_sig.clone());
+    assert_eq!(verify(&tx, &script_sig, &p2sh), Ok(()));
+    let mut padded = Script::new();
+    padded.push_opcode(Opcode::Op0).push_data(&p2wpkh);
+    assert_eq!(
+        verify(&tx, &padded, &p2sh),
+        Err(ScriptError::WitnessMalleatedP2sh)
+    );
+
+    let uncompressed = pk.point().sec(false);
+    let h160 = pk.point().hash160(false);
+    let script_code = Script::from(p2pkh_script(&h160));
+    let signature = witness_signature(&tx, 1, &pk, &script_code, amount, SIGHASH_ALL);
+    tx.inputs_mut()[1].set_witness(vec![signature, uncompressed]);
+    assert_eq!(
+        verify(&tx, &Script::new(), &Script::from(p2wpkh_script(&h160))),
+        Err(ScriptError::WitnessPubkeyType)
+    );
+}
+
+#[test]
+fn test_sign_p2wsh() {
+    let keys = [1u32, 2].map(|k| S256PrivateKey::from_value(U256::from(k * 7919)));
+    let mut witness_script = Script::new();
+    witness_script.push_int(2);
+    for key in &keys {
+        witness_script.push_data(&key.point().sec(true));
+    }
+    witness_script
+        .push_int(2)
+        .push_opcode(Opcode::CheckMultiSig);
+    let program: [u8; 32] = Sha256::digest(witness_script.as_bytes()).into();
+    let script_pubkey = Script::from(p2wsh_script(&program));
+    let amount = 1_000_000;
+
+    for hash_type in [
+        SIGHASH_ALL,
+        SIGHASH_NONE,
+        SIGHASH_SINGLE,
+        SIGHASH_ALL | SIGHASH_ANYONECANPAY,
+        SIGHASH_NONE | SIGHASH_ANYONECANPAY,
+        SIGHASH_SINGLE | SIGHASH_ANYONECANPAY,
+    ] {
+        let mut tx = spend(2, 2);
+        let mut witness = vec![vec![]];
+        for key in &keys {
+            witness.push(witness_signature(
+                &tx,
+                0,
+                key,
+                &witness_script,
+                amount,
+                hash_type,
+            ));
+        }
+        witness.push(witness_script.to_vec());
+        tx.inputs_mut()[0].set_witness(witness.clone());
+        let verify = |tx: &Tx, witness: &[Vec<u8>]| {
+            verify_script(
+                &Script::new(),
+                &script_pubkey,
+                witness,
+                STANDARD_VERIFY_FLAGS,
+                &TransactionChecker::new(tx, 0, amount),
+            )
+        };
+        assert_eq!(verify(&tx, &witness), Ok(()));
+
+        let mut modified = tx.clone();
+        modified.inputs_mut()[1].set_sequence(0);
+        assert_eq!(
+            verify(&modified, &witness).is_ok(),
+            hash_type & 0x1f != SIGHASH_ALL || hash_type & SIGHASH_ANYONECANPAY != 0
+        );
+
+        let mut wrong_script = witness.clone();
+        *wrong_script.last_mut().unwrap() = vec![0x51];
+        assert_eq!(
+            verify(&tx, &wrong_script),
+            Err(ScriptError::WitnessProgramMismatch)
+        );
+    }
+    assert_eq!(
+        verify_script(
+            &Script::new(),
+            &script_pubkey,
+            &[],
+            STANDARD_VERIFY_FLAGS,
+            &TransactionChecker::new(&spend(1, 1), 0, amount),
+        ),
+        Err(ScriptError::WitnessProgramWitnessEmpty)
+    );
+}
+
+const BIP341_TX: &str = "02000000097de20cbff686da83a54981d2b9bab3586f4ca7e48f57f5b55963115f3b334e9c010000000000000000d7b7cab57b1393ace2d064f4d4a2cb8af6def61273e127517d44759b6dafdd990000000000fffffffff8e1f583384333689228c5d28eac13366be082dc57441760d957275419a418420000000000fffffffff0689180aa63b30cb162a73c6d2a38b7eeda2a83ece74310fda0843ad604853b0100000000feffffffaa5202bdf6d8ccd2ee0f0202afbbb7461d9264a25e5bfd3c5a52ee1239e0ba6c0000000000feffffff956149bdc66faa968eb2be2d2faa29718acbfe3941215893a2a3446d32acd050000000000000000000e664b9773b88c09c32cb70a2a3e4da0ced63b7ba3b22f848531bbb1d5d5f4c94010000000000000000e9aa6b8e6c9de67619e6a3924ae25696bb7b694bb677a632a74ef7eadfd4eabf0000000000ffffffffa778eb6a263dc090464cd125c466b5a99667720b1c110468831d058aa1b82af10100000000ffffffff0200ca9a3b000000001976a91406afd46bcdfd22ef94ac122aa11f241244a37ecc88ac807840cb0000000020ac9a87f5594be208f8532db38cff670c450ed2fea8fcdefcc9a663f78bab962b0065cd1d";
+
+const BIP341_UTXOS: [(&str, u64); 9] = [
+    (
+        "512053a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343",
+        420000000,
+    ),
+    (
+        "5120147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3",
+        462000000,
+    ),
+    (
+        "76a914751e76e8199196d454941c45d1b3a323f1433bd688ac",
+        294000000,
+    ),
+    (
+        "5120e4d810fd50586274face62b8a807eb9719cef49c04177cc6b76a9a4251d5450e",
+        504000000,
+    ),
+    (
+        "512091b64d5324723a985170e4dc5a0f84c041804f2cd12660fa5dec09fc21783605",
+        630000000,
+    ),
+    ("00147dd65592d0ab2fe0d0257d571abf032cd9db93dc", 378000000),
+    (
+        "512075169f4001aa68f15bbed28b218df1d0a62cbbcf1188c6665110c293c907b831",
+        672000000,
+    ),
+    (
+        "5120712447206d7a5238acc7ff53fbe94a3b64539ad291c7cdbc490b7577e4b17df5",
+        546000000,
+    ),
+    (
+        "512077e30a5522dd9f894c3f8b8bd4c4b2cf82ca7da8a3ea6a239655c39c050ab220",
+        588000000,
+    ),
+];
+
+// keyPathSpending: (input index, hash type, sigHash)
+const BIP341_KEY_PATH: [(usize, u32, &str); 7] = [
+    (
+        0,
+        0x03,
+        "2514a6272f85cfa0f45eb907fcb0d121b808ed37c6ea160a5a9046ed5526d555",
+    ),
+    (
+        1,
+        0x83,
+        "325a644af47e8a5a2591cda0ab0723978537318f10e6a63d4eed783b96a71a4d",
+    ),
+    (
+        3,
+        0x01,
+        "bf013ea93474aa67815b1b6cc441d23b64fa310911d991e713cd34c7f5d46669",
+    ),
+    (
+        4,
+        0x00,
+        "4f900a0bae3f1446fd48490c2958b5a023228f01661cda3496a11da502a7f7ef",
+    ),
+    (
+        6,
+        0x02,
+        "15f25c298eb5cdc7eb1d638dd2d45c97c4c59dcaec6679cfc16ad84f30876b85",
+    ),
+    (
+        7,
+        0x82,
+        "cd292de50313804dabe4685e83f923d2969577191a3e1d2882220dca88cbeb10",
+    ),
+    (
+        8,
+        0x81,
+        "cccb739eca6c13a8a89e6e5cd317ffe55669bbda23f2fd37b0f18755e008edd2",
+    ),
+];
+
+fn bip341_spent_outputs() -> Vec<TxOut> {
+    BIP341_UTXOS
+        .iter()
+        .map(|(script_pubkey, amount)| TxOut::new(*amount, script(script_pubkey)))
+        .collect()
+}
+
+#[test]
+fn test_bip341_key_path() {
+    let tx = BIP341_TX.parse::<Tx>().unwrap();
+    let spent_outputs = bip341_spent_outputs();
+    let cache = SighashCache::with_spent_outputs(&tx, &spent_outputs);
+    for (index, hash_type, expected) in BIP341_KEY_PATH {
+        assert_eq!(
+            hex::encode(
+                &cache
+                    .taproot_key_spend_sighash(index, hash_type, None)
+                    .unwrap()
+            ),
+            expected
+        );
+    }
+}
+
+#[test]
+fn test_taproot_script_path_and_annex() {
+    let tx = BIP341_TX.parse::<Tx>().unwrap();
+    let spent_outputs = bip341_spent_outputs();
+    let cache = SighashCache::with_spent_outputs(&tx, &spent_outputs);
+    let leaf_script =
+        script("2079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798ac");
+    let leaf_hash = tap_leaf_hash(TAPSCRIPT_LEAF_VERSION, &leaf_script);
+    let annex = hex::decode("50aabbcc").unwrap();
+
+    assert_eq!(
+        hex::encode(
+            &cache
+                .taproot_key_spend_sighash(3, SIGHASH_ALL, Some(&annex))
+                .unwrap()
+        ),
+        "c1860a79362416783ff8d7efc888d5b9a3a1066f5180155aaec55e01f2836c80"
+    );
+    let script_path = [
+        (
+            1,
+            SIGHASH_SINGLE | SIGHASH_ANYONECANPAY,
+            None,
+            u32::MAX,
+            "79dd054beaa01d2513a75b2baad40181adef82c6a95245488696900c7c792bc0",
+        ),
+        (
+            7,
+            SIGHASH_NONE | SIGHASH_ANYONECANPAY,
+            Some(&annex[..]),
+            3,
+            "da52958be30a3d0cda5feb893a019e12910a04c3337d4312233a2fc72254b6ad",
+        ),
+        (
+            2,
+            SIGHASH_DEFAULT,
+            None,
+            u32::MAX,
+            "cb9b56da82efd6875daed996817b33fb8dd3bc627f4490a190506de02ff06419",
+        ),
+    ];
+    for (index, hash_type, annex, code_separator, expected) in script_path {
+        let sighash = cache
+            .taproot_script_spend_sighash(index, hash_type, annex, leaf_hash, code_separator)
+            .unwrap();
+        assert_eq!(hex::encode(&sighash), expected);
+    }
+
+    // SIGHASH_DEFAULT and SIGHASH_ALL commit to the same data but differ in the hash type byte
+    assert_ne!(
+        cache.taproot_key_spend_sighash(0, SIGHASH_DEFAULT, None),
+        cache.taproot_key_spend_sighash(0, SIGHASH_ALL, None)
+    );
+}
+
+#[test]
+fn test_taproot_sighash_errors() {
+    let tx = BIP341_TX.parse::<Tx>().unwrap();
+    let spent_outputs = bip341_spent_outputs();
+    let cache = SighashCache::with_spent_outputs(&tx, &spent_outputs);
+    assert!(cache.taproot_key_spend_sighash(0, 0x04, None).is_err());
+    assert!(cache.taproot_key_spend_sighash(0, 0x80, None).is_err());
+    assert!(cache
+        .taproot_key_spend_sighash(9, SIGHASH_ALL, None)
+        .is_err());
+    assert!(cache
+        .taproot_key_spend_sighash(4, SIGHASH_SINGLE, None)
+        .is_err());
+    assert!(cache
+        .taproot_key_spend_sighash(0, SIGHASH_ALL, Some(&[0x51]))
+        .is_err());
+    let partial = SighashCache::with_spent_outputs(&tx, &spent_outputs[..8]);
+    assert!(partial
+        .taproot_key_spend_sighash(0, SIGHASH_ALL, None)
+        .is_err());
+    assert!(SighashCache::new(&tx)
+        .taproot_key_spend_sighash(0, SIGHASH_ALL, None)
+        .is_err());
+}
@@ -0,0 +1,93 @@
+use crate::core::s256ecc::{S256Point, S256PrivateKey};
+use crate::ser::hex;
+use bnum::types::U256;
+
+// BIP340 test vectors: (secret key, public key, aux_rand, message, signature)
+const SIGNING_VECTORS: [(&str, &str, &str, &str, &str); 4] = [
+    (
+        "0000000000000000000000000000000000000000000000000000000000000003",
+        "f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9",
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "e907831f80848d1069a5371b402410364bdf1c5f8307b0084c55f1ce2dca821525f66a4a85ea8b71e482a74f382d2ce5ebeee8fdb2172f477df4900d310536c0",
+    ),
+    (
+        "b7e151628aed2a6abf7158809cf4f3c762e7160f38b4da56a784d9045190cfef",
+        "dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659",
+        "0000000000000000000000000000000000000000000000000000000000000001",
+        "243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89",
+        "6896bd60eeae296db48a229ff71dfe071bde413e6d43f917dc8dcf8c78de33418906d11ac976abccb20b091292bff4ea897efcb639ea871cfa95f6de339e4b0a",
+    ),
+    (
+        "c90fdaa22168c234c4c6628b80dc1cd129024e088a67cc74020bbea63b14e5c9",
+        "dd308afec5777e13121fa72b9cc1b7cc0139715309b086c960e18fd969774eb8",
+        "c87aa53824b4d7ae2eb035a2b5bbbccc080e76cdc6d1692c4b0b62d798e6d906",
+        "7e2d58d8b3bcdf1abadec7829054f90dda9805aab56c77333024b9d0a508b75c",
+        "5831aaeed7b44bb74e5eab94ba9d4294c49bcf2a60728d8b4c200f50dd313c1bab745879a5ad954a72c45a91c3a51d3c7adea98d82f8481e0e1e03674a6f3fb7",
+    ),
+    (
+        "0b432b2677937381aef05bb02a66ecd012773062cf3fa2549e44f58ed2401710",
+        "25d1dff95105f5253c4022f628a996ad3a0d95fbf21d468a1b33f8c160d8f517",
+        "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+        "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+        "7eb0509757e246f19449885651611cb965ecc1a187dd51b64fda1edc9637d5ec97582b9cb13db3933705b32ba982af5af25fd78881ebb32771fc5922efc66ea3",
+    ),
+];
+
+fn bytes32(s: &str) -> [u8; 32] {
+    hex::decode(s).unwrap().try_into().unwrap()
+}
+
+#[test]
+fn test_schnorr_signing() {
+    for (secret, pubkey, aux_rand, msg, signature) in SIGNING_VECTORS {
+        let key = S256PrivateKey::from_value(U256::from_be_bytes(bytes32(secret)));
+        assert_eq!(hex::encode(&key.point().xonly()), pubkey);
+        let sig = key.sign_schnorr(&bytes32(msg), &bytes32(aux_rand));
+        assert_eq!(hex::encode(&sig), signature);
+        let point = S256Point::lift_x(&bytes32(pubkey)).unwrap();
+        assert!(point.verify_schnorr(&bytes32(msg), &sig));
+        assert!(key.point().verify_schnorr(&bytes32(msg), &sig));
+        assert!(!point.verify_schnorr(&[0xaa; 32], &sig));
+    }
+}
+
+#[test]
+fn test_schnorr_verification_failures() {
+    let pubkey = S256Point::lift_x(&bytes32(
+        "dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659",
+    ))
+    .unwrap();
+    let msg = bytes32("243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89");
+    let invalid = [
+        // has_even_y(R) is false
+        "fff97bd5755eeea420453a14355235d382f6472f8568a18b2f057a14602975563cc27944640ac607cd107ae10923d9ef7a73c643e166be5ebeafa34b1ac553e2",
+        // negated message
+        "1fa62e331edbc21c394792d2ab1100a7b432b013df3f6ff4f99fcb33e0e1515f28890b3edb6e7189b630448b515ce4f8622a954cfe545735aaea5134fccdb2bd",
+        // negated s value
+        "6cff5c3ba86c69ea4b7376f31a9bcb4f74c1976089b2d9963da2e5543e177769961764b3aa9b2ffcb6ef947b6887a226e8d7c93e00c5ed0c1834ff0d0c2e6da6",
+        // s is equal to the curve order
+        "6cff5c3ba86c69ea4b7376f31a9bcb4f74c1976089b2d9963da2e5543e177769fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141",
+    ];
+    for signature in invalid {
+        assert!(!pubkey.verify_schnorr(&msg, &hex::decode(signature).unwrap()));
+    }
+    assert!(!pubkey.verify_schnorr(&msg, &[0u8; 63]));
+    assert!(S256Point::lift_x(&bytes32(
+        "eefdea4cdb677750a420fee807eacf21eb9898ae79b9768766e4faa04a2d4a34"
+    ))
+    .is_err());
+}
+
+#[test]
+fn test_private_key_tap_tweak() {
+    for secret in [1u32, 2, 3, 0xdeadbeef] {
+        let key = S256PrivateKey::from_value(U256::from(secret));
+        let merkle_root = Some([7u8; 32]);
+        for root in [None, merkle_root] {
+            let tweaked = key.tap_tweak(root).unwrap();
+            let output_key = key.point().tap_tweak(root).unwrap();
+            assert_eq!(tweaked.point().xonly(), output_key.xonly());
+        }
+    }
+}
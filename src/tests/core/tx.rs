@@ -0,0 +1,123 @@
+use crate::core::tx::{OutPoint, Tx};
+use crate::ser::hex;
+use crate::ser::varint::{encode_varint, read_varint};
+use std::io::Cursor;
+
+const TX: &str = "0100000001813f79011acb80925dfe69b3def355fe914bd1d96a3f5f71bf8303c6a989c7d1000000006b483045022100ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320b0277457c98f02207a986d955c6e0cb35d446a89d3f56100f4d7f67801c31967743a9c8e10615bed01210349fc4e631e3624a545de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278afeffffff02a135ef01000000001976a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88ac99c39800000000001976a9141c4bc762dd5423e332166702cb75f40df79fea1288ac19430600";
+
+const SATOSHI_TO_HAL: &str = "0100000001c997a5e56e104102fa209c6a852dd90660a20b2d9c352423edce25857fcd3704000000004847304402204e45e16932b8af514961a1d3a1a25fdf3f4f7732e9d624c6c61548ab5fb8cd410220181522ec8eca07de4860a4acdd12909d831cc56cbbac4622082221a8768d1d0901ffffffff0200ca9a3b00000000434104ae1a62fe09c5f51b13905f07f06b99a2f7159b2225f374cd378d71302fa28414e7aab37397f554a7df5f142c21c1b7303b8a0626f1baded5c72a704f7e6cd84cac00286bee0000000043410411db93e1dcdb8a016b49840f8c53bc1eb68a382e97b1482ecad7b148a6909a5cb2e0eaddfb84ccf9744464f82e160bfa9b8b64f9d4c03f999b8643f656b412a3ac00000000";
+
+#[test]
+fn test_varint() {
+    for n in [
+        0,
+        0xfc,
+        0xfd,
+        0xffff,
+        0x10000,
+        0xffff_ffff,
+        0x1_0000_0000,
+        u64::MAX,
+    ] {
+        let encoded = encode_varint(n);
+        assert_eq!(read_varint(&mut Cursor::new(encoded)).unwrap(), n);
+    }
+    assert!(read_varint(&mut Cursor::new(vec![0xfd, 0x10, 0x00])).is_err());
+    assert!(read_varint(&mut Cursor::new(vec![0xfe, 0xff])).is_err());
+}
+
+#[test]
+fn test_parse_tx() {
+    let tx = TX.parse::<Tx>().unwrap();
+    assert_eq!(tx.version(), 1);
+    assert_eq!(tx.inputs().len(), 1);
+    assert_eq!(
+        tx.inputs()[0].previous_output(),
+        OutPoint::new(
+            hex::decode("d1c789a9c60383bf715f3f6ad9d14b91fe55f3deb369fe5d9280cb1a01793f81")
+                .unwrap()
+                .try_into()
+                .unwrap(),
+            0
+        )
+    );
+    assert_eq!(tx.inputs()[0].sequence(), 0xfffffffe);
+    assert_eq!(tx.outputs().len(), 2);
+    assert_eq!(tx.outputs()[0].amount(), 32454049);
+    assert_eq!(
+        hex::encode(tx.outputs()[1].script_pubkey()),
+        "76a9141c4bc762dd5423e332166702cb75f40df79fea1288ac"
+    );
+    assert_eq!(tx.outputs()[1].amount(), 10011545);
+    assert_eq!(tx.locktime(), 410393);
+    assert!(!tx.is_coinbase());
+}
+
+#[test]
+fn test_tx_round_trip() {
+    let vectors = [
+        (
+            TX,
+            "452c629d67e41baec3ac6f04fe744b4b9617f8f859c63b3002f8684e7a4fee03",
+        ),
+        (
+            SATOSHI_TO_HAL,
+            "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16",
+        ),
+    ];
+    for (raw, txid) in vectors {
+        let tx = raw.parse::<Tx>().unwrap();
+        assert_eq!(tx.to_string(), raw);
+        assert_eq!(tx.id(), txid);
+    }
+    let tx = SATOSHI_TO_HAL.parse::<Tx>().unwrap();
+    assert_eq!(tx.output_value(), 5_000_000_000);
+}
+
+#[test]
+fn test_invalid_tx() {
+    assert!(TX[..TX.len() - 2].parse::<Tx>().is_err());
+    assert!(format!("{}00", TX).parse::<Tx>().is_err());
+}
+
+const SEGWIT_TX: &str = "01000000000102fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f00000000494830450221008b9d1dc26ba6a9cb62127b02742fa9d754cd3bebf337f7a55d114c8e5cdd30be022040529b194ba3f9281a99f2b1c0a19c0489bc22ede944ccf4ecbab4cc618ef3ed01eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac000247304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01cc44a0220573a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee0121025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee635711000000";
+
+#[test]
+fn test_segwit_tx() {
+    let tx = SEGWIT_TX.parse::<Tx>().unwrap();
+    assert!(tx.is_segwit());
+    assert!(tx.inputs()[0].witness().is_empty());
+    assert_eq!(tx.inputs()[1].witness().len(), 2);
+    assert_eq!(
+        hex::encode(&tx.inputs()[1].witness()[1]),
+        "025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee6357"
+    );
+    assert_eq!(tx.locktime(), 17);
+    assert_eq!(tx.to_string(), SEGWIT_TX);
+    assert_eq!(
+        tx.id(),
+        "e8151a2af31c368a35053ddd4bdb285a8595c769a3ad83e0fa02314a602d4609"
+    );
+    assert_eq!(
+        tx.wid(),
+        "c36c38370907df2324d9ce9d149d191192f338b37665a82e78e76a12c909b762"
+    );
+    assert_eq!(tx.serialize_legacy().len(), 233);
+    assert_eq!(tx.weight(), 1042);
+    assert_eq!(tx.vsize(), 261);
+}
+
+#[test]
+fn test_legacy_weight() {
+    let tx = TX.parse::<Tx>().unwrap();
+    assert!(!tx.is_segwit());
+    assert_eq!(tx.id(), tx.wid());
+    assert_eq!(tx.weight(), TX.len() / 2 * 4);
+    assert_eq!(tx.vsize(), TX.len() / 2);
+}
+
+#[test]
+fn test_invalid_segwit_tx() {
+    let bad_flag = format!("{}02{}", &SEGWIT_TX[..10], &SEGWIT_TX[12..]);
+    assert!(bad_flag.parse::<Tx>().is_err());
+}
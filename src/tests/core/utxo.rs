@@ -0,0 +1,267 @@
+use crate::core::block::{merkle_root, Block, BlockHeader};
+use crate::core::script::Script;
+use crate::core::sha256ser::DoubleSha256;
+use crate::core::tx::{OutPoint, Tx, TxIn, TxOut, SEQUENCE_FINAL};
+use crate::core::utxo::{
+    BlockUndo, Coin, LogStore, MemoryStore, UtxoSet, UtxoStore, COINBASE_MATURITY, MAX_MONEY,
+};
+use crate::ser::chained_hash::ChainedCompute;
+use crate::ser::hex;
+use crate::tests::util::{temp_path, REGTEST_BITS};
+use std::fs::{self, OpenOptions};
+use std::io::{Cursor, Write};
+
+const SUBSIDY: u64 = 5_000_000_000;
+
+fn script(hex_str: &str) -> Script {
+    Script::from(hex::decode(hex_str).unwrap())
+}
+
+fn miner() -> Script {
+    script("0014751e76e8199196d454941c45d1b3a323f1433bd6")
+}
+
+fn payee() -> Script {
+    script("00141d0f172a0ecb48aee1be1f2687d2963ae33f71a1")
+}
+
+fn coinbase(height: u32) -> Tx {
+    let script_sig = Script::from([&[4][..], &height.to_le_bytes()].concat());
+    Tx::new(
+        2,
+        vec![TxIn::new(OutPoint::NULL, script_sig, SEQUENCE_FINAL)],
+        vec![TxOut::new(SUBSIDY, miner())],
+        0,
+    )
+}
+
+fn spend(outpoint: OutPoint, outputs: Vec<TxOut>) -> Tx {
+    Tx::new(
+        2,
+        vec![TxIn::new(outpoint, Script::new(), SEQUENCE_FINAL)],
+        outputs,
+        0,
+    )
+}
+
+fn block(prev: [u8; 32], txs: Vec<Tx>) -> Block {
+    let hashes: Vec<[u8; 32]> = txs.iter().map(Tx::hash).collect();
+    let header = BlockHeader::new(4, prev, merkle_root(&hashes).0, 0, REGTEST_BITS, 0);
+    Block::new(header, txs)
+}
+
+// Connects blocks paying only their coinbase up to `height`
+fn mine<S: UtxoStore>(utxos: &mut UtxoSet<S>, height: u32) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    while utxos.tip().is_none_or(|(_, tip)| tip < height) {
+        let (prev, next) = utxos
+            .tip()
+            .map_or(([0; 32], 0), |(hash, tip)| (hash, tip + 1));
+        let block = block(prev, vec![coinbase(next)]);
+        utxos.connect_block(&block).unwrap();
+        blocks.push(block);
+    }
+    blocks
+}
+
+fn sorted_coins(store: &mut impl UtxoStore) -> Vec<(OutPoint, Coin)> {
+    let mut coins = store.coins().unwrap();
+    coins.sort_by_key(|(outpoint, _)| *outpoint);
+    coins
+}
+
+#[test]
+fn test_coin() {
+    let coin = Coin::new(TxOut::new(SUBSIDY, miner()), 7, true);
+    assert_eq!(
+        Coin::parse(&mut Cursor::new(coin.serialize())).unwrap(),
+        coin
+    );
+    assert!(!coin.is_mature(7 + COINBASE_MATURITY - 1));
+    assert!(coin.is_mature(7 + COINBASE_MATURITY));
+    assert!(Coin::new(TxOut::new(1, payee()), 7, false).is_mature(8));
+
+    let undo = BlockUndo::parse(&mut Cursor::new(BlockUndo::default().serialize())).unwrap();
+    assert!(undo.spent().is_empty());
+}
+
+#[test]
+fn test_connect_block() {
+    let mut utxos = UtxoSet::new(MemoryStore::new());
+    assert!(utxos
+        .connect_block(&block([1; 32], vec![coinbase(0)]))
+        .is_err());
+    let blocks = mine(&mut utxos, 101);
+    let genesis = OutPoint::new(blocks[0].txs()[0].hash(), 0);
+    assert!(utxos.get(&genesis).unwrap().is_none());
+    // The coinbases of blocks 1 and 2 can be spent in block 102
+    assert_eq!(
+        utxos.balance(&miner()).unwrap(),
+        (2 * SUBSIDY, 99 * SUBSIDY)
+    );
+    assert_eq!(utxos.spendable(&miner()).unwrap().len(), 2);
+
+    let first = OutPoint::new(blocks[1].txs()[0].hash(), 0);
+    let immature = OutPoint::new(blocks[3].txs()[0].hash(), 0);
+    let pay = spend(
+        first,
+        vec![
+            TxOut::new(SUBSIDY - 1000, payee()),
+            TxOut::new(0, script("6a0102")),
+        ],
+    );
+    assert_eq!(utxos.check_tx(&pay).unwrap(), 1000);
+    let too_much = spend(first, vec![TxOut::new(SUBSIDY + 1, payee())]);
+    assert!(utxos.check_tx(&too_much).is_err());
+    assert!(utxos
+        .check_tx(&spend(immature, vec![TxOut::new(1, payee())]))
+        .is_err());
+    assert!(utxos
+        .check_tx(&spend(OutPoint::new([9; 32], 0), vec![]))
+        .is_err());
+    assert!(utxos.check_tx(&coinbase(102)).is_err());
+    let overflow = spend(
+        first,
+        vec![TxOut::new(u64::MAX, payee()), TxOut::new(2, payee())],
+    );
+    assert!(utxos.check_tx(&overflow).is_err());
+    assert!(utxos
+        .check_tx(&spend(first, vec![TxOut::new(MAX_MONEY + 1, payee())]))
+        .is_err());
+
+    // Spending an output of the same block works, spending one twice doesn't
+    let tip = utxos.tip().unwrap().0;
+    let forward = spend(
+        OutPoint::new(pay.hash(), 0),
+        vec![TxOut::new(SUBSIDY - 2000, payee())],
+    );
+    let double = spend(first, vec![TxOut::new(1, payee())]);
+    let invalid = block(
+        tip,
+        vec![coinbase(102), pay.clone(), forward.clone(), double],
+    );
+    assert!(utxos.connect_block(&invalid).is_err());
+    let minted = Tx::new(
+        2,
+        coinbase(102).inputs().to_vec(),
+        vec![TxOut::new(MAX_MONEY + 1, miner())],
+        0,
+    );
+    assert!(utxos.connect_block(&block(tip, vec![minted])).is_err());
+    assert_eq!(utxos.tip().unwrap(), (tip, 101));
+    let next = block(tip, vec![coinbase(102), pay.clone(), forward.clone()]);
+    let undo = utxos.connect_block(&next).unwrap();
+    assert_eq!(utxos.tip().unwrap(), (next.hash(), 102));
+    assert_eq!(undo.spent().len(), 2);
+    assert_eq!(undo.spent()[0].height(), 1);
+    assert!(utxos.get(&first).unwrap().is_none());
+    assert!(utxos.get(&OutPoint::new(pay.hash(), 0)).unwrap().is_none());
+    assert!(utxos.get(&OutPoint::new(pay.hash(), 1)).unwrap().is_none());
+    assert_eq!(utxos.balance(&payee()).unwrap(), (SUBSIDY - 2000, 0));
+    assert_eq!(
+        utxos.balance(&miner()).unwrap(),
+        (2 * SUBSIDY, 99 * SUBSIDY)
+    );
+    assert!(utxos.connect_block(&next).is_err());
+}
+
+#[test]
+fn test_disconnect_block() {
+    let mut utxos = UtxoSet::new(MemoryStore::new());
+    let blocks = mine(&mut utxos, 101);
+    let before = sorted_coins(&mut utxos.store().clone());
+    let first = OutPoint::new(blocks[1].txs()[0].hash(), 0);
+    let pay = spend(first, vec![TxOut::new(SUBSIDY, payee())]);
+    let next = block(blocks[101].hash(), vec![coinbase(102), pay]);
+    let undo = utxos.connect_block(&next).unwrap();
+    let undo = BlockUndo::parse(&mut Cursor::new(undo.serialize())).unwrap();
+
+    assert!(utxos
+        .disconnect_block(&blocks[101], &BlockUndo::default())
+        .is_err());
+    assert!(utxos
+        .disconnect_block(&next, &BlockUndo::default())
+        .is_err());
+    assert!(utxos
+        .disconnect_block(&Block::new(*next.header(), Vec::new()), &undo)
+        .is_err());
+    assert!(utxos
+        .disconnect_block(&Block::new(*next.header(), vec![coinbase(102)]), &undo)
+        .is_err());
+    utxos.disconnect_block(&next, &undo).unwrap();
+    assert_eq!(utxos.tip().unwrap(), (blocks[101].hash(), 101));
+    assert_eq!(sorted_coins(&mut utxos.store().clone()), before);
+    assert_eq!(
+        utxos.get(&first).unwrap(),
+        Some(Coin::new(TxOut::new(SUBSIDY, miner()), 1, true))
+    );
+}
+
+#[test]
+fn test_log_store() {
+    let path = temp_path("utxo", "log");
+    let mut utxos = UtxoSet::new(LogStore::open(&path).unwrap());
+    let blocks = mine(&mut utxos, 101);
+    let first = OutPoint::new(blocks[1].txs()[0].hash(), 0);
+    let pay = spend(first, vec![TxOut::new(SUBSIDY - 500, payee())]);
+    let next = block(blocks[101].hash(), vec![coinbase(102), pay]);
+    let undo = utxos.connect_block(&next).unwrap();
+    let mut memory = UtxoSet::new(MemoryStore::new());
+    for block in blocks.iter().chain([&next]) {
+        memory.connect_block(block).unwrap();
+    }
+    drop(utxos);
+
+    // A batch cut short by a crash is dropped on open
+    let committed = fs::metadata(&path).unwrap().len();
+    let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+    file.write_all(&[1, 0xab, 0xcd]).unwrap();
+    drop(file);
+    let mut store = LogStore::open(&path).unwrap();
+    assert_eq!(fs::metadata(&path).unwrap().len(), committed);
+    assert_eq!(store.tip(), Some((next.hash(), 102)));
+    assert_eq!(
+        sorted_coins(&mut store),
+        sorted_coins(&mut memory.store().clone())
+    );
+
+    let mut utxos = UtxoSet::new(store);
+    assert_eq!(utxos.balance(&payee()).unwrap(), (SUBSIDY - 500, 0));
+    utxos.disconnect_block(&next, &undo).unwrap();
+    drop(utxos);
+    let mut utxos = UtxoSet::new(LogStore::open(&path).unwrap());
+    assert_eq!(utxos.tip(), Some((blocks[101].hash(), 101)));
+    assert_eq!(utxos.balance(&payee()).unwrap(), (0, 0));
+    assert!(utxos.get(&first).unwrap().is_some());
+    drop(utxos);
+
+    // A batch running past the end of the file is torn, damage before it is reported
+    let committed = fs::metadata(&path).unwrap().len();
+    let len = 100u32.to_le_bytes();
+    let torn = [&len[..], &DoubleSha256::compute(&len)[..4], &[0; 60]].concat();
+    let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+    file.write_all(&torn).unwrap();
+    drop(file);
+    LogStore::open(&path).unwrap();
+    assert_eq!(fs::metadata(&path).unwrap().len(), committed);
+
+    let bytes = fs::read(&path).unwrap();
+    let mut damaged = bytes.clone();
+    damaged[0] = 0x7f;
+    fs::write(&path, &damaged).unwrap();
+    assert!(LogStore::open(&path).is_err());
+    assert_eq!(fs::metadata(&path).unwrap().len(), committed);
+
+    // The second batch adds the coinbase of block 1, give its script a huge length
+    let mut damaged = bytes.clone();
+    let start = 49;
+    let len = u32::from_le_bytes(damaged[start..start + 4].try_into().unwrap()) as usize;
+    let records = start + 8..start + 8 + len;
+    damaged[records.start + 1 + 36 + 4 + 8] = 0xff;
+    let checksum = DoubleSha256::compute(&damaged[records.clone()]);
+    damaged[records.end..records.end + 4].copy_from_slice(&checksum[..4]);
+    fs::write(&path, &damaged).unwrap();
+    assert!(LogStore::open(&path).is_err());
+    assert_eq!(fs::metadata(&path).unwrap().len(), committed);
+    fs::remove_file(&path).unwrap();
+}
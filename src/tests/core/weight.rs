@@ -0,0 +1,227 @@
+use crate::core::address::{p2pkh_script, p2sh_script, p2tr_script, p2wpkh_script, p2wsh_script};
+use crate::core::builder::{sign_tx, Utxo};
+use crate::core::coin_selection::{WeightedUtxo, P2WPKH_INPUT_WEIGHT};
+use crate::core::s256ecc::{S256CurveCfg, S256PrivateKey, S256Signature};
+use crate::core::script::Script;
+use crate::core::sha256ser::Sha256Ripemd160;
+use crate::core::sighash::{SighashCache, SIGHASH_ALL};
+use crate::core::tx::{OutPoint, Tx, TxIn, TxOut};
+use crate::core::weight::{
+    estimate_fee, estimate_vsize, estimate_weight, InputType, MAX_DER_SIGNATURE_LEN,
+    MAX_ECDSA_SIGNATURE_LEN,
+};
+use crate::ecc::elliptic_curve::EllipticCurve;
+use crate::ser::chained_hash::ChainedCompute;
+use crate::tests::util::{keys, multisig_script};
+use bnum::types::U256;
+use sha2::{Digest, Sha256};
+
+fn multisig_signatures(z: U256, keys: &[S256PrivateKey]) -> Vec<Vec<u8>> {
+    keys.iter()
+        .map(|key| [key.sign(z).der_encoded(), vec![SIGHASH_ALL as u8]].concat())
+        .collect()
+}
+
+#[test]
+fn test_max_der_signature_len() {
+    let n = S256CurveCfg::N;
+    // r with its high bit set needs padding, low-S never does
+    let low_s = S256Signature::from_values(n - U256::ONE, n >> 1);
+    let der = low_s.der();
+    assert_eq!(der[..3], [0x02, 33, 0x00]);
+    assert_eq!(der[35..37], [0x02, 32]);
+    assert_eq!(der.len() as u64 + 2, MAX_DER_SIGNATURE_LEN);
+    assert_eq!(low_s.der_encoded().len() as u64, MAX_DER_SIGNATURE_LEN);
+    let high_s = S256Signature::from_values(n - U256::ONE, n - U256::ONE);
+    assert_eq!(high_s.der_encoded().len() as u64, MAX_DER_SIGNATURE_LEN + 1);
+
+    let keys = keys(8);
+    for (index, key) in keys.iter().enumerate() {
+        let z = U256::from(index as u32 + 1) << 200;
+        assert!(key.sign(z).der_encoded().len() as u64 <= MAX_DER_SIGNATURE_LEN);
+    }
+}
+
+#[test]
+fn test_input_weights() {
+    assert_eq!(InputType::P2wpkh.weight(), P2WPKH_INPUT_WEIGHT);
+    assert_eq!(InputType::P2pkh.weight(), 592);
+    assert_eq!(InputType::P2pkhUncompressed.weight(), 720);
+    assert_eq!(InputType::P2shP2wpkh.weight(), 364);
+    assert_eq!(InputType::P2tr.weight(), 230);
+    assert_eq!(InputType::P2wshMultisig(2, 3).weight(), 418);
+    assert_eq!(InputType::P2shP2wshMultisig(2, 3).weight(), 558);
+    assert_eq!(InputType::P2shMultisig(2, 3).weight(), 1188);
+    // 15 keys need a PUSHDATA2 for the redeem script
+    assert_eq!(
+        InputType::P2shMultisig(1, 15).script_sig_len(),
+        1 + 73 + 3 + 513
+    );
+    // Counts above 16 are pushed as script numbers
+    let many_keys = keys(20);
+    let script_len = multisig_script(&many_keys, 17).len() as u64;
+    assert_eq!(
+        InputType::P2wshMultisig(17, 20).witness_len(),
+        1 + 1 + 17 * (1 + MAX_ECDSA_SIGNATURE_LEN) + 3 + script_len
+    );
+
+    assert!(!InputType::P2pkh.is_segwit());
+    assert!(!InputType::P2shMultisig(1, 1).is_segwit());
+    assert!(InputType::P2shP2wpkh.is_segwit());
+    assert_eq!(InputType::P2tr.script_sig_len(), 0);
+
+    let keys = keys(8);
+    let hash = keys[0].point().hash160(true);
+    assert_eq!(
+        InputType::from_script_pubkey(&Script::from(p2pkh_script(&hash))),
+        Some(InputType::P2pkh)
+    );
+    assert_eq!(
+        InputType::from_script_pubkey(&Script::from(p2wpkh_script(&hash))),
+        Some(InputType::P2wpkh)
+    );
+    assert_eq!(
+        InputType::from_script_pubkey(&Script::from(p2sh_script(&hash))),
+        None
+    );
+    let utxo = Utxo::new(OutPoint::new([1; 32], 0), 10_000, Script::new());
+    assert_eq!(
+        WeightedUtxo::from_input_type(utxo, InputType::P2tr).input_weight(),
+        230
+    );
+}
+
+#[test]
+fn test_estimate_fee() {
+    // One P2WPKH input paying two P2WPKH outputs is the familiar 141 vbytes
+    let outputs = vec![
+        TxOut::new(50_000, Script::from(p2wpkh_script(&[1; 20]))),
+        TxOut::new(40_000, Script::from(p2wpkh_script(&[2; 20]))),
+    ];
+    assert_eq!(estimate_weight(&[InputType::P2wpkh], &outputs), 562);
+    assert_eq!(estimate_vsize(&[InputType::P2wpkh], &outputs), 141);
+    assert_eq!(estimate_fee(&[InputType::P2wpkh], &outputs, 3), 423);
+    // Legacy only transactions have no segwit overhead
+    assert_eq!(
+        estimate_weight(&[InputType::P2pkh], &outputs),
+        4 * 10 + 592 + 248
+    );
+    // Legacy inputs in a segwit transaction carry an empty witness
+    assert_eq!(
+        estimate_weight(&[InputType::P2pkh, InputType::P2tr], &outputs),
+        4 * 10 + 592 + 230 + 248 + 2 + 1
+    );
+}
+
+#[test]
+fn test_estimate_signed_transactions() {
+    let keys = keys(8);
+    let p2wsh_multisig = multisig_script(&keys[4..7], 2);
+    let p2sh_multisig = multisig_script(&keys[5..8], 2);
+    let p2sh_p2wsh_multisig = multisig_script(&keys[4..8], 3);
+    let nested = [
+        &[0x00, 0x20][..],
+        &Sha256::digest(p2sh_p2wsh_multisig.as_bytes()),
+    ]
+    .concat();
+    let spent = vec![
+        TxOut::new(
+            10_000,
+            Script::from(p2pkh_script(&keys[0].point().hash160(true))),
+        ),
+        TxOut::new(
+            20_000,
+            Script::from(p2pkh_script(&keys[1].point().hash160(false))),
+        ),
+        TxOut::new(
+            30_000,
+            Script::from(p2wpkh_script(&keys[2].point().hash160(true))),
+        ),
+        TxOut::new(
+            40_000,
+            Script::from(p2sh_script(&Sha256Ripemd160::compute(
+                &keys[3].point().p2sh_p2wpkh_redeem_script(),
+            ))),
+        ),
+        TxOut::new(
+            50_000,
+            Script::from(p2tr_script(
+                &keys[4].point().tap_tweak(None).unwrap().xonly(),
+            )),
+        ),
+        TxOut::new(
+            60_000,
+            Script::from(p2wsh_script(&Sha256::digest(p2wsh_multisig.as_bytes()))),
+        ),
+        TxOut::new(
+            70_000,
+            Script::from(p2sh_script(&Sha256Ripemd160::compute(
+                p2sh_multisig.as_bytes(),
+            ))),
+        ),
+        TxOut::new(
+            80_000,
+            Script::from(p2sh_script(&Sha256Ripemd160::compute(&nested))),
+        ),
+    ];
+    let input_types = [
+        InputType::P2pkh,
+        InputType::P2pkhUncompressed,
+        InputType::P2wpkh,
+        InputType::P2shP2wpkh,
+        InputType::P2tr,
+        InputType::P2wshMultisig(2, 3),
+        InputType::P2shMultisig(2, 3),
+        InputType::P2shP2wshMultisig(3, 4),
+    ];
+    let inputs = (0..spent.len() as u32)
+        .map(|vout| TxIn::new(OutPoint::new([9; 32], vout), Script::new(), 0))
+        .collect();
+    let outputs = vec![TxOut::new(300_000, Script::from(p2wpkh_script(&[3; 20])))];
+    let mut tx = Tx::new(2, inputs, outputs.clone(), 0);
+
+    // Single key inputs go through the builder's signer, multisig inputs are signed here
+    let unsigned = tx.clone();
+    let cache = SighashCache::with_spent_outputs(&unsigned, &spent);
+    let mut single = Tx::new(2, unsigned.inputs()[..5].to_vec(), outputs.clone(), 0);
+    sign_tx(&mut single, &spent[..5], &keys).unwrap();
+    for (input, signed) in tx.inputs_mut().iter_mut().zip(single.inputs()) {
+        input.set_script_sig(signed.script_sig().clone());
+        input.set_witness(signed.witness().to_vec());
+    }
+    // Signatures over a different message have the same size distribution
+    let z = cache
+        .segwit_v0_sighash(5, &p2wsh_multisig, 60_000, SIGHASH_ALL)
+        .unwrap();
+    let mut witness = vec![Vec::new()];
+    witness.extend(multisig_signatures(z, &keys[4..6]));
+    witness.push(p2wsh_multisig.as_bytes().to_vec());
+    tx.inputs_mut()[5].set_witness(witness);
+
+    let z = cache.tx().legacy_sighash(6, &p2sh_multisig, SIGHASH_ALL);
+    let mut script_sig = Script::new();
+    script_sig.push_int(0);
+    for signature in multisig_signatures(z, &keys[5..7]) {
+        script_sig.push_data(&signature);
+    }
+    script_sig.push_data(p2sh_multisig.as_bytes());
+    tx.inputs_mut()[6].set_script_sig(script_sig);
+
+    let z = cache
+        .segwit_v0_sighash(7, &p2sh_p2wsh_multisig, 80_000, SIGHASH_ALL)
+        .unwrap();
+    let mut witness = vec![Vec::new()];
+    witness.extend(multisig_signatures(z, &keys[4..7]));
+    witness.push(p2sh_p2wsh_multisig.as_bytes().to_vec());
+    tx.inputs_mut()[7].set_witness(witness);
+    let mut script_sig = Script::new();
+    script_sig.push_data(&nested);
+    tx.inputs_mut()[7].set_script_sig(script_sig);
+
+    let estimate = estimate_weight(&input_types, &outputs);
+    let actual = tx.weight() as u64;
+    assert!(estimate >= actual);
+    // At most one byte per ECDSA signature, four weight units when it's in a scriptSig
+    assert!(estimate - actual <= 4 * 4 + 7);
+    assert!(estimate_vsize(&input_types, &outputs) >= tx.vsize() as u64);
+}
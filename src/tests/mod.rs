@@ -1,2 +1,5 @@
 mod core;
 mod ecc;
+mod network;
+mod ser;
+mod util;
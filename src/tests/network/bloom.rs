@@ -0,0 +1,196 @@
+use crate::core::merkle::MerkleBlock;
+use crate::core::network::Network;
+use crate::core::script::Script;
+use crate::core::tx::{OutPoint, Tx, TxIn, TxOut};
+use crate::network::bloom::{
+    murmur3, BloomFilter, BLOOM_UPDATE_ALL, BLOOM_UPDATE_NONE, BLOOM_UPDATE_P2PUBKEY_ONLY,
+    MAX_HASH_FUNCS,
+};
+use crate::network::message::{NetworkMessage, RawMessage};
+use crate::ser::hex;
+use std::io::Cursor;
+
+// Programming Bitcoin transaction paying 1c4bc762… and bc3b654d…
+const TX: &str = "0100000001813f79011acb80925dfe69b3def355fe914bd1d96a3f5f71bf8303c6a989c7d1000000006b483045022100ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320b0277457c98f02207a986d955c6e0cb35d446a89d3f56100f4d7f67801c31967743a9c8e10615bed01210349fc4e631e3624a545de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278afeffffff02a135ef01000000001976a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88ac99c39800000000001976a9141c4bc762dd5423e332166702cb75f40df79fea1288ac19430600";
+
+const MERKLE_BLOCK: &str = "00000020df3b053dc46f162a9b00c7f0d5124e2676d47bbe7c5d0793a500000000000000ef445fef2ed495c275892206ca533e7411907971013ab83e3b47bd0d692d14d4dc7c835b67d8001ac157e670bf0d00000aba412a0d1480e370173072c9562becffe87aa661c1e4a6dbc305d38ec5dc088a7cf92e6458aca7b32edae818f9c2c98c37e06bf72ae0ce80649a38655ee1e27d34d9421d940b16732f24b94023e9d572a7f9ab8023434a4feb532d2adfc8c2c2158785d1bd04eb99df2e86c54bc13e139862897217400def5d72c280222c4cbaee7261831e1550dbb8fa82853e9fe506fc5fda3f7b919d8fe74b6282f92763cef8e625f977af7c8619c32a369b832bc2d051ecd9c73c51e76370ceabd4f25097c256597fa898d404ed53425de608ac6bfe426f6e2bb457f1c554866eb69dcb8d6bf6f880e9a59b3cd053e6c7060eeacaacf4dac6697dac20e4bd3f38a2ea2543d1ab7953e3430790a9f81e1c67f5b58c825acf46bd02848384eebe9af917274cdfbb1a28a5d58a23a17977def0de10d644258d9c54f886d47d293a411cb6226103b55635";
+
+// Uncompressed key of 5Kg1gnAjaLfKiwhhPpGS3QfRg2m6awQvaj98JCZBZQ5SuS2F15C
+const PUBKEY: &str = "045b81f0017e2091e2edcd5eecf10d5bdd120a5514cb3ee65b8447ec18bfc4575c6d5bf415e54e03b1067934a0f0ba76b01c6b9ab227142ee1d543764b69d901e0";
+
+fn hash160(hex_str: &str) -> [u8; 20] {
+    hex::decode(hex_str).unwrap().try_into().unwrap()
+}
+
+fn spend(outpoint: OutPoint) -> Tx {
+    let input = TxIn::new(outpoint, Script::new(), 0xffff_ffff);
+    Tx::new(
+        2,
+        vec![input],
+        vec![TxOut::new(1000, Script::from(vec![0x51]))],
+        0,
+    )
+}
+
+#[test]
+fn test_murmur3() {
+    // Bitcoin Core hash_tests
+    let vectors: [(u32, u32, &str); 14] = [
+        (0x0000_0000, 0x0000_0000, ""),
+        (0x6a39_6f08, 0xfba4_c795, ""),
+        (0x81f1_6f39, 0xffff_ffff, ""),
+        (0x514e_28b7, 0x0000_0000, "00"),
+        (0xea3f_0b17, 0xfba4_c795, "00"),
+        (0xfd6c_f10d, 0x0000_0000, "ff"),
+        (0x16c6_b7ab, 0x0000_0000, "0011"),
+        (0x8eb5_1c3d, 0x0000_0000, "001122"),
+        (0xb447_1bf8, 0x0000_0000, "00112233"),
+        (0xe230_1fa8, 0x0000_0000, "0011223344"),
+        (0xfc2e_4a15, 0x0000_0000, "001122334455"),
+        (0xb074_502c, 0x0000_0000, "00112233445566"),
+        (0x8034_d2a0, 0x0000_0000, "0011223344556677"),
+        (0xb469_8def, 0x0000_0000, "001122334455667788"),
+    ];
+    for (expected, seed, data) in vectors {
+        assert_eq!(
+            murmur3(&hex::decode(data).unwrap(), seed),
+            expected,
+            "{}",
+            data
+        );
+    }
+}
+
+#[test]
+fn test_insert_serialize() {
+    // Bitcoin Core bloom_tests
+    for (tweak, expected) in [
+        (0, "03614e9b050000000000000001"),
+        (2_147_483_649, "03ce4299050000000100008001"),
+    ] {
+        let mut filter = BloomFilter::new(3, 0.01, tweak, BLOOM_UPDATE_ALL);
+        filter.insert_hash160(&hash160("99108ad8ed9bb6274d3980bab5a85c048f0950c8"));
+        assert!(filter.contains(&hash160("99108ad8ed9bb6274d3980bab5a85c048f0950c8")));
+        assert!(!filter.contains(&hash160("19108ad8ed9bb6274d3980bab5a85c048f0950c8")));
+        filter
+            .insert_hash160(&hash160("b5a2c786d9ef4658287ced5914b37a1b4aa32eee"))
+            .insert_hash160(&hash160("b9300670b4c5366e95b2699e8b18bc75e5f729c5"));
+        assert!(filter.contains(&hash160("b5a2c786d9ef4658287ced5914b37a1b4aa32eee")));
+        assert_eq!(hex::encode(&filter.serialize()), expected);
+        let parsed = BloomFilter::parse(&mut Cursor::new(filter.serialize())).unwrap();
+        assert_eq!(parsed, filter);
+    }
+
+    let mut filter = BloomFilter::new(2, 0.001, 0, BLOOM_UPDATE_ALL);
+    filter.insert_pubkey(&hex::decode(PUBKEY).unwrap());
+    assert_eq!(
+        hex::encode(&filter.serialize()),
+        "038fc16b080000000000000001"
+    );
+}
+
+#[test]
+fn test_sizing() {
+    let filter = BloomFilter::new(10, 0.000_001, 0, BLOOM_UPDATE_NONE);
+    assert_eq!(filter.data().len(), 35);
+    assert_eq!(filter.hash_funcs(), 19);
+    assert_eq!(
+        BloomFilter::new(1, 1e-30, 0, BLOOM_UPDATE_NONE).hash_funcs(),
+        MAX_HASH_FUNCS
+    );
+    let filter = BloomFilter::new(1_000_000, 0.0001, 0, BLOOM_UPDATE_NONE);
+    assert_eq!(filter.data().len(), 36_000);
+
+    let oversize = [&[0xfd, 0xa1, 0x8c][..], &[0; 36_001], &[0; 9]].concat();
+    assert!(BloomFilter::parse(&mut Cursor::new(oversize)).is_err());
+    let too_many_funcs = hex::decode("0100330000000000000000").unwrap();
+    assert!(BloomFilter::parse(&mut Cursor::new(too_many_funcs)).is_err());
+}
+
+#[test]
+fn test_relevant_transactions() {
+    let tx: Tx = TX.parse().unwrap();
+    let txid = tx.hash();
+    let paid = hash160("bc3b654dca7e56b04dca18f2566cdaf02e8d9ada");
+
+    let mut filter = BloomFilter::new(10, 0.000_001, 0, BLOOM_UPDATE_ALL);
+    assert!(!filter.is_relevant_and_update(&tx));
+    filter.insert_hash160(&paid);
+    assert!(filter.is_relevant_and_update(&tx));
+    assert!(filter.contains_outpoint(&OutPoint::new(txid, 0)));
+    assert!(filter.is_relevant_and_update(&spend(OutPoint::new(txid, 0))));
+    assert!(!filter.is_relevant_and_update(&spend(OutPoint::new(txid, 1))));
+
+    // Outpoints are only added for pay-to-pubkey outputs, or not at all
+    for flags in [BLOOM_UPDATE_NONE, BLOOM_UPDATE_P2PUBKEY_ONLY] {
+        let mut filter = BloomFilter::new(10, 0.000_001, 0, flags);
+        filter.insert_hash160(&paid);
+        assert!(filter.is_relevant_and_update(&tx));
+        assert!(!filter.is_relevant_and_update(&spend(OutPoint::new(txid, 0))));
+    }
+    let pubkey = hex::decode(PUBKEY).unwrap();
+    let p2pk = Script::from([&[0x41][..], &pubkey, &[0xac]].concat());
+    let p2pk_tx = Tx::new(
+        2,
+        spend(OutPoint::NULL).inputs().to_vec(),
+        vec![TxOut::new(1, p2pk)],
+        0,
+    );
+    let mut filter = BloomFilter::new(10, 0.000_001, 0, BLOOM_UPDATE_P2PUBKEY_ONLY);
+    filter.insert_pubkey(&pubkey);
+    assert!(filter.is_relevant_and_update(&p2pk_tx));
+    assert!(filter.is_relevant_and_update(&spend(OutPoint::new(p2pk_tx.hash(), 0))));
+
+    // By txid, spent outpoint, or data pushed in the input
+    let mut filter = BloomFilter::new(10, 0.000_001, 0, BLOOM_UPDATE_NONE);
+    filter.insert_txid(&txid);
+    assert!(filter.contains_txid(&txid));
+    assert!(filter.is_relevant_and_update(&tx));
+    let mut filter = BloomFilter::new(10, 0.000_001, 0, BLOOM_UPDATE_NONE);
+    filter.insert_outpoint(&tx.inputs()[0].previous_output());
+    assert!(filter.is_relevant_and_update(&tx));
+    let mut filter = BloomFilter::new(10, 0.000_001, 0, BLOOM_UPDATE_NONE);
+    filter.insert(
+        &hex::decode("0349fc4e631e3624a545de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278a").unwrap(),
+    );
+    assert!(filter.is_relevant_and_update(&tx));
+}
+
+#[test]
+fn test_filter_messages() {
+    let mut filter = BloomFilter::new(10, 0.01, 5, BLOOM_UPDATE_P2PUBKEY_ONLY);
+    filter.insert(b"element");
+    let merkle_block: MerkleBlock = MERKLE_BLOCK.parse().unwrap();
+    for message in [
+        NetworkMessage::FilterLoad(filter),
+        NetworkMessage::FilterAdd(vec![7; 520]),
+        NetworkMessage::FilterClear,
+        NetworkMessage::MerkleBlock(merkle_block),
+    ] {
+        let bytes = message.serialize(Network::Testnet);
+        assert_eq!(
+            NetworkMessage::parse(&mut Cursor::new(bytes), Network::Testnet).unwrap(),
+            message
+        );
+    }
+    let payload = NetworkMessage::FilterAdd(vec![7; 521]).payload();
+    assert!(NetworkMessage::from_raw(&RawMessage::new("filteradd", payload).unwrap()).is_err());
+}
+
+#[test]
+fn test_empty_filter() {
+    let payload = hex::decode("00000000000000000001").unwrap();
+    let raw = RawMessage::new("filterload", payload).unwrap();
+    let NetworkMessage::FilterLoad(mut filter) = NetworkMessage::from_raw(&raw).unwrap() else {
+        panic!("expected filterload");
+    };
+    assert!(filter.data().is_empty());
+    filter.insert(b"element");
+    assert!(filter.data().is_empty());
+    assert!(filter.contains(b"anything"));
+    assert!(filter.contains_txid(&[0; 32]));
+
+    let tx: Tx = TX.parse().unwrap();
+    assert!(filter.is_relevant_and_update(&tx));
+    assert!(filter.is_relevant_and_update(&spend(OutPoint::NULL)));
+}
@@ -0,0 +1,314 @@
+use crate::core::block::{merkle_root, Block, BlockHeader};
+use crate::core::chain::HeaderChain;
+use crate::core::network::Network;
+use crate::core::script::Script;
+use crate::core::tx::{OutPoint, Tx, TxIn, TxOut};
+use crate::network::filter::{
+    filter_header, siphash24, BlockFilter, CFCheckptMessage, CFHeadersMessage, FilterHeaderChain,
+    FilterRequest, FILTER_TYPE_BASIC,
+};
+use crate::network::message::{NetworkMessage, RawMessage};
+use crate::ser::hex;
+use crate::ser::varint::encode_varint;
+use crate::tests::util::{mine_chain, REGTEST_BITS};
+use std::io::Cursor;
+
+const GENESIS_COINBASE: &str = "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000";
+
+// BIP158 test vectors: testnet blocks 1 to 3 with their basic filter and filter header
+const TESTNET_BLOCKS: [(&str, &str, &str); 3] = [
+    (
+        "0100000043497fd7f826957108f4a30fd9cec3aeba79972084e90ead01ea330900000000bac8b0fa927c0ac8234287e33c5f74d38d354820e24756ad709d7038fc5f31f020e7494dffff001d03e4b6720101000000010000000000000000000000000000000000000000000000000000000000000000ffffffff0e0420e7494d017f062f503253482fffffffff0100f2052a010000002321021aeaf2f8638a129a3156fbe7e5ef635226b0bafd495ff03afe2c843d7e3a4b51ac00000000",
+        "015d5000",
+        "d7bdac13a59d745b1add0d2ce852f1a0442e8945fc1bf3848d3cbffd88c24fe1",
+    ),
+    (
+        "0100000006128e87be8b1b4dea47a7247d5528d2702c96826c7a648497e773b800000000e241352e3bec0a95a6217e10c3abb54adfa05abb12c126695595580fb92e222032e7494dffff001d00d235340101000000010000000000000000000000000000000000000000000000000000000000000000ffffffff0e0432e7494d010e062f503253482fffffffff0100f2052a010000002321038a7f6ef1c8ca0c588aa53fa860128077c9e6c11e6830f4d7ee4e763a56b7718fac00000000",
+        "0174a170",
+        "186afd11ef2b5e7e3504f2e8cbf8df28a1fd251fe53d60dff8b1467d1b386cf0",
+    ),
+    (
+        "0100000020782a005255b657696ea057d5b98f34defcf75196f64f6eeac8026c0000000041ba5afc532aae03151b8aa87b65e1594f97504a768e010c98c0add79216247186e7494dffff001d058dc2b60101000000010000000000000000000000000000000000000000000000000000000000000000ffffffff0e0486e7494d0151062f503253482fffffffff0100f2052a01000000232103f6d9ff4c12959445ca5549c811683bf9c88e637b222dd2e0311154c4c85cf423ac00000000",
+        "016cf7a0",
+        "8d63aadf5ab7257cb6d2316a57b16f517bff1c6388f124ec4c04af1212729d2a",
+    ),
+];
+
+// Testnet block 926485 and the scripts its inputs spend
+const BLOCK_926485: &str = "0000002060bbab0edbf3ef8a49608ee326f8fd75c473b7e3982095e2d100000000000000c30134f8c9b6d2470488d7a67a888f6fa12f8692e0c3411fbfb92f0f68f67eedae03ca57ef13021acc22dc4105010000000001010000000000000000000000000000000000000000000000000000000000000000ffffffff2f0315230e0004ae03ca57043e3d1e1d0c8796bf579aef0c0000000000122f4e696e6a61506f6f6c2f5345475749542fffffffff038427a112000000001976a914876fbb82ec05caa6af7a3b5e5a983aae6c6cc6d688ac0000000000000000266a24aa21a9ed5c748e121c0fe146d973a4ac26fa4a68b0549d46ee22d25f50a5e46fe1b377ee00000000000000002952534b424c4f434b3acd16772ad61a3c5f00287480b720f6035d5e54c9efc71be94bb5e3727f10909001200000000000000000000000000000000000000000000000000000000000000000000000000100000000010145310e878941a1b2bc2d33797ee4d89d95eaaf2e13488063a2aa9a74490f510a0100000023220020b6744de4f6ec63cc92f7c220cdefeeb1b1bed2b66c8e5706d80ec247d37e65a1ffffffff01002d3101000000001976a9143ebc40e411ed3c76f86711507ab952300890397288ac0400473044022001dd489a5d4e2fbd8a3ade27177f6b49296ba7695c40dbbe650ea83f106415fd02200b23a0602d8ff1bdf79dee118205fc7e9b40672bf31563e5741feb53fb86388501483045022100f88f040e90cc5dc6c6189d04718376ac19ed996bf9e4a3c29c3718d90ffd27180220761711f16c9e3a44f71aab55cbc0634907a1fa8bb635d971a9a01d368727bea10169522103b3623117e988b76aaabe3d63f56a4fc88b228a71e64c4cc551d1204822fe85cb2103dd823066e096f72ed617a41d3ca56717db335b1ea47a1b4c5c9dbdd0963acba621033d7c89bd9da29fa8d44db7906a9778b53121f72191184a9fee785c39180e4be153ae00000000010000000120925534261de4dcebb1ed5ab1b62bfe7a3ef968fb111dc2c910adfebc6e3bdf010000006b483045022100f50198f5ae66211a4f485190abe4dc7accdabe3bc214ebc9ea7069b97097d46e0220316a70a03014887086e335fc1b48358d46cd6bdc9af3b57c109c94af76fc915101210316cff587a01a2736d5e12e53551b18d73780b83c3bfb4fcf209c869b11b6415effffffff0220a10700000000001976a91450333046115eaa0ac9e0216565f945070e44573988ac2e7cd01a000000001976a914c01a7ca16b47be50cbdbc60724f701d52d75156688ac00000000010000000203a25f58630d7a1ea52550365fd2156683f56daf6ca73a4b4bbd097e66516322010000006a47304402204efc3d70e4ca3049c2a425025edf22d5ca355f9ec899dbfbbeeb2268533a0f2b02204780d3739653035af4814ea52e1396d021953f948c29754edd0ee537364603dc012103f7a897e4dbecab2264b21917f90664ea8256189ea725d28740cf7ba5d85b5763ffffffff03a25f58630d7a1ea52550365fd2156683f56daf6ca73a4b4bbd097e66516322000000006a47304402202d96defdc5b4af71d6ba28c9a6042c2d5ee7bc6de565d4db84ef517445626e03022022da80320e9e489c8f41b74833dfb6a54a4eb5087cdb46eb663eef0b25caa526012103f7a897e4dbecab2264b21917f90664ea8256189ea725d28740cf7ba5d85b5763ffffffff0200e1f5050000000017a914b7e6f7ff8658b2d1fb107e3d7be7af4742e6b1b3876f88fc00000000001976a914913bcc2be49cb534c20474c4dee1e9c4c317e7eb88ac0000000001000000043ffd60d3818431c495b89be84afac205d5d1ed663009291c560758bbd0a66df5010000006b483045022100f344607de9df42049688dcae8ff1db34c0c7cd25ec05516e30d2bc8f12ac9b2f022060b648f6a21745ea6d9782e17bcc4277b5808326488a1f40d41e125879723d3a012103f7a897e4dbecab2264b21917f90664ea8256189ea725d28740cf7ba5d85b5763ffffffffa5379401cce30f84731ef1ba65ce27edf2cc7ce57704507ebe8714aa16a96b92010000006a473044022020c37a63bf4d7f564c2192528709b6a38ab8271bd96898c6c2e335e5208661580220435c6f1ad4d9305d2c0a818b2feb5e45d443f2f162c0f61953a14d097fd07064012103f7a897e4dbecab2264b21917f90664ea8256189ea725d28740cf7ba5d85b5763ffffffff70e731e193235ff12c3184510895731a099112ffca4b00246c60003c40f843ce000000006a473044022053760f74c29a879e30a17b5f03a5bb057a5751a39f86fa6ecdedc36a1b7db04c022041d41c9b95f00d2d10a0373322a9025dba66c942196bc9d8adeb0e12d3024728012103f7a897e4dbecab2264b21917f90664ea8256189ea725d28740cf7ba5d85b5763ffffffff66b7a71b3e50379c8e85fc18fe3f1a408fc985f257036c34702ba205cef09f6f000000006a4730440220499bf9e2db3db6e930228d0661395f65431acae466634d098612fd80b08459ee022040e069fc9e3c60009f521cef54c38aadbd1251aee37940e6018aadb10f194d6a012103f7a897e4dbecab2264b21917f90664ea8256189ea725d28740cf7ba5d85b5763ffffffff0200e1f5050000000017a9148fc37ad460fdfbd2b44fe446f6e3071a4f64faa6878f447f0b000000001976a914913bcc2be49cb534c20474c4dee1e9c4c317e7eb88ac00000000";
+
+const SPENT_926485: [&str; 8] = [
+    "a914feb8a29635c56d9cd913122f90678756bf23887687",
+    "76a914c01a7ca16b47be50cbdbc60724f701d52d75156688ac",
+    "76a914913bcc2be49cb534c20474c4dee1e9c4c317e7eb88ac",
+    "76a914913bcc2be49cb534c20474c4dee1e9c4c317e7eb88ac",
+    "76a914913bcc2be49cb534c20474c4dee1e9c4c317e7eb88ac",
+    "76a914913bcc2be49cb534c20474c4dee1e9c4c317e7eb88ac",
+    "76a914913bcc2be49cb534c20474c4dee1e9c4c317e7eb88ac",
+    "76a914913bcc2be49cb534c20474c4dee1e9c4c317e7eb88ac",
+];
+
+fn hash(hex_str: &str) -> [u8; 32] {
+    hex::decode(hex_str).unwrap().try_into().unwrap()
+}
+
+// Stand-in filter for each block of the chain
+fn block_filter(block_hash: [u8; 32]) -> BlockFilter {
+    BlockFilter::new(block_hash, [&block_hash[..]])
+}
+
+fn cfheaders(
+    chain: &HeaderChain,
+    prev_header: [u8; 32],
+    start: u32,
+    stop: u32,
+) -> CFHeadersMessage {
+    let filter_hashes = (start..=stop)
+        .map(|height| block_filter(chain.hash_at(height).unwrap()).hash())
+        .collect();
+    CFHeadersMessage::new(
+        FILTER_TYPE_BASIC,
+        chain.hash_at(stop).unwrap(),
+        prev_header,
+        filter_hashes,
+    )
+}
+
+#[test]
+fn test_siphash() {
+    let k0 = u64::from_le_bytes([0, 1, 2, 3, 4, 5, 6, 7]);
+    let k1 = u64::from_le_bytes([8, 9, 10, 11, 12, 13, 14, 15]);
+    assert_eq!(siphash24(k0, k1, &[]), 0x726f_db47_dd0e_0e31);
+    let data: Vec<u8> = (0..15).collect();
+    assert_eq!(siphash24(k0, k1, &data), 0xa129_ca61_49be_45e5);
+}
+
+#[test]
+fn test_bip158_vectors() {
+    let coinbase: Tx = GENESIS_COINBASE.parse().unwrap();
+    let genesis = Block::new(Network::Testnet.genesis_header(), vec![coinbase]);
+    let filter = BlockFilter::from_block(&genesis, &[]).unwrap();
+    assert_eq!(hex::encode(filter.content()), "019dfca8");
+    let mut prev_header = filter.header(&[0; 32]);
+    assert_eq!(
+        prev_header,
+        hash("21584579b7eb08997773e5aeff3a7f932700042d0ed2a6129012b7d7ae81b750")
+    );
+    let script_pubkey = genesis.txs()[0].outputs()[0].script_pubkey().as_bytes();
+    assert!(filter.matches(script_pubkey).unwrap());
+    assert!(!filter.matches(b"not in the block").unwrap());
+    assert!(BlockFilter::from_block(&genesis, &[Script::new()]).is_err());
+
+    let mut prev_block = genesis.hash();
+    for (raw_block, content, header) in TESTNET_BLOCKS {
+        let block: Block = raw_block.parse().unwrap();
+        assert_eq!(block.header().prev_block(), prev_block);
+        let filter = BlockFilter::from_block(&block, &[]).unwrap();
+        assert_eq!(hex::encode(filter.content()), content);
+        assert_eq!(filter.header(&prev_header), hash(header));
+        prev_block = block.hash();
+        prev_header = hash(header);
+    }
+
+    let block: Block = BLOCK_926485.parse().unwrap();
+    assert_eq!(
+        block.hash(),
+        hash("000000000000015d6077a411a8f5cc95caf775ccf11c54e27df75ce58d187313")
+    );
+    let spent: Vec<Script> = SPENT_926485
+        .iter()
+        .map(|script| Script::from(hex::decode(script).unwrap()))
+        .collect();
+    let filter = BlockFilter::from_block(&block, &spent).unwrap();
+    assert_eq!(
+        hex::encode(filter.content()),
+        "09027acea61b6cc3fb33f5d52f7d088a6b2f75d234e89ca800"
+    );
+    assert!(filter.matches(spent[0].as_bytes()).unwrap());
+    let coinbase_outputs = block.txs()[0].outputs();
+    assert!(coinbase_outputs[1].script_pubkey().is_op_return());
+    assert!(!filter
+        .matches(coinbase_outputs[1].script_pubkey().as_bytes())
+        .unwrap());
+    assert!(filter
+        .matches(coinbase_outputs[2].script_pubkey().as_bytes())
+        .unwrap());
+    assert!(BlockFilter::from_block(&block, &spent[1..]).is_err());
+}
+
+#[test]
+fn test_filter_construction() {
+    let mut block_hash = [0x11; 32];
+    block_hash[31] = 0x22;
+    let elements: Vec<Vec<u8>> = (0..10)
+        .chain([3])
+        .map(|n| format!("element{}", n).into_bytes())
+        .collect();
+    let filter = BlockFilter::new(block_hash, elements.iter().map(Vec::as_slice));
+    assert_eq!(filter.content()[0], 10);
+    for element in &elements {
+        assert!(filter.matches(element).unwrap());
+    }
+    assert!(filter.match_any([&b"other"[..], &b"element7"[..]]).unwrap());
+    let others: Vec<Vec<u8>> = (10..20)
+        .map(|n| format!("element{}", n).into_bytes())
+        .collect();
+    assert!(!filter.match_any(others.iter().map(Vec::as_slice)).unwrap());
+
+    let empty = BlockFilter::new(block_hash, []);
+    assert_eq!(empty.content(), [0]);
+    assert!(!empty.matches(b"element0").unwrap());
+    let truncated = BlockFilter::from_content(block_hash, filter.content()[..1].to_vec());
+    assert!(truncated.matches(b"element9").is_err());
+    for n in [1 << 32, u64::MAX] {
+        let mut content = encode_varint(n);
+        content.extend([0xff; 8]);
+        let oversized = BlockFilter::from_content(block_hash, content);
+        assert!(oversized.matches(b"element0").is_err());
+    }
+}
+
+#[test]
+fn test_block_elements() {
+    let paid = Script::from(hex::decode("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap());
+    let spent = Script::from(
+        hex::decode("51200101010101010101010101010101010101010101010101010101010101010101")
+            .unwrap(),
+    );
+    let coinbase = Tx::new(
+        2,
+        vec![TxIn::new(OutPoint::NULL, Script::from(vec![0x51]), 0)],
+        vec![
+            TxOut::new(50, paid.clone()),
+            TxOut::new(0, Script::from(vec![0x6a, 0x01, 0x02])),
+        ],
+        0,
+    );
+    let spend = Tx::new(
+        2,
+        vec![
+            TxIn::new(OutPoint::new([1; 32], 0), Script::new(), 0),
+            TxIn::new(OutPoint::new([2; 32], 0), Script::new(), 0),
+        ],
+        vec![TxOut::new(10, paid.clone()), TxOut::new(0, Script::new())],
+        0,
+    );
+    let txs = vec![coinbase, spend];
+    let hashes: Vec<[u8; 32]> = txs.iter().map(Tx::hash).collect();
+    let header = BlockHeader::new(4, [0; 32], merkle_root(&hashes).0, 0, REGTEST_BITS, 0);
+    let block = Block::new(header, txs);
+    let filter = BlockFilter::from_block(&block, &[spent.clone(), Script::new()]).unwrap();
+    // Only the paid and spent scripts, each once
+    let expected = BlockFilter::new(block.hash(), [paid.as_bytes(), spent.as_bytes()]);
+    assert_eq!(filter, expected);
+    assert!(!filter.matches(&[0x6a, 0x01, 0x02]).unwrap());
+}
+
+#[test]
+fn test_filter_header_chain() {
+    let chain = mine_chain(1200);
+    let first = cfheaders(&chain, [0; 32], 0, 999);
+    let headers = first.headers();
+    assert_eq!(
+        headers[0],
+        filter_header(&first.filter_hashes()[0], &[0; 32])
+    );
+    let checkpoint = CFCheckptMessage::new(
+        FILTER_TYPE_BASIC,
+        chain.hash_at(1200).unwrap(),
+        vec![filter_header(
+            &block_filter(chain.hash_at(1000).unwrap()).hash(),
+            &headers[999],
+        )],
+    );
+
+    let mut filter_headers = FilterHeaderChain::new();
+    filter_headers.set_checkpoints(&checkpoint).unwrap();
+    // Must start from genesis and stop at a best chain block
+    assert!(filter_headers
+        .accept(&cfheaders(&chain, [0; 32], 1, 10), &chain)
+        .is_err());
+    let wrong_stop = CFHeadersMessage::new(
+        FILTER_TYPE_BASIC,
+        chain.hash_at(1000).unwrap(),
+        first.prev_header(),
+        first.filter_hashes().to_vec(),
+    );
+    assert!(filter_headers.accept(&wrong_stop, &chain).is_err());
+    filter_headers.accept(&first, &chain).unwrap();
+    assert_eq!(filter_headers.len(), 1000);
+    assert_eq!(filter_headers.header_at(999), Some(headers[999]));
+
+    // Continuing from the wrong header, or through a bad checkpoint, fails
+    assert!(filter_headers
+        .accept(&cfheaders(&chain, [0; 32], 1000, 1200), &chain)
+        .is_err());
+    let mut bad = cfheaders(&chain, headers[999], 1000, 1200);
+    let mut hashes = bad.filter_hashes().to_vec();
+    hashes[0] = [0; 32];
+    bad = CFHeadersMessage::new(
+        FILTER_TYPE_BASIC,
+        bad.stop_hash(),
+        bad.prev_header(),
+        hashes,
+    );
+    assert!(filter_headers.accept(&bad, &chain).is_err());
+    filter_headers
+        .accept(&cfheaders(&chain, headers[999], 1000, 1200), &chain)
+        .unwrap();
+    assert_eq!(filter_headers.len(), 1201);
+
+    let filter = block_filter(chain.hash_at(1100).unwrap());
+    assert!(filter_headers.check_filter(&filter, 1100).is_ok());
+    assert!(filter_headers.check_filter(&filter, 1101).is_err());
+    assert!(filter_headers.check_filter(&filter, 1201).is_err());
+    let genesis_filter = block_filter(chain.hash_at(0).unwrap());
+    assert!(filter_headers.check_filter(&genesis_filter, 0).is_ok());
+
+    let conflicting = CFCheckptMessage::new(
+        FILTER_TYPE_BASIC,
+        chain.hash_at(1200).unwrap(),
+        vec![[0; 32]],
+    );
+    assert!(filter_headers.set_checkpoints(&conflicting).is_err());
+}
+
+#[test]
+fn test_filter_messages() {
+    let block_hash = hash("000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943");
+    let filter = BlockFilter::from_content(block_hash, hex::decode("019dfca8").unwrap());
+    for message in [
+        NetworkMessage::GetCFilters(FilterRequest::new(FILTER_TYPE_BASIC, 0, block_hash)),
+        NetworkMessage::CFilter(filter.clone()),
+        NetworkMessage::GetCFHeaders(FilterRequest::new(FILTER_TYPE_BASIC, 1, block_hash)),
+        NetworkMessage::CFHeaders(CFHeadersMessage::new(
+            FILTER_TYPE_BASIC,
+            block_hash,
+            [0; 32],
+            vec![filter.hash()],
+        )),
+        NetworkMessage::GetCFCheckpt(FILTER_TYPE_BASIC, block_hash),
+        NetworkMessage::CFCheckpt(CFCheckptMessage::new(
+            FILTER_TYPE_BASIC,
+            block_hash,
+            vec![[3; 32], [4; 32]],
+        )),
+    ] {
+        let bytes = message.serialize(Network::Testnet);
+        assert_eq!(
+            NetworkMessage::parse(&mut Cursor::new(bytes), Network::Testnet).unwrap(),
+            message
+        );
+    }
+    // Hashes are sent in internal byte order
+    let payload = NetworkMessage::CFilter(filter).payload();
+    let mut wire_hash = block_hash;
+    wire_hash.reverse();
+    assert_eq!(
+        hex::encode(&payload),
+        format!("00{}04019dfca8", hex::encode(&wire_hash))
+    );
+    let mut unknown_type = payload.clone();
+    unknown_type[0] = 1;
+    assert!(NetworkMessage::from_raw(&RawMessage::new("cfilter", unknown_type).unwrap()).is_err());
+}
@@ -0,0 +1,268 @@
+use crate::core::block::Block;
+use crate::core::network::Network;
+use crate::core::tx::Tx;
+use crate::network::address::{AddrV2, AddrV2Address, NetAddress, NODE_NETWORK, NODE_WITNESS};
+use crate::network::message::{
+    GetHeadersMessage, Inventory, NetworkMessage, RawMessage, RejectMessage, VersionMessage,
+    INV_BLOCK, INV_TX, INV_WITNESS_FLAG, MAX_MESSAGE_SIZE, REJECT_DUST,
+};
+use crate::ser::hex;
+use crate::tests::util::send;
+use std::io::{BufReader, Cursor};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream};
+use std::thread;
+
+// Version message from the Bitcoin wiki
+const VERSION: &str = "f9beb4d976657273696f6e0000000000650000005f1a69d2721101000100000000000000bc8f5e5400000000010000000000000000000000000000000000ffffc61b6409208d010000000000000000000000000000000000ffffcb0071c0208d128035cbc97953f80f2f5361746f7368693a302e392e332fcf05050001";
+const VERACK: &str = "f9beb4d976657261636b000000000000000000005df6e0e2";
+
+const TX: &str = "0100000001813f79011acb80925dfe69b3def355fe914bd1d96a3f5f71bf8303c6a989c7d1000000006b483045022100ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320b0277457c98f02207a986d955c6e0cb35d446a89d3f56100f4d7f67801c31967743a9c8e10615bed01210349fc4e631e3624a545de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278afeffffff02a135ef01000000001976a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88ac99c39800000000001976a9141c4bc762dd5423e332166702cb75f40df79fea1288ac19430600";
+
+fn parse(bytes: &[u8], network: Network) -> Result<NetworkMessage, String> {
+    NetworkMessage::parse(&mut Cursor::new(bytes), network)
+}
+
+fn roundtrip(message: NetworkMessage) {
+    let bytes = message.serialize(Network::Regtest);
+    assert_eq!(parse(&bytes, Network::Regtest).unwrap(), message);
+}
+
+#[test]
+fn test_version_message() {
+    let bytes = hex::decode(VERSION).unwrap();
+    let message = parse(&bytes, Network::Mainnet).unwrap();
+    let NetworkMessage::Version(version) = &message else {
+        panic!("Expected a version message, got {:?}", message);
+    };
+    assert_eq!(version.version(), 70002);
+    assert_eq!(version.services(), NODE_NETWORK);
+    assert_eq!(version.timestamp(), 0x545e_8fbc);
+    assert_eq!(
+        version.receiver().socket_addr(),
+        "198.27.100.9:8333".parse::<SocketAddr>().unwrap()
+    );
+    assert_eq!(
+        version.sender().socket_addr(),
+        "203.0.113.192:8333".parse::<SocketAddr>().unwrap()
+    );
+    assert_eq!(version.nonce(), 0xf853_79c9_cb35_8012);
+    assert_eq!(version.user_agent(), "/Satoshi:0.9.3/");
+    assert_eq!(version.start_height(), 329_167);
+    assert!(version.relay());
+    assert_eq!(message.serialize(Network::Mainnet), bytes);
+
+    // Without the relay flag, as sent before BIP37
+    let raw = message.to_raw();
+    let payload = &raw.payload()[..raw.payload().len() - 1];
+    let old =
+        NetworkMessage::from_raw(&RawMessage::new("version", payload.to_vec()).unwrap()).unwrap();
+    assert_eq!(old, message);
+
+    let mut version = VersionMessage::new(1_700_000_000, 42, 100);
+    version
+        .set_services(NODE_NETWORK | NODE_WITNESS)
+        .set_receiver(NetAddress::new(
+            NODE_NETWORK,
+            IpAddr::V6(Ipv6Addr::LOCALHOST),
+            18444,
+        ))
+        .set_user_agent("/test:1.0/")
+        .set_relay(true);
+    roundtrip(NetworkMessage::Version(version));
+}
+
+#[test]
+fn test_empty_messages() {
+    let bytes = hex::decode(VERACK).unwrap();
+    assert_eq!(
+        parse(&bytes, Network::Mainnet).unwrap(),
+        NetworkMessage::Verack
+    );
+    assert_eq!(NetworkMessage::Verack.serialize(Network::Mainnet), bytes);
+    for message in [
+        NetworkMessage::Verack,
+        NetworkMessage::GetAddr,
+        NetworkMessage::SendAddrV2,
+        NetworkMessage::SendHeaders,
+        NetworkMessage::WtxidRelay,
+    ] {
+        roundtrip(message);
+    }
+}
+
+#[test]
+fn test_message_roundtrips() {
+    let genesis = Network::Mainnet.genesis_header();
+    let tx: Tx = TX.parse().unwrap();
+    let inventory = vec![
+        Inventory::new(INV_TX, tx.hash()),
+        Inventory::new(INV_BLOCK | INV_WITNESS_FLAG, genesis.hash()),
+    ];
+    roundtrip(NetworkMessage::Ping(0x0123_4567_89ab_cdef));
+    roundtrip(NetworkMessage::Pong(7));
+    roundtrip(NetworkMessage::GetHeaders(GetHeadersMessage::new(
+        vec![genesis.hash(), [1; 32]],
+        [0; 32],
+    )));
+    roundtrip(NetworkMessage::Headers(vec![
+        genesis,
+        Network::Regtest.genesis_header(),
+    ]));
+    roundtrip(NetworkMessage::Inv(inventory.clone()));
+    roundtrip(NetworkMessage::GetData(inventory.clone()));
+    roundtrip(NetworkMessage::NotFound(inventory));
+    roundtrip(NetworkMessage::Tx(tx.clone()));
+    roundtrip(NetworkMessage::Block(Block::new(genesis, vec![tx.clone()])));
+    roundtrip(NetworkMessage::Addr(vec![(
+        1_700_000_000,
+        NetAddress::new(NODE_NETWORK, IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 8333),
+    )]));
+    roundtrip(NetworkMessage::AddrV2(vec![
+        AddrV2::new(
+            1,
+            NODE_NETWORK,
+            AddrV2Address::Ipv4(Ipv4Addr::new(1, 2, 3, 4)),
+            8333,
+        ),
+        AddrV2::new(2, 0, AddrV2Address::TorV3([7; 32]), 9050),
+        AddrV2::new(3, 0, AddrV2Address::Unknown(42, vec![1, 2, 3]), 1),
+    ]));
+    roundtrip(NetworkMessage::SendCmpct(true, 2));
+    roundtrip(NetworkMessage::FeeFilter(1000));
+    roundtrip(NetworkMessage::Reject(RejectMessage::new(
+        "tx",
+        REJECT_DUST,
+        "dust",
+        tx.hash().to_vec(),
+    )));
+    roundtrip(NetworkMessage::Unknown(
+        RawMessage::new("wtxidrelay2", vec![1, 2]).unwrap(),
+    ));
+
+    // Block hashes go on the wire in internal byte order
+    let payload =
+        NetworkMessage::GetData(vec![Inventory::new(INV_BLOCK, genesis.hash())]).payload();
+    let mut wire_hash = genesis.hash();
+    wire_hash.reverse();
+    assert_eq!(
+        hex::encode(&payload),
+        format!("0102000000{}", hex::encode(&wire_hash))
+    );
+}
+
+#[test]
+fn test_addr_v2() {
+    let entry = AddrV2::new(
+        1,
+        NODE_NETWORK,
+        AddrV2Address::Ipv6(Ipv6Addr::LOCALHOST),
+        8333,
+    );
+    assert_eq!(
+        entry.socket_addr(),
+        Some("[::1]:8333".parse::<SocketAddr>().unwrap())
+    );
+    assert_eq!(
+        AddrV2::new(1, 0, AddrV2Address::I2p([0; 32]), 0).socket_addr(),
+        None
+    );
+    // An IPv4 address must be 4 bytes
+    let bad = hex::decode("01000000000103010203208d").unwrap();
+    assert!(AddrV2::parse(&mut Cursor::new(&bad)).is_err());
+    let good = hex::decode("0100000000010401020304208d").unwrap();
+    assert_eq!(
+        AddrV2::parse(&mut Cursor::new(&good))
+            .unwrap()
+            .socket_addr(),
+        Some("1.2.3.4:8333".parse::<SocketAddr>().unwrap())
+    );
+}
+
+#[test]
+fn test_invalid_messages() {
+    let verack = hex::decode(VERACK).unwrap();
+    assert!(parse(&verack, Network::Testnet).is_err());
+
+    let mut bad_checksum = NetworkMessage::Ping(1).serialize(Network::Mainnet);
+    let last = bad_checksum.len() - 1;
+    bad_checksum[last] ^= 1;
+    assert!(parse(&bad_checksum, Network::Mainnet).is_err());
+
+    let mut bad_command = verack.clone();
+    // Bytes after the terminating null must stay null
+    bad_command[12] = b'x';
+    assert!(parse(&bad_command, Network::Mainnet).is_err());
+
+    let mut oversize = verack.clone();
+    oversize[16..20].copy_from_slice(&(MAX_MESSAGE_SIZE as u32 + 1).to_le_bytes());
+    assert!(parse(&oversize, Network::Mainnet).is_err());
+
+    assert!(parse(&verack[..20], Network::Mainnet).is_err());
+
+    let trailing = RawMessage::new("ping", vec![0; 9]).unwrap();
+    assert!(NetworkMessage::from_raw(&trailing).is_err());
+    let short = RawMessage::new("pong", vec![0; 7]).unwrap();
+    assert!(NetworkMessage::from_raw(&short).is_err());
+    // Headers must carry an empty transaction count
+    let mut headers = NetworkMessage::Headers(vec![Network::Mainnet.genesis_header()]).payload();
+    let last = headers.len() - 1;
+    headers[last] = 1;
+    assert!(NetworkMessage::from_raw(&RawMessage::new("headers", headers).unwrap()).is_err());
+    let too_many = RawMessage::new("inv", vec![0xfe, 0x51, 0xc3, 0, 0]).unwrap();
+    assert!(NetworkMessage::from_raw(&too_many).is_err());
+
+    assert!(RawMessage::new("sendaddrv2", vec![]).is_ok());
+    assert!(RawMessage::new("thirteenbytes", vec![]).is_err());
+    assert!(RawMessage::new("v\u{e9}rack", vec![]).is_err());
+    assert!(RawMessage::new("ver ack", vec![]).is_err());
+}
+
+#[test]
+fn test_local_peer() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let peer = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        // Runs until the connection closes
+        while let Ok(message) = NetworkMessage::parse(&mut reader, Network::Regtest) {
+            match message {
+                NetworkMessage::Version(version) => {
+                    assert_eq!(version.user_agent(), "/test:1.0/");
+                    let mut reply = VersionMessage::new(1_700_000_001, 2, 10);
+                    reply.set_services(NODE_NETWORK);
+                    send(&mut stream, NetworkMessage::Version(reply));
+                    send(&mut stream, NetworkMessage::Verack);
+                }
+                NetworkMessage::Verack => {}
+                NetworkMessage::Ping(nonce) => send(&mut stream, NetworkMessage::Pong(nonce)),
+                message => panic!("Unexpected {} message.", message.command()),
+            }
+        }
+    });
+
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut version = VersionMessage::new(1_700_000_000, 1, 0);
+    version.set_user_agent("/test:1.0/");
+    send(&mut stream, NetworkMessage::Version(version));
+    let NetworkMessage::Version(remote) =
+        NetworkMessage::parse(&mut reader, Network::Regtest).unwrap()
+    else {
+        panic!("Expected the peer's version first.");
+    };
+    assert_eq!(remote.start_height(), 10);
+    assert_eq!(
+        NetworkMessage::parse(&mut reader, Network::Regtest).unwrap(),
+        NetworkMessage::Verack
+    );
+    send(&mut stream, NetworkMessage::Verack);
+    send(&mut stream, NetworkMessage::Ping(0xdead_beef));
+    assert_eq!(
+        NetworkMessage::parse(&mut reader, Network::Regtest).unwrap(),
+        NetworkMessage::Pong(0xdead_beef)
+    );
+    drop(stream);
+    drop(reader);
+    peer.join().unwrap();
+}
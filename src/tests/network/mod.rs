@@ -0,0 +1,5 @@
+mod bloom;
+mod filter;
+mod message;
+mod peer;
+mod spv;
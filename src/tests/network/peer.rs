@@ -0,0 +1,227 @@
+use crate::core::network::Network;
+use crate::network::address::{NODE_NETWORK, NODE_WITNESS};
+use crate::network::message::{Inventory, NetworkMessage, VersionMessage, INV_TX};
+use crate::network::peer::{Peer, PeerConfig};
+use std::io::{BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+const SERVICES: u64 = NODE_NETWORK | NODE_WITNESS;
+
+type Script = fn(&mut StandIn);
+
+struct StandIn {
+    stream: TcpStream,
+    reader: BufReader<TcpStream>,
+}
+
+impl StandIn {
+    fn send(&mut self, message: NetworkMessage) {
+        self.stream
+            .write_all(&message.serialize(Network::Regtest))
+            .unwrap();
+    }
+
+    fn receive(&mut self) -> NetworkMessage {
+        NetworkMessage::parse(&mut self.reader, Network::Regtest).unwrap()
+    }
+
+    fn version(&self, version: u32, services: u64) -> NetworkMessage {
+        let mut message = VersionMessage::new(1_700_000_000, 7, 200);
+        message.set_version(version).set_services(services);
+        NetworkMessage::Version(message)
+    }
+
+    // Answers our version the way Bitcoin Core does
+    fn accept_handshake(&mut self, version: u32) {
+        assert!(matches!(self.receive(), NetworkMessage::Version(_)));
+        self.send(self.version(version, SERVICES));
+        if version >= 70016 {
+            self.send(NetworkMessage::WtxidRelay);
+        }
+        self.send(NetworkMessage::SendAddrV2);
+        self.send(NetworkMessage::Verack);
+        if version >= 70016 {
+            assert_eq!(self.receive(), NetworkMessage::WtxidRelay);
+        }
+        assert_eq!(self.receive(), NetworkMessage::SendAddrV2);
+        assert_eq!(self.receive(), NetworkMessage::Verack);
+    }
+}
+
+fn stand_in(script: impl FnOnce(&mut StandIn) + Send + 'static) -> (SocketAddr, JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handle = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let reader = BufReader::new(stream.try_clone().unwrap());
+        script(&mut StandIn { stream, reader });
+    });
+    (addr, handle)
+}
+
+fn config() -> PeerConfig {
+    let mut config = PeerConfig::new(Network::Regtest);
+    config.timeout(Duration::from_secs(5));
+    config
+}
+
+#[test]
+fn test_handshake() {
+    let (addr, node) = stand_in(|node| {
+        node.accept_handshake(70016);
+        assert_eq!(node.receive(), NetworkMessage::SendHeaders);
+        assert_eq!(node.receive(), NetworkMessage::SendCmpct(false, 2));
+        node.send(NetworkMessage::SendHeaders);
+        node.send(NetworkMessage::SendCmpct(true, 2));
+        node.send(NetworkMessage::FeeFilter(1000));
+        node.send(NetworkMessage::Ping(42));
+        node.send(NetworkMessage::Inv(vec![Inventory::new(INV_TX, [1; 32])]));
+        assert_eq!(node.receive(), NetworkMessage::Pong(42));
+    });
+    let mut peer = Peer::connect(addr, &config()).unwrap();
+    assert_eq!(peer.version(), 70016);
+    assert_eq!(peer.services(), SERVICES);
+    assert_eq!(peer.remote().start_height(), 200);
+    assert!(peer.wtxid_relay());
+    assert!(peer.addrv2());
+    // Filters need NODE_BLOOM, refused without dropping the peer
+    assert!(peer.send(&NetworkMessage::FilterClear).is_err());
+    assert!(peer.is_connected());
+    assert_eq!(
+        peer.receive().unwrap(),
+        NetworkMessage::Inv(vec![Inventory::new(INV_TX, [1; 32])])
+    );
+    assert!(peer.send_headers());
+    assert_eq!(peer.compact_blocks(), Some(true));
+    assert_eq!(peer.fee_filter(), 1000);
+    node.join().unwrap();
+    assert!(peer.receive().is_err());
+    assert!(!peer.is_connected());
+    assert!(peer.send(&NetworkMessage::GetAddr).is_err());
+}
+
+#[test]
+fn test_handshake_old_peer() {
+    let (addr, node) = stand_in(|node| {
+        node.accept_handshake(70012);
+        assert_eq!(node.receive(), NetworkMessage::SendHeaders);
+    });
+    let mut config = config();
+    config.required_services(NODE_NETWORK);
+    let peer = Peer::connect(addr, &config).unwrap();
+    assert_eq!(peer.version(), 70012);
+    assert!(!peer.wtxid_relay());
+    assert_eq!(peer.compact_blocks(), None);
+    node.join().unwrap();
+}
+
+#[test]
+fn test_handshake_rejections() {
+    let cases: Vec<(Script, &str)> = vec![
+        (
+            |node| {
+                node.receive();
+                node.send(node.version(31799, SERVICES));
+            },
+            "Obsolete protocol version",
+        ),
+        (
+            |node| {
+                node.receive();
+                node.send(node.version(70016, NODE_NETWORK));
+            },
+            "lack required",
+        ),
+        (
+            |node| {
+                node.receive();
+                node.send(NetworkMessage::Verack);
+            },
+            "verack before version",
+        ),
+        (
+            |node| {
+                let version = node.receive();
+                node.send(version);
+            },
+            "Connected to self",
+        ),
+        (
+            |node| {
+                node.receive();
+                node.send(node.version(70016, SERVICES));
+                node.send(node.version(70016, SERVICES));
+            },
+            "Duplicate version",
+        ),
+    ];
+    for (script, expected) in cases {
+        let (addr, node) = stand_in(move |node| {
+            script(node);
+            // Hold the connection until the peer drops it
+            while NetworkMessage::parse(&mut node.reader, Network::Regtest).is_ok() {}
+        });
+        let error = Peer::connect(addr, &config()).unwrap_err();
+        assert!(error.contains(expected), "{}", error);
+        node.join().unwrap();
+    }
+}
+
+#[test]
+fn test_misbehaving_peer() {
+    let (addr, node) = stand_in(|node| {
+        node.accept_handshake(70016);
+        node.send(NetworkMessage::SendAddrV2);
+        while NetworkMessage::parse(&mut node.reader, Network::Regtest).is_ok() {}
+    });
+    let mut peer = Peer::connect(addr, &config()).unwrap();
+    let error = peer.receive().unwrap_err();
+    assert!(
+        error.contains("Feature negotiation after verack"),
+        "{}",
+        error
+    );
+    assert!(!peer.is_connected());
+    assert_eq!(peer.receive().unwrap_err(), error);
+    node.join().unwrap();
+}
+
+#[test]
+fn test_timeouts() {
+    // The node never answers our version
+    let (addr, node) = stand_in(|node| {
+        node.receive();
+        while NetworkMessage::parse(&mut node.reader, Network::Regtest).is_ok() {}
+    });
+    let mut config = config();
+    config.timeout(Duration::from_millis(200));
+    assert!(Peer::connect(addr, &config).is_err());
+    node.join().unwrap();
+
+    // The node answers one ping but not the next
+    let (addr, node) = stand_in(|node| {
+        node.accept_handshake(70016);
+        node.receive();
+        node.receive();
+        let NetworkMessage::Ping(nonce) = node.receive() else {
+            panic!("Expected a ping.");
+        };
+        node.send(NetworkMessage::Pong(nonce + 1));
+        node.send(NetworkMessage::Pong(nonce));
+        node.send(NetworkMessage::GetAddr);
+        assert!(matches!(node.receive(), NetworkMessage::Ping(_)));
+        node.send(NetworkMessage::GetAddr);
+        while NetworkMessage::parse(&mut node.reader, Network::Regtest).is_ok() {}
+    });
+    let mut peer = Peer::connect(addr, &config).unwrap();
+    peer.ping().unwrap();
+    assert_eq!(peer.receive().unwrap(), NetworkMessage::GetAddr);
+    assert!(peer.latency().is_some());
+    peer.ping().unwrap();
+    assert_eq!(peer.receive().unwrap(), NetworkMessage::GetAddr);
+    thread::sleep(Duration::from_millis(250));
+    assert!(peer.receive().unwrap_err().contains("Ping timed out"));
+    node.join().unwrap();
+}
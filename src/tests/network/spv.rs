@@ -0,0 +1,198 @@
+use crate::core::block::{BlockHeader, BLOCK_HEADER_SIZE};
+use crate::core::chain::ChainUpdate;
+use crate::core::network::Network;
+use crate::network::address::{NODE_NETWORK, NODE_WITNESS};
+use crate::network::message::{Inventory, NetworkMessage, VersionMessage, INV_BLOCK, MAX_HEADERS};
+use crate::network::peer::{Peer, PeerConfig};
+use crate::network::spv::SpvClient;
+use crate::tests::util::{extend, send, temp_path};
+use std::fs::{self, OpenOptions};
+use std::io::{BufReader, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+fn mock_peer(
+    headers: Vec<BlockHeader>,
+    mut announce: Vec<BlockHeader>,
+) -> (SocketAddr, JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handle = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut chain = [vec![Network::Regtest.genesis_header()], headers].concat();
+        let mut version = VersionMessage::new(1_700_000_000, 7, chain.len() as i32 - 1);
+        version.set_services(NODE_NETWORK | NODE_WITNESS);
+        send(&mut stream, NetworkMessage::Version(version));
+        send(&mut stream, NetworkMessage::Verack);
+        while let Ok(message) = NetworkMessage::parse(&mut reader, Network::Regtest) {
+            let NetworkMessage::GetHeaders(request) = message else {
+                continue;
+            };
+            let start = request
+                .locator()
+                .iter()
+                .find_map(|hash| chain.iter().position(|header| header.hash() == *hash))
+                .map_or(0, |position| position + 1);
+            let batch: Vec<BlockHeader> = chain[start..]
+                .iter()
+                .take(MAX_HEADERS as usize)
+                .copied()
+                .collect();
+            let full = batch.len() as u64 == MAX_HEADERS;
+            send(&mut stream, NetworkMessage::Headers(batch));
+            if !full && !announce.is_empty() {
+                let header = announce.remove(0);
+                chain.push(header);
+                send(
+                    &mut stream,
+                    NetworkMessage::Inv(vec![Inventory::new(INV_BLOCK, header.hash())]),
+                );
+            }
+        }
+    });
+    (addr, handle)
+}
+
+fn connect(addr: SocketAddr) -> Peer {
+    let mut config = PeerConfig::new(Network::Regtest);
+    config.timeout(Duration::from_secs(5));
+    Peer::connect(addr, &config).unwrap()
+}
+
+#[test]
+fn test_sync() {
+    let genesis = Network::Regtest.genesis_header();
+    let headers = extend(&genesis, 1, 2100);
+    let (addr, node) = mock_peer(headers.clone(), Vec::new());
+    let path = temp_path("spv", "sync");
+    let mut client = SpvClient::open(Network::Regtest, &path).unwrap();
+    assert_eq!(client.chain().height(), 0);
+    let mut peer = connect(addr);
+    let updates = client.sync(&mut peer).unwrap();
+    assert_eq!(updates.len(), 2100);
+    assert!(updates
+        .iter()
+        .all(|update| *update == ChainUpdate::Extended));
+    assert_eq!(client.chain().height(), 2100);
+    assert_eq!(client.chain().tip().hash(), headers[2099].hash());
+    // Nothing new the second time
+    assert!(client.sync(&mut peer).unwrap().is_empty());
+    drop(peer);
+    node.join().unwrap();
+    assert_eq!(
+        fs::metadata(&path).unwrap().len(),
+        2100 * BLOCK_HEADER_SIZE as u64
+    );
+    drop(client);
+
+    let client = SpvClient::open(Network::Regtest, &path).unwrap();
+    assert_eq!(client.chain().height(), 2100);
+    assert_eq!(client.chain().tip().hash(), headers[2099].hash());
+    drop(client);
+
+    // A header cut short by a crash is dropped
+    let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+    file.write_all(&[0xab; 40]).unwrap();
+    drop(file);
+    let client = SpvClient::open(Network::Regtest, &path).unwrap();
+    assert_eq!(client.chain().height(), 2100);
+    assert_eq!(
+        fs::metadata(&path).unwrap().len(),
+        2100 * BLOCK_HEADER_SIZE as u64
+    );
+    drop(client);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_follow_announcements() {
+    let genesis = Network::Regtest.genesis_header();
+    let headers = extend(&genesis, 1, 11);
+    let (addr, node) = mock_peer(headers[..10].to_vec(), vec![headers[10]]);
+    let path = temp_path("spv", "follow");
+    let mut client = SpvClient::open(Network::Regtest, &path).unwrap();
+    let mut peer = connect(addr);
+    assert_eq!(client.sync(&mut peer).unwrap().len(), 10);
+
+    let inv = peer.receive().unwrap();
+    assert!(matches!(inv, NetworkMessage::Inv(_)));
+    assert!(client.handle_message(&mut peer, &inv).unwrap().is_empty());
+    let reply = peer.receive().unwrap();
+    assert_eq!(reply, NetworkMessage::Headers(vec![headers[10]]));
+    assert_eq!(
+        client.handle_message(&mut peer, &reply).unwrap(),
+        vec![ChainUpdate::Extended]
+    );
+    assert_eq!(client.chain().tip().hash(), headers[10].hash());
+    // Known blocks are not asked for again
+    assert!(client.handle_message(&mut peer, &inv).unwrap().is_empty());
+    drop(peer);
+    node.join().unwrap();
+    drop(client);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_invalid_headers() {
+    let genesis = Network::Regtest.genesis_header();
+    let mut headers = extend(&genesis, 1, 5);
+    let mut bad = extend(&headers[4], 1, 1)[0];
+    while bad.check_pow() {
+        bad.set_nonce(bad.nonce() + 1);
+    }
+    headers.push(bad);
+    let (addr, node) = mock_peer(headers.clone(), Vec::new());
+    let path = temp_path("spv", "invalid");
+    let mut client = SpvClient::open(Network::Regtest, &path).unwrap();
+    let mut peer = connect(addr);
+    let error = client.sync(&mut peer).unwrap_err();
+    assert!(error.contains("fails proof of work"), "{}", error);
+    assert!(!peer.is_connected());
+    // Headers before the bad one are kept
+    assert_eq!(client.chain().height(), 5);
+    drop(peer);
+    node.join().unwrap();
+    drop(client);
+    let client = SpvClient::open(Network::Regtest, &path).unwrap();
+    assert_eq!(client.chain().height(), 5);
+    assert_eq!(client.chain().tip().hash(), headers[4].hash());
+    drop(client);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_reorg_persistence() {
+    let genesis = Network::Regtest.genesis_header();
+    let first = extend(&genesis, 1, 3);
+    let second = extend(&genesis, 2, 5);
+    let path = temp_path("spv", "reorg");
+    let mut client = SpvClient::open(Network::Regtest, &path).unwrap();
+    assert_eq!(
+        client.process_headers(&first, u32::MAX).unwrap(),
+        vec![ChainUpdate::Extended; 3]
+    );
+    assert!(client
+        .process_headers(&[first[0], second[1]], u32::MAX)
+        .is_err());
+    let updates = client.process_headers(&second, u32::MAX).unwrap();
+    assert_eq!(updates[..3], vec![ChainUpdate::SideChain; 3]);
+    assert_eq!(
+        updates[3],
+        ChainUpdate::Reorg {
+            disconnected: first.iter().rev().map(BlockHeader::hash).collect(),
+            connected: second[..4].iter().map(BlockHeader::hash).collect(),
+        }
+    );
+    assert_eq!(updates[4], ChainUpdate::Extended);
+    drop(client);
+
+    let client = SpvClient::open(Network::Regtest, &path).unwrap();
+    assert_eq!(client.chain().height(), 5);
+    assert_eq!(client.chain().tip().hash(), second[4].hash());
+    assert!(client.chain().contains(&first[2].hash()));
+    assert!(!client.chain().is_in_best_chain(&first[2].hash()));
+    drop(client);
+    fs::remove_file(&path).unwrap();
+}
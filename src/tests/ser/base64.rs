@@ -0,0 +1,32 @@
+use crate::ser::base64::{decode, encode};
+
+// RFC 4648 test vectors
+const VECTORS: [(&str, &str); 7] = [
+    ("", ""),
+    ("f", "Zg=="),
+    ("fo", "Zm8="),
+    ("foo", "Zm9v"),
+    ("foob", "Zm9vYg=="),
+    ("fooba", "Zm9vYmE="),
+    ("foobar", "Zm9vYmFy"),
+];
+
+#[test]
+fn test_base64_vectors() {
+    for (data, encoded) in VECTORS {
+        assert_eq!(encode(data.as_bytes()), encoded);
+        assert_eq!(decode(encoded).unwrap(), data.as_bytes());
+    }
+    let bytes: Vec<u8> = (0..=255).collect();
+    assert_eq!(decode(&encode(&bytes)).unwrap(), bytes);
+    assert_eq!(encode(&[0xfb, 0xff, 0xbf]), "+/+/");
+}
+
+#[test]
+fn test_base64_invalid() {
+    for invalid in [
+        "Zg", "Zg=", "Zm9", "Z===", "Zg==Zg==", "Zm9v!A==", "Zh==", "Zm9=", "Zm 9v",
+    ] {
+        assert!(decode(invalid).is_err(), "{}", invalid);
+    }
+}
@@ -0,0 +1,148 @@
+use crate::core::address::{address_from_script, script_from_address};
+use crate::core::network::Network;
+use crate::core::s256ecc::S256PrivateKey;
+use crate::ser::bech32::{decode, decode_segwit_address, encode, Bech32Error, Variant};
+use crate::ser::hex;
+use bnum::types::U256;
+
+#[test]
+fn test_bech32_valid_strings() {
+    let bech32 = [
+        "A12UEL5L",
+        "a12uel5l",
+        "abcdef1qpzry9x8gf2tvdw0s3jn54khce6mua7lmqqqxw",
+        "split1checkupstagehandshakeupstreamerranterredcaperred2y9e3w",
+        "?1ezyfcl",
+    ];
+    let bech32m = [
+        "A1LQFN3A",
+        "a1lqfn3a",
+        "abcdef1l7aum6echk45nj3s0wdvt2fg8x9yrzpqzd3ryx",
+        "split1checkupstagehandshakeupstreamerranterredcaperredlc445v",
+        "?1v759aa",
+    ];
+    for (strings, variant) in [(bech32, Variant::Bech32), (bech32m, Variant::Bech32m)] {
+        for s in strings {
+            let (hrp, data, decoded_variant) = decode(s).unwrap();
+            assert_eq!(decoded_variant, variant);
+            assert_eq!(encode(&hrp, &data, variant).unwrap(), s.to_lowercase());
+        }
+    }
+}
+
+#[test]
+fn test_bech32_invalid_strings() {
+    assert_eq!(decode("1nwldj5"), Err(Bech32Error::InvalidHrp));
+    assert_eq!(decode("pzry9x0s0muk"), Err(Bech32Error::MissingSeparator));
+    assert_eq!(
+        decode("A1G7SGD8"),
+        Err(Bech32Error::InvalidChecksum(vec![]))
+    );
+    assert_eq!(decode("a12UEL5L"), Err(Bech32Error::MixedCase));
+    assert_eq!(decode("x1b4n0q5v"), Err(Bech32Error::InvalidCharacter(2)));
+    assert_eq!(decode("li1dgmt3"), Err(Bech32Error::InvalidLength(8)));
+}
+
+#[test]
+fn test_bech32_error_position() {
+    let address = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+    let corrupted = format!("{}x{}", &address[..10], &address[11..]);
+    assert_eq!(
+        decode(&corrupted),
+        Err(Bech32Error::InvalidChecksum(vec![10]))
+    );
+}
+
+#[test]
+fn test_segwit_addresses() {
+    let vectors = [
+        (
+            "bc",
+            "BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4",
+            "0014751e76e8199196d454941c45d1b3a323f1433bd6",
+        ),
+        (
+            "tb",
+            "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7",
+            "00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262",
+        ),
+        (
+            "bc",
+            "bc1pw508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7kt5nd6y",
+            "5128751e76e8199196d454941c45d1b3a323f1433bd6751e76e8199196d454941c45d1b3a323f1433bd6",
+        ),
+        ("bc", "BC1SW50QGDZ25J", "6002751e"),
+        (
+            "bc",
+            "bc1zw508d6qejxtdg4y5r3zarvaryvaxxpcs",
+            "5210751e76e8199196d454941c45d1b3a323",
+        ),
+        (
+            "tb",
+            "tb1pqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesf3hn0c",
+            "5120000000c4a5cad46221b2a187905e5266362b99d5e91c6ce24d165dab93e86433",
+        ),
+        (
+            "bc",
+            "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+            "512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+        ),
+    ];
+    for (hrp, address, script) in vectors {
+        let network = if hrp == "tb" {
+            Network::Testnet
+        } else {
+            Network::Mainnet
+        };
+        let script_pubkey = script_from_address(address, network).unwrap();
+        assert_eq!(hex::encode(&script_pubkey), script);
+        assert_eq!(
+            address_from_script(&script_pubkey, network).unwrap(),
+            address.to_lowercase()
+        );
+    }
+}
+
+#[test]
+fn test_invalid_segwit_addresses() {
+    let invalid = [
+        "tc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vq5zuyut",
+        "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqh2y7hd",
+        "BC1S0XLXVLHEMJA6C4DQV22UAPCTQUPFHLXM9H8Z3K2E72Q4K9HCZ7VQ54WELL",
+        "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kemeawh",
+        "bc1p38j9r5y49hruaue7wxjce0updqjuyyx0kh56v8s25huc6995vvpql3jow4",
+        "BC130XLXVLHEMJA6C4DQV22UAPCTQUPFHLXM9H8Z3K2E72Q4K9HCZ7VQ7ZWS8R",
+        "bc1pw5dgrnzv",
+        "bc1q0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7v8n0nx0muaewav253zgeav",
+        "BC1QR508D6QEJXTDG4Y5R3ZARVARYV98GJ9P",
+        "tb1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vq47Zagq",
+        "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7v07qwwzcrf",
+        "tb1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vpggkg4j",
+        "bc1gmk9yu",
+    ];
+    for address in invalid {
+        assert!(decode_segwit_address("bc", address).is_err());
+        assert!(decode_segwit_address("tb", address).is_err());
+    }
+    assert_eq!(
+        decode_segwit_address("bc", "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kemeawh"),
+        Err(Bech32Error::WrongVariant)
+    );
+}
+
+#[test]
+fn test_point_segwit_addresses() {
+    let point = S256PrivateKey::from_value(U256::ONE).point();
+    assert_eq!(
+        point.p2wpkh_address(Network::Mainnet),
+        "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"
+    );
+    assert_eq!(
+        point.p2wpkh_address(Network::Testnet),
+        "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx"
+    );
+    assert_eq!(
+        point.p2wsh_address(Network::Mainnet),
+        "bc1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3qccfmv3"
+    );
+}
@@ -0,0 +1,70 @@
+use crate::core::block::BlockHeader;
+use crate::core::chain::HeaderChain;
+use crate::core::network::Network;
+use crate::core::s256ecc::S256PrivateKey;
+use crate::core::script::{Opcode, Script};
+use crate::network::message::NetworkMessage;
+use bnum::types::U256;
+use std::fs;
+use std::io::Write;
+use std::net::TcpStream;
+use std::path::PathBuf;
+
+pub const REGTEST_BITS: u32 = 0x207f_ffff;
+
+pub fn mine(prev: &BlockHeader, tag: u8, timestamp: u32, bits: u32) -> BlockHeader {
+    let mut header = BlockHeader::new(4, prev.hash(), [tag; 32], timestamp, bits, 0);
+    while !header.check_pow() {
+        header.set_nonce(header.nonce() + 1);
+    }
+    header
+}
+
+pub fn extend(prev: &BlockHeader, tag: u8, count: usize) -> Vec<BlockHeader> {
+    let mut headers: Vec<BlockHeader> = Vec::new();
+    for _ in 0..count {
+        let parent = headers.last().unwrap_or(prev);
+        let header = mine(parent, tag, parent.timestamp() + 600, REGTEST_BITS);
+        headers.push(header);
+    }
+    headers
+}
+
+pub fn mine_chain(count: usize) -> HeaderChain {
+    let mut chain = HeaderChain::new(Network::Regtest);
+    let genesis = *chain.tip().header();
+    for header in extend(&genesis, 1, count) {
+        chain.accept_header(header, u32::MAX).unwrap();
+    }
+    chain
+}
+
+pub fn temp_path(prefix: &str, name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("{}_{}_{}.dat", prefix, name, std::process::id()));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+pub fn send(stream: &mut TcpStream, message: NetworkMessage) {
+    stream
+        .write_all(&message.serialize(Network::Regtest))
+        .unwrap();
+}
+
+pub fn keys(count: u32) -> Vec<S256PrivateKey> {
+    (1..=count)
+        .map(|secret| S256PrivateKey::from_value(U256::from(secret * 7_919)))
+        .collect()
+}
+
+pub fn multisig_script(keys: &[S256PrivateKey], threshold: i64) -> Script {
+    let mut script = Script::new();
+    script.push_int(threshold);
+    for key in keys {
+        script.push_data(&key.point().sec(true));
+    }
+    script
+        .push_int(keys.len() as i64)
+        .push_opcode(Opcode::CheckMultiSig);
+    script
+}